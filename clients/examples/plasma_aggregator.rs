@@ -1,78 +1,109 @@
-#[macro_use]
-extern crate futures;
-
 use abi_utils::Decodable;
 use abi_utils::Encodable;
 use bincode::serialize;
+use bytes::Bytes;
 use ethereum_types::Address;
-use futures::{future, Async, Future, Poll, Stream};
+use futures::future;
+use plasma_clients::plasma::tx_journal::now;
 use plasma_clients::plasma::{
-    utils::string_to_address, Command, FetchBlockRequest, PlasmaAggregator,
+    block_manager::CommitmentChainOptions,
+    block_scheduler::{BlockProducer, BlockSchedulerOptions},
+    order_book::MatchOrderRequest,
+    token::default_tokens,
+    utils::string_to_address,
+    Command, CommandResponse, FetchBlockRangeRequest, FetchBlockRequest, PlasmaAggregator,
+};
+use plasma_clients::state_channel::{
+    channel_topic, is_channel_topic, ChannelCommand, Hub, PaymentChannel, StateChannel,
 };
-use plasma_core::data_structure::Transaction;
+use plasma_core::data_structure::{BatchTransaction, Transaction};
 use plasma_db::prelude::*;
 use pubsub_messaging::{spawn_server, Message, Sender, ServerHandler, WsMessage};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::timer::Interval;
 
+#[derive(Clone)]
 struct Handle {
     plasma_aggregator: Arc<Mutex<PlasmaAggregator<CoreDbLevelDbImpl>>>,
-    interval: Interval,
-    interval_sec: u64,
+    hub: Arc<Mutex<Hub<CoreDbLevelDbImpl>>>,
 }
 
 impl Handle {
-    fn new(plasma_aggregator: PlasmaAggregator<CoreDbLevelDbImpl>, interval_sec: u64) -> Self {
+    fn new(
+        plasma_aggregator: PlasmaAggregator<CoreDbLevelDbImpl>,
+        hub: Hub<CoreDbLevelDbImpl>,
+    ) -> Self {
         Self {
             plasma_aggregator: Arc::new(Mutex::new(plasma_aggregator)),
-            interval: Interval::new_interval(Duration::from_secs(interval_sec)),
-            interval_sec,
-        }
-    }
-}
-
-impl Clone for Handle {
-    fn clone(&self) -> Self {
-        Self {
-            plasma_aggregator: self.plasma_aggregator.clone(),
-            interval: Interval::new_interval(Duration::from_secs(self.interval_sec)),
-            interval_sec: self.interval_sec,
+            hub: Arc::new(Mutex::new(hub)),
         }
     }
-}
-impl Stream for Handle {
-    type Item = ();
-    type Error = ();
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        try_ready!(self.interval.poll().map_err(|_| ()));
-        let mut agg = self.plasma_aggregator.lock().unwrap();
-        if agg.submit_next_block().is_ok() {
-            println!("succeeded to submit");
-            Ok(Async::Ready(Some(())))
-        } else {
-            Ok(Async::Ready(Some(())))
+    /// Handles the channel command sent to the hub and resolves expired locks.
+    fn handle_hub_message(&self, msg: &Message, sender: &Sender) {
+        let hub = self.hub.lock().unwrap();
+        let now = now();
+        let mut commands = match ChannelCommand::from_abi(&msg.message) {
+            Ok(command) => hub.handle_command(&command, now).unwrap_or_else(|e| {
+                println!("failed to handle channel command: {:?}", e);
+                vec![]
+            }),
+            Err(e) => {
+                println!("invalid channel command: {:?}", e);
+                vec![]
+            }
+        };
+        commands.extend(hub.expire_locks(now));
+        for (to, command) in commands {
+            let message = Message::new(channel_topic(to), command.to_abi());
+            let _ = sender.broadcast(WsMessage::Binary(serialize(&message).unwrap()));
         }
     }
 }
 
 impl ServerHandler for Handle {
     fn handle_message(&mut self, msg: Message, sender: Sender) {
+        // relay state channel commands to the participants
+        if is_channel_topic(&msg.to) {
+            let hub_address = self.hub.lock().unwrap().get_my_address();
+            if msg.to == channel_topic(hub_address) {
+                self.handle_hub_message(&msg, &sender);
+            } else {
+                let _ = sender.broadcast(WsMessage::Binary(serialize(&msg).unwrap()));
+            }
+            return;
+        }
         let mut agg = self.plasma_aggregator.lock().unwrap();
         let command = Command::from_abi(&msg.message).unwrap();
         if command.command_type.0 == 0 {
             let tx = Transaction::from_abi(&command.body).unwrap();
-            let ingest_result = agg.ingest_transaction(tx).unwrap();
+            let response = match agg.ingest_transaction(tx.clone()) {
+                Ok(ingest_result) => {
+                    let message = Message::new(
+                        "BROADCAST".to_owned(),
+                        Command::create_new_tx_event(ingest_result)
+                            .to_abi()
+                            .to_vec(),
+                    );
+                    let msg = WsMessage::Binary(serialize(&message).unwrap());
+                    let _ = sender.broadcast(msg);
+                    // replaceable transactions aren't promised
+                    let body = agg
+                        .create_inclusion_promise(&tx)
+                        .map(|promise| Bytes::from(promise.to_abi()))
+                        .unwrap_or_default();
+                    CommandResponse::ok(command.request_id, body)
+                }
+                Err(e) => CommandResponse::error(command.request_id, &e),
+            };
+            broadcast_order_book_updates(&mut agg, &sender);
+            // replies only to the sender of the transaction
             let message = Message::new(
-                "BROADCAST".to_owned(),
-                Command::create_new_tx_event(ingest_result)
-                    .to_abi()
-                    .to_vec(),
+                "Client".to_owned(),
+                Command::create_response(response).to_abi().to_vec(),
             );
-
             let msg = WsMessage::Binary(serialize(&message).unwrap());
-            let _ = sender.broadcast(msg);
+            let _ = sender.send(msg);
         } else if command.command_type.0 == 1 {
             let fetch_request = FetchBlockRequest::from_abi(&command.body).unwrap();
             println!("fetch block {:?}", fetch_request);
@@ -85,12 +116,81 @@ impl ServerHandler for Handle {
                 let msg = WsMessage::Binary(serialize(&message).unwrap());
                 let _ = sender.broadcast(msg);
             }
+        } else if command.command_type.0 == 5 {
+            let fetch_request = FetchBlockRangeRequest::from_abi(&command.body).unwrap();
+            println!("fetch blocks {:?}", fetch_request);
+            // replies only to the requesting client
+            for plasma_block in
+                agg.get_plasma_blocks(fetch_request.from_block.0, fetch_request.to_block.0)
+            {
+                let message = Message::new(
+                    "Client".to_owned(),
+                    Command::create_plasma_block(plasma_block).to_abi().to_vec(),
+                );
+                let msg = WsMessage::Binary(serialize(&message).unwrap());
+                let _ = sender.send(msg);
+            }
+        } else if command.command_type.0 == 7 {
+            let batch = BatchTransaction::from_abi(&command.body).unwrap();
+            let response = match agg.ingest_batch_transaction(batch) {
+                Ok(ingest_results) => {
+                    let mut promises = vec![];
+                    for ingest_result in ingest_results {
+                        promises.extend(agg.create_inclusion_promise(&ingest_result.transaction));
+                        let message = Message::new(
+                            "BROADCAST".to_owned(),
+                            Command::create_new_tx_event(ingest_result)
+                                .to_abi()
+                                .to_vec(),
+                        );
+                        let msg = WsMessage::Binary(serialize(&message).unwrap());
+                        let _ = sender.broadcast(msg);
+                    }
+                    CommandResponse::ok(
+                        command.request_id,
+                        CommandResponse::encode_inclusion_promises(&promises),
+                    )
+                }
+                Err(e) => CommandResponse::error(command.request_id, &e),
+            };
+            broadcast_order_book_updates(&mut agg, &sender);
+            let message = Message::new(
+                "Client".to_owned(),
+                Command::create_response(response).to_abi().to_vec(),
+            );
+            let msg = WsMessage::Binary(serialize(&message).unwrap());
+            let _ = sender.send(msg);
+        } else if command.command_type.0 == 9 {
+            let request = MatchOrderRequest::from_abi(&command.body).unwrap();
+            let response = match agg.match_order(&request) {
+                Ok(batch) => CommandResponse::ok(command.request_id, Bytes::from(batch.to_abi())),
+                Err(e) => CommandResponse::error(command.request_id, &e),
+            };
+            // replies only to the taker
+            let message = Message::new(
+                "Client".to_owned(),
+                Command::create_response(response).to_abi().to_vec(),
+            );
+            let msg = WsMessage::Binary(serialize(&message).unwrap());
+            let _ = sender.send(msg);
         } else {
             println!("undefined command type {:?}", command.command_type.0);
         }
     }
 }
 
+/// Broadcasts changes of the order book caused by ingested transactions.
+fn broadcast_order_book_updates(agg: &mut PlasmaAggregator<CoreDbLevelDbImpl>, sender: &Sender) {
+    for update in agg.take_order_book_updates() {
+        let message = Message::new(
+            "BROADCAST".to_owned(),
+            Command::create_order_book_update(&update).to_abi().to_vec(),
+        );
+        let msg = WsMessage::Binary(serialize(&message).unwrap());
+        let _ = sender.broadcast(msg);
+    }
+}
+
 fn main() {
     let mut aggregator = PlasmaAggregator::new(
         "aggregator", // db name
@@ -98,37 +198,48 @@ fn main() {
         Address::zero(),
         string_to_address("9FBDa871d559710256a2502A2517b794B482Db40"),
         "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3",
-    );
+    )
+    .expect("failed to open aggregator");
+    aggregator
+        .register_tokens(default_tokens())
+        .expect("failed to register tokens");
     aggregator.insert_test_ranges();
+    let default_options = CommitmentChainOptions::default();
+    aggregator.set_commitment_chain_options(CommitmentChainOptions {
+        endpoint: std::env::var("COMMITMENT_CHAIN_ENDPOINT").unwrap_or(default_options.endpoint),
+        abi_path: std::env::var("COMMITMENT_CHAIN_ABI").unwrap_or(default_options.abi_path),
+    });
+    match aggregator.resubmit_unsubmitted_blocks() {
+        Ok(block_numbers) => println!("resubmitted blocks {:?}", block_numbers),
+        Err(e) => println!("failed to resubmit blocks {:?}", e),
+    }
 
-    let interval_second = 5;
-    let handle = Handle::new(aggregator, interval_second);
+    let hub = Hub::new(PaymentChannel::new(
+        StateChannel::new(
+            "hub",
+            "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3",
+        )
+        .expect("failed to open hub"),
+    ));
+    let handle = Handle::new(aggregator, hub);
 
     handle
         .plasma_aggregator
         .lock()
         .unwrap()
         .show_queued_state_updates();
-    let h = handle.clone();
+    let (producer, _producer_handle) = BlockProducer::new(
+        handle.plasma_aggregator.clone(),
+        BlockSchedulerOptions {
+            interval: Some(Duration::from_secs(5)),
+            ..Default::default()
+        },
+    );
     tokio::run(future::lazy(move || {
-        tokio::spawn(Worker { handle: h });
+        tokio::spawn(producer);
         if let Ok(server) = spawn_server("127.0.0.1:8080".to_owned(), handle.clone()) {
             let _ = server.handle.join();
         }
         Ok(())
     }));
 }
-
-struct Worker {
-    pub handle: Handle,
-}
-impl Future for Worker {
-    type Item = ();
-    type Error = ();
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        loop {
-            let _ = try_ready!(self.handle.poll());
-        }
-    }
-}
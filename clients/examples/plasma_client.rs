@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use futures::future;
-use plasma_clients::plasma::{utils::*, PlasmaClientShell};
+use plasma_clients::plasma::{token::default_tokens, utils::*, PlasmaClientShell};
 
 fn main() {
     let mut shell = PlasmaClientShell::new(
@@ -10,13 +10,20 @@ fn main() {
     );
     tokio::run(future::lazy(move || {
         shell.connect();
+        shell.register_tokens(default_tokens()).unwrap();
         println!("{:?}", shell.get_balance(&Bytes::from("")));
         let session = &Bytes::from("");
         let (property, metadata) = shell.ownership_property(
             session,
             string_to_address("2932b7a2355d6fecc4b5c0b6bd44cc31df247a2e"),
         );
-        shell.send_transaction(session, None, 0, 10, property, metadata);
+        match shell.send_transaction(session, None, 0, 10, property, metadata) {
+            Ok(Some(promise)) => {
+                println!("promised inclusion at {:?}", promise.get_block_number())
+            }
+            Ok(None) => println!("accepted without inclusion promise"),
+            Err(e) => println!("rejected: {:?}", e),
+        }
         Ok(())
     }));
 }
@@ -2,9 +2,21 @@
 extern crate clap;
 
 use clap::{App, Arg, SubCommand};
-use ethereum_types::Address;
 use futures::future;
-use plasma_clients::plasma::{utils::*, PlasmaClientShell};
+use plasma_clients::plasma::{token::default_tokens, utils::*, PlasmaClientShell};
+
+/// Connects the shell and encrypts keys stored raw by older versions with a password read from stdin.
+fn connect(shell: &mut PlasmaClientShell) {
+    shell.connect();
+    if !shell.has_raw_keys() {
+        return;
+    }
+    println!("Unencrypted keys are found. Enter a password to encrypt them:");
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password).unwrap();
+    let sessions = shell.encrypt_raw_keys(password.trim_end()).unwrap();
+    println!("{} keys are encrypted", sessions.len());
+}
 
 fn main() {
     let matches = App::new("OVM Wallet!!!")
@@ -39,6 +51,49 @@ fn main() {
                         .value_name("secret_key")
                         .takes_value(true)
                         .help("hex secret key"),
+                )
+                .arg(
+                    Arg::with_name("password")
+                        .short("p")
+                        .value_name("password")
+                        .takes_value(true)
+                        .help("password to encrypt the key"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("export account as V3 keystore JSON")
+                .version("1.0"),
+        )
+        .subcommand(
+            SubCommand::with_name("mnemonic")
+                .about("create HD wallet")
+                .version("1.0")
+                .arg(
+                    Arg::with_name("password")
+                        .short("p")
+                        .value_name("password")
+                        .takes_value(true)
+                        .help("password to encrypt the seed"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("restore HD wallet and its used accounts")
+                .version("1.0")
+                .arg(
+                    Arg::with_name("mnemonic")
+                        .short("m")
+                        .value_name("mnemonic")
+                        .takes_value(true)
+                        .help("BIP-39 mnemonic"),
+                )
+                .arg(
+                    Arg::with_name("password")
+                        .short("p")
+                        .value_name("password")
+                        .takes_value(true)
+                        .help("password to encrypt the seed"),
                 ),
         )
         .subcommand(
@@ -72,6 +127,13 @@ fn main() {
                         .value_name("to")
                         .takes_value(true)
                         .help("to address"),
+                )
+                .arg(
+                    Arg::with_name("password")
+                        .short("p")
+                        .value_name("password")
+                        .takes_value(true)
+                        .help("password to unlock the account"),
                 ),
         )
         .get_matches();
@@ -84,37 +146,64 @@ fn main() {
     );
 
     if matches.subcommand_matches("balance").is_some() {
-        let eth_address = Address::zero();
-        let dai_address = string_to_address("0000000000000000000000000000000000000001");
         tokio::run(future::lazy(move || {
-            shell.connect();
-            println!(
-                "Balance\n\t{:?} ETH\n\t{:?} DAI",
-                shell
-                    .get_balance(&decode_session(session_str.clone()).unwrap())
-                    .get(&eth_address)
-                    .unwrap_or(&0),
-                shell
-                    .get_balance(&decode_session(session_str).unwrap())
-                    .get(&dai_address)
-                    .unwrap_or(&0),
-            );
+            connect(&mut shell);
+            let balances = shell.get_balance(&decode_session(session_str).unwrap());
+            println!("Balance");
+            for token in shell.get_all_tokens().unwrap_or_else(|_| vec![]) {
+                println!(
+                    "\t{:?} {}",
+                    balances.get(&token.get_address()).unwrap_or(&0),
+                    token.get_symbol()
+                );
+            }
             Ok(())
         }));
     } else if matches.subcommand_matches("init").is_some() {
         tokio::run(future::lazy(move || {
-            shell.connect();
+            connect(&mut shell);
+            shell.register_tokens(default_tokens()).unwrap();
             shell.initialize();
             Ok(())
         }));
     } else if let Some(matches) = matches.subcommand_matches("import") {
         let secret_key = value_t!(matches, "secret_key", String).unwrap();
+        let password = value_t!(matches, "password", String).unwrap();
         tokio::run(future::lazy(move || {
-            shell.connect();
-            let (session, _key) = shell.import_account(&secret_key);
+            connect(&mut shell);
+            let (session, _address) = shell.import_account(&secret_key, &password).unwrap();
             println!("session: {}", hex::encode(session.to_vec()));
             Ok(())
         }));
+    } else if matches.subcommand_matches("export").is_some() {
+        tokio::run(future::lazy(move || {
+            connect(&mut shell);
+            let keystore = shell
+                .export_keystore(&decode_session(session_str).unwrap())
+                .unwrap();
+            println!("{}", keystore);
+            Ok(())
+        }));
+    } else if let Some(matches) = matches.subcommand_matches("mnemonic") {
+        let password = value_t!(matches, "password", String).unwrap();
+        tokio::run(future::lazy(move || {
+            connect(&mut shell);
+            let mnemonic = shell.generate_mnemonic(&password).unwrap();
+            let (session, _address) = shell.derive_account(&password, 0).unwrap();
+            println!("mnemonic: {}", mnemonic);
+            println!("session: {}", hex::encode(session.to_vec()));
+            Ok(())
+        }));
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        let mnemonic = value_t!(matches, "mnemonic", String).unwrap();
+        let password = value_t!(matches, "password", String).unwrap();
+        tokio::run(future::lazy(move || {
+            connect(&mut shell);
+            for (session, _address) in shell.import_mnemonic(&mnemonic, &password).unwrap() {
+                println!("session: {}", hex::encode(session.to_vec()));
+            }
+            Ok(())
+        }));
     } else if let Some(matches) = matches.subcommand_matches("send") {
         let token_address_opt = value_t!(matches, "token", String)
             .map(|a| string_to_address(&a))
@@ -122,16 +211,25 @@ fn main() {
         let to_address = string_to_address(&value_t!(matches, "to", String).unwrap());
         let start = value_t!(matches, "start", u64).unwrap();
         let end = value_t!(matches, "end", u64).unwrap();
+        let password = value_t!(matches, "password", String).unwrap();
         println!(
             "Send {:?}-{:?} token={:?} to {:?} ",
             start, end, token_address_opt, to_address
         );
         tokio::run(future::lazy(move || {
-            shell.connect();
+            connect(&mut shell);
             let session = &decode_session(session_str).unwrap();
+            shell.unlock_account(session, &password).unwrap();
             let (property, metadata) = shell.ownership_property(session, to_address);
-            shell.send_transaction(session, token_address_opt, start, end, property, metadata);
-            println!("Sent!!!");
+            match shell.send_transaction(session, token_address_opt, start, end, property, metadata)
+            {
+                Ok(Some(promise)) => println!(
+                    "Sent!!! promised inclusion at block {:?}",
+                    promise.get_block_number()
+                ),
+                Ok(None) => println!("Sent!!! accepted without inclusion promise"),
+                Err(e) => println!("Rejected: {:?}", e),
+            }
             Ok(())
         }));
     }
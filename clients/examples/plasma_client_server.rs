@@ -1,36 +1,109 @@
-use abi_utils::{Decodable, Integer};
+use abi_utils::{Decodable, Encodable, Integer};
 use actix_web::{error, middleware::Logger, web, App, HttpResponse, HttpServer, Result};
-use chrono::{DateTime, Local};
+use bytes::Bytes;
+use chrono::{DateTime, Local, TimeZone};
 use env_logger;
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
 use log::info;
 use ovm::types::Property;
 use plasma_clients::plasma::{
+    coin_selection::CoinSelectionPolicy,
+    data_availability::{MassExitPolicy, PlannedExit},
+    defragmentation::DefragmentationPolicy,
     error::{Error, ErrorKind},
     query::query_exchanged,
+    token::default_tokens,
+    tx_journal::TransactionStatus,
     utils::*,
+    watchtower::{AuditAction, Delegation, WatchtowerPolicy},
     PlasmaClientShell,
 };
 use plasma_core::data_structure::{Range, EXCHANGE_TYPE, PAYMENT_TYPE};
 use serde::{Deserialize, Serialize};
 
 // Create Account
+#[derive(Deserialize, Debug)]
+struct CreateAccountRequest {
+    password: String,
+}
+
 #[derive(Serialize)]
 struct CreateAccountResponse {
     address: Address,
     session: String,
 }
 
-fn create_account(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
-    let (session, key) = plasma_client.create_account();
+fn create_account(
+    body: web::Json<CreateAccountRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let (session, address) = plasma_client
+        .create_account(&body.password)
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(CreateAccountResponse {
+        address,
+        session: encode_session(session),
+    }))
+}
+
+// Import Keystore
+#[derive(Deserialize, Debug)]
+struct ImportKeystoreRequest {
+    keystore: String,
+    password: String,
+}
+
+fn import_keystore(
+    body: web::Json<ImportKeystoreRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let (session, address) = plasma_client
+        .import_keystore(&body.keystore, &body.password)
+        .map_err(error::ErrorBadRequest)?;
     Ok(HttpResponse::Ok().json(CreateAccountResponse {
-        address: Address::from(key.public().address()),
+        address,
         session: encode_session(session),
     }))
 }
 
+// Unlock and Lock Account
+#[derive(Deserialize, Debug)]
+struct UnlockAccountRequest {
+    session: String,
+    password: String,
+}
+
+fn unlock_account(
+    body: web::Json<UnlockAccountRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let address = plasma_client
+        .unlock_account(&session, &body.password)
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(CreateAccountResponse {
+        address,
+        session: body.session.clone(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct LockAccountRequest {
+    session: String,
+}
+
+fn lock_account(
+    body: web::Json<LockAccountRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    Ok(HttpResponse::Ok().json(plasma_client.lock_account(&session)))
+}
+
 fn get_all_tokens(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
-    let tokens = plasma_client.get_all_tokens();
+    let tokens = plasma_client
+        .get_all_tokens()
+        .map_err(error::ErrorInternalServerError)?;
     Ok(HttpResponse::Ok().json(tokens))
 }
 
@@ -45,6 +118,9 @@ struct Balance {
     token_address: Address,
     token_name: String,
     balance: u64,
+    // Locked in open orders and expired orders. Cancelling them returns them to balance.
+    in_orders: u64,
+    in_expired_orders: u64,
 }
 
 fn get_balance(
@@ -53,13 +129,19 @@ fn get_balance(
 ) -> Result<HttpResponse> {
     info!("PARAMS: {:?}", params);
     let session = decode_session(params.session.clone()).unwrap();
+    let (in_orders, in_expired_orders) = plasma_client.get_order_balance(&session);
     let balance: Vec<Balance> = plasma_client
         .get_balance(&session)
         .iter()
         .map(|(k, v)| Balance {
             token_address: *k,
-            token_name: plasma_client.get_token_name(*k),
+            token_name: plasma_client
+                .get_token_name(*k)
+                .unwrap_or(None)
+                .unwrap_or_else(|| "unknown".to_string()),
             balance: *v,
+            in_orders: *in_orders.get(k).unwrap_or(&0),
+            in_expired_orders: *in_expired_orders.get(k).unwrap_or(&0),
         })
         .collect();
 
@@ -102,34 +184,56 @@ fn get_payment_history(
     info!("PARAMS: {:?}", params);
     let session = decode_session(params.session.clone()).unwrap();
     let my_address = plasma_client.get_my_address(&session).unwrap();
-    let txs = plasma_client.get_related_transactions(&session);
-    let history: Vec<PaymentHistory> = txs
+    let token_name = |address: Address| {
+        plasma_client
+            .get_token_name(address)
+            .unwrap_or(None)
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+    // sent payments are taken from the journal to show their status
+    let mut history: Vec<PaymentHistory> = plasma_client
+        .get_transaction_journal(&session)
+        .into_iter()
+        .filter(|entry| entry.get_transaction().get_metadata().get_meta_type() == PAYMENT_TYPE)
+        .map(|entry| {
+            let tx = entry.get_transaction();
+            PaymentHistory {
+                history_type: PaymentHistoryType::SEND,
+                amount: tx.get_range().get_amount(),
+                address: tx.get_metadata().get_to(),
+                timestamp: Local.timestamp(entry.get_submitted_at() as i64, 0),
+                status: match entry.get_status() {
+                    TransactionStatus::Pending | TransactionStatus::Accepted => {
+                        PaymentHistoryStatus::PENDING
+                    }
+                    TransactionStatus::Confirmed => PaymentHistoryStatus::CONFIRMED,
+                    TransactionStatus::Failed | TransactionStatus::Dropped => {
+                        PaymentHistoryStatus::FAILED
+                    }
+                },
+                token_name: token_name(tx.get_deposit_contract_address()),
+            }
+        })
+        .collect();
+    let received = plasma_client
+        .get_related_transactions(&session)
         .into_iter()
         .filter_map(|tx| {
             let metadata = tx.get_metadata();
-            if metadata.get_meta_type() == PAYMENT_TYPE {
-                let send = metadata.get_from() == my_address;
+            if metadata.get_meta_type() == PAYMENT_TYPE && metadata.get_from() != my_address {
                 Some(PaymentHistory {
-                    history_type: if send {
-                        PaymentHistoryType::SEND
-                    } else {
-                        PaymentHistoryType::RECEIVE
-                    },
+                    history_type: PaymentHistoryType::RECEIVE,
                     amount: tx.get_range().get_amount(),
-                    address: if send {
-                        metadata.get_to()
-                    } else {
-                        metadata.get_from()
-                    },
+                    address: metadata.get_from(),
                     timestamp: Local::now(),
                     status: PaymentHistoryStatus::CONFIRMED,
-                    token_name: plasma_client.get_token_name(tx.get_deposit_contract_address()),
+                    token_name: token_name(tx.get_deposit_contract_address()),
                 })
             } else {
                 None
             }
-        })
-        .collect();
+        });
+    history.extend(received);
     Ok(HttpResponse::Ok().json(history))
 }
 
@@ -148,30 +252,338 @@ fn send_payment(
     plasma_client: web::Data<PlasmaClientShell>,
 ) -> Result<HttpResponse> {
     let session = decode_session(body.session.clone()).unwrap();
-    let account = plasma_client.get_my_address(&session).unwrap();
-    if let Some(range) = plasma_client.search_range(body.token_address, body.amount, account) {
-        println!("Range: {:?}", range);
-        let (property, metadata) = plasma_client.ownership_property(&session, body.to);
-        plasma_client.send_transaction(
+    match plasma_client.send_payment(
+        &session,
+        Some(body.token_address),
+        body.amount,
+        body.to,
+        CoinSelectionPolicy::default(),
+    ) {
+        Ok(txs) => {
+            println!(
+                "Ranges: {:?}",
+                txs.iter().map(|tx| tx.get_range()).collect::<Vec<_>>()
+            );
+            Ok(HttpResponse::Ok().json(SendPayment {
+                token_address: body.token_address,
+                from: body.from,
+                to: body.to,
+                session: body.session.clone(),
+                amount: body.amount,
+            }))
+        }
+        Err(e) => Err(error::ErrorBadRequest(e)),
+    }
+}
+
+// Send Batch Payment
+#[derive(Deserialize, Serialize, Debug)]
+struct BatchPaymentItem {
+    to: Address,
+    amount: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct SendBatchPayment {
+    token_address: Address,
+    payments: Vec<BatchPaymentItem>,
+    session: String,
+}
+
+fn send_batch_payment(
+    body: web::Json<SendBatchPayment>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let payments: Vec<(Address, u64)> = body.payments.iter().map(|p| (p.to, p.amount)).collect();
+    match plasma_client.send_batch_payment(
+        &session,
+        Some(body.token_address),
+        &payments,
+        CoinSelectionPolicy::default(),
+    ) {
+        Ok(batch) => {
+            println!(
+                "Ranges: {:?}",
+                batch
+                    .get_entries()
+                    .iter()
+                    .map(|e| e.get_range())
+                    .collect::<Vec<_>>()
+            );
+            Ok(HttpResponse::Ok().json(&body.payments))
+        }
+        Err(e) => Err(error::ErrorBadRequest(e)),
+    }
+}
+
+// Preview Payment
+#[derive(Deserialize, Debug)]
+struct PreviewPaymentRequest {
+    token_address: Address,
+    amount: u64,
+    session: String,
+}
+
+#[derive(Serialize)]
+struct PreviewRange {
+    block_number: u64,
+    start: u64,
+    end: u64,
+    change_start: Option<u64>,
+    change_end: Option<u64>,
+}
+
+fn preview_payment(
+    body: web::Json<PreviewPaymentRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let selected = plasma_client
+        .preview_payment(
             &session,
             Some(body.token_address),
-            range.get_start(),
-            range.get_start() + body.amount,
-            property,
-            metadata,
-        );
-        return Ok(HttpResponse::Ok().json(SendPayment {
-            token_address: body.token_address,
-            from: body.from,
-            to: body.to,
-            session: body.session.clone(),
-            amount: body.amount,
-        }));
+            body.amount,
+            CoinSelectionPolicy::default(),
+        )
+        .map_err(error::ErrorBadRequest)?;
+    let preview: Vec<PreviewRange> = selected
+        .iter()
+        .map(|s| PreviewRange {
+            block_number: s.get_state_update().get_block_number().0,
+            start: s.get_range().get_start(),
+            end: s.get_range().get_end(),
+            change_start: s.get_change().map(|r| r.get_start()),
+            change_end: s.get_change().map(|r| r.get_end()),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(preview))
+}
+
+// Estimate Fee
+#[derive(Serialize)]
+struct EstimateFeeResponse {
+    fee_token: Option<Address>,
+    fee: u64,
+}
+
+fn estimate_fee(
+    body: web::Json<SendBatchPayment>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let payments: Vec<(Address, u64)> = body.payments.iter().map(|p| (p.to, p.amount)).collect();
+    let fee = plasma_client
+        .estimate_fee(
+            &session,
+            Some(body.token_address),
+            &payments,
+            CoinSelectionPolicy::default(),
+        )
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(EstimateFeeResponse {
+        fee_token: plasma_client.get_fee_policy().map(|p| p.fee_token),
+        fee,
+    }))
+}
+
+// Fragmentation
+#[derive(Deserialize, Debug)]
+struct GetFragmentationMetricsRequest {
+    session: String,
+}
+
+fn get_fragmentation_metrics(
+    params: web::Query<GetFragmentationMetricsRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(params.session.clone()).unwrap();
+    Ok(HttpResponse::Ok().json(plasma_client.get_fragmentation_metrics(&session)))
+}
+
+#[derive(Deserialize, Debug)]
+struct DefragmentRequest {
+    session: String,
+    max_ranges_per_token: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MergedRange {
+    token_address: Address,
+    start: u64,
+    end: u64,
+}
+
+fn defragment(
+    body: web::Json<DefragmentRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let mut policy = DefragmentationPolicy::default();
+    if let Some(max_ranges_per_token) = body.max_ranges_per_token {
+        policy.max_ranges_per_token = max_ranges_per_token;
+    }
+    let merged: Vec<MergedRange> = plasma_client
+        .defragment(&session, &policy)
+        .map_err(error::ErrorBadRequest)?
+        .iter()
+        .map(|tx| MergedRange {
+            token_address: tx.get_deposit_contract_address(),
+            start: tx.get_range().get_start(),
+            end: tx.get_range().get_end(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(merged))
+}
+
+// Misbehaviour Evidences
+#[derive(Serialize)]
+struct Evidence {
+    transaction_hash: H256,
+    block_number: u64,
+    evidence: String,
+}
+
+fn get_misbehaviour_evidences(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
+    let evidences: Vec<Evidence> = plasma_client
+        .get_misbehaviour_evidences()
+        .iter()
+        .map(|e| Evidence {
+            transaction_hash: e.get_promise().get_transaction_hash(),
+            block_number: e.get_promise().get_block_number(),
+            evidence: encode_hex(&Bytes::from(e.to_abi())),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(evidences))
+}
+
+// Watchtower
+#[derive(Deserialize, Debug)]
+struct DelegateMonitoringRequest {
+    owner: Address,
+    token_address: Address,
+    start: u64,
+    end: u64,
+}
+
+fn delegate_monitoring(
+    body: web::Json<DelegateMonitoringRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    plasma_client
+        .delegate_monitoring(&Delegation::new(
+            body.owner,
+            body.token_address,
+            Range::new(body.start, body.end),
+        ))
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(()))
+}
+
+#[derive(Serialize)]
+struct AuditLogItem {
+    claim: String,
+    claimed_at: u64,
+    action: AuditAction,
+    transaction_hash: H256,
+    reason: String,
+}
+
+fn get_watchtower_audit_log(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
+    let log: Vec<AuditLogItem> = plasma_client
+        .get_watchtower_audit_log()
+        .iter()
+        .map(|e| AuditLogItem {
+            claim: encode_hex(&Bytes::from(e.get_claim().to_abi())),
+            claimed_at: e.get_claimed_at(),
+            action: e.get_action(),
+            transaction_hash: e.get_transaction_hash(),
+            reason: e.get_reason(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(log))
+}
+
+// Exits
+#[derive(Serialize)]
+struct ExitItem {
+    owner: Address,
+    token_address: Address,
+    start: u64,
+    end: u64,
+    block_number: u64,
+}
+
+impl From<&PlannedExit> for ExitItem {
+    fn from(exit: &PlannedExit) -> Self {
+        Self {
+            owner: exit.get_owner(),
+            token_address: exit.get_deposit_contract_address(),
+            start: exit.get_range().get_start(),
+            end: exit.get_range().get_end(),
+            block_number: exit.get_block_number().0,
+        }
     }
+}
 
-    Err(error::ErrorBadRequest(Error::from(
-        ErrorKind::InvalidParameter,
-    )))
+#[derive(Serialize)]
+struct StartedExit {
+    exit: ExitItem,
+    transaction_hash: H256,
+}
+
+#[derive(Serialize)]
+struct ExitStatusResponse {
+    withheld_block_number: Option<u64>,
+    exits: Vec<StartedExit>,
+}
+
+fn get_exits(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
+    let exits = plasma_client
+        .get_exits()
+        .iter()
+        .map(|record| StartedExit {
+            exit: ExitItem::from(record.get_exit()),
+            transaction_hash: record.get_transaction_hash(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(ExitStatusResponse {
+        withheld_block_number: plasma_client.get_withheld_block(),
+        exits,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct GetMassExitPlanRequest {
+    withheld_block_number: Option<u64>,
+    exits_per_round: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MassExitPlanResponse {
+    withheld_block_number: u64,
+    rounds: Vec<Vec<ExitItem>>,
+}
+
+fn get_mass_exit_plan(
+    params: web::Query<GetMassExitPlanRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let mut policy = MassExitPolicy::default();
+    if let Some(exits_per_round) = params.exits_per_round {
+        policy.exits_per_round = exits_per_round;
+    }
+    let plan = plasma_client
+        .get_mass_exit_plan(params.withheld_block_number, &policy)
+        .map_err(error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(MassExitPlanResponse {
+        withheld_block_number: plan.withheld_block_number,
+        rounds: plan
+            .rounds
+            .iter()
+            .map(|round| round.iter().map(ExitItem::from).collect())
+            .collect(),
+    }))
 }
 
 // Get Exchange Offers
@@ -204,6 +616,11 @@ struct ExchangeOfferRequest {
     token_address: Address,
     amount: u64,
     counter_party: CounterParty,
+    #[serde(default)]
+    expiry_block: Option<u64>,
+    // The offer can be partially filled at the same price.
+    #[serde(default)]
+    partially_fillable: bool,
 }
 
 fn get_exchange_offers(plasma_client: web::Data<PlasmaClientShell>) -> Result<HttpResponse> {
@@ -227,6 +644,134 @@ fn get_exchange_offers(plasma_client: web::Data<PlasmaClientShell>) -> Result<Ht
     Ok(HttpResponse::Ok().json(orders))
 }
 
+// Order Book
+#[derive(Deserialize, Debug)]
+struct GetOrderBookRequest {
+    token_address: Address,
+    c_token_address: Address,
+}
+
+#[derive(Serialize)]
+struct OrderBookEntry {
+    exchange_id: String,
+    maker: Address,
+    start: u64,
+    end: u64,
+    c_amount: u64,
+    expiry_block: Option<u64>,
+    fill_size: Option<u64>,
+}
+
+fn get_order_book(
+    params: web::Query<GetOrderBookRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let orders: Vec<OrderBookEntry> = plasma_client
+        .get_order_book(params.token_address, params.c_token_address)
+        .iter()
+        .map(|order| OrderBookEntry {
+            exchange_id: encode_hex(&order.get_state_update().get_hash()),
+            maker: order.get_maker(),
+            start: order.get_range().get_start(),
+            end: order.get_range().get_end(),
+            c_amount: order.get_c_amount(),
+            expiry_block: order.get_expiry_block(),
+            fill_size: order.get_fill_size(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+// Take Best Order
+#[derive(Deserialize, Debug)]
+struct TakeBestOrderRequest {
+    token_address: Address,
+    c_token_address: Address,
+    amount: u64,
+    session: String,
+}
+
+fn take_best_order(
+    body: web::Json<TakeBestOrderRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let batch = plasma_client
+        .take_best_order(
+            &session,
+            body.token_address,
+            body.c_token_address,
+            body.amount,
+        )
+        .map_err(error::ErrorBadRequest)?;
+    let ranges: Vec<(u64, u64)> = batch
+        .get_entries()
+        .iter()
+        .map(|e| (e.get_range().get_start(), e.get_range().get_end()))
+        .collect();
+    Ok(HttpResponse::Ok().json(ranges))
+}
+
+// Fill Order
+#[derive(Deserialize, Debug)]
+struct FillOrderRequest {
+    token_address: Address,
+    c_token_address: Address,
+    start: u64,
+    amount: u64,
+    session: String,
+}
+
+fn fill_order(
+    body: web::Json<FillOrderRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let batch = plasma_client
+        .fill_order(
+            &session,
+            body.token_address,
+            body.c_token_address,
+            body.start,
+            body.amount,
+        )
+        .map_err(error::ErrorBadRequest)?;
+    let ranges: Vec<(u64, u64)> = batch
+        .get_entries()
+        .iter()
+        .map(|e| (e.get_range().get_start(), e.get_range().get_end()))
+        .collect();
+    Ok(HttpResponse::Ok().json(ranges))
+}
+
+// Cancel Order
+#[derive(Deserialize, Debug)]
+struct CancelOrderRequest {
+    token_address: Address,
+    start: u64,
+    end: u64,
+    session: String,
+}
+
+fn cancel_order(
+    body: web::Json<CancelOrderRequest>,
+    plasma_client: web::Data<PlasmaClientShell>,
+) -> Result<HttpResponse> {
+    let session = decode_session(body.session.clone()).unwrap();
+    let txs = plasma_client
+        .cancel_order(
+            &session,
+            body.token_address,
+            Range::new(body.start, body.end),
+        )
+        .map_err(error::ErrorBadRequest)?;
+    let ranges: Vec<(u64, u64)> = txs
+        .iter()
+        .map(|tx| (tx.get_range().get_start(), tx.get_range().get_end()))
+        .collect();
+    Ok(HttpResponse::Ok().json(ranges))
+}
+
 // Get Exchange History
 #[derive(Deserialize, Serialize)]
 enum ExchangeHistoryType {
@@ -348,22 +893,26 @@ fn send_exchange(
                 order.counter_party.token_address,
                 will_update_range,
             );
-            plasma_client.send_transaction(
-                &session,
-                Some(order.counter_party.token_address),
-                will_update_range.get_start(),
-                will_update_range.get_end(),
-                property1,
-                metadata1,
-            );
-            plasma_client.send_transaction(
-                &session,
-                Some(order.token_address),
-                order.start,
-                order.end,
-                property2,
-                metadata2,
-            );
+            plasma_client
+                .send_transaction(
+                    &session,
+                    Some(order.counter_party.token_address),
+                    will_update_range.get_start(),
+                    will_update_range.get_end(),
+                    property1,
+                    metadata1,
+                )
+                .map_err(error::ErrorBadRequest)?;
+            plasma_client
+                .send_transaction(
+                    &session,
+                    Some(order.token_address),
+                    order.start,
+                    order.end,
+                    property2,
+                    metadata2,
+                )
+                .map_err(error::ErrorBadRequest)?;
             Ok(HttpResponse::Ok().json(SendExchange {
                 from: body.from,
                 exchange_id: body.exchange_id.clone(),
@@ -402,15 +951,19 @@ fn create_exchange_offer(
             &session,
             body.offer.counter_party.token_address,
             Integer(body.offer.counter_party.amount),
+            body.offer.expiry_block,
+            Some(body.offer.amount).filter(|_| body.offer.partially_fillable),
         );
-        plasma_client.send_transaction(
-            &session,
-            Some(body.offer.token_address),
-            range.get_start(),
-            range.get_start() + body.offer.amount,
-            property,
-            metadata,
-        );
+        plasma_client
+            .send_transaction(
+                &session,
+                Some(body.offer.token_address),
+                range.get_start(),
+                range.get_start() + body.offer.amount,
+                property,
+                metadata,
+            )
+            .map_err(error::ErrorBadRequest)?;
         Ok(HttpResponse::Ok().json(CreateExchangeOfferRequest {
             from: body.from,
             offer: body.offer.clone(),
@@ -434,19 +987,53 @@ pub fn main() {
             string_to_address("9FBDa871d559710256a2502A2517b794B482Db40"),
         );
         client.connect();
+        client.register_tokens(default_tokens()).unwrap();
         client.initialize();
+        client.set_universal_decision_contract_address(string_to_address(
+            "661E0De345B6AE4848c4Efd7F4094ae1014091F7",
+        ));
+        client.enable_watchtower(
+            string_to_address("661E0De345B6AE4848c4Efd7F4094ae1014091F7"),
+            WatchtowerPolicy::default(),
+        );
         let data = web::Data::new(client);
         App::new()
             .wrap(Logger::default())
             .register_data(data)
             .route("/create_account", web::post().to(create_account))
+            .route("/import_keystore", web::post().to(import_keystore))
+            .route("/unlock_account", web::post().to(unlock_account))
+            .route("/lock_account", web::post().to(lock_account))
             .route("/get_all_tokens", web::post().to(get_all_tokens))
             .route("/get_balance", web::get().to(get_balance))
             .route("/get_payment_history", web::get().to(get_payment_history))
             .route("/send_payment", web::post().to(send_payment))
+            .route("/send_batch_payment", web::post().to(send_batch_payment))
+            .route("/preview_payment", web::post().to(preview_payment))
+            .route("/estimate_fee", web::post().to(estimate_fee))
+            .route(
+                "/get_fragmentation_metrics",
+                web::get().to(get_fragmentation_metrics),
+            )
+            .route("/defragment", web::post().to(defragment))
+            .route(
+                "/get_misbehaviour_evidences",
+                web::get().to(get_misbehaviour_evidences),
+            )
+            .route("/delegate_monitoring", web::post().to(delegate_monitoring))
+            .route(
+                "/get_watchtower_audit_log",
+                web::get().to(get_watchtower_audit_log),
+            )
+            .route("/get_exits", web::get().to(get_exits))
+            .route("/get_mass_exit_plan", web::get().to(get_mass_exit_plan))
             .route("/get_exchange_offers", web::get().to(get_exchange_offers))
             .route("/get_exchange_history", web::get().to(get_exchange_history))
             .route("/send_exchange", web::post().to(send_exchange))
+            .route("/get_order_book", web::get().to(get_order_book))
+            .route("/take_best_order", web::post().to(take_best_order))
+            .route("/fill_order", web::post().to(fill_order))
+            .route("/cancel_order", web::post().to(cancel_order))
             .route(
                 "/create_exchange_offer",
                 web::post().to(create_exchange_offer),
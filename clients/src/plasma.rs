@@ -1,18 +1,36 @@
 pub mod block_db;
 pub mod block_manager;
+pub mod block_scheduler;
+pub mod block_sync;
+pub mod block_validator;
+pub mod coin_selection;
 pub mod command;
+pub mod data_availability;
+pub mod defragmentation;
 pub mod error;
+pub mod fee;
+pub mod hd_wallet;
+pub mod inclusion_promise;
+pub mod mempool;
+pub mod misbehaviour;
+pub mod order_book;
 pub mod plasma_aggregator;
 pub mod plasma_block;
+pub mod plasma_channel;
 pub mod plasma_client;
 pub mod query;
 pub mod state_db;
 pub mod state_manager;
 pub mod token;
+pub mod token_db;
+pub mod tx_journal;
 pub mod utils;
 pub mod wallet_db;
 pub mod wallet_manager;
+pub mod watchtower;
 
-pub use command::{Command, FetchBlockRequest, NewTransactionEvent};
+pub use command::{
+    Command, CommandResponse, FetchBlockRangeRequest, FetchBlockRequest, NewTransactionEvent,
+};
 pub use plasma_aggregator::PlasmaAggregator;
 pub use plasma_client::{PlasmaClient, PlasmaClientController, PlasmaClientShell};
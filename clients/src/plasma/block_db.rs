@@ -1,8 +1,11 @@
 use super::command::NewTransactionEvent;
 use super::error::{Error, ErrorKind};
 use super::plasma_block::PlasmaBlock;
+use abi_derive::{AbiDecodable, AbiEncodable};
 use abi_utils::{Decodable, Encodable};
 use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::Address;
 use ovm::types::{Integer, StateUpdate};
 use plasma_db::{
     traits::{kvs::KeyValueStore, rangestore::RangeStore},
@@ -12,6 +15,20 @@ use plasma_db::{
 const MIN_RANGE: u64 = 0;
 const MAX_RANGE: u64 = std::u64::MAX;
 
+/// Root of a block which was built but whose submission to CommitmentContract
+/// has not been confirmed yet, or the root submitted last.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct BlockRootRecord {
+    pub block_number: Integer,
+    pub root: Bytes,
+}
+
+impl BlockRootRecord {
+    pub fn new(block_number: Integer, root: Bytes) -> Self {
+        Self { block_number, root }
+    }
+}
+
 pub struct BlockDb<'a, KVS: KeyValueStore> {
     db: &'a RangeDbImpl<KVS>,
 }
@@ -21,11 +38,35 @@ impl<'a, KVS: KeyValueStore> BlockDb<'a, KVS> {
         BlockDb { db: range_db }
     }
 
+    /// Remembers that deposit_contract_address has queued entries.
+    /// Queues are keyed by deposit contract because ranges of different tokens overlap.
+    fn add_queued_token(&self, deposit_contract_address: Address) -> Result<(), Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .bucket(&Bytes::from("queued_tokens").into())
+            .put(&deposit_contract_address.as_bytes().into(), &[])
+            .map_err::<Error, _>(Into::into)
+    }
+
+    fn get_queued_tokens(&self) -> Vec<Address> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .bucket(&Bytes::from("queued_tokens").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| Address::from_slice(kv.get_key().as_bytes()))
+            .collect()
+    }
+
     pub fn enqueue_state_update(&self, state_update: &StateUpdate) -> Result<(), Error> {
         let range = state_update.get_range();
-
+        let token = state_update.get_deposit_contract_address();
+        self.add_queued_token(token)?;
         self.db
             .bucket(&Bytes::from(&"queued_state_updates"[..]))
+            .bucket(&Bytes::from(token.as_bytes()))
             .put(range.get_start(), range.get_end(), &state_update.to_abi())
             .map_err::<Error, _>(Into::into)?;
         Ok(())
@@ -33,65 +74,94 @@ impl<'a, KVS: KeyValueStore> BlockDb<'a, KVS> {
 
     pub fn get_queued_state_updates(
         &self,
+        deposit_contract_address: Address,
         start: u64,
         end: u64,
     ) -> Result<Vec<StateUpdate>, Error> {
-        let res = self
-            .db
+        self.db
             .bucket(&Bytes::from(&"queued_state_updates"[..]))
+            .bucket(&Bytes::from(deposit_contract_address.as_bytes()))
             .get(start, end)?
             .iter()
-            .map(|range| StateUpdate::from_abi(range.get_value()).unwrap())
-            .collect();
-
-        Ok(res)
+            .map(|range| StateUpdate::from_abi(range.get_value()).map_err(Into::into))
+            .collect()
     }
 
     pub fn get_pending_state_updates(&self) -> Result<Vec<StateUpdate>, Error> {
-        let res = self
-            .db
-            .bucket(&Bytes::from(&"queued_state_updates"[..]))
-            .get(MIN_RANGE, MAX_RANGE)?
-            .iter()
-            .map(|range| StateUpdate::from_abi(range.get_value()).unwrap())
-            .collect();
+        let mut res = vec![];
+        for token in self.get_queued_tokens() {
+            res.extend(self.get_queued_state_updates(token, MIN_RANGE, MAX_RANGE)?);
+        }
         Ok(res)
     }
 
     pub fn delete_all_queued_state_updates(&self) -> Result<(), Error> {
+        for token in self.get_queued_tokens() {
+            self.delete_queued_state_updates(token, MIN_RANGE, MAX_RANGE)?;
+        }
+        Ok(())
+    }
+
+    pub fn delete_queued_state_updates(
+        &self,
+        deposit_contract_address: Address,
+        start: u64,
+        end: u64,
+    ) -> Result<(), Error> {
         let _ = self
             .db
             .bucket(&Bytes::from(&"queued_state_updates"[..]))
-            .del_batch(MIN_RANGE, MAX_RANGE)?;
+            .bucket(&Bytes::from(deposit_contract_address.as_bytes()))
+            .del_batch(start, end)?;
         Ok(())
     }
 
     pub fn enqueue_tx(&self, tx: NewTransactionEvent) -> Result<(), Error> {
         let range = tx.transaction.get_range();
-
+        let token = tx.transaction.get_deposit_contract_address();
+        self.add_queued_token(token)?;
         self.db
             .bucket(&Bytes::from(&"queued_txs"[..]))
+            .bucket(&Bytes::from(token.as_bytes()))
             .put(range.get_start(), range.get_end(), &tx.to_abi())
             .map_err::<Error, _>(Into::into)?;
         Ok(())
     }
 
     pub fn get_pending_txs(&self) -> Result<Vec<NewTransactionEvent>, Error> {
-        let res = self
-            .db
-            .bucket(&Bytes::from(&"queued_txs"[..]))
-            .get(MIN_RANGE, MAX_RANGE)?
-            .iter()
-            .map(|range| NewTransactionEvent::from_abi(range.get_value()).unwrap())
-            .collect();
+        let mut res = vec![];
+        for token in self.get_queued_tokens() {
+            for range in self
+                .db
+                .bucket(&Bytes::from(&"queued_txs"[..]))
+                .bucket(&Bytes::from(token.as_bytes()))
+                .get(MIN_RANGE, MAX_RANGE)?
+                .iter()
+            {
+                res.push(NewTransactionEvent::from_abi(range.get_value())?);
+            }
+        }
         Ok(res)
     }
 
     pub fn delete_all_queued_txs(&self) -> Result<(), Error> {
+        for token in self.get_queued_tokens() {
+            self.delete_queued_txs(token, MIN_RANGE, MAX_RANGE)?;
+        }
+        Ok(())
+    }
+
+    pub fn delete_queued_txs(
+        &self,
+        deposit_contract_address: Address,
+        start: u64,
+        end: u64,
+    ) -> Result<(), Error> {
         let _ = self
             .db
             .bucket(&Bytes::from(&"queued_txs"[..]))
-            .del_batch(MIN_RANGE, MAX_RANGE)?;
+            .bucket(&Bytes::from(deposit_contract_address.as_bytes()))
+            .del_batch(start, end)?;
         Ok(())
     }
 
@@ -119,6 +189,77 @@ impl<'a, KVS: KeyValueStore> BlockDb<'a, KVS> {
             .put(&index.into(), &block.to_abi())?;
         Ok(())
     }
+
+    pub fn get_current_block_number(&self) -> Result<Option<u64>, Error> {
+        let result = self
+            .db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .get(&Bytes::from("current_block_number").into())?;
+        Ok(result.map(|b| Integer::from(Bytes::from(b)).0))
+    }
+
+    pub fn save_current_block_number(&self, block_number: u64) -> Result<(), Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .put(
+                &Bytes::from("current_block_number").into(),
+                &Bytes::from(Integer::new(block_number)),
+            )?;
+        Ok(())
+    }
+
+    pub fn get_last_submitted_root(&self) -> Result<Option<BlockRootRecord>, Error> {
+        let result = self
+            .db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .get(&Bytes::from("last_submitted_root").into())?;
+        if let Some(record) = result {
+            Ok(Some(BlockRootRecord::from_abi(&record)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn save_last_submitted_root(&self, record: &BlockRootRecord) -> Result<(), Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .put(&Bytes::from("last_submitted_root").into(), &record.to_abi())?;
+        Ok(())
+    }
+
+    pub fn save_unsubmitted_root(&self, record: &BlockRootRecord) -> Result<(), Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .bucket(&Bytes::from("unsubmitted_roots").into())
+            .put(&record.block_number.0.into(), &record.to_abi())?;
+        Ok(())
+    }
+
+    /// Returns roots which are not confirmed to be submitted ordered by block number.
+    pub fn get_unsubmitted_roots(&self) -> Result<Vec<BlockRootRecord>, Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .bucket(&Bytes::from("unsubmitted_roots").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| BlockRootRecord::from_abi(kv.get_value()).map_err(Into::into))
+            .collect()
+    }
+
+    pub fn delete_unsubmitted_root(&self, block_number: u64) -> Result<(), Error> {
+        self.db
+            .get_db()
+            .bucket(&Bytes::from("plasma_block_db").into())
+            .bucket(&Bytes::from("unsubmitted_roots").into())
+            .del(&block_number.into())?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -165,6 +306,56 @@ mod tests {
         assert_eq!(block.get_state_updates().len(), 1);
     }
 
+    #[test]
+    fn test_recover_block_number_and_unsubmitted_roots() {
+        let db = CoreDbMemoryImpl::open("test");
+        let range_db = RangeDbImpl::from(db);
+        let block_db = BlockDb::from(&range_db);
+
+        assert_eq!(block_db.get_current_block_number().unwrap(), None);
+        assert!(block_db.save_current_block_number(3).is_ok());
+        assert_eq!(block_db.get_current_block_number().unwrap(), Some(3));
+
+        let record1 = BlockRootRecord::new(Integer::new(1), Bytes::from(&[1; 32][..]));
+        let record2 = BlockRootRecord::new(Integer::new(2), Bytes::from(&[2; 32][..]));
+        assert!(block_db.save_unsubmitted_root(&record2).is_ok());
+        assert!(block_db.save_unsubmitted_root(&record1).is_ok());
+        assert_eq!(
+            block_db.get_unsubmitted_roots().unwrap(),
+            vec![record1.clone(), record2.clone()]
+        );
+        assert!(block_db.delete_unsubmitted_root(1).is_ok());
+        assert!(block_db.save_last_submitted_root(&record1).is_ok());
+        assert_eq!(block_db.get_unsubmitted_roots().unwrap(), vec![record2]);
+        assert_eq!(block_db.get_last_submitted_root().unwrap(), Some(record1));
+    }
+
+    #[test]
+    fn test_queues_are_keyed_by_deposit_contract() {
+        let db = CoreDbMemoryImpl::open("test");
+        let range_db = RangeDbImpl::from(db);
+        let block_db = BlockDb::from(&range_db);
+        let token1 = Address::from([1; 20]);
+        let token2 = Address::from([2; 20]);
+        for token in &[token1, token2] {
+            assert!(block_db
+                .enqueue_state_update(&StateUpdate::new(
+                    Integer::new(1),
+                    *token,
+                    Range::new(0, 10),
+                    Property::new(Address::zero(), vec![]),
+                ))
+                .is_ok());
+        }
+        assert_eq!(block_db.get_pending_state_updates().unwrap().len(), 2);
+        assert!(block_db.delete_queued_state_updates(token1, 0, 10).is_ok());
+        let pending = block_db.get_pending_state_updates().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].get_deposit_contract_address(), token2);
+        assert!(block_db.delete_all_queued_state_updates().is_ok());
+        assert!(block_db.get_pending_state_updates().unwrap().is_empty());
+    }
+
     #[test]
     fn test_abi_plasma_block() {
         let plasma_block = PlasmaBlock::new(
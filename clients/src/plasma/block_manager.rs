@@ -1,4 +1,4 @@
-use super::block_db::BlockDb;
+use super::block_db::{BlockDb, BlockRootRecord};
 use super::command::NewTransactionEvent;
 use super::error::Error;
 use super::plasma_block::PlasmaBlock;
@@ -10,24 +10,54 @@ use plasma_db::prelude::*;
 use std::fs::File;
 use std::io::BufReader;
 
+/// Where block roots are submitted.
+#[derive(Clone, Debug)]
+pub struct CommitmentChainOptions {
+    /// JSON-RPC endpoint of the main chain.
+    pub endpoint: String,
+    /// Path of the ABI JSON of CommitmentChain contract.
+    pub abi_path: String,
+}
+
+impl Default for CommitmentChainOptions {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://127.0.0.1:8545".to_string(),
+            abi_path: "../contract-wrapper/CommitmentChain.json".to_string(),
+        }
+    }
+}
+
 pub struct BlockManager<KVS: KeyValueStore> {
     db: RangeDbImpl<KVS>,
     commitment_contract_address: Address,
     aggregator_address: Address,
     current_block_number: u64,
+    commitment_chain_options: CommitmentChainOptions,
 }
 
 impl<KVS: KeyValueStore + DatabaseTrait> BlockManager<KVS> {
-    pub fn new(aggregator_address: Address, commitment_contract_address: Address) -> Self {
-        let db = KVS::open("plasma_aggregator_db");
+    /// Opens block db and restores current block number from it.
+    pub fn new(
+        db_name: &str,
+        aggregator_address: Address,
+        commitment_contract_address: Address,
+    ) -> Result<Self, Error> {
+        let db = KVS::open(db_name);
         let db = RangeDbImpl::from(db);
+        let current_block_number = BlockDb::from(&db).get_current_block_number()?.unwrap_or(1);
 
-        BlockManager {
+        Ok(BlockManager {
             aggregator_address,
             commitment_contract_address,
             db,
-            current_block_number: 1,
-        }
+            current_block_number,
+            commitment_chain_options: CommitmentChainOptions::default(),
+        })
+    }
+
+    pub fn set_commitment_chain_options(&mut self, options: CommitmentChainOptions) {
+        self.commitment_chain_options = options;
     }
 
     pub fn get_queued_state_updates(&self) -> Vec<StateUpdate> {
@@ -47,10 +77,22 @@ impl<KVS: KeyValueStore + DatabaseTrait> BlockManager<KVS> {
         block_db.enqueue_tx(tx).map_err::<Error, _>(Into::into)
     }
 
-    /// generate block from queued state updates
-    /// save block in block_db, submit to CommitmentContract
-    /// return generated block
-    pub fn submit_next_block(&mut self) -> Result<(), Error> {
+    /// remove queued state updates and transactions of the deposit contract within the range
+    pub fn dequeue_range(
+        &self,
+        deposit_contract_address: Address,
+        start: u64,
+        end: u64,
+    ) -> Result<(), Error> {
+        let block_db = BlockDb::from(&self.db);
+        block_db.delete_queued_state_updates(deposit_contract_address, start, end)?;
+        block_db.delete_queued_txs(deposit_contract_address, start, end)
+    }
+
+    /// generate block from queued state updates and save it in block_db.
+    /// Its root is marked unsubmitted until resubmit_unsubmitted_blocks sends it to CommitmentContract,
+    /// so that it can be resubmitted after a crash or a failed submission.
+    pub fn build_next_block(&mut self) -> Result<BlockRootRecord, Error> {
         let block_db = BlockDb::from(&self.db);
         let state_updates = block_db
             .get_pending_state_updates()
@@ -59,24 +101,54 @@ impl<KVS: KeyValueStore + DatabaseTrait> BlockManager<KVS> {
         let mut block = PlasmaBlock::new(self.current_block_number, state_updates, transactions);
 
         let root = block.merkelize()?;
+        let record = BlockRootRecord::new(Integer::new(block.get_block_number()), root);
+
+        block_db.save_block(&block)?;
+        block_db.save_unsubmitted_root(&record)?;
+        block_db.delete_all_queued_state_updates()?;
+        block_db.delete_all_queued_txs()?;
+        let next_block_number = self.get_next_block_number();
+        block_db.save_current_block_number(next_block_number)?;
+        self.save_next_block_number(next_block_number);
+        Ok(record)
+    }
 
-        // send root hash to commitment contract
-        let f = File::open("../contract-wrapper/CommitmentChain.json").unwrap();
+    /// Submits roots of blocks which were built but not confirmed to be submitted.
+    /// Returns block numbers of resubmitted blocks.
+    pub fn resubmit_unsubmitted_blocks(&self) -> Result<Vec<u64>, Error> {
+        let block_db = BlockDb::from(&self.db);
+        let mut resubmitted = vec![];
+        for record in block_db.get_unsubmitted_roots()? {
+            self.submit_root(&record)?;
+            resubmitted.push(record.block_number.0);
+        }
+        Ok(resubmitted)
+    }
+
+    pub fn get_last_submitted_root(&self) -> Result<Option<BlockRootRecord>, Error> {
+        BlockDb::from(&self.db).get_last_submitted_root()
+    }
+
+    /// Sends root hash to commitment contract and marks it submitted once the transaction is mined.
+    fn submit_root(&self, record: &BlockRootRecord) -> Result<(), Error> {
+        let options = &self.commitment_chain_options;
+        let f = File::open(&options.abi_path)?;
         let reader = BufReader::new(f);
-        let contract_abi = ContractABI::load(reader).unwrap();
+        let contract_abi = ContractABI::load(reader)?;
         let contract = CommitmentContractAdaptor::new(
-            "http://127.0.0.1:8545",
+            &options.endpoint,
             self.commitment_contract_address,
             contract_abi,
-        )
-        .unwrap();
-        let _ = contract.submit_block(self.aggregator_address, block.get_block_number(), root)?;
-
-        let _ = block_db.save_block(&block);
-        let _ = block_db.delete_all_queued_state_updates();
-        let _ = block_db.delete_all_queued_txs();
-        self.save_next_block_number(self.get_next_block_number());
-        Ok(())
+        )?;
+        contract.submit_block(
+            self.aggregator_address,
+            record.block_number.0,
+            record.root.clone(),
+        )?;
+
+        let block_db = BlockDb::from(&self.db);
+        block_db.delete_unsubmitted_root(record.block_number.0)?;
+        block_db.save_last_submitted_root(record)
     }
 
     pub fn get_block_range(&self, block_number: Integer) -> Result<PlasmaBlock, Error> {
@@ -0,0 +1,397 @@
+use super::plasma_aggregator::PlasmaAggregator;
+use futures::{Async, Future, Poll, Stream};
+use plasma_db::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::timer::Interval;
+
+/// What to do when a policy is triggered without queued state updates.
+/// Empty blocks are never produced because they can't be merkelized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyBlockPolicy {
+    /// Counts a skipped block and restarts the interval as if a block was produced.
+    Skip,
+    /// Keeps the interval elapsed so that the first queued state update is submitted at the next poll.
+    WaitForStateUpdate,
+}
+
+/// Policies deciding when the aggregator produces the next block.
+/// Each policy is disabled by setting None.
+#[derive(Clone, Debug)]
+pub struct BlockSchedulerOptions {
+    /// Produces a block when this duration has passed since the last block.
+    pub interval: Option<Duration>,
+    /// Produces a block as soon as this number of state updates is queued.
+    pub max_queued_state_updates: Option<usize>,
+    /// Produces a block when the oldest pending transaction is older than this duration.
+    pub max_pending_age: Option<Duration>,
+    /// Handles policies triggered without queued state updates.
+    pub empty_block_policy: EmptyBlockPolicy,
+    /// How often the policies are checked.
+    pub poll_interval: Duration,
+}
+
+impl Default for BlockSchedulerOptions {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(5)),
+            max_queued_state_updates: None,
+            max_pending_age: None,
+            empty_block_policy: EmptyBlockPolicy::Skip,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubmitReason {
+    Interval,
+    QueueFull,
+    PendingTooOld,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BlockSchedulerMetrics {
+    pub submitted_blocks: u64,
+    pub failed_submissions: u64,
+    pub skipped_empty_blocks: u64,
+    pub expired_transactions: u64,
+    pub failed_expirations: u64,
+    /// The latest error of expiring transactions or submitting a block.
+    pub last_error: Option<String>,
+    pub last_block_number: Option<u64>,
+    pub last_block_state_updates: usize,
+    pub last_submit_reason: Option<SubmitReason>,
+    pub last_submitted_at: Option<Instant>,
+}
+
+pub struct BlockScheduler {
+    options: BlockSchedulerOptions,
+    last_block_at: Instant,
+    metrics: BlockSchedulerMetrics,
+}
+
+impl BlockScheduler {
+    pub fn new(options: BlockSchedulerOptions) -> Self {
+        Self {
+            options,
+            last_block_at: Instant::now(),
+            metrics: Default::default(),
+        }
+    }
+
+    pub fn get_options(&self) -> &BlockSchedulerOptions {
+        &self.options
+    }
+
+    pub fn get_metrics(&self) -> &BlockSchedulerMetrics {
+        &self.metrics
+    }
+
+    /// Returns the reason to produce a block now, or None if no policy is triggered.
+    pub fn should_submit(
+        &self,
+        now: Instant,
+        queued_state_updates: usize,
+        oldest_pending_at: Option<Instant>,
+    ) -> Option<SubmitReason> {
+        if let Some(max) = self.options.max_queued_state_updates {
+            if queued_state_updates >= max {
+                return Some(SubmitReason::QueueFull);
+            }
+        }
+        if let (Some(max_age), Some(oldest)) = (self.options.max_pending_age, oldest_pending_at) {
+            if now.duration_since(oldest) >= max_age {
+                return Some(SubmitReason::PendingTooOld);
+            }
+        }
+        if let Some(interval) = self.options.interval {
+            if now.duration_since(self.last_block_at) >= interval {
+                return Some(SubmitReason::Interval);
+            }
+        }
+        None
+    }
+
+    /// Expires stale pending transactions, then checks policies and submits next block if one of them is triggered.
+    pub fn tick<KVS: KeyValueStore + DatabaseTrait>(
+        &mut self,
+        aggregator: &mut PlasmaAggregator<KVS>,
+        now: Instant,
+    ) -> Option<SubmitReason> {
+        match aggregator.expire_stale_transactions(now) {
+            Ok(expired) => self.metrics.expired_transactions += expired.len() as u64,
+            Err(e) => {
+                self.metrics.failed_expirations += 1;
+                self.metrics.last_error = Some(format!("failed to expire transactions: {}", e));
+            }
+        }
+        let queued_state_updates = aggregator.get_queued_state_update_count();
+        let reason = self.should_submit(
+            now,
+            queued_state_updates,
+            aggregator.get_oldest_pending_transaction_time(),
+        )?;
+        if queued_state_updates == 0 {
+            self.metrics.skipped_empty_blocks += 1;
+            if self.options.empty_block_policy == EmptyBlockPolicy::Skip {
+                self.last_block_at = now;
+            }
+            return None;
+        }
+        let block_number = aggregator.get_current_block_number();
+        match aggregator.submit_next_block() {
+            Ok(()) => {
+                self.metrics.submitted_blocks += 1;
+                self.metrics.last_block_number = Some(block_number);
+                self.metrics.last_block_state_updates = queued_state_updates;
+                self.metrics.last_submit_reason = Some(reason);
+                self.metrics.last_submitted_at = Some(now);
+            }
+            Err(e) => {
+                self.metrics.failed_submissions += 1;
+                self.metrics.last_error =
+                    Some(format!("failed to submit block {}: {}", block_number, e));
+            }
+        }
+        self.last_block_at = now;
+        Some(reason)
+    }
+}
+
+/// Handle to stop the block producer and read its metrics from other tasks.
+#[derive(Clone)]
+pub struct BlockProducerHandle {
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<Mutex<BlockSchedulerMetrics>>,
+}
+
+impl BlockProducerHandle {
+    /// Stops the block producer at its next poll.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    pub fn get_metrics(&self) -> BlockSchedulerMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+}
+
+/// Background task producing blocks with BlockScheduler.
+/// Ticks run in `tokio_threadpool::blocking` because submitting a block waits for the L1 receipt.
+/// ```ignore
+/// let (producer, handle) = BlockProducer::new(aggregator.clone(), Default::default());
+/// tokio::spawn(producer);
+/// ```
+pub struct BlockProducer<KVS: KeyValueStore> {
+    aggregator: Arc<Mutex<PlasmaAggregator<KVS>>>,
+    scheduler: BlockScheduler,
+    interval: Interval,
+    /// A tick which is due but waits for a blocking thread.
+    tick_pending: bool,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<Mutex<BlockSchedulerMetrics>>,
+}
+
+impl<KVS: KeyValueStore + DatabaseTrait> BlockProducer<KVS> {
+    pub fn new(
+        aggregator: Arc<Mutex<PlasmaAggregator<KVS>>>,
+        options: BlockSchedulerOptions,
+    ) -> (Self, BlockProducerHandle) {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let metrics = Arc::new(Mutex::new(BlockSchedulerMetrics::default()));
+        let interval = Interval::new_interval(options.poll_interval);
+        (
+            Self {
+                aggregator,
+                scheduler: BlockScheduler::new(options),
+                interval,
+                tick_pending: false,
+                shutdown: shutdown.clone(),
+                metrics: metrics.clone(),
+            },
+            BlockProducerHandle { shutdown, metrics },
+        )
+    }
+}
+
+impl<KVS: KeyValueStore + DatabaseTrait> Future for BlockProducer<KVS> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                return Ok(Async::Ready(()));
+            }
+            if self.tick_pending {
+                let aggregator = &self.aggregator;
+                let scheduler = &mut self.scheduler;
+                let tick = || {
+                    let mut aggregator = aggregator.lock().unwrap();
+                    scheduler.tick(&mut aggregator, Instant::now());
+                };
+                match tokio_threadpool::blocking(tick) {
+                    Ok(Async::Ready(())) => {}
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    // not on a threadpool, e.g. on a current thread runtime
+                    Err(_) => {
+                        let mut aggregator = self.aggregator.lock().unwrap();
+                        self.scheduler.tick(&mut aggregator, Instant::now());
+                    }
+                }
+                self.tick_pending = false;
+                *self.metrics.lock().unwrap() = self.scheduler.get_metrics().clone();
+            }
+            match self.interval.poll().map_err(|_| ())? {
+                Async::Ready(Some(_)) => self.tick_pending = true,
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::block_manager::CommitmentChainOptions;
+    use super::super::mempool::MempoolOptions;
+    use super::super::plasma_client::PlasmaClientShell;
+    use super::*;
+    use abi_utils::Encodable;
+    use bytes::Bytes;
+    use ethereum_types::Address;
+    use ethsign::SecretKey;
+    use ovm::deciders::SignVerifier;
+    use plasma_core::data_structure::{Metadata, Range, Transaction, TransactionParams};
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+
+    const PRIVATE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+
+    fn create_aggregator() -> PlasmaAggregator<CoreDbMemoryImpl> {
+        let mut aggregator = PlasmaAggregator::new(
+            "test",
+            Address::zero(),
+            Address::zero(),
+            Address::zero(),
+            PRIVATE_KEY,
+        )
+        .unwrap();
+        aggregator.insert_test_ranges();
+        aggregator
+    }
+
+    fn create_transaction(range: Range) -> Transaction {
+        let secret_key = SecretKey::from_raw(&hex::decode(PRIVATE_KEY).unwrap()).unwrap();
+        let parameters = PlasmaClientShell::create_ownership_state_object(Address::zero()).to_abi();
+        let transaction_params =
+            TransactionParams::new(Address::zero(), range, Bytes::from(parameters));
+        let signature = SignVerifier::sign(&secret_key, &Bytes::from(transaction_params.to_abi()));
+        Transaction::from_params(transaction_params, signature, Metadata::default())
+    }
+
+    fn create_scheduler(empty_block_policy: EmptyBlockPolicy) -> BlockScheduler {
+        BlockScheduler::new(BlockSchedulerOptions {
+            interval: Some(Duration::from_secs(10)),
+            max_queued_state_updates: None,
+            max_pending_age: None,
+            empty_block_policy,
+            poll_interval: Duration::from_secs(1),
+        })
+    }
+
+    #[test]
+    fn test_should_submit_by_queue_size() {
+        let scheduler = BlockScheduler::new(BlockSchedulerOptions {
+            interval: None,
+            max_queued_state_updates: Some(10),
+            max_pending_age: None,
+            empty_block_policy: EmptyBlockPolicy::Skip,
+            poll_interval: Duration::from_secs(1),
+        });
+        let now = Instant::now();
+        assert_eq!(scheduler.should_submit(now, 9, None), None);
+        assert_eq!(
+            scheduler.should_submit(now, 10, None),
+            Some(SubmitReason::QueueFull)
+        );
+    }
+
+    #[test]
+    fn test_should_submit_by_age_and_interval() {
+        let scheduler = BlockScheduler::new(BlockSchedulerOptions {
+            interval: Some(Duration::from_secs(10)),
+            max_queued_state_updates: None,
+            max_pending_age: Some(Duration::from_secs(3)),
+            empty_block_policy: EmptyBlockPolicy::Skip,
+            poll_interval: Duration::from_secs(1),
+        });
+        let now = Instant::now();
+        assert_eq!(scheduler.should_submit(now, 1, Some(now)), None);
+        assert_eq!(
+            scheduler.should_submit(now + Duration::from_secs(3), 1, Some(now)),
+            Some(SubmitReason::PendingTooOld)
+        );
+        assert_eq!(
+            scheduler.should_submit(now + Duration::from_secs(10), 1, None),
+            Some(SubmitReason::Interval)
+        );
+    }
+
+    #[test]
+    fn test_tick_skips_empty_block() {
+        let mut aggregator = create_aggregator();
+        let now = Instant::now() + Duration::from_secs(10);
+        let mut skipping = create_scheduler(EmptyBlockPolicy::Skip);
+        assert_eq!(skipping.tick(&mut aggregator, now), None);
+        assert_eq!(skipping.get_metrics().skipped_empty_blocks, 1);
+        assert_eq!(skipping.get_metrics().submitted_blocks, 0);
+        assert_eq!(skipping.should_submit(now, 1, None), None);
+        let mut waiting = create_scheduler(EmptyBlockPolicy::WaitForStateUpdate);
+        assert_eq!(waiting.tick(&mut aggregator, now), None);
+        assert_eq!(waiting.get_metrics().skipped_empty_blocks, 1);
+        assert_eq!(
+            waiting.should_submit(now, 1, None),
+            Some(SubmitReason::Interval)
+        );
+        assert_eq!(aggregator.get_current_block_number(), 1);
+    }
+
+    #[test]
+    fn test_tick_records_failed_submission_and_expiry() {
+        let mut aggregator = create_aggregator();
+        aggregator.set_commitment_chain_options(CommitmentChainOptions {
+            abi_path: "not_found.json".to_string(),
+            ..Default::default()
+        });
+        aggregator.set_mempool_options(MempoolOptions {
+            max_age: Duration::from_secs(0),
+            ..Default::default()
+        });
+        let mut scheduler = create_scheduler(EmptyBlockPolicy::Skip);
+        assert!(aggregator
+            .ingest_transaction(create_transaction(Range::new(5, 15)))
+            .is_ok());
+        // the interval hasn't passed, so the stale transaction is only expired
+        let now = Instant::now() + Duration::from_secs(1);
+        assert_eq!(scheduler.tick(&mut aggregator, now), None);
+        assert_eq!(scheduler.get_metrics().expired_transactions, 1);
+        assert!(aggregator
+            .ingest_transaction(create_transaction(Range::new(5, 15)))
+            .is_ok());
+        let now = Instant::now() + Duration::from_secs(10);
+        aggregator.set_mempool_options(Default::default());
+        assert_eq!(
+            scheduler.tick(&mut aggregator, now),
+            Some(SubmitReason::Interval)
+        );
+        let metrics = scheduler.get_metrics();
+        assert_eq!(metrics.failed_submissions, 1);
+        assert_eq!(metrics.submitted_blocks, 0);
+        assert!(metrics.last_error.is_some());
+        assert_eq!(metrics.last_block_number, None);
+        // the block is persisted even though it wasn't submitted to the commitment chain
+        assert_eq!(aggregator.get_current_block_number(), 2);
+    }
+}
@@ -0,0 +1,247 @@
+use super::error::{Error, ErrorKind};
+use super::plasma_block::PlasmaBlock;
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{Event, EventParam, ParamType};
+use ethereum_types::Address;
+use event_watcher::event_watcher::Log;
+use ovm::types::StateUpdate;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Stores roots observed in BlockSubmitted events and the height client has synced to.
+pub struct SyncDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> SyncDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_submitted_root(&self, block_number: u64, root: &Bytes) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("block_sync").into())
+            .bucket(&Bytes::from("submitted_roots").into())
+            .put(&block_number.into(), root)?;
+        let latest = self.get_latest_submitted_block_number()?;
+        if latest.map_or(true, |n| n < block_number) {
+            self.db.bucket(&Bytes::from("block_sync").into()).put(
+                &Bytes::from("latest_submitted_block_number").into(),
+                &Bytes::from(Integer::new(block_number)),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_submitted_root(&self, block_number: u64) -> Result<Option<Bytes>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("block_sync").into())
+            .bucket(&Bytes::from("submitted_roots").into())
+            .get(&block_number.into())?;
+        Ok(result.map(Bytes::from))
+    }
+
+    pub fn get_latest_submitted_block_number(&self) -> Result<Option<u64>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("block_sync").into())
+            .get(&Bytes::from("latest_submitted_block_number").into())?;
+        Ok(result.map(|b| Integer::from(Bytes::from(b)).0))
+    }
+
+    /// Returns 0 if client hasn't synced any block yet.
+    pub fn get_last_synced_block_number(&self) -> Result<u64, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("block_sync").into())
+            .get(&Bytes::from("last_synced_block_number").into())?;
+        Ok(result.map_or(0, |b| Integer::from(Bytes::from(b)).0))
+    }
+
+    pub fn set_last_synced_block_number(&self, block_number: u64) -> Result<(), Error> {
+        self.db.bucket(&Bytes::from("block_sync").into()).put(
+            &Bytes::from("last_synced_block_number").into(),
+            &Bytes::from(Integer::new(block_number)),
+        )?;
+        Ok(())
+    }
+
+    /// Buffers the next block to sync which arrived before its root was submitted.
+    /// Only one block is kept because blocks are applied in order.
+    pub fn put_pending_block(&self, block: &PlasmaBlock) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("block_sync").into())
+            .put(&Bytes::from("pending_block").into(), &block.to_abi())?;
+        Ok(())
+    }
+
+    /// Removes and returns the buffered block if it is block_number.
+    pub fn take_pending_block(&self, block_number: u64) -> Result<Option<PlasmaBlock>, Error> {
+        let bucket = self.db.bucket(&Bytes::from("block_sync").into());
+        let key = Bytes::from("pending_block").into();
+        let block = match bucket.get(&key)? {
+            Some(b) => PlasmaBlock::from_abi(&b)?,
+            None => return Ok(None),
+        };
+        if block.get_block_number() != block_number {
+            return Ok(None);
+        }
+        bucket.del(&key)?;
+        Ok(Some(block))
+    }
+
+    /// Returns block range which client has to fetch to catch up, if any.
+    pub fn get_missing_block_range(&self) -> Result<Option<(u64, u64)>, Error> {
+        let from = self.get_last_synced_block_number()? + 1;
+        match self.get_latest_submitted_block_number()? {
+            Some(to) if from <= to => Ok(Some((from, to))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// BlockSubmitted(uint64 blockNumber, bytes32 root) of CommitmentChain.
+pub fn block_submitted_event() -> Event {
+    Event {
+        name: "BlockSubmitted".to_owned(),
+        inputs: vec![
+            EventParam {
+                name: "blockNumber".to_owned(),
+                kind: ParamType::Uint(64),
+                indexed: false,
+            },
+            EventParam {
+                name: "root".to_owned(),
+                kind: ParamType::FixedBytes(32),
+                indexed: false,
+            },
+        ],
+        anonymous: false,
+    }
+}
+
+/// Decodes block number and root of BlockSubmitted event.
+pub fn decode_block_submitted(log: &Log) -> Option<(u64, Bytes)> {
+    if log.event_signature != block_submitted_event().signature() || log.params.len() < 2 {
+        return None;
+    }
+    let block_number = log.params[0].token.clone().to_uint()?;
+    let root = log.params[1].token.clone().to_fixed_bytes()?;
+    if block_number > u64::max_value().into() {
+        return None;
+    }
+    Some((block_number.as_u64(), Bytes::from(root)))
+}
+
+/// Recomputes the root of served block and compares it with the root committed on L1.
+pub fn verify_block_root(block: &mut PlasmaBlock, expected_root: &Bytes) -> Result<Bytes, Error> {
+    let root = block.merkelize()?;
+    if &root == expected_root {
+        Ok(root)
+    } else {
+        Err(Error::from(ErrorKind::InvalidBlock))
+    }
+}
+
+/// Filters state updates which client has to store.
+/// A state update is relevant if it is owned by one of addresses, is an order,
+/// or overrides a range client already knows.
+pub fn filter_relevant_state_updates(
+    state_updates: &[StateUpdate],
+    addresses: &[Address],
+    known_state_updates: &[StateUpdate],
+) -> Vec<StateUpdate> {
+    state_updates
+        .iter()
+        .filter(|s| {
+            s.try_get_owner()
+                .map_or(false, |owner| addresses.contains(&owner))
+                || s.is_order_state()
+                || known_state_updates.iter().any(|k| {
+                    k.get_deposit_contract_address() == s.get_deposit_contract_address()
+                        && k.get_range().get_start() < s.get_range().get_end()
+                        && s.get_range().get_start() < k.get_range().get_end()
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::statements::plasma::create_ownership_state_object;
+    use plasma_core::data_structure::Range;
+    use plasma_db::{impls::kvs::CoreDbMemoryImpl, traits::DatabaseTrait};
+
+    #[test]
+    fn test_missing_block_range() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let sync_db = SyncDb::new(&kvs);
+        assert_eq!(sync_db.get_missing_block_range().unwrap(), None);
+        assert!(sync_db.put_submitted_root(1, &Bytes::from("root1")).is_ok());
+        assert!(sync_db.put_submitted_root(2, &Bytes::from("root2")).is_ok());
+        assert_eq!(sync_db.get_missing_block_range().unwrap(), Some((1, 2)));
+        assert!(sync_db.set_last_synced_block_number(2).is_ok());
+        assert_eq!(sync_db.get_missing_block_range().unwrap(), None);
+        assert_eq!(
+            sync_db.get_submitted_root(1).unwrap(),
+            Some(Bytes::from("root1"))
+        );
+    }
+
+    #[test]
+    fn test_pending_block() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let sync_db = SyncDb::new(&kvs);
+        let state_update = StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(0, 10),
+            create_ownership_state_object(Address::zero()),
+        );
+        let block = PlasmaBlock::new(1, vec![state_update], vec![]);
+        assert!(sync_db.put_pending_block(&block).is_ok());
+        assert!(sync_db.take_pending_block(2).unwrap().is_none());
+        let pending = sync_db.take_pending_block(1).unwrap().unwrap();
+        assert_eq!(pending.get_block_number(), 1);
+        assert!(sync_db.take_pending_block(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_filter_relevant_state_updates() {
+        let alice = Address::random();
+        let bob = Address::random();
+        let to_alice = StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(0, 10),
+            create_ownership_state_object(alice),
+        );
+        let to_bob = StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(10, 20),
+            create_ownership_state_object(bob),
+        );
+        let bob_from_alice = StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(20, 30),
+            create_ownership_state_object(bob),
+        );
+        let known = StateUpdate::new(
+            Integer::new(0),
+            Address::zero(),
+            Range::new(20, 40),
+            create_ownership_state_object(alice),
+        );
+        let result = filter_relevant_state_updates(
+            &[to_alice.clone(), to_bob, bob_from_alice.clone()],
+            &[alice],
+            &[known],
+        );
+        assert_eq!(result, vec![to_alice, bob_from_alice]);
+    }
+}
@@ -0,0 +1,409 @@
+use super::block_sync::verify_block_root;
+use super::command::NewTransactionEvent;
+use super::error::{Error, ErrorKind};
+use super::plasma_block::PlasmaBlock;
+use super::utils::is_covered;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ovm::db::{RangeAtBlockDb, SignedByDb, TransactionDb};
+use ovm::deciders::SignVerifier;
+use ovm::property_executor::PropertyExecutor;
+use ovm::types::{Property, StateUpdate};
+use plasma_core::data_structure::Transaction;
+use plasma_db::traits::kvs::KeyValueStore;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The root of the served block differs from the root committed on L1.
+    RootMismatch,
+    /// A state update has a block number other than the block's.
+    WrongBlockNumber,
+    /// No transaction in the block produces the state update.
+    UnjustifiedStateUpdate,
+    /// Two state updates of the same deposit contract overlap.
+    OverlappingRanges,
+}
+
+impl Violation {
+    fn to_integer(self) -> Integer {
+        Integer::new(match self {
+            Violation::RootMismatch => 0,
+            Violation::WrongBlockNumber => 1,
+            Violation::UnjustifiedStateUpdate => 2,
+            Violation::OverlappingRanges => 3,
+        })
+    }
+
+    fn from_integer(violation: Integer) -> Self {
+        match violation.0 {
+            1 => Violation::WrongBlockNumber,
+            2 => Violation::UnjustifiedStateUpdate,
+            3 => Violation::OverlappingRanges,
+            _ => Violation::RootMismatch,
+        }
+    }
+}
+
+/// Proof that the aggregator committed an invalid block.
+/// block is the encoded PlasmaBlock and indices point to the offending state updates in it.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct FraudEvidence {
+    violation: Integer,
+    block: Bytes,
+    root: Bytes,
+    indices: Vec<Integer>,
+}
+
+impl FraudEvidence {
+    pub fn new(violation: Integer, block: Bytes, root: Bytes, indices: Vec<Integer>) -> Self {
+        Self {
+            violation,
+            block,
+            root,
+            indices,
+        }
+    }
+
+    pub fn get_violation(&self) -> Violation {
+        Violation::from_integer(self.violation)
+    }
+
+    pub fn get_block(&self) -> Result<PlasmaBlock, Error> {
+        Ok(PlasmaBlock::from_abi(&self.block)?)
+    }
+
+    pub fn get_root(&self) -> &Bytes {
+        &self.root
+    }
+
+    pub fn get_indices(&self) -> Vec<usize> {
+        self.indices.iter().map(|i| i.0 as usize).collect()
+    }
+
+    /// Validates the block again and checks that it has the same violation.
+    pub fn verify<KVS: KeyValueStore>(&self, decider: &PropertyExecutor<KVS>) -> bool {
+        match self.get_block() {
+            Ok(mut block) => validate_block(&mut block, &self.root, decider)
+                .iter()
+                .any(|e| e == self),
+            Err(_) => false,
+        }
+    }
+}
+
+fn create_evidence(
+    violation: Violation,
+    block: &PlasmaBlock,
+    root: &Bytes,
+    indices: &[usize],
+) -> FraudEvidence {
+    FraudEvidence::new(
+        violation.to_integer(),
+        Bytes::from(block.to_abi()),
+        root.clone(),
+        indices.iter().map(|i| Integer::new(*i as u64)).collect(),
+    )
+}
+
+/// Whether all signatures of the transaction recover a signer.
+fn is_signed(transaction: &Transaction) -> bool {
+    let message = transaction.get_signed_message();
+    let signatures = transaction.get_signatures();
+    !signatures.is_empty()
+        && signatures
+            .iter()
+            .all(|signature| SignVerifier::try_recover(signature, &message).is_some())
+}
+
+/// Stores the transaction at the blocks of its prev states and its signatures so that deciders can find them.
+pub fn store_transaction_witnesses<KVS: KeyValueStore>(
+    decider: &PropertyExecutor<KVS>,
+    tx: &NewTransactionEvent,
+) -> Result<(), Error> {
+    let transaction_db = TransactionDb::new(decider.get_range_db());
+    let signed_by_db = SignedByDb::new(decider.get_db());
+    for previous_block_number in tx.prev_state_block_numbers.iter() {
+        transaction_db.put_transaction(previous_block_number.0, tx.transaction.clone());
+    }
+    let message = tx.transaction.get_signed_message();
+    for signature in tx.transaction.get_signatures() {
+        let signer = SignVerifier::try_recover(&signature, &message)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidBlock))?;
+        signed_by_db
+            .store_witness(signer, message.clone(), signature)
+            .map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
+    }
+    Ok(())
+}
+
+/// Whether the transaction deprecates the state updates it spends.
+/// Prev states are read from synced blocks. States at block 0 are deposits which aren't in any block,
+/// so only the signature of transactions spending them can be checked.
+fn deprecates_prev_states<KVS: KeyValueStore>(
+    tx: &NewTransactionEvent,
+    decider: &PropertyExecutor<KVS>,
+) -> bool {
+    let transaction = &tx.transaction;
+    let range_at_block_db = RangeAtBlockDb::new(decider.get_range_db());
+    let spends_deposit = tx.prev_state_block_numbers.iter().any(|n| n.0 == 0);
+    let mut prev_states = vec![];
+    for block_number in tx.prev_state_block_numbers.iter().filter(|n| n.0 > 0) {
+        match range_at_block_db.get_state_updates(
+            *block_number,
+            transaction.get_deposit_contract_address(),
+            transaction.get_range(),
+        ) {
+            Ok(state_updates) => prev_states.extend(state_updates),
+            Err(_) => return false,
+        }
+    }
+    prev_states.sort_by_key(|s| s.get_range().get_start());
+    if !spends_deposit && !is_covered(transaction.get_range(), &prev_states) {
+        return false;
+    }
+    store_transaction_witnesses(decider, tx).is_ok()
+        && prev_states
+            .iter()
+            .all(|prev_state| prev_state.verify_state_transition(decider, transaction))
+}
+
+/// Whether a signed transaction of the block produces the state update and deprecates its prev states.
+fn is_justified<KVS: KeyValueStore>(
+    state_update: &StateUpdate,
+    block: &PlasmaBlock,
+    decider: &PropertyExecutor<KVS>,
+) -> bool {
+    block.get_transactions().iter().any(|tx| {
+        let transaction = &tx.transaction;
+        transaction.get_deposit_contract_address() == state_update.get_deposit_contract_address()
+            && transaction.get_range() == state_update.get_range()
+            && is_signed(transaction)
+            && tx
+                .prev_state_block_numbers
+                .iter()
+                .all(|n| n.0 < block.get_block_number())
+            && Property::from_abi(transaction.get_parameters())
+                .map_or(false, |p| &p == state_update.get_property())
+            && deprecates_prev_states(tx, decider)
+    })
+}
+
+/// Validates the block served by the aggregator against the root committed on L1.
+/// Returns evidences of all violations. The block is valid if nothing is returned.
+/// State updates aren't checked if the root doesn't match because the block isn't the committed one.
+pub fn validate_block<KVS: KeyValueStore>(
+    block: &mut PlasmaBlock,
+    committed_root: &Bytes,
+    decider: &PropertyExecutor<KVS>,
+) -> Vec<FraudEvidence> {
+    if verify_block_root(block, committed_root).is_err() {
+        return vec![create_evidence(
+            Violation::RootMismatch,
+            block,
+            committed_root,
+            &[],
+        )];
+    }
+    let mut evidences = vec![];
+    let state_updates = block.get_state_updates();
+    for (i, s) in state_updates.iter().enumerate() {
+        if s.get_block_number().0 != block.get_block_number() {
+            evidences.push(create_evidence(
+                Violation::WrongBlockNumber,
+                block,
+                committed_root,
+                &[i],
+            ));
+        } else if !is_justified(s, block, decider) {
+            evidences.push(create_evidence(
+                Violation::UnjustifiedStateUpdate,
+                block,
+                committed_root,
+                &[i],
+            ));
+        }
+        for (j, t) in state_updates.iter().enumerate().skip(i + 1) {
+            if s.get_deposit_contract_address() == t.get_deposit_contract_address()
+                && s.get_range().get_start() < t.get_range().get_end()
+                && t.get_range().get_start() < s.get_range().get_end()
+            {
+                evidences.push(create_evidence(
+                    Violation::OverlappingRanges,
+                    block,
+                    committed_root,
+                    &[i, j],
+                ));
+            }
+        }
+    }
+    evidences
+}
+
+/// Fraud evidences keyed by block number.
+pub struct FraudDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> FraudDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_evidences(
+        &self,
+        block_number: u64,
+        evidences: &[FraudEvidence],
+    ) -> Result<(), Error> {
+        let bucket = self
+            .db
+            .bucket(&Bytes::from("fraud_evidences").into())
+            .bucket(&block_number.into());
+        for (i, evidence) in evidences.iter().enumerate() {
+            bucket.put(&(i as u64).into(), &evidence.to_abi())?;
+        }
+        Ok(())
+    }
+
+    /// Records the first invalid block. Blocks after it aren't synced.
+    pub fn set_fraudulent_block(&self, block_number: u64) -> Result<(), Error> {
+        if self.get_fraudulent_block()?.is_none() {
+            self.db.put(
+                &Bytes::from("fraudulent_block_number").into(),
+                &Bytes::from(Integer::new(block_number)),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_fraudulent_block(&self) -> Result<Option<u64>, Error> {
+        Ok(self
+            .db
+            .get(&Bytes::from("fraudulent_block_number").into())?
+            .map(|b| Integer::from(Bytes::from(b)).0))
+    }
+
+    pub fn get_evidences(&self, block_number: u64) -> Result<Vec<FraudEvidence>, Error> {
+        self.db
+            .bucket(&Bytes::from("fraud_evidences").into())
+            .bucket(&block_number.into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| FraudEvidence::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plasma::command::NewTransactionEvent;
+    use crate::plasma::utils::string_to_address;
+    use ethereum_types::Address;
+    use ethsign::SecretKey;
+    use ovm::property_executor::PropertyExecuterOptions;
+    use ovm::statements::plasma::create_ownership_state_object;
+    use plasma_core::data_structure::{
+        Metadata, Range, Transaction, TransactionParams, PAYMENT_TYPE,
+    };
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+
+    const PRIVATE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+
+    fn create_decider() -> PropertyExecutor<CoreDbMemoryImpl> {
+        let decider = PropertyExecutor::new(PropertyExecuterOptions {
+            is_aggregator: false,
+            db_name: "test".to_string(),
+        });
+        // the state spent by transactions in block 2
+        let prev_state = StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(0, 30),
+            create_ownership_state_object(string_to_address(
+                "627306090abab3a6e1400e9345bc60c78a8bef57",
+            )),
+        );
+        RangeAtBlockDb::new(decider.get_range_db())
+            .store_witness(Bytes::default(), true, Bytes::default(), prev_state)
+            .unwrap();
+        decider
+    }
+
+    fn create_state_update(start: u64, end: u64) -> StateUpdate {
+        StateUpdate::new(
+            Integer::new(2),
+            Address::zero(),
+            Range::new(start, end),
+            create_ownership_state_object(Address::zero()),
+        )
+    }
+
+    fn create_transaction(state_update: &StateUpdate, is_signed: bool) -> NewTransactionEvent {
+        let transaction_params = TransactionParams::new(
+            state_update.get_deposit_contract_address(),
+            state_update.get_range(),
+            Bytes::from(state_update.get_property().to_abi()),
+        );
+        let signature = if is_signed {
+            let secret_key = SecretKey::from_raw(&hex::decode(PRIVATE_KEY).unwrap()).unwrap();
+            SignVerifier::sign(&secret_key, &Bytes::from(transaction_params.to_abi()))
+        } else {
+            Bytes::from(&[1; 65][..])
+        };
+        NewTransactionEvent::new(
+            vec![Integer::new(1)],
+            Transaction::from_params(
+                transaction_params,
+                signature,
+                Metadata::new(PAYMENT_TYPE, Address::zero(), Address::zero()),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_validate_block() {
+        let decider = create_decider();
+        let s1 = create_state_update(0, 10);
+        let s2 = create_state_update(5, 20);
+        let s3 = create_state_update(20, 30);
+        let mut valid = PlasmaBlock::new(2, vec![s1.clone()], vec![create_transaction(&s1, true)]);
+        let root = valid.merkelize().unwrap();
+        assert!(validate_block(&mut valid, &root, &decider).is_empty());
+        let evidences = validate_block(&mut valid, &Bytes::from(&[0; 32][..]), &decider);
+        assert_eq!(evidences.len(), 1);
+        assert_eq!(evidences[0].get_violation(), Violation::RootMismatch);
+
+        // s3 is produced by a transaction which isn't signed by the owner of its prev state
+        let mut invalid = PlasmaBlock::new(
+            2,
+            vec![s1.clone(), s2.clone(), s3.clone()],
+            vec![
+                create_transaction(&s1, true),
+                create_transaction(&s2, true),
+                create_transaction(&s3, false),
+            ],
+        );
+        let root = invalid.merkelize().unwrap();
+        let evidences = validate_block(&mut invalid, &root, &decider);
+        assert_eq!(evidences.len(), 2);
+        assert_eq!(evidences[0].get_violation(), Violation::OverlappingRanges);
+        assert_eq!(evidences[0].get_indices(), vec![0, 1]);
+        assert_eq!(
+            evidences[1].get_violation(),
+            Violation::UnjustifiedStateUpdate
+        );
+        assert_eq!(evidences[1].get_indices(), vec![2]);
+        let decoded = FraudEvidence::from_abi(&evidences[1].to_abi()).unwrap();
+        assert!(decoded.verify(&decider));
+
+        let fraud_db = FraudDb::new(decider.get_db());
+        fraud_db.put_evidences(2, &evidences).unwrap();
+        assert_eq!(fraud_db.get_evidences(2).unwrap(), evidences);
+        assert_eq!(fraud_db.get_fraudulent_block().unwrap(), None);
+        fraud_db.set_fraudulent_block(2).unwrap();
+        fraud_db.set_fraudulent_block(3).unwrap();
+        assert_eq!(fraud_db.get_fraudulent_block().unwrap(), Some(2));
+    }
+}
@@ -0,0 +1,299 @@
+use super::error::{Error, ErrorKind};
+use ovm::types::StateUpdate;
+use plasma_core::data_structure::Range;
+
+/// Upper bound of search steps for ExactMatch.
+const MAX_EXACT_MATCH_STEPS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoinSelectionPolicy {
+    /// Spends as few ranges as possible. The smallest range covering the amount is preferred.
+    FewestRanges,
+    /// Spends ranges updated in older blocks first.
+    OldestFirst,
+    /// Spends whole ranges whose total is exactly the amount, so that no change is left.
+    ExactMatch,
+}
+
+impl Default for CoinSelectionPolicy {
+    fn default() -> Self {
+        CoinSelectionPolicy::FewestRanges
+    }
+}
+
+/// A part of owned range which will be spent.
+/// The rest of the owned range is kept as change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedRange {
+    state_update: StateUpdate,
+    range: Range,
+}
+
+impl SelectedRange {
+    pub fn new(state_update: StateUpdate, range: Range) -> Self {
+        Self {
+            state_update,
+            range,
+        }
+    }
+
+    pub fn get_state_update(&self) -> &StateUpdate {
+        &self.state_update
+    }
+
+    pub fn get_range(&self) -> Range {
+        self.range
+    }
+
+    pub fn get_amount(&self) -> u64 {
+        self.range.get_amount()
+    }
+
+    pub fn get_change(&self) -> Option<Range> {
+        let end = self.state_update.get_range().get_end();
+        if self.range.get_end() < end {
+            Some(Range::new(self.range.get_end(), end))
+        } else {
+            None
+        }
+    }
+}
+
+/// Selects ranges to spend amount from owned state updates.
+/// State updates which aren't ownership states, e.g. orders, are never selected.
+pub fn select_coins(
+    owned: &[StateUpdate],
+    amount: u64,
+    policy: CoinSelectionPolicy,
+) -> Result<Vec<SelectedRange>, Error> {
+    if amount == 0 {
+        return Err(Error::from(ErrorKind::InvalidParameter));
+    }
+    let mut candidates: Vec<StateUpdate> = owned
+        .iter()
+        .filter(|s| s.is_ownership_state() && s.get_amount() > 0)
+        .cloned()
+        .collect();
+    let total: u64 = candidates.iter().map(|s| s.get_amount()).sum();
+    if total < amount {
+        return Err(Error::from(ErrorKind::InsufficientBalance));
+    }
+    match policy {
+        CoinSelectionPolicy::FewestRanges => {
+            if let Some(s) = candidates
+                .iter()
+                .filter(|s| s.get_amount() >= amount)
+                .min_by_key(|s| s.get_amount())
+            {
+                return take_until(&[s.clone()], amount);
+            }
+            candidates.sort_by(|a, b| b.get_amount().cmp(&a.get_amount()));
+            take_until(&candidates, amount)
+        }
+        CoinSelectionPolicy::OldestFirst => {
+            candidates.sort_by_key(|s| (s.get_block_number().0, s.get_range().get_start()));
+            take_until(&candidates, amount)
+        }
+        CoinSelectionPolicy::ExactMatch => {
+            candidates.sort_by(|a, b| b.get_amount().cmp(&a.get_amount()));
+            let mut chosen = vec![];
+            let mut budget = MAX_EXACT_MATCH_STEPS;
+            if search_exact(&candidates, 0, amount, &mut chosen, &mut budget) {
+                Ok(chosen
+                    .iter()
+                    .map(|i| &candidates[*i])
+                    .map(|s| SelectedRange::new(s.clone(), s.get_range()))
+                    .collect())
+            } else {
+                Err(Error::from(ErrorKind::ExactMatchNotFound))
+            }
+        }
+    }
+}
+
+/// Spends ranges in given order from the start of each range.
+fn take_until(sorted: &[StateUpdate], amount: u64) -> Result<Vec<SelectedRange>, Error> {
+    let mut remaining = amount;
+    let mut selected = vec![];
+    for s in sorted {
+        if remaining == 0 {
+            break;
+        }
+        let start = s.get_range().get_start();
+        let spent = std::cmp::min(remaining, s.get_amount());
+        selected.push(SelectedRange::new(
+            s.clone(),
+            Range::new(start, start + spent),
+        ));
+        remaining -= spent;
+    }
+    if remaining > 0 {
+        Err(Error::from(ErrorKind::InsufficientBalance))
+    } else {
+        Ok(selected)
+    }
+}
+
+/// Divides selected ranges among recipients in order.
+/// Returns ranges for each amount. The total of amounts must be the total of selected ranges.
+pub fn split_selected_ranges(
+    selected: &[SelectedRange],
+    amounts: &[u64],
+) -> Result<Vec<Vec<SelectedRange>>, Error> {
+    let total: u64 = selected.iter().map(|s| s.get_amount()).sum();
+    let requested = amounts
+        .iter()
+        .try_fold(0u64, |sum, amount| sum.checked_add(*amount))
+        .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+    if amounts.iter().any(|a| *a == 0) || requested != total {
+        return Err(Error::from(ErrorKind::InvalidParameter));
+    }
+    let mut pieces = selected.iter().map(|s| (s, s.get_range().get_start()));
+    let mut current = pieces.next();
+    let mut result = vec![];
+    for amount in amounts {
+        let mut remaining = *amount;
+        let mut ranges = vec![];
+        while remaining > 0 {
+            let (s, start) = current.ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+            let spent = std::cmp::min(remaining, s.get_range().get_end() - start);
+            ranges.push(SelectedRange::new(
+                s.get_state_update().clone(),
+                Range::new(start, start + spent),
+            ));
+            remaining -= spent;
+            current = if start + spent < s.get_range().get_end() {
+                Some((s, start + spent))
+            } else {
+                pieces.next()
+            };
+        }
+        result.push(ranges);
+    }
+    Ok(result)
+}
+
+fn search_exact(
+    candidates: &[StateUpdate],
+    from: usize,
+    remaining: u64,
+    chosen: &mut Vec<usize>,
+    budget: &mut usize,
+) -> bool {
+    if remaining == 0 {
+        return true;
+    }
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+    for (i, candidate) in candidates.iter().enumerate().skip(from) {
+        let amount = candidate.get_amount();
+        if amount <= remaining {
+            chosen.push(i);
+            if search_exact(candidates, i + 1, remaining - amount, chosen, budget) {
+                return true;
+            }
+            chosen.pop();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi_utils::Integer;
+    use ethereum_types::{Address, H256};
+    use ovm::statements::create_hash_lock_property;
+    use ovm::statements::plasma::create_ownership_state_object;
+
+    fn create_state_update(block_number: u64, start: u64, end: u64) -> StateUpdate {
+        StateUpdate::new(
+            Integer::new(block_number),
+            Address::zero(),
+            Range::new(start, end),
+            create_ownership_state_object(Address::zero()),
+        )
+    }
+
+    fn owned() -> Vec<StateUpdate> {
+        vec![
+            create_state_update(3, 0, 10),
+            create_state_update(1, 20, 50),
+            create_state_update(2, 60, 65),
+        ]
+    }
+
+    #[test]
+    fn test_fewest_ranges() {
+        let selected = select_coins(&owned(), 8, CoinSelectionPolicy::FewestRanges).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].get_range(), Range::new(0, 8));
+        assert_eq!(selected[0].get_change(), Some(Range::new(8, 10)));
+        let selected = select_coins(&owned(), 35, CoinSelectionPolicy::FewestRanges).unwrap();
+        assert_eq!(
+            selected.iter().map(|s| s.get_range()).collect::<Vec<_>>(),
+            vec![Range::new(20, 50), Range::new(0, 5)]
+        );
+    }
+
+    #[test]
+    fn test_oldest_first() {
+        let selected = select_coins(&owned(), 32, CoinSelectionPolicy::OldestFirst).unwrap();
+        assert_eq!(
+            selected.iter().map(|s| s.get_range()).collect::<Vec<_>>(),
+            vec![Range::new(20, 50), Range::new(60, 62)]
+        );
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let selected = select_coins(&owned(), 15, CoinSelectionPolicy::ExactMatch).unwrap();
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|s| s.get_change().is_none()));
+        assert!(select_coins(&owned(), 12, CoinSelectionPolicy::ExactMatch).is_err());
+        assert!(select_coins(&owned(), 100, CoinSelectionPolicy::FewestRanges).is_err());
+    }
+
+    #[test]
+    fn test_only_ownership_states_are_selected() {
+        let mut owned = owned();
+        owned.push(StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(100, 200),
+            create_hash_lock_property(H256::zero()),
+        ));
+        let selected = select_coins(&owned, 40, CoinSelectionPolicy::FewestRanges).unwrap();
+        assert!(selected.iter().all(|s| s.get_range().get_end() <= 65));
+        assert!(select_coins(&owned, 46, CoinSelectionPolicy::OldestFirst).is_err());
+    }
+
+    #[test]
+    fn test_split_selected_ranges() {
+        let selected = select_coins(&owned(), 35, CoinSelectionPolicy::FewestRanges).unwrap();
+        let split = split_selected_ranges(&selected, &[10, 22, 3]).unwrap();
+        let ranges: Vec<Vec<Range>> = split
+            .iter()
+            .map(|r| r.iter().map(|s| s.get_range()).collect())
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                vec![Range::new(20, 30)],
+                vec![Range::new(30, 50), Range::new(0, 2)],
+                vec![Range::new(2, 5)],
+            ]
+        );
+        assert!(split_selected_ranges(&selected, &[10, 20]).is_err());
+        // the sum of amounts overflows and must not wrap around to the total
+        assert_eq!(
+            split_selected_ranges(&selected, &[36, std::u64::MAX])
+                .unwrap_err()
+                .kind()
+                .code(),
+            ErrorKind::InvalidParameter.code()
+        );
+    }
+}
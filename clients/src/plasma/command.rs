@@ -1,43 +1,102 @@
+use super::error::{Error, ErrorKind};
+use super::inclusion_promise::InclusionPromise;
+use super::order_book::{MatchOrderRequest, OrderBookUpdate};
 use super::plasma_block::PlasmaBlock;
 use abi_derive::{AbiDecodable, AbiEncodable};
-use abi_utils::{Encodable, Integer};
+use abi_utils::{Decodable, Encodable, Integer};
 use bytes::Bytes;
 use ethabi::{ParamType, Token};
 use ovm::types::StateUpdateList;
-use plasma_core::data_structure::Transaction;
+use plasma_core::data_structure::{BatchTransaction, Transaction};
 
+/// request_id correlates a request with its CommandResponse. 0 means no response is expected.
 #[derive(Clone, Debug, AbiDecodable, AbiEncodable)]
 pub struct Command {
     pub command_type: Integer,
     pub body: Bytes,
+    pub request_id: Integer,
 }
 
 impl Command {
-    pub fn new(command_type: Integer, body: Bytes) -> Self {
-        Self { command_type, body }
+    pub fn new(command_type: Integer, body: Bytes, request_id: Integer) -> Self {
+        Self {
+            command_type,
+            body,
+            request_id,
+        }
+    }
+    pub fn with_request_id(mut self, request_id: u64) -> Self {
+        self.request_id = Integer::new(request_id);
+        self
+    }
+    pub fn create_transaction(transaction: &Transaction) -> Self {
+        Command {
+            command_type: Integer(0),
+            body: Bytes::from(transaction.to_abi()),
+            request_id: Integer(0),
+        }
     }
     pub fn create_fetch_block_request(block_number: Integer) -> Self {
         Command {
             command_type: Integer(1),
             body: Bytes::from(FetchBlockRequest { block_number }.to_abi()),
+            request_id: Integer(0),
         }
     }
     pub fn create_state_update_list(state_update_list: StateUpdateList) -> Self {
         Command {
             command_type: Integer(2),
             body: Bytes::from(state_update_list.to_abi()),
+            request_id: Integer(0),
         }
     }
     pub fn create_plasma_block(plasma_block: PlasmaBlock) -> Self {
         Command {
             command_type: Integer(3),
             body: Bytes::from(plasma_block.to_abi()),
+            request_id: Integer(0),
         }
     }
     pub fn create_new_tx_event(new_tx_event: NewTransactionEvent) -> Self {
         Command {
             command_type: Integer(4),
             body: Bytes::from(new_tx_event.to_abi()),
+            request_id: Integer(0),
+        }
+    }
+    pub fn create_fetch_block_range_request(from_block: Integer, to_block: Integer) -> Self {
+        Command {
+            command_type: Integer(5),
+            body: Bytes::from(FetchBlockRangeRequest::new(from_block, to_block).to_abi()),
+            request_id: Integer(0),
+        }
+    }
+    pub fn create_response(response: CommandResponse) -> Self {
+        Command {
+            command_type: Integer(6),
+            body: Bytes::from(response.to_abi()),
+            request_id: response.request_id,
+        }
+    }
+    pub fn create_batch_transaction(batch: &BatchTransaction) -> Self {
+        Command {
+            command_type: Integer(7),
+            body: Bytes::from(batch.to_abi()),
+            request_id: Integer(0),
+        }
+    }
+    pub fn create_order_book_update(update: &OrderBookUpdate) -> Self {
+        Command {
+            command_type: Integer(8),
+            body: Bytes::from(update.to_abi()),
+            request_id: Integer(0),
+        }
+    }
+    pub fn create_match_order_request(request: &MatchOrderRequest) -> Self {
+        Command {
+            command_type: Integer(9),
+            body: Bytes::from(request.to_abi()),
+            request_id: Integer(0),
         }
     }
 }
@@ -53,6 +112,26 @@ impl FetchBlockRequest {
     }
 }
 
+/// Maximum number of blocks served for a FetchBlockRangeRequest.
+pub const MAX_BLOCKS_PER_FETCH: u64 = 100;
+
+/// Requests blocks from from_block to to_block inclusive.
+/// At most MAX_BLOCKS_PER_FETCH blocks are served.
+#[derive(Clone, Debug, AbiDecodable, AbiEncodable)]
+pub struct FetchBlockRangeRequest {
+    pub from_block: Integer,
+    pub to_block: Integer,
+}
+
+impl FetchBlockRangeRequest {
+    pub fn new(from_block: Integer, to_block: Integer) -> Self {
+        Self {
+            from_block,
+            to_block,
+        }
+    }
+}
+
 /// prev_state_block_number is the block numbers which the transaction deprecated
 #[derive(Clone, Debug, AbiDecodable, AbiEncodable)]
 pub struct NewTransactionEvent {
@@ -68,3 +147,114 @@ impl NewTransactionEvent {
         }
     }
 }
+
+/// Response to a request. error_code is ErrorKind::code of the error or 0 on success.
+/// body is the encoded result, e.g. InclusionPromise for a transaction.
+#[derive(Clone, Debug, AbiDecodable, AbiEncodable)]
+pub struct CommandResponse {
+    pub request_id: Integer,
+    pub error_code: Integer,
+    pub message: Bytes,
+    pub body: Bytes,
+}
+
+impl CommandResponse {
+    pub fn new(request_id: Integer, error_code: Integer, message: Bytes, body: Bytes) -> Self {
+        Self {
+            request_id,
+            error_code,
+            message,
+            body,
+        }
+    }
+    pub fn ok(request_id: Integer, body: Bytes) -> Self {
+        Self::new(request_id, Integer(0), Bytes::default(), body)
+    }
+    pub fn error(request_id: Integer, error: &Error) -> Self {
+        Self::new(
+            request_id,
+            Integer::new(error.kind().code()),
+            Bytes::from(format!("{}", error)),
+            Bytes::default(),
+        )
+    }
+    pub fn is_ok(&self) -> bool {
+        self.error_code.0 == 0
+    }
+    pub fn get_error_kind(&self) -> Option<ErrorKind> {
+        if self.is_ok() {
+            None
+        } else {
+            Some(ErrorKind::from_code(self.error_code.0))
+        }
+    }
+    pub fn get_message(&self) -> String {
+        String::from_utf8_lossy(&self.message).to_string()
+    }
+    /// Decodes InclusionPromise of a transaction request.
+    /// Returns None if the transaction is accepted without a promise, e.g. a replaceable one.
+    pub fn into_inclusion_promise(self) -> Result<Option<InclusionPromise>, Error> {
+        if let Some(kind) = self.get_error_kind() {
+            return Err(Error::from(kind));
+        }
+        if self.body.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(InclusionPromise::from_abi(&self.body)?))
+    }
+    /// Encodes InclusionPromises of all entries of a batch as the body.
+    pub fn encode_inclusion_promises(promises: &[InclusionPromise]) -> Bytes {
+        Bytes::from(ethabi::encode(&[Token::Array(
+            promises.iter().map(|p| Token::Bytes(p.to_abi())).collect(),
+        )]))
+    }
+    /// Decodes InclusionPromises of a batch transaction request.
+    pub fn into_inclusion_promises(self) -> Result<Vec<InclusionPromise>, Error> {
+        if let Some(kind) = self.get_error_kind() {
+            return Err(Error::from(kind));
+        }
+        let tokens = ethabi::decode(&[ParamType::Array(Box::new(ParamType::Bytes))], &self.body)?;
+        match tokens[0].clone().to_array() {
+            Some(promises) => promises
+                .iter()
+                .map(|p| match p.clone().to_bytes() {
+                    Some(bytes) => Ok(InclusionPromise::from_abi(&bytes)?),
+                    None => Err(Error::from(ErrorKind::AbiDecode)),
+                })
+                .collect(),
+            None => Err(Error::from(ErrorKind::AbiDecode)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::{Address, H256};
+    use ethsign::SecretKey;
+    use plasma_core::data_structure::Range;
+
+    #[test]
+    fn test_transaction_accepted_without_promise() {
+        let response = CommandResponse::ok(Integer(1), Bytes::default());
+        let decoded = CommandResponse::from_abi(&response.to_abi()).unwrap();
+        assert_eq!(decoded.into_inclusion_promise().unwrap(), None);
+
+        let raw_key =
+            hex::decode("c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3")
+                .unwrap();
+        let promise = InclusionPromise::sign(
+            &SecretKey::from_raw(&raw_key).unwrap(),
+            H256::zero(),
+            Address::zero(),
+            Range::new(0, 10),
+            5,
+        );
+        let response = CommandResponse::ok(Integer(2), Bytes::from(promise.to_abi()));
+        assert_eq!(response.into_inclusion_promise().unwrap(), Some(promise));
+
+        let response =
+            CommandResponse::error(Integer(3), &Error::from(ErrorKind::InvalidTransaction));
+        assert!(response.into_inclusion_promise().is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use super::error::Error;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ovm::types::StateUpdate;
+use plasma_core::data_structure::Range;
+use plasma_db::traits::kvs::KeyValueStore;
+
+#[derive(Clone, Debug)]
+pub struct WithholdingPolicy {
+    /// Seconds to wait for a requested block before it is regarded as withheld.
+    pub timeout: u64,
+}
+
+impl Default for WithholdingPolicy {
+    fn default() -> Self {
+        Self { timeout: 300 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MassExitPolicy {
+    /// The number of exits started in one round so that exits don't congest L1.
+    pub exits_per_round: usize,
+}
+
+impl Default for MassExitPolicy {
+    fn default() -> Self {
+        Self {
+            exits_per_round: 50,
+        }
+    }
+}
+
+/// Exit of an owned range from the checkpoint at block_number.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct PlannedExit {
+    owner: Address,
+    deposit_contract_address: Address,
+    range: Range,
+    block_number: Integer,
+}
+
+impl PlannedExit {
+    pub fn new(
+        owner: Address,
+        deposit_contract_address: Address,
+        range: Range,
+        block_number: Integer,
+    ) -> Self {
+        Self {
+            owner,
+            deposit_contract_address,
+            range,
+            block_number,
+        }
+    }
+
+    /// Returns None unless the state update is an ownership state.
+    pub fn from_state_update(state_update: &StateUpdate) -> Option<Self> {
+        if !state_update.is_ownership_state() {
+            return None;
+        }
+        Some(Self::new(
+            state_update.try_get_owner()?,
+            state_update.get_deposit_contract_address(),
+            state_update.get_range(),
+            state_update.get_block_number(),
+        ))
+    }
+
+    pub fn get_owner(&self) -> Address {
+        self.owner
+    }
+
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.deposit_contract_address
+    }
+
+    pub fn get_range(&self) -> Range {
+        self.range
+    }
+
+    pub fn get_block_number(&self) -> Integer {
+        self.block_number
+    }
+}
+
+/// Exit started on L1. transaction_hash is the hash of the claim transaction.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct ExitRecord {
+    exit: PlannedExit,
+    transaction_hash: H256,
+}
+
+impl ExitRecord {
+    pub fn new(exit: PlannedExit, transaction_hash: H256) -> Self {
+        Self {
+            exit,
+            transaction_hash,
+        }
+    }
+
+    pub fn get_exit(&self) -> &PlannedExit {
+        &self.exit
+    }
+
+    pub fn get_transaction_hash(&self) -> H256 {
+        self.transaction_hash
+    }
+}
+
+/// Orders exits by priority.
+/// Older checkpoints come first because they are final earlier, then larger ranges.
+pub fn plan_exits(state_updates: &[StateUpdate]) -> Vec<PlannedExit> {
+    let mut exits: Vec<PlannedExit> = state_updates
+        .iter()
+        .filter_map(PlannedExit::from_state_update)
+        .collect();
+    exits.sort_by(|a, b| {
+        a.block_number
+            .0
+            .cmp(&b.block_number.0)
+            .then(b.range.get_amount().cmp(&a.range.get_amount()))
+    });
+    exits
+}
+
+/// Exits of many users split into rounds.
+/// Only states committed before the withheld block are exited because later states are unknown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MassExitPlan {
+    pub withheld_block_number: u64,
+    pub rounds: Vec<Vec<PlannedExit>>,
+}
+
+impl MassExitPlan {
+    pub fn create(
+        withheld_block_number: u64,
+        state_updates: &[StateUpdate],
+        policy: &MassExitPolicy,
+    ) -> Self {
+        let available: Vec<StateUpdate> = state_updates
+            .iter()
+            .filter(|s| s.get_block_number().0 < withheld_block_number)
+            .cloned()
+            .collect();
+        let rounds = plan_exits(&available)
+            .chunks(policy.exits_per_round.max(1))
+            .map(|round| round.to_vec())
+            .collect();
+        Self {
+            withheld_block_number,
+            rounds,
+        }
+    }
+
+    pub fn get_exit_count(&self) -> usize {
+        self.rounds.iter().map(|round| round.len()).sum()
+    }
+}
+
+/// Tracks blocks requested from the aggregator and exits started because of withheld blocks.
+pub struct AvailabilityDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> AvailabilityDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    /// Records the first time the block was requested. Later requests don't reset the time.
+    pub fn record_requested(&self, block_number: u64, now: u64) -> Result<(), Error> {
+        let bucket = self
+            .db
+            .bucket(&Bytes::from("data_availability").into())
+            .bucket(&Bytes::from("requested_at").into());
+        if bucket.get(&block_number.into())?.is_none() {
+            bucket.put(&block_number.into(), &Bytes::from(Integer::new(now)))?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_requested(&self, block_number: u64) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("data_availability").into())
+            .bucket(&Bytes::from("requested_at").into())
+            .del(&block_number.into())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_requested_at(&self, block_number: u64) -> Result<Option<u64>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("data_availability").into())
+            .bucket(&Bytes::from("requested_at").into())
+            .get(&block_number.into())?;
+        Ok(result.map(|b| Integer::from(Bytes::from(b)).0))
+    }
+
+    /// Returns the oldest block among from_block..=to_block requested timeout seconds or more before now.
+    pub fn find_withheld_block(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        now: u64,
+        timeout: u64,
+    ) -> Result<Option<u64>, Error> {
+        for block_number in from_block..=to_block {
+            if let Some(requested_at) = self.get_requested_at(block_number)? {
+                if requested_at + timeout <= now {
+                    return Ok(Some(block_number));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get_withheld_block(&self) -> Result<Option<u64>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("data_availability").into())
+            .get(&Bytes::from("withheld_block_number").into())?;
+        Ok(result.map(|b| Integer::from(Bytes::from(b)).0))
+    }
+
+    pub fn set_withheld_block(&self, block_number: u64) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("data_availability").into())
+            .put(
+                &Bytes::from("withheld_block_number").into(),
+                &Bytes::from(Integer::new(block_number)),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn put_exit(&self, record: &ExitRecord) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("exits").into())
+            .put(&Bytes::from(record.exit.to_abi()).into(), &record.to_abi())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_exits(&self) -> Result<Vec<ExitRecord>, Error> {
+        self.db
+            .bucket(&Bytes::from("exits").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| ExitRecord::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::statements::plasma::create_ownership_state_object;
+    use plasma_db::{impls::kvs::CoreDbMemoryImpl, traits::DatabaseTrait};
+
+    fn create_state_update(block_number: u64, start: u64, end: u64) -> StateUpdate {
+        StateUpdate::new(
+            Integer::new(block_number),
+            Address::zero(),
+            Range::new(start, end),
+            create_ownership_state_object(Address::zero()),
+        )
+    }
+
+    #[test]
+    fn test_find_withheld_block() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let db = AvailabilityDb::new(&kvs);
+        db.record_requested(3, 100).unwrap();
+        db.record_requested(4, 100).unwrap();
+        // requesting again doesn't reset the time
+        db.record_requested(3, 200).unwrap();
+        assert_eq!(db.find_withheld_block(3, 4, 399, 300).unwrap(), None);
+        assert_eq!(db.find_withheld_block(3, 4, 400, 300).unwrap(), Some(3));
+        db.remove_requested(3).unwrap();
+        assert_eq!(db.find_withheld_block(3, 4, 400, 300).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_mass_exit_plan() {
+        let state_updates = vec![
+            create_state_update(2, 0, 10),
+            create_state_update(1, 10, 15),
+            create_state_update(2, 20, 40),
+            create_state_update(5, 40, 50),
+        ];
+        let plan = MassExitPlan::create(5, &state_updates, &MassExitPolicy { exits_per_round: 2 });
+        assert_eq!(plan.get_exit_count(), 3);
+        assert_eq!(plan.rounds.len(), 2);
+        assert_eq!(plan.rounds[0][0].get_range(), Range::new(10, 15));
+        assert_eq!(plan.rounds[0][1].get_range(), Range::new(20, 40));
+        assert_eq!(plan.rounds[1][0].get_range(), Range::new(0, 10));
+    }
+}
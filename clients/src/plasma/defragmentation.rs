@@ -0,0 +1,181 @@
+use ethereum_types::Address;
+use ovm::types::StateUpdate;
+use plasma_core::data_structure::Range;
+use serde::Serialize;
+
+/// How fragmented owned ranges of a token are.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FragmentationMetrics {
+    pub deposit_contract_address: Address,
+    pub range_count: usize,
+    /// The number of ranges after all adjacent ranges are merged.
+    pub merged_range_count: usize,
+    pub total_amount: u64,
+    pub largest_range: u64,
+}
+
+impl FragmentationMetrics {
+    pub fn from_state_updates(deposit_contract_address: Address, owned: &[StateUpdate]) -> Self {
+        let reduction: usize = find_merge_candidates(owned)
+            .iter()
+            .map(|c| c.get_reduction())
+            .sum();
+        Self {
+            deposit_contract_address,
+            range_count: owned.len(),
+            merged_range_count: owned.len() - reduction,
+            total_amount: owned.iter().map(|s| s.get_amount()).sum(),
+            largest_range: owned.iter().map(|s| s.get_amount()).max().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DefragmentationPolicy {
+    /// Merges ranges while a token has more owned ranges than this.
+    pub max_ranges_per_token: usize,
+}
+
+impl Default for DefragmentationPolicy {
+    fn default() -> Self {
+        Self {
+            max_ranges_per_token: 10,
+        }
+    }
+}
+
+/// Adjacent owned ranges which are merged into one range by a self-transfer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeCandidate {
+    state_updates: Vec<StateUpdate>,
+}
+
+impl MergeCandidate {
+    pub fn get_state_updates(&self) -> &[StateUpdate] {
+        &self.state_updates
+    }
+
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.state_updates[0].get_deposit_contract_address()
+    }
+
+    pub fn get_range(&self) -> Range {
+        Range::new(
+            self.state_updates[0].get_range().get_start(),
+            self.state_updates[self.state_updates.len() - 1]
+                .get_range()
+                .get_end(),
+        )
+    }
+
+    /// The number of ranges reduced by merging.
+    pub fn get_reduction(&self) -> usize {
+        self.state_updates.len() - 1
+    }
+}
+
+/// Finds runs of adjacent ranges.
+/// Only ownership state objects are merged because other state objects can't be spent by a self-transfer.
+pub fn find_merge_candidates(owned: &[StateUpdate]) -> Vec<MergeCandidate> {
+    let mut sorted: Vec<&StateUpdate> = owned.iter().collect();
+    sorted.sort_by_key(|s| (s.get_deposit_contract_address(), s.get_range().get_start()));
+    let mut candidates = vec![];
+    let mut run: Vec<StateUpdate> = vec![];
+    for s in sorted {
+        let adjacent = run.last().map_or(false, |last| {
+            last.get_deposit_contract_address() == s.get_deposit_contract_address()
+                && last.get_range().get_end() == s.get_range().get_start()
+        });
+        if !(adjacent && s.is_ownership_state()) {
+            if run.len() > 1 {
+                candidates.push(MergeCandidate {
+                    state_updates: run.clone(),
+                });
+            }
+            run.clear();
+        }
+        if s.is_ownership_state() {
+            run.push(s.clone());
+        }
+    }
+    if run.len() > 1 {
+        candidates.push(MergeCandidate { state_updates: run });
+    }
+    candidates
+}
+
+/// Chooses merges to keep owned ranges of a token below the policy.
+/// Merges reducing more ranges are chosen first. Returns nothing if the token is already below the limit.
+pub fn plan_defragmentation(
+    owned: &[StateUpdate],
+    policy: &DefragmentationPolicy,
+) -> Vec<MergeCandidate> {
+    let mut range_count = owned.len();
+    if range_count <= policy.max_ranges_per_token {
+        return vec![];
+    }
+    let mut candidates = find_merge_candidates(owned);
+    candidates.sort_by(|a, b| b.get_reduction().cmp(&a.get_reduction()));
+    let mut plan = vec![];
+    for candidate in candidates {
+        if range_count <= policy.max_ranges_per_token {
+            break;
+        }
+        range_count -= candidate.get_reduction();
+        plan.push(candidate);
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi_utils::Integer;
+    use ovm::statements::plasma::create_ownership_state_object;
+
+    fn create_state_update(start: u64, end: u64) -> StateUpdate {
+        StateUpdate::new(
+            Integer::new(1),
+            Address::zero(),
+            Range::new(start, end),
+            create_ownership_state_object(Address::zero()),
+        )
+    }
+
+    fn owned() -> Vec<StateUpdate> {
+        vec![
+            create_state_update(10, 20),
+            create_state_update(0, 10),
+            create_state_update(30, 40),
+            create_state_update(40, 45),
+            create_state_update(45, 50),
+            create_state_update(60, 70),
+        ]
+    }
+
+    #[test]
+    fn test_find_merge_candidates() {
+        let candidates = find_merge_candidates(&owned());
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].get_range(), Range::new(0, 20));
+        assert_eq!(candidates[1].get_range(), Range::new(30, 50));
+        let metrics = FragmentationMetrics::from_state_updates(Address::zero(), &owned());
+        assert_eq!(metrics.range_count, 6);
+        assert_eq!(metrics.merged_range_count, 3);
+        assert_eq!(metrics.total_amount, 45);
+    }
+
+    #[test]
+    fn test_plan_defragmentation() {
+        let policy = DefragmentationPolicy {
+            max_ranges_per_token: 4,
+        };
+        let plan = plan_defragmentation(&owned(), &policy);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].get_range(), Range::new(30, 50));
+        let policy = DefragmentationPolicy {
+            max_ranges_per_token: 6,
+        };
+        assert!(plan_defragmentation(&owned(), &policy).is_empty());
+    }
+}
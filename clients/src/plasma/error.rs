@@ -28,6 +28,101 @@ pub enum ErrorKind {
     MerkelizingError,
     #[fail(display = "Invalid Parameter")]
     InvalidParameter,
+    #[fail(display = "Conflicting Transaction")]
+    ConflictingTransaction,
+    #[fail(display = "Invalid Block")]
+    InvalidBlock,
+    #[fail(display = "Block Root Not Found")]
+    BlockRootNotFound,
+    #[fail(display = "Insufficient Balance")]
+    InsufficientBalance,
+    #[fail(display = "Exact Match Not Found")]
+    ExactMatchNotFound,
+    #[fail(display = "Request Timeout")]
+    RequestTimeout,
+    #[fail(display = "Insufficient Fee")]
+    InsufficientFee,
+    #[fail(display = "Order Not Found")]
+    OrderNotFound,
+    #[fail(display = "Order Expired")]
+    OrderExpired,
+    #[fail(display = "Invalid Mnemonic")]
+    InvalidMnemonic,
+    #[fail(display = "Invalid Password")]
+    InvalidPassword,
+    #[fail(display = "Seed Not Found")]
+    SeedNotFound,
+    #[fail(display = "Key Locked")]
+    KeyLocked,
+    #[fail(display = "Invalid Keystore")]
+    InvalidKeystore,
+    #[fail(display = "Not Connected")]
+    NotConnected,
+    #[fail(display = "Unknown Error")]
+    UnknownError,
+}
+
+impl ErrorKind {
+    /// Code of the error kind sent to clients in command responses. 0 means success.
+    pub fn code(&self) -> u64 {
+        match self {
+            ErrorKind::Io => 1,
+            ErrorKind::AbiDecode => 2,
+            ErrorKind::AbiError => 3,
+            ErrorKind::PlasmaCoreError => 4,
+            ErrorKind::PlasmaDbError => 5,
+            ErrorKind::ContractError => 6,
+            ErrorKind::InvalidTransaction => 7,
+            ErrorKind::MerkelizingError => 8,
+            ErrorKind::InvalidParameter => 9,
+            ErrorKind::ConflictingTransaction => 10,
+            ErrorKind::InvalidBlock => 11,
+            ErrorKind::BlockRootNotFound => 12,
+            ErrorKind::InsufficientBalance => 13,
+            ErrorKind::ExactMatchNotFound => 14,
+            ErrorKind::RequestTimeout => 15,
+            ErrorKind::UnknownError => 16,
+            ErrorKind::InsufficientFee => 17,
+            ErrorKind::OrderNotFound => 18,
+            ErrorKind::OrderExpired => 19,
+            ErrorKind::InvalidMnemonic => 20,
+            ErrorKind::InvalidPassword => 21,
+            ErrorKind::SeedNotFound => 22,
+            ErrorKind::KeyLocked => 23,
+            ErrorKind::InvalidKeystore => 24,
+            ErrorKind::NotConnected => 25,
+        }
+    }
+
+    pub fn from_code(code: u64) -> Self {
+        match code {
+            1 => ErrorKind::Io,
+            2 => ErrorKind::AbiDecode,
+            3 => ErrorKind::AbiError,
+            4 => ErrorKind::PlasmaCoreError,
+            5 => ErrorKind::PlasmaDbError,
+            6 => ErrorKind::ContractError,
+            7 => ErrorKind::InvalidTransaction,
+            8 => ErrorKind::MerkelizingError,
+            9 => ErrorKind::InvalidParameter,
+            10 => ErrorKind::ConflictingTransaction,
+            11 => ErrorKind::InvalidBlock,
+            12 => ErrorKind::BlockRootNotFound,
+            13 => ErrorKind::InsufficientBalance,
+            14 => ErrorKind::ExactMatchNotFound,
+            15 => ErrorKind::RequestTimeout,
+            17 => ErrorKind::InsufficientFee,
+            18 => ErrorKind::OrderNotFound,
+            19 => ErrorKind::OrderExpired,
+            20 => ErrorKind::InvalidMnemonic,
+            21 => ErrorKind::InvalidPassword,
+            22 => ErrorKind::SeedNotFound,
+            23 => ErrorKind::KeyLocked,
+            24 => ErrorKind::InvalidKeystore,
+            25 => ErrorKind::NotConnected,
+            _ => ErrorKind::UnknownError,
+        }
+    }
 }
 
 #[derive(Debug)]
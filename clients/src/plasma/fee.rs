@@ -0,0 +1,146 @@
+use super::error::{Error, ErrorKind};
+use abi_utils::{Decodable, Encodable};
+use bytes::Bytes;
+use ethereum_types::Address;
+use ovm::types::state_object_kind::OWNERSHIP_KIND;
+use ovm::types::{match_state_object_kind, Property, StateUpdate};
+use plasma_core::data_structure::TransactionParams;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Fee paid to the aggregator in fee_token.
+/// A fee is an entry of a batch transaction which transfers a range of fee_token to recipient.
+/// Every other entry of the batch requires min_fee_per_transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeePolicy {
+    pub fee_token: Address,
+    pub recipient: Address,
+    pub min_fee_per_transaction: u64,
+}
+
+impl FeePolicy {
+    pub fn new(fee_token: Address, recipient: Address, min_fee_per_transaction: u64) -> Self {
+        Self {
+            fee_token,
+            recipient,
+            min_fee_per_transaction,
+        }
+    }
+
+    /// Whether the entry pays a fee to the recipient.
+    pub fn is_fee_entry(&self, entry: &TransactionParams) -> bool {
+        entry.get_deposit_contract_address() == self.fee_token
+            && Property::from_abi(entry.get_parameters())
+                .ok()
+                .and_then(|p| match_state_object_kind(OWNERSHIP_KIND, &p))
+                .and_then(|captures| captures.get_address("owner"))
+                == Some(self.recipient)
+    }
+
+    pub fn is_fee_state_update(&self, state_update: &StateUpdate) -> bool {
+        state_update.get_deposit_contract_address() == self.fee_token
+            && state_update.is_ownership_state()
+            && state_update.try_get_owner() == Some(self.recipient)
+    }
+
+    /// Returns the fee required for entries which aren't fees themselves.
+    pub fn estimate_fee(&self, transaction_count: usize) -> u64 {
+        self.min_fee_per_transaction * transaction_count as u64
+    }
+
+    pub fn get_paid_fee(&self, entries: &[TransactionParams]) -> u64 {
+        entries
+            .iter()
+            .filter(|e| self.is_fee_entry(e))
+            .map(|e| e.get_range().get_amount())
+            .sum()
+    }
+
+    pub fn get_required_fee(&self, entries: &[TransactionParams]) -> u64 {
+        self.estimate_fee(entries.iter().filter(|e| !self.is_fee_entry(e)).count())
+    }
+
+    /// Checks that entries ingested together pay enough fee.
+    pub fn check_fee(&self, entries: &[TransactionParams]) -> Result<(), Error> {
+        if self.get_paid_fee(entries) < self.get_required_fee(entries) {
+            return Err(Error::from(ErrorKind::InsufficientFee));
+        }
+        Ok(())
+    }
+}
+
+/// Fees collected by the aggregator keyed by block number.
+pub struct FeeDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> FeeDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_collected_fees(
+        &self,
+        block_number: u64,
+        state_updates: &[StateUpdate],
+    ) -> Result<(), Error> {
+        let bucket = self
+            .db
+            .bucket(&Bytes::from("collected_fees").into())
+            .bucket(&block_number.into());
+        for state_update in state_updates {
+            bucket.put(
+                &state_update.get_range().get_start().into(),
+                &state_update.to_abi(),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_collected_fees(&self, block_number: u64) -> Result<Vec<StateUpdate>, Error> {
+        self.db
+            .bucket(&Bytes::from("collected_fees").into())
+            .bucket(&block_number.into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| StateUpdate::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::statements::plasma::create_ownership_state_object;
+    use plasma_core::data_structure::Range;
+
+    fn create_entry(token: Address, start: u64, end: u64, owner: Address) -> TransactionParams {
+        TransactionParams::new(
+            token,
+            Range::new(start, end),
+            Bytes::from(create_ownership_state_object(owner).to_abi()),
+        )
+    }
+
+    #[test]
+    fn test_check_fee() {
+        let fee_token = Address::from([1; 20]);
+        let aggregator = Address::from([2; 20]);
+        let alice = Address::from([3; 20]);
+        let policy = FeePolicy::new(fee_token, aggregator, 5);
+        let payments = vec![
+            create_entry(Address::zero(), 0, 10, alice),
+            create_entry(Address::zero(), 10, 20, alice),
+        ];
+        assert_eq!(policy.get_required_fee(&payments), 10);
+
+        let mut entries = payments.clone();
+        entries.push(create_entry(fee_token, 100, 110, aggregator));
+        assert!(policy.check_fee(&entries).is_ok());
+
+        entries.pop();
+        entries.push(create_entry(fee_token, 100, 109, aggregator));
+        assert!(policy.check_fee(&entries).is_err());
+        // the fee entry itself doesn't require a fee
+        assert!(policy.check_fee(&entries[2..]).is_ok());
+    }
+}
@@ -0,0 +1,215 @@
+use super::error::{Error, ErrorKind};
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use ethereum_types::Address;
+use ethsign::SecretKey as EthSecretKey;
+use hmac::{Hmac, Mac};
+use secp256k1::{PublicKey, SecretKey};
+use sha2::Sha512;
+
+/// Child indices from this offset derive hardened keys.
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+/// Number of consecutive unused accounts after which account discovery stops (BIP-44).
+pub const GAP_LIMIT: u32 = 20;
+
+/// Generates a new 24 words English BIP-39 mnemonic.
+pub fn generate_mnemonic() -> String {
+    Mnemonic::new(MnemonicType::Words24, Language::English).into_phrase()
+}
+
+/// Validates phrase and returns its BIP-39 seed with passphrase.
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mnemonic = Mnemonic::from_phrase(phrase, Language::English)
+        .map_err(|_| Error::from(ErrorKind::InvalidMnemonic))?;
+    Ok(Seed::new(&mnemonic, passphrase).as_bytes().to_vec())
+}
+
+/// BIP-32 derivation path like m/44'/60'/0'/0/0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivationPath {
+    indices: Vec<u32>,
+}
+
+impl DerivationPath {
+    pub fn new(indices: Vec<u32>) -> Self {
+        Self { indices }
+    }
+
+    /// Parses a path written as m/i/j'/... where ' marks a hardened index.
+    pub fn parse(path: &str) -> Result<Self, Error> {
+        let mut elements = path.split('/');
+        if elements.next() != Some("m") {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let indices: Result<Vec<u32>, Error> = elements
+            .map(|element| {
+                let (index, offset) = if element.ends_with('\'') {
+                    (&element[..element.len() - 1], HARDENED_OFFSET)
+                } else {
+                    (element, 0)
+                };
+                match index.parse::<u32>() {
+                    Ok(index) if index < HARDENED_OFFSET => Ok(index + offset),
+                    _ => Err(Error::from(ErrorKind::InvalidParameter)),
+                }
+            })
+            .collect();
+        Ok(Self::new(indices?))
+    }
+
+    /// BIP-44 path of the Ethereum account at index, m/44'/60'/0'/0/index.
+    pub fn ethereum(index: u32) -> Self {
+        Self::new(vec![
+            44 + HARDENED_OFFSET,
+            60 + HARDENED_OFFSET,
+            HARDENED_OFFSET,
+            0,
+            index,
+        ])
+    }
+
+    pub fn get_indices(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+/// BIP-32 extended private key.
+#[derive(Clone)]
+pub struct ExtendedPrivateKey {
+    secret_key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedPrivateKey {
+    /// Master key of seed.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        Self::from_hmac(b"Bitcoin seed", seed)
+    }
+
+    fn from_hmac(key: &[u8], data: &[u8]) -> Result<Self, Error> {
+        let mut mac = Hmac::<Sha512>::new_varkey(key).unwrap();
+        mac.input(data);
+        let output = mac.result().code();
+        let mut secret_key = [0; 32];
+        let mut chain_code = [0; 32];
+        secret_key.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        // the key must be in the range of secp256k1 private keys
+        SecretKey::parse(&secret_key).map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        Ok(Self {
+            secret_key,
+            chain_code,
+        })
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, Error> {
+        let parent = SecretKey::parse(&self.secret_key)
+            .map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        let mut data = if index >= HARDENED_OFFSET {
+            let mut data = vec![0];
+            data.extend_from_slice(&self.secret_key);
+            data
+        } else {
+            PublicKey::from_secret_key(&parent)
+                .serialize_compressed()
+                .to_vec()
+        };
+        data.extend_from_slice(&index.to_be_bytes());
+        let child = Self::from_hmac(&self.chain_code, &data)?;
+        let mut secret_key = SecretKey::parse(&child.secret_key).unwrap();
+        secret_key
+            .tweak_add_assign(&parent)
+            .map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        Ok(Self {
+            secret_key: secret_key.serialize(),
+            chain_code: child.chain_code,
+        })
+    }
+
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, Error> {
+        path.get_indices()
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    pub fn get_secret_key(&self) -> &[u8; 32] {
+        &self.secret_key
+    }
+
+    pub fn get_chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+}
+
+/// Raw private key of the Ethereum account at index.
+pub fn derive_ethereum_key(seed: &[u8], index: u32) -> Result<[u8; 32], Error> {
+    Ok(*ExtendedPrivateKey::from_seed(seed)?
+        .derive(&DerivationPath::ethereum(index))?
+        .get_secret_key())
+}
+
+/// Returns indices of the Ethereum accounts of seed for which is_used returns true.
+/// Scanning stops after GAP_LIMIT consecutive unused accounts.
+pub fn discover_accounts<F>(seed: &[u8], is_used: F) -> Result<Vec<u32>, Error>
+where
+    F: Fn(Address) -> bool,
+{
+    let mut used = vec![];
+    let mut index = 0;
+    let mut gap = 0;
+    while gap < GAP_LIMIT {
+        let secret_key = EthSecretKey::from_raw(&derive_ethereum_key(seed, index)?)
+            .map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        if is_used(secret_key.public().address().into()) {
+            used.push(index);
+            gap = 0;
+        } else {
+            gap += 1;
+        }
+        index += 1;
+    }
+    Ok(used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_bip32_test_vector() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivateKey::from_seed(&seed).unwrap();
+        assert_eq!(
+            hex::encode(master.get_secret_key()),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        let child = master
+            .derive(&DerivationPath::parse("m/0'/1").unwrap())
+            .unwrap();
+        assert_eq!(
+            hex::encode(child.get_secret_key()),
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+        );
+        assert_eq!(
+            hex::encode(child.get_chain_code()),
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"
+        );
+        assert!(DerivationPath::parse("44'/60'").is_err());
+    }
+
+    #[test]
+    fn test_discover_ethereum_accounts() {
+        let seed = mnemonic_to_seed(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "",
+        )
+        .unwrap();
+        let secret_key = EthSecretKey::from_raw(&derive_ethereum_key(&seed, 0).unwrap()).unwrap();
+        let address: Address = secret_key.public().address().into();
+        assert_eq!(
+            hex::encode(address.as_bytes()),
+            "9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+        assert_eq!(discover_accounts(&seed, |a| a == address).unwrap(), vec![0]);
+        assert!(mnemonic_to_seed("abandon about", "").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use super::error::{Error, ErrorKind};
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ethsign::SecretKey;
+use ovm::deciders::SignVerifier;
+use plasma_core::data_structure::Range;
+
+/// Aggregator's signed promise to include the transaction in block block_number.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct InclusionPromise {
+    transaction_hash: H256,
+    deposit_contract_address: Address,
+    range: Range,
+    block_number: Integer,
+    signature: Bytes,
+}
+
+impl InclusionPromise {
+    pub fn new(
+        transaction_hash: H256,
+        deposit_contract_address: Address,
+        range: Range,
+        block_number: Integer,
+        signature: Bytes,
+    ) -> Self {
+        Self {
+            transaction_hash,
+            deposit_contract_address,
+            range,
+            block_number,
+            signature,
+        }
+    }
+
+    pub fn sign(
+        key: &SecretKey,
+        transaction_hash: H256,
+        deposit_contract_address: Address,
+        range: Range,
+        block_number: u64,
+    ) -> Self {
+        let mut promise = Self::new(
+            transaction_hash,
+            deposit_contract_address,
+            range,
+            Integer::new(block_number),
+            Bytes::default(),
+        );
+        promise.signature = SignVerifier::sign(key, &promise.to_body_abi());
+        promise
+    }
+
+    /// The message which the aggregator signs.
+    pub fn to_body_abi(&self) -> Bytes {
+        Bytes::from(ethabi::encode(&[
+            Token::FixedBytes(self.transaction_hash.as_bytes().to_vec()),
+            Token::Address(self.deposit_contract_address),
+            Token::Tuple(self.range.to_tuple()),
+            Token::Uint(self.block_number.0.into()),
+        ]))
+    }
+
+    /// Fails with InvalidParameter if the signature is malformed.
+    pub fn get_signer(&self) -> Result<Address, Error> {
+        SignVerifier::try_recover(&self.signature, &self.to_body_abi())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))
+    }
+
+    pub fn get_transaction_hash(&self) -> H256 {
+        self.transaction_hash
+    }
+
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.deposit_contract_address
+    }
+
+    pub fn get_range(&self) -> Range {
+        self.range
+    }
+
+    pub fn get_block_number(&self) -> u64 {
+        self.block_number.0
+    }
+
+    pub fn get_signature(&self) -> &Bytes {
+        &self.signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_promise() {
+        let raw_key =
+            hex::decode("c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3")
+                .unwrap();
+        let secret_key = SecretKey::from_raw(&raw_key).unwrap();
+        let promise = InclusionPromise::sign(
+            &secret_key,
+            H256::zero(),
+            Address::zero(),
+            Range::new(0, 10),
+            5,
+        );
+        let decoded = InclusionPromise::from_abi(&promise.to_abi()).unwrap();
+        assert_eq!(decoded, promise);
+        assert_eq!(
+            decoded.get_signer().unwrap(),
+            Address::from(secret_key.public().address())
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use super::error::{Error, ErrorKind};
+use ethereum_types::Address;
+use ovm::types::StateUpdate;
+use plasma_core::data_structure::{Range, Transaction};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Defines how the mempool handles a transaction which spends a range
+/// already spent by a pending transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keeps pending transactions and rejects the new one.
+    Reject,
+    /// Replaces pending transactions if all of them were signed by the sender of the new one.
+    ReplaceBySameSender,
+}
+
+#[derive(Clone, Debug)]
+pub struct MempoolOptions {
+    pub conflict_policy: ConflictPolicy,
+    /// Pending transactions older than max_age are expired by `remove_expired`.
+    pub max_age: Duration,
+}
+
+impl Default for MempoolOptions {
+    fn default() -> Self {
+        Self {
+            conflict_policy: ConflictPolicy::Reject,
+            max_age: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Transaction which was ingested but not included in a block yet.
+/// prev_states are the state updates which the transaction deprecated.
+/// promised is true if the aggregator promised to include the transaction in the current block.
+#[derive(Clone, Debug)]
+pub struct PendingTransaction {
+    transaction: Transaction,
+    sender: Address,
+    prev_states: Vec<StateUpdate>,
+    received_at: Instant,
+    promised: bool,
+}
+
+impl PendingTransaction {
+    pub fn new(
+        transaction: Transaction,
+        sender: Address,
+        prev_states: Vec<StateUpdate>,
+        received_at: Instant,
+    ) -> Self {
+        Self {
+            transaction,
+            sender,
+            prev_states,
+            received_at,
+            promised: false,
+        }
+    }
+
+    pub fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn get_sender(&self) -> Address {
+        self.sender
+    }
+
+    pub fn get_prev_states(&self) -> &[StateUpdate] {
+        &self.prev_states
+    }
+
+    pub fn get_received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    pub fn is_promised(&self) -> bool {
+        self.promised
+    }
+}
+
+/// Pending transactions indexed by deposit contract address and range.
+/// Ranges of pending transactions in the same deposit contract never intersect.
+pub struct Mempool {
+    options: MempoolOptions,
+    pending: HashMap<Address, Vec<PendingTransaction>>,
+}
+
+impl Mempool {
+    pub fn new(options: MempoolOptions) -> Self {
+        Self {
+            options,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn get_options(&self) -> &MempoolOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: MempoolOptions) {
+        self.options = options;
+    }
+
+    /// Returns pending transactions spending any part of the range of given transaction.
+    pub fn get_conflicts(&self, transaction: &Transaction) -> Vec<&PendingTransaction> {
+        let range = transaction.get_range();
+        self.pending
+            .get(&transaction.get_deposit_contract_address())
+            .map(|list| {
+                list.iter()
+                    .filter(|p| intersect(&p.transaction.get_range(), &range))
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![])
+    }
+
+    /// Applies conflict policy to given transaction.
+    /// Returns pending transactions which have to be evicted before the transaction is accepted.
+    pub fn check_conflicts(
+        &self,
+        transaction: &Transaction,
+        sender: Address,
+    ) -> Result<Vec<PendingTransaction>, Error> {
+        let conflicts = self.get_conflicts(transaction);
+        if conflicts.is_empty() {
+            return Ok(vec![]);
+        }
+        match self.options.conflict_policy {
+            ConflictPolicy::Reject => Err(Error::from(ErrorKind::ConflictingTransaction)),
+            ConflictPolicy::ReplaceBySameSender => {
+                if conflicts.iter().all(|p| p.sender == sender && !p.promised) {
+                    Ok(conflicts.into_iter().cloned().collect())
+                } else {
+                    Err(Error::from(ErrorKind::ConflictingTransaction))
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, pending_transaction: PendingTransaction) -> Result<(), Error> {
+        if !self
+            .get_conflicts(&pending_transaction.transaction)
+            .is_empty()
+        {
+            return Err(Error::from(ErrorKind::ConflictingTransaction));
+        }
+        let list = self
+            .pending
+            .entry(
+                pending_transaction
+                    .transaction
+                    .get_deposit_contract_address(),
+            )
+            .or_default();
+        let start = pending_transaction.transaction.get_range().get_start();
+        let index = list
+            .iter()
+            .position(|p| start < p.transaction.get_range().get_start())
+            .unwrap_or_else(|| list.len());
+        list.insert(index, pending_transaction);
+        Ok(())
+    }
+
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        self.pending
+            .get(&transaction.get_deposit_contract_address())
+            .map_or(false, |list| {
+                list.iter().any(|p| &p.transaction == transaction)
+            })
+    }
+
+    pub fn remove(&mut self, transaction: &Transaction) -> Option<PendingTransaction> {
+        let list = self
+            .pending
+            .get_mut(&transaction.get_deposit_contract_address())?;
+        let index = list.iter().position(|p| &p.transaction == transaction)?;
+        Some(list.remove(index))
+    }
+
+    /// Marks the pending transaction as promised. Returns false if it isn't pending.
+    pub fn mark_promised(&mut self, transaction: &Transaction) -> bool {
+        let pending = self
+            .pending
+            .get_mut(&transaction.get_deposit_contract_address())
+            .and_then(|list| list.iter_mut().find(|p| &p.transaction == transaction));
+        match pending {
+            Some(p) => {
+                p.promised = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns pending transactions received more than max_age before now.
+    /// Promised transactions are kept until they are included in a block.
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<PendingTransaction> {
+        let max_age = self.options.max_age;
+        let mut expired = vec![];
+        for list in self.pending.values_mut() {
+            let (stale, fresh): (Vec<_>, Vec<_>) = list
+                .drain(..)
+                .partition(|p| !p.promised && now.duration_since(p.received_at) > max_age);
+            *list = fresh;
+            expired.extend(stale);
+        }
+        expired
+    }
+
+    /// Returns all pending transactions ordered by range in each deposit contract.
+    pub fn get_pending_transactions(&self) -> Vec<&PendingTransaction> {
+        self.pending.values().flat_map(|list| list.iter()).collect()
+    }
+
+    pub fn get_pending_transactions_of(
+        &self,
+        deposit_contract_address: Address,
+    ) -> Vec<&PendingTransaction> {
+        self.pending
+            .get(&deposit_contract_address)
+            .map(|list| list.iter().collect())
+            .unwrap_or_else(|| vec![])
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.values().map(|list| list.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+fn intersect(a: &Range, b: &Range) -> bool {
+    a.get_start() < b.get_end() && b.get_start() < a.get_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use plasma_core::data_structure::Metadata;
+
+    fn create_transaction(start: u64, end: u64) -> Transaction {
+        Transaction::new(
+            Address::zero(),
+            Range::new(start, end),
+            Bytes::default(),
+            Bytes::default(),
+            Metadata::default(),
+        )
+    }
+
+    #[test]
+    fn test_detect_conflicts() {
+        let mut mempool = Mempool::new(MempoolOptions::default());
+        let sender = Address::zero();
+        assert!(mempool
+            .insert(PendingTransaction::new(
+                create_transaction(0, 10),
+                sender,
+                vec![],
+                Instant::now()
+            ))
+            .is_ok());
+        // adjacent range doesn't conflict
+        assert!(mempool
+            .check_conflicts(&create_transaction(10, 20), sender)
+            .unwrap()
+            .is_empty());
+        assert!(mempool
+            .check_conflicts(&create_transaction(5, 15), sender)
+            .is_err());
+    }
+
+    #[test]
+    fn test_replace_by_same_sender() {
+        let mut mempool = Mempool::new(MempoolOptions {
+            conflict_policy: ConflictPolicy::ReplaceBySameSender,
+            max_age: Duration::from_secs(60),
+        });
+        let sender = Address::zero();
+        let other = Address::random();
+        assert!(mempool
+            .insert(PendingTransaction::new(
+                create_transaction(0, 10),
+                sender,
+                vec![],
+                Instant::now()
+            ))
+            .is_ok());
+        assert_eq!(
+            mempool
+                .check_conflicts(&create_transaction(5, 15), sender)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(mempool
+            .check_conflicts(&create_transaction(5, 15), other)
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let mut mempool = Mempool::new(MempoolOptions {
+            conflict_policy: ConflictPolicy::Reject,
+            max_age: Duration::from_secs(0),
+        });
+        let received_at = Instant::now();
+        assert!(mempool
+            .insert(PendingTransaction::new(
+                create_transaction(0, 10),
+                Address::zero(),
+                vec![],
+                received_at
+            ))
+            .is_ok());
+        let expired = mempool.remove_expired(received_at + Duration::from_secs(1));
+        assert_eq!(expired.len(), 1);
+        assert!(mempool.is_empty());
+    }
+}
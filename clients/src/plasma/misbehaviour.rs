@@ -0,0 +1,278 @@
+use super::error::{Error, ErrorKind};
+use super::inclusion_promise::InclusionPromise;
+use super::plasma_block::PlasmaBlock;
+use super::utils::get_transaction_hash;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ovm::types::core::Property;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Proof that the aggregator broke an inclusion promise.
+/// block is the encoded PlasmaBlock whose root was submitted to the commitment contract.
+/// The bundle is self-contained so that it can be verified off-chain or by a slashing contract.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct MisbehaviourEvidence {
+    promise: InclusionPromise,
+    block: Bytes,
+    root: Bytes,
+}
+
+impl MisbehaviourEvidence {
+    pub fn new(promise: InclusionPromise, block: Bytes, root: Bytes) -> Self {
+        Self {
+            promise,
+            block,
+            root,
+        }
+    }
+
+    pub fn get_promise(&self) -> &InclusionPromise {
+        &self.promise
+    }
+
+    pub fn get_block(&self) -> Result<PlasmaBlock, Error> {
+        Ok(PlasmaBlock::from_abi(&self.block)?)
+    }
+
+    pub fn get_root(&self) -> &Bytes {
+        &self.root
+    }
+
+    /// Checks that the promise was signed by aggregator_address, the block matches root
+    /// and the block doesn't include the promised state update.
+    pub fn verify(&self, aggregator_address: Address) -> Result<(), Error> {
+        if self.promise.get_signer()? != aggregator_address {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let mut block = self.get_block()?;
+        if block.get_block_number() != self.promise.get_block_number()
+            || block.merkelize()? != self.root
+        {
+            return Err(Error::from(ErrorKind::InvalidBlock));
+        }
+        if is_promise_kept(&self.promise, &block) {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        Ok(())
+    }
+}
+
+/// Whether the block includes the promised transaction and the state update it results in.
+pub fn is_promise_kept(promise: &InclusionPromise, block: &PlasmaBlock) -> bool {
+    let transaction = match block
+        .get_transactions()
+        .iter()
+        .map(|e| &e.transaction)
+        .find(|tx| get_transaction_hash(tx) == promise.get_transaction_hash())
+    {
+        Some(tx) => tx,
+        None => return false,
+    };
+    let state_object = match Property::from_abi(transaction.get_parameters()) {
+        Ok(property) => property,
+        Err(_) => return false,
+    };
+    let range = promise.get_range();
+    transaction.get_deposit_contract_address() == promise.get_deposit_contract_address()
+        && transaction.get_range() == range
+        && block.get_state_updates().iter().any(|s| {
+            s.get_deposit_contract_address() == promise.get_deposit_contract_address()
+                && s.get_block_number().0 == promise.get_block_number()
+                && s.get_range().get_start() <= range.get_start()
+                && range.get_end() <= s.get_range().get_end()
+                && s.get_property() == &state_object
+        })
+}
+
+/// Inclusion promises waiting for their blocks and evidences of broken ones.
+/// Both are keyed by transaction hash.
+pub struct PromiseDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> PromiseDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_promise(&self, promise: &InclusionPromise) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("inclusion_promises").into())
+            .put(
+                &Bytes::from(promise.get_transaction_hash().as_bytes()).into(),
+                &promise.to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_promises(&self) -> Result<Vec<InclusionPromise>, Error> {
+        self.db
+            .bucket(&Bytes::from("inclusion_promises").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| InclusionPromise::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+
+    pub fn remove_promise(&self, transaction_hash: H256) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("inclusion_promises").into())
+            .del(&Bytes::from(transaction_hash.as_bytes()).into())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn put_evidence(&self, evidence: &MisbehaviourEvidence) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("misbehaviour_evidences").into())
+            .put(
+                &Bytes::from(evidence.get_promise().get_transaction_hash().as_bytes()).into(),
+                &evidence.to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_evidences(&self) -> Result<Vec<MisbehaviourEvidence>, Error> {
+        self.db
+            .bucket(&Bytes::from("misbehaviour_evidences").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| {
+                MisbehaviourEvidence::from_abi(kv.get_value()).map_err::<Error, _>(Into::into)
+            })
+            .collect()
+    }
+
+    /// Resolves promises of the block. Kept promises are removed and broken ones are turned into evidences.
+    /// Returns the evidences.
+    pub fn check_block(
+        &self,
+        block: &PlasmaBlock,
+        root: &Bytes,
+    ) -> Result<Vec<MisbehaviourEvidence>, Error> {
+        let mut evidences = vec![];
+        for promise in self.get_promises()? {
+            if promise.get_block_number() != block.get_block_number() {
+                continue;
+            }
+            if !is_promise_kept(&promise, block) {
+                let evidence = MisbehaviourEvidence::new(
+                    promise.clone(),
+                    Bytes::from(block.to_abi()),
+                    root.clone(),
+                );
+                self.put_evidence(&evidence)?;
+                evidences.push(evidence);
+            }
+            self.remove_promise(promise.get_transaction_hash())?;
+        }
+        Ok(evidences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plasma::command::NewTransactionEvent;
+    use abi_utils::Integer;
+    use ethsign::SecretKey;
+    use ovm::statements::plasma::create_ownership_state_object;
+    use ovm::types::StateUpdate;
+    use plasma_core::data_structure::{Metadata, Range, Transaction};
+    use plasma_db::{impls::kvs::CoreDbMemoryImpl, traits::DatabaseTrait};
+
+    fn create_transaction(range: Range, owner: Address) -> Transaction {
+        Transaction::new(
+            Address::zero(),
+            range,
+            Bytes::from(create_ownership_state_object(owner).to_abi()),
+            Bytes::default(),
+            Metadata::default(),
+        )
+    }
+
+    #[test]
+    fn test_broken_promise() {
+        let raw_key =
+            hex::decode("c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3")
+                .unwrap();
+        let secret_key = SecretKey::from_raw(&raw_key).unwrap();
+        let aggregator_address = Address::from(secret_key.public().address());
+        let owner = Address::random();
+        let included = create_transaction(Range::new(0, 10), owner);
+        // included but its state update transfers the range to someone else
+        let tampered = create_transaction(Range::new(10, 20), owner);
+        let promise = |tx: &Transaction| {
+            InclusionPromise::sign(
+                &secret_key,
+                get_transaction_hash(tx),
+                Address::zero(),
+                tx.get_range(),
+                1,
+            )
+        };
+        let kept = promise(&included);
+        let broken = promise(&tampered);
+        let missing = promise(&create_transaction(Range::new(20, 30), owner));
+        let mut block = PlasmaBlock::new(
+            1,
+            vec![
+                StateUpdate::new(
+                    Integer::new(1),
+                    Address::zero(),
+                    Range::new(0, 10),
+                    create_ownership_state_object(owner),
+                ),
+                StateUpdate::new(
+                    Integer::new(1),
+                    Address::zero(),
+                    Range::new(10, 30),
+                    create_ownership_state_object(Address::zero()),
+                ),
+            ],
+            vec![
+                NewTransactionEvent::new(vec![], included),
+                NewTransactionEvent::new(vec![], tampered),
+            ],
+        );
+        let root = block.merkelize().unwrap();
+
+        let kvs = CoreDbMemoryImpl::open("test");
+        let promise_db = PromiseDb::new(&kvs);
+        assert!(promise_db.put_promise(&kept).is_ok());
+        assert!(promise_db.put_promise(&broken).is_ok());
+        assert!(promise_db.put_promise(&missing).is_ok());
+        let evidences = promise_db.check_block(&block, &root).unwrap();
+        assert_eq!(evidences.len(), 2);
+        assert!(evidences.iter().all(|e| e.get_promise() != &kept));
+        assert!(promise_db.get_promises().unwrap().is_empty());
+
+        let decoded = MisbehaviourEvidence::from_abi(&evidences[0].to_abi()).unwrap();
+        assert!(decoded.verify(aggregator_address).is_ok());
+        assert!(decoded.verify(Address::zero()).is_err());
+
+        // a corrupted signature is rejected without panicking
+        let promise = decoded.get_promise();
+        let corrupted = MisbehaviourEvidence::new(
+            InclusionPromise::new(
+                promise.get_transaction_hash(),
+                promise.get_deposit_contract_address(),
+                promise.get_range(),
+                Integer::new(promise.get_block_number()),
+                Bytes::from(vec![0xff; 65]),
+            ),
+            Bytes::from(block.to_abi()),
+            root,
+        );
+        assert_eq!(
+            corrupted
+                .verify(aggregator_address)
+                .unwrap_err()
+                .kind()
+                .code(),
+            ErrorKind::InvalidParameter.code()
+        );
+    }
+}
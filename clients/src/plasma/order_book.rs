@@ -0,0 +1,531 @@
+use super::error::{Error, ErrorKind};
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::Address;
+use ovm::statements::plasma::{get_fill_price, verify_fill, NO_EXPIRY, WHOLE_FILL_ONLY};
+use ovm::types::state_object_kind::{EXCHANGED_KIND, ORDER_KIND, OWNERSHIP_KIND};
+use ovm::types::{match_state_object_kind, Property, StateUpdate};
+use plasma_core::data_structure::{BatchTransaction, Range, TransactionParams};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Making order which sells the range of state_update for c_amount of c_token.
+/// It can't be taken in blocks after expiry_block.
+/// If fill_size is set, c_amount is the price of fill_size and any part of the range can be taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Order {
+    state_update: StateUpdate,
+    maker: Address,
+    c_token: Address,
+    c_amount: u64,
+    expiry_block: Option<u64>,
+    fill_size: Option<u64>,
+}
+
+impl Order {
+    /// Returns None unless the state update is a making order.
+    pub fn from_state_update(state_update: &StateUpdate) -> Option<Self> {
+        let captures = match_state_object_kind(ORDER_KIND, state_update.get_property())?;
+        if state_update.get_amount() == 0 {
+            return None;
+        }
+        Some(Self {
+            state_update: state_update.clone(),
+            maker: captures.get_address("maker")?,
+            c_token: captures.get_address("token")?,
+            c_amount: captures.get_integer("amount")?.0,
+            expiry_block: Some(captures.get_integer("expiry")?)
+                .filter(|expiry| *expiry != NO_EXPIRY)
+                .map(|expiry| expiry.0),
+            fill_size: Some(captures.get_integer("fill_size")?)
+                .filter(|fill_size| *fill_size != WHOLE_FILL_ONLY)
+                .map(|fill_size| fill_size.0),
+        })
+    }
+
+    pub fn get_state_update(&self) -> &StateUpdate {
+        &self.state_update
+    }
+
+    pub fn get_maker(&self) -> Address {
+        self.maker
+    }
+
+    pub fn get_token(&self) -> Address {
+        self.state_update.get_deposit_contract_address()
+    }
+
+    pub fn get_range(&self) -> Range {
+        self.state_update.get_range()
+    }
+
+    pub fn get_amount(&self) -> u64 {
+        self.state_update.get_amount()
+    }
+
+    pub fn get_c_token(&self) -> Address {
+        self.c_token
+    }
+
+    pub fn get_c_amount(&self) -> u64 {
+        self.c_amount
+    }
+
+    pub fn get_expiry_block(&self) -> Option<u64> {
+        self.expiry_block
+    }
+
+    /// Whether the order can't be taken in the block of next_block_number.
+    pub fn is_expired(&self, next_block_number: u64) -> bool {
+        self.expiry_block
+            .map_or(false, |expiry| expiry < next_block_number)
+    }
+
+    pub fn get_fill_size(&self) -> Option<u64> {
+        self.fill_size
+    }
+
+    pub fn is_partially_fillable(&self) -> bool {
+        self.fill_size.is_some()
+    }
+
+    /// Returns c_amount to pay for filled amount from the start of the range.
+    /// None if the order can't be filled by the amount.
+    pub fn get_fill_price(&self, filled: u64) -> Option<u64> {
+        match self.fill_size {
+            Some(fill_size) if filled > 0 && filled <= self.get_amount() => Some(get_fill_price(
+                Integer::new(self.c_amount),
+                Integer::new(fill_size),
+                filled,
+            )),
+            None if filled == self.get_amount() => Some(self.c_amount),
+            _ => None,
+        }
+    }
+
+    /// The range taken by filling amount from the start.
+    pub fn get_fill_range(&self, filled: u64) -> Range {
+        let start = self.get_range().get_start();
+        Range::new(start, start + filled)
+    }
+
+    /// Returns the parts of the order out of the range, which stay the same order.
+    pub fn get_remainders(&self, range: Range) -> Vec<StateUpdate> {
+        let mut remainders = vec![];
+        if self.get_range().get_start() < range.get_start() {
+            let mut remainder = self.state_update.clone();
+            remainder.set_end(range.get_start().min(self.get_range().get_end()));
+            remainders.push(remainder);
+        }
+        if range.get_end() < self.get_range().get_end() {
+            let mut remainder = self.state_update.clone();
+            remainder.set_start(range.get_end().max(self.get_range().get_start()));
+            remainders.push(remainder);
+        }
+        remainders
+    }
+
+    /// Whether the entry takes a part of the order and pays its price to the maker.
+    pub fn is_filled_by(&self, entry: &TransactionParams) -> bool {
+        verify_fill(
+            entry,
+            &self.state_update,
+            self.c_token,
+            Integer::new(self.c_amount),
+            self.maker,
+            Integer::new(self.fill_size.unwrap_or(0)),
+        )
+    }
+
+    pub fn get_pair(&self) -> TokenPair {
+        TokenPair::new(self.get_token(), self.c_token)
+    }
+
+    /// Amount of the range which c_amount is paid for.
+    fn get_price_base(&self) -> u64 {
+        self.fill_size.unwrap_or_else(|| self.get_amount())
+    }
+
+    /// Compares c_amount per unit without rounding. The lower price is the better one for takers.
+    pub fn cmp_price(&self, other: &Order) -> Ordering {
+        (u128::from(self.c_amount) * u128::from(other.get_price_base()))
+            .cmp(&(u128::from(other.c_amount) * u128::from(self.get_price_base())))
+    }
+}
+
+/// token is sold by makers for c_token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TokenPair {
+    pub token: Address,
+    pub c_token: Address,
+}
+
+impl TokenPair {
+    pub fn new(token: Address, c_token: Address) -> Self {
+        Self { token, c_token }
+    }
+}
+
+/// Open making orders indexed by token pair.
+/// Orders of each pair are sorted by price, then by block number and start of the range.
+#[derive(Clone, Debug, Default)]
+pub struct OrderBook {
+    orders: HashMap<TokenPair, Vec<Order>>,
+}
+
+impl OrderBook {
+    pub fn from_state_updates(state_updates: &[StateUpdate]) -> Self {
+        let mut order_book = Self::default();
+        for state_update in state_updates {
+            order_book.insert(state_update);
+        }
+        order_book
+    }
+
+    /// Adds the state update if it is a making order. Returns the added order.
+    pub fn insert(&mut self, state_update: &StateUpdate) -> Option<Order> {
+        let order = Order::from_state_update(state_update)?;
+        let orders = self.orders.entry(order.get_pair()).or_default();
+        orders.retain(|o| !overlaps(&o.get_range(), &order.get_range()));
+        let index = orders
+            .iter()
+            .position(|o| {
+                order
+                    .cmp_price(o)
+                    .then(
+                        order
+                            .state_update
+                            .get_block_number()
+                            .0
+                            .cmp(&o.state_update.get_block_number().0),
+                    )
+                    .then(
+                        order
+                            .get_range()
+                            .get_start()
+                            .cmp(&o.get_range().get_start()),
+                    )
+                    == Ordering::Less
+            })
+            .unwrap_or_else(|| orders.len());
+        orders.insert(index, order.clone());
+        Some(order)
+    }
+
+    /// Removes orders of token overlapping the range. Returns removed orders.
+    pub fn remove_range(&mut self, token: Address, range: Range) -> Vec<Order> {
+        let mut removed = vec![];
+        for (pair, orders) in self.orders.iter_mut() {
+            if pair.token != token {
+                continue;
+            }
+            let (overlapping, rest): (Vec<Order>, Vec<Order>) = orders
+                .drain(..)
+                .partition(|o| overlaps(&o.get_range(), &range));
+            *orders = rest;
+            removed.extend(overlapping);
+        }
+        self.orders.retain(|_, orders| !orders.is_empty());
+        removed
+    }
+
+    /// Removes orders which can't be taken in the block of next_block_number. Returns removed orders.
+    pub fn remove_expired(&mut self, next_block_number: u64) -> Vec<Order> {
+        let mut removed = vec![];
+        for orders in self.orders.values_mut() {
+            let (expired, rest): (Vec<Order>, Vec<Order>) = orders
+                .drain(..)
+                .partition(|o| o.is_expired(next_block_number));
+            *orders = rest;
+            removed.extend(expired);
+        }
+        self.orders.retain(|_, orders| !orders.is_empty());
+        removed
+    }
+
+    pub fn apply(&mut self, update: &OrderBookUpdate) {
+        let state_update = &update.state_update;
+        if update.added {
+            self.insert(state_update);
+        } else {
+            self.remove_range(
+                state_update.get_deposit_contract_address(),
+                state_update.get_range(),
+            );
+        }
+    }
+
+    pub fn get_pairs(&self) -> Vec<TokenPair> {
+        self.orders.keys().cloned().collect()
+    }
+
+    pub fn get_orders(&self, pair: &TokenPair) -> Vec<Order> {
+        self.orders.get(pair).cloned().unwrap_or_else(|| vec![])
+    }
+
+    pub fn get_best_order(&self, pair: &TokenPair) -> Option<&Order> {
+        self.orders.get(pair).and_then(|orders| orders.first())
+    }
+
+    /// Returns the best priced order which can be taken in the block of next_block_number and the amount to fill.
+    /// Partially fillable orders are filled up to max_amount. Others must sell max_amount or less.
+    pub fn find_match(
+        &self,
+        pair: &TokenPair,
+        max_amount: u64,
+        next_block_number: u64,
+    ) -> Option<(&Order, u64)> {
+        self.orders
+            .get(pair)?
+            .iter()
+            .filter(|o| !o.is_expired(next_block_number))
+            .find_map(|o| {
+                let filled = if o.is_partially_fillable() {
+                    o.get_amount().min(max_amount)
+                } else {
+                    o.get_amount()
+                };
+                o.get_fill_price(filled)
+                    .filter(|_| filled <= max_amount)
+                    .map(|_| (o, filled))
+            })
+    }
+}
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.get_start() < b.get_end() && b.get_start() < a.get_end()
+}
+
+/// Change of the order book broadcasted by the aggregator.
+/// Removed orders are identified by the token and range of state_update.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct OrderBookUpdate {
+    added: bool,
+    state_update: StateUpdate,
+}
+
+impl OrderBookUpdate {
+    pub fn new(added: bool, state_update: StateUpdate) -> Self {
+        Self {
+            added,
+            state_update,
+        }
+    }
+
+    pub fn added(order: &Order) -> Self {
+        Self::new(true, order.state_update.clone())
+    }
+
+    pub fn removed(order: &Order) -> Self {
+        Self::new(false, order.state_update.clone())
+    }
+
+    pub fn is_added(&self) -> bool {
+        self.added
+    }
+
+    pub fn get_state_update(&self) -> &StateUpdate {
+        &self.state_update
+    }
+}
+
+/// Request to the aggregator to match the taker with the best order selling amount of token or less.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct MatchOrderRequest {
+    pub taker: Address,
+    pub token: Address,
+    pub c_token: Address,
+    pub amount: Integer,
+}
+
+impl MatchOrderRequest {
+    pub fn new(taker: Address, token: Address, c_token: Address, amount: Integer) -> Self {
+        Self {
+            taker,
+            token,
+            c_token,
+            amount,
+        }
+    }
+}
+
+/// Checks the unsigned batch built by the aggregator before the taker signs it.
+/// It must take exactly one order for the taker and pay its c_range to the maker.
+/// The other entries may only pay fees to fee_recipient.
+pub fn check_match(
+    batch: &BatchTransaction,
+    taker: Address,
+    fee_recipient: Option<Address>,
+) -> Result<(), Error> {
+    let mut taking = None;
+    let mut payees = vec![];
+    for entry in batch.get_entries() {
+        let property = Property::from_abi(entry.get_parameters())?;
+        if let Some(captures) = match_state_object_kind(EXCHANGED_KIND, &property) {
+            if taking.is_some() || captures.get_address("owner") != Some(taker) {
+                return Err(Error::from(ErrorKind::InvalidParameter));
+            }
+            taking = Some((
+                captures.get_address("maker"),
+                captures.get_address("c_token"),
+                captures.get_range("c_range"),
+            ));
+        } else if let Some(captures) = match_state_object_kind(OWNERSHIP_KIND, &property) {
+            payees.push((
+                captures.get_address("owner"),
+                entry.get_deposit_contract_address(),
+                entry.get_range(),
+            ));
+        } else {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+    }
+    let (maker, c_token, c_range) = match taking {
+        Some((Some(maker), Some(c_token), Some(c_range))) => (maker, c_token, c_range),
+        _ => return Err(Error::from(ErrorKind::InvalidParameter)),
+    };
+    let pays_maker = payees.iter().any(|p| *p == (Some(maker), c_token, c_range));
+    let pays_only_fees = payees
+        .iter()
+        .filter(|p| **p != (Some(maker), c_token, c_range))
+        .all(|(owner, _, _)| owner.is_some() && *owner == fee_recipient);
+    if pays_maker && pays_only_fees {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::InvalidParameter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::statements::plasma::{
+        create_making_order_state_object, create_ownership_state_object,
+    };
+
+    fn create_order(
+        block_number: u64,
+        start: u64,
+        end: u64,
+        c_token: Address,
+        c_amount: u64,
+        expiry_block: Option<u64>,
+        fill_size: Option<u64>,
+    ) -> StateUpdate {
+        StateUpdate::new(
+            Integer::new(block_number),
+            Address::zero(),
+            Range::new(start, end),
+            create_making_order_state_object(
+                Address::zero(),
+                c_token,
+                Integer::new(c_amount),
+                expiry_block.map(Integer::new),
+                fill_size.map(Integer::new),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_order_book() {
+        let c_token = Address::from([1; 20]);
+        let pair = TokenPair::new(Address::zero(), c_token);
+        let state_updates = vec![
+            create_order(1, 0, 10, c_token, 30, None, None),
+            create_order(1, 10, 30, c_token, 40, None, None),
+            create_order(2, 30, 40, c_token, 20, None, None),
+            StateUpdate::new(
+                Integer::new(1),
+                Address::zero(),
+                Range::new(40, 50),
+                create_ownership_state_object(Address::zero()),
+            ),
+        ];
+        let mut order_book = OrderBook::from_state_updates(&state_updates);
+        let ranges: Vec<Range> = order_book
+            .get_orders(&pair)
+            .iter()
+            .map(|o| o.get_range())
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![Range::new(10, 30), Range::new(30, 40), Range::new(0, 10)]
+        );
+        assert_eq!(
+            order_book.get_best_order(&pair).unwrap().get_range(),
+            Range::new(10, 30)
+        );
+        assert_eq!(
+            order_book.find_match(&pair, 10, 3).unwrap().0.get_range(),
+            Range::new(30, 40)
+        );
+
+        let removed = order_book.remove_range(Address::zero(), Range::new(20, 35));
+        assert_eq!(removed.len(), 2);
+        order_book.apply(&OrderBookUpdate::added(&removed[0]));
+        assert_eq!(order_book.get_orders(&pair).len(), 2);
+        let update =
+            OrderBookUpdate::from_abi(&OrderBookUpdate::removed(&removed[0]).to_abi()).unwrap();
+        order_book.apply(&update);
+        assert_eq!(order_book.get_orders(&pair).len(), 1);
+    }
+
+    #[test]
+    fn test_expired_orders() {
+        let c_token = Address::from([1; 20]);
+        let pair = TokenPair::new(Address::zero(), c_token);
+        let state_updates = vec![
+            create_order(1, 0, 10, c_token, 10, Some(3), None),
+            create_order(1, 10, 20, c_token, 20, None, None),
+        ];
+        let mut order_book = OrderBook::from_state_updates(&state_updates);
+        assert_eq!(
+            order_book.get_best_order(&pair).unwrap().get_expiry_block(),
+            Some(3)
+        );
+        assert_eq!(
+            order_book.find_match(&pair, 10, 3).unwrap().0.get_range(),
+            Range::new(0, 10)
+        );
+        assert!(order_book.find_match(&pair, 10, 4).is_none());
+        let removed = order_book.remove_expired(4);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].get_range(), Range::new(0, 10));
+        assert_eq!(order_book.get_orders(&pair).len(), 1);
+    }
+
+    #[test]
+    fn test_partial_fill() {
+        let c_token = Address::from([1; 20]);
+        let pair = TokenPair::new(Address::zero(), c_token);
+        let state_updates = vec![
+            create_order(1, 0, 100, c_token, 30, None, Some(10)),
+            create_order(1, 100, 110, c_token, 40, None, None),
+        ];
+        let mut order_book = OrderBook::from_state_updates(&state_updates);
+        let (order, filled) = order_book.find_match(&pair, 25, 1).unwrap();
+        let order = order.clone();
+        assert_eq!(filled, 25);
+        assert_eq!(order.get_fill_price(filled), Some(75));
+        assert_eq!(order.get_fill_price(101), None);
+        assert_eq!(
+            order_book.get_orders(&pair)[1].get_fill_price(5),
+            None,
+            "an order without fill size is taken whole"
+        );
+
+        let taken = order.get_fill_range(filled);
+        order_book.remove_range(Address::zero(), taken);
+        for remainder in order.get_remainders(taken) {
+            order_book.insert(&remainder);
+        }
+        let best = order_book.get_best_order(&pair).unwrap();
+        assert_eq!(best.get_range(), Range::new(25, 100));
+        assert_eq!(best.get_fill_price(75), Some(225));
+        // the whole order inserted again replaces its remainder
+        order_book.insert(&state_updates[0]);
+        assert_eq!(order_book.get_orders(&pair).len(), 2);
+    }
+}
@@ -1,34 +1,65 @@
-use super::block_manager::BlockManager;
-use super::command::NewTransactionEvent;
+use super::block_manager::{BlockManager, CommitmentChainOptions};
+use super::coin_selection::{select_coins, split_selected_ranges, CoinSelectionPolicy};
+use super::command::{NewTransactionEvent, MAX_BLOCKS_PER_FETCH};
 use super::error::{Error, ErrorKind};
+use super::fee::{FeeDb, FeePolicy};
+use super::inclusion_promise::InclusionPromise;
+use super::mempool::{ConflictPolicy, Mempool, MempoolOptions, PendingTransaction};
+use super::order_book::{MatchOrderRequest, Order, OrderBook, OrderBookUpdate, TokenPair};
 use super::plasma_block::PlasmaBlock;
 use super::plasma_client::PlasmaClientShell;
 use super::state_db::StateDb;
-use super::token::Token;
+use super::token::{default_tokens, Token};
+use super::token_db::TokenDb;
 use super::utils::*;
+use abi_utils::Encodable;
 use bytes::Bytes;
 use ethereum_types::Address;
 use ethsign::SecretKey;
+use ovm::statements::plasma::create_taking_order_state_object;
 use ovm::{
     db::*,
     deciders::SignVerifier,
     property_executor::{PropertyExecuterOptions, PropertyExecutor},
     types::*,
 };
-use plasma_core::data_structure::{Range, Transaction};
+use plasma_core::data_structure::{
+    BatchTransaction, Metadata, Range, Transaction, TransactionParams, EXCHANGE_TYPE, PAYMENT_TYPE,
+};
 use plasma_db::prelude::*;
+use plasma_db::range::Range as RangeRecord;
+use std::time::Instant;
+
+/// Transaction which passed validation but isn't applied to the state yet.
+struct ValidatedTransaction {
+    transaction: Transaction,
+    sender: Address,
+    replaced: Vec<PendingTransaction>,
+    prev_states: Vec<StateUpdate>,
+    next_states: Vec<StateUpdate>,
+}
+
+/// Witnesses stored while validating, which are removed again if validation fails.
+#[derive(Default)]
+struct WitnessUndo {
+    transactions: Vec<(u64, Range, Vec<RangeRecord>)>,
+    witnesses: Vec<(Address, Bytes)>,
+}
 
 pub struct PlasmaAggregator<KVS: KeyValueStore> {
     aggregator_address: Address,
     commitment_contract_address: Address,
     deposit_contract_address: Address,
     //_secret_key: SecretKey,
-    _raw_key: Vec<u8>,
+    raw_key: Vec<u8>,
     _my_address: Address,
     block_manager: BlockManager<KVS>,
     decider: PropertyExecutor<KVS>,
+    mempool: Mempool,
+    fee_policy: Option<FeePolicy>,
+    order_book: OrderBook,
+    order_book_updates: Vec<OrderBookUpdate>,
     //_secret_key: SecretKey,
-    //    state_update_queue:
 }
 
 impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
@@ -38,17 +69,23 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
         deposit_contract_address: Address,
         commitment_contract_address: Address,
         private_key: &str,
-    ) -> Self {
-        let raw_key = hex::decode(private_key).unwrap();
-        let secret_key = SecretKey::from_raw(&raw_key).unwrap();
+    ) -> Result<Self, Error> {
+        let raw_key =
+            hex::decode(private_key).map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        let secret_key =
+            SecretKey::from_raw(&raw_key).map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
         let my_address: Address = secret_key.public().address().into();
-        let block_manager = BlockManager::new(aggregator_address, commitment_contract_address);
+        let block_manager = BlockManager::new(
+            &format!("{}-{}", db_name, "block"),
+            aggregator_address,
+            commitment_contract_address,
+        )?;
 
-        PlasmaAggregator {
+        let mut aggregator = PlasmaAggregator {
             aggregator_address,
             deposit_contract_address,
             commitment_contract_address,
-            _raw_key: raw_key,
+            raw_key,
             //_secret_key: secret_key,
             _my_address: my_address,
             block_manager,
@@ -57,76 +94,575 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
                 is_aggregator: true,
                 db_name: db_name.to_string(),
             }),
+            mempool: Mempool::new(MempoolOptions::default()),
+            fee_policy: None,
+            order_book: OrderBook::default(),
+            order_book_updates: vec![],
+        };
+        aggregator.order_book = OrderBook::from_state_updates(&aggregator.get_all_state_updates());
+        aggregator
+            .order_book
+            .remove_expired(aggregator.get_current_block_number());
+        Ok(aggregator)
+    }
+
+    /// Signs a promise to include the ingested transaction in the current block.
+    /// The promised transaction is never expired from the mempool.
+    /// Returns None if the transaction can be replaced by its sender or isn't pending.
+    pub fn create_inclusion_promise(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Option<InclusionPromise> {
+        if self.mempool.get_options().conflict_policy != ConflictPolicy::Reject
+            || !self.mempool.mark_promised(transaction)
+        {
+            return None;
+        }
+        let secret_key = SecretKey::from_raw(&self.raw_key).ok()?;
+        Some(InclusionPromise::sign(
+            &secret_key,
+            get_transaction_hash(transaction),
+            transaction.get_deposit_contract_address(),
+            transaction.get_range(),
+            self.block_manager.get_current_block_number(),
+        ))
+    }
+
+    pub fn set_mempool_options(&mut self, options: MempoolOptions) {
+        self.mempool.set_options(options);
+    }
+
+    /// Must be set before resubmitting blocks on startup.
+    pub fn set_commitment_chain_options(&mut self, options: CommitmentChainOptions) {
+        self.block_manager.set_commitment_chain_options(options);
+    }
+
+    /// Transactions are processed for free if no fee policy is set.
+    pub fn set_fee_policy(&mut self, fee_policy: Option<FeePolicy>) {
+        self.fee_policy = fee_policy;
+    }
+
+    pub fn get_fee_policy(&self) -> Option<&FeePolicy> {
+        self.fee_policy.as_ref()
+    }
+
+    // 0. reject entries of a batch. they are accepted only together by ingest_batch_transaction.
+    //    if fee policy is set, check that the transaction pays enough fee.
+    // 1. validate the transaction without changing the state. See validate_transaction.
+    // 2. evict pending transactions replaced by the transaction.
+    // 3. add new state_update to a queue and the mempool.
+    pub fn ingest_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<NewTransactionEvent, Error> {
+        if transaction.is_batched() {
+            return Err(Error::from(ErrorKind::InvalidTransaction));
+        }
+        if let Some(fee_policy) = &self.fee_policy {
+            fee_policy.check_fee(&[transaction.to_params()])?;
+        }
+        let mut undo = WitnessUndo::default();
+        match self.validate_transaction(&transaction, &mut undo) {
+            Ok(validated) => {
+                for replaced in validated.replaced.iter() {
+                    self.evict_pending_transaction(replaced)?;
+                }
+                self.queue_transaction(validated, Instant::now())
+            }
+            Err(e) => {
+                self.undo_witnesses(undo)?;
+                Err(e)
+            }
         }
     }
 
-    // 1. query all state_updates overlapping with given range.
-    // 2. check if the range of transaction is covered by queried state_updates. If not, return
-    //    InvalidTransaction Error.
-    // 3. for all state_updates, check state transition using state_update.property.decide(transaction).
+    // 1. check conflicts with pending transactions in mempool. Replaced ones are evicted only after validation.
+    // 2. query all state_updates overlapping with given range. Parts spent by replaced transactions
+    //    are validated against the state updates which the replaced ones deprecated.
+    // 3. check if the range of transaction is covered by queried state_updates. If not, return
+    //    InvalidTransaction Error. Expired orders can only be spent by the maker to cancel them,
+    //    and others must be taken by paying the price of the filled part.
+    // 4. store the transaction and its signatures as witnesses, recording them in undo.
+    // 5. for all state_updates, check state transition using state_update.property.decide(transaction).
     //    any of these throw error, return InvalidTransaction Error.
-    // 4. if all transitions are verified, add new state_update to a queue.
     //
     // TODO:
     // - handle multi prev_states case.
     // - fix decide logic for state transition.
-    pub fn ingest_transaction(
-        &mut self,
-        transaction: Transaction,
-    ) -> Result<NewTransactionEvent, Error> {
-        let transaction_db = TransactionDb::new(self.decider.get_range_db());
-        let signed_by_db = SignedByDb::new(self.decider.get_db());
+    fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+        undo: &mut WitnessUndo,
+    ) -> Result<ValidatedTransaction, Error> {
+        let message = transaction.get_signed_message();
+        let sender = SignVerifier::try_recover(&transaction.get_raw_signature(), &message)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidTransaction))?;
+        let replaced = self.mempool.check_conflicts(transaction, sender)?;
         let next_block_number = self.block_manager.get_current_block_number();
-        let mut state_db = StateDb::new(self.decider.get_range_db());
-        let state_updates = state_db
-            .get_verified_state_updates(
-                transaction.get_deposit_contract_address(),
-                transaction.get_range().get_start(),
-                transaction.get_range().get_end(),
-            )
-            .unwrap();
-        if state_updates.is_empty() {
+        let state_updates = self.get_prev_states(transaction, &replaced)?;
+        if !is_covered(transaction.get_range(), &state_updates) {
             return Err(Error::from(ErrorKind::InvalidTransaction));
         }
-        // Store witness
-        // TODO: if one of these Database operation failed, need to roll back all of them.
-        for prev_state in state_updates.clone() {
-            transaction_db.put_transaction(prev_state.get_block_number().0, transaction.clone());
-        }
-        let message = Bytes::from(transaction.to_body_abi());
-        assert!(signed_by_db
-            .store_witness(
-                SignVerifier::recover(transaction.get_signature(), &message),
-                message,
-                transaction.get_signature().clone(),
-            )
-            .is_ok());
+        for prev_state in state_updates.iter() {
+            if let Some(order) = Order::from_state_update(prev_state) {
+                if order.get_maker() == sender {
+                    continue;
+                }
+                if order.is_expired(next_block_number) {
+                    return Err(Error::from(ErrorKind::OrderExpired));
+                }
+                if !order.is_filled_by(&transaction.to_params()) {
+                    return Err(Error::from(ErrorKind::InvalidTransaction));
+                }
+            }
+        }
+        self.store_witnesses(transaction, &state_updates, undo)?;
         // Check that the transaction deprecate all previous state_updates within same coin range.
-        for prev_state in state_updates.clone() {
+        let mut next_states = vec![];
+        for prev_state in state_updates.iter() {
             // Current execute_state_transition returns next state_update which has the same range as transaction.
             // It means same next_state is added to storage multiple times and it's overwrite.
-            if let Ok(next_state) = prev_state.execute_state_transition(
+            match prev_state.execute_state_transition(
                 &self.decider,
-                &transaction,
+                transaction,
                 Integer(next_block_number),
             ) {
-                self.block_manager.enqueue_state_update(&next_state)?;
-                state_db.put_verified_state_update(&next_state)?;
-            } else {
-                return Err(Error::from(ErrorKind::InvalidTransaction));
+                Ok(next_state) => next_states.push(next_state),
+                Err(_) => return Err(Error::from(ErrorKind::InvalidTransaction)),
             }
         }
-        let prev_block_numbers = state_updates.iter().map(|s| s.get_block_number()).collect();
-        let new_tx = NewTransactionEvent::new(prev_block_numbers, transaction.clone());
+        Ok(ValidatedTransaction {
+            transaction: transaction.clone(),
+            sender,
+            replaced,
+            prev_states: state_updates,
+            next_states,
+        })
+    }
+
+    /// State updates which transaction deprecates once the replaced pending transactions are evicted.
+    fn get_prev_states(
+        &self,
+        transaction: &Transaction,
+        replaced: &[PendingTransaction],
+    ) -> Result<Vec<StateUpdate>, Error> {
+        let range = transaction.get_range();
+        // state updates of replaced transactions are substituted by their prev_states
+        let is_replaced = |r: &Range| {
+            replaced.iter().flat_map(|p| p.get_prev_states()).any(|s| {
+                let replaced_range = s.get_range();
+                replaced_range.get_start() < r.get_end() && r.get_start() < replaced_range.get_end()
+            })
+        };
+        let state_db = StateDb::new(self.decider.get_range_db());
+        let mut state_updates: Vec<StateUpdate> = state_db
+            .get_verified_state_updates(
+                transaction.get_deposit_contract_address(),
+                range.get_start(),
+                range.get_end(),
+            )?
+            .into_iter()
+            .filter(|s| !is_replaced(&s.get_range()))
+            .collect();
+        for pending in replaced {
+            state_updates.extend(
+                pending
+                    .get_prev_states()
+                    .iter()
+                    .filter(|s| {
+                        s.get_range().get_start() < range.get_end()
+                            && range.get_start() < s.get_range().get_end()
+                    })
+                    .cloned(),
+            );
+        }
+        state_updates.sort_by_key(|s| s.get_range().get_start());
+        Ok(state_updates)
+    }
+
+    /// Stores transaction at the blocks of prev_states and its signatures so that deciders can find them.
+    fn store_witnesses(
+        &self,
+        transaction: &Transaction,
+        prev_states: &[StateUpdate],
+        undo: &mut WitnessUndo,
+    ) -> Result<(), Error> {
+        let transaction_db = TransactionDb::new(self.decider.get_range_db());
+        let signed_by_db = SignedByDb::new(self.decider.get_db());
+        let message = transaction.get_signed_message();
+        for prev_state in prev_states {
+            let block_number = prev_state.get_block_number().0;
+            let records = transaction_db.get_records(block_number, transaction.get_range())?;
+            undo.transactions
+                .push((block_number, transaction.get_range(), records));
+            transaction_db.put_transaction(block_number, transaction.clone());
+        }
+        for signature in transaction.get_signatures() {
+            let signer = SignVerifier::try_recover(&signature, &message)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidTransaction))?;
+            if signed_by_db.get_witness(signer, &message).is_err() {
+                undo.witnesses.push((signer, message.clone()));
+            }
+            signed_by_db
+                .store_witness(signer, message.clone(), signature)
+                .map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
+        }
+        Ok(())
+    }
+
+    /// Removes witnesses stored for rejected transactions in reverse order.
+    fn undo_witnesses(&self, undo: WitnessUndo) -> Result<(), Error> {
+        let transaction_db = TransactionDb::new(self.decider.get_range_db());
+        let signed_by_db = SignedByDb::new(self.decider.get_db());
+        for (block_number, range, records) in undo.transactions.iter().rev() {
+            transaction_db.restore_records(*block_number, *range, records)?;
+        }
+        for (signer, message) in undo.witnesses.iter() {
+            signed_by_db
+                .remove_witness(*signer, message)
+                .map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
+        }
+        Ok(())
+    }
+
+    /// Queues the next states of validated transaction and adds it to the mempool.
+    /// Pending transactions replaced by it must be evicted before.
+    fn queue_transaction(
+        &mut self,
+        validated: ValidatedTransaction,
+        received_at: Instant,
+    ) -> Result<NewTransactionEvent, Error> {
+        let mut state_db = StateDb::new(self.decider.get_range_db());
+        for next_state in validated.next_states.iter() {
+            self.block_manager.enqueue_state_update(next_state)?;
+            state_db.put_verified_state_update(next_state)?;
+        }
+        let prev_block_numbers = validated
+            .prev_states
+            .iter()
+            .map(|s| s.get_block_number())
+            .collect();
+        let new_tx = NewTransactionEvent::new(prev_block_numbers, validated.transaction.clone());
         self.block_manager.enqueue_tx(new_tx.clone())?;
+        self.mempool.insert(PendingTransaction::new(
+            validated.transaction,
+            validated.sender,
+            validated.prev_states,
+            received_at,
+        ))?;
+        self.update_order_book(
+            new_tx.transaction.get_deposit_contract_address(),
+            new_tx.transaction.get_range(),
+            &validated.next_states,
+        );
         Ok(new_tx)
     }
 
+    /// Removes orders spent in the range and adds new ones.
+    /// Parts of removed orders out of the range are added again as they stay the same orders.
+    /// Updates are kept until they are taken for broadcast.
+    fn update_order_book(&mut self, token: Address, range: Range, state_updates: &[StateUpdate]) {
+        let mut remainders = vec![];
+        for order in self.order_book.remove_range(token, range) {
+            self.order_book_updates
+                .push(OrderBookUpdate::removed(&order));
+            remainders.extend(order.get_remainders(range));
+        }
+        for state_update in remainders.iter().chain(state_updates.iter()) {
+            if let Some(order) = self.order_book.insert(state_update) {
+                self.order_book_updates.push(OrderBookUpdate::added(&order));
+            }
+        }
+    }
+
+    pub fn get_order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    /// Returns order book updates since the last call.
+    pub fn take_order_book_updates(&mut self) -> Vec<OrderBookUpdate> {
+        std::mem::take(&mut self.order_book_updates)
+    }
+
+    /// Builds the unsigned batch which takes the best order for the taker.
+    /// Partially fillable orders are filled up to the requested amount.
+    /// The taker pays the price to the maker from one owned range, and the fee if fee policy is set.
+    pub fn match_order(&self, request: &MatchOrderRequest) -> Result<BatchTransaction, Error> {
+        let (order, filled) = self
+            .order_book
+            .find_match(
+                &TokenPair::new(request.token, request.c_token),
+                request.amount.0,
+                self.get_current_block_number(),
+            )
+            .ok_or_else(|| Error::from(ErrorKind::OrderNotFound))?;
+        let state_db = StateDb::new(self.decider.get_range_db());
+        let owned = |token: Address| -> Result<Vec<StateUpdate>, Error> {
+            Ok(state_db
+                .get_owned_state_updates(token, request.taker)?
+                .into_iter()
+                .filter(|s| s.is_ownership_state())
+                .collect())
+        };
+        let price = order
+            .get_fill_price(filled)
+            .ok_or_else(|| Error::from(ErrorKind::OrderNotFound))?;
+        let mut payments = vec![(order.get_maker(), price)];
+        let mut fee_payments = vec![];
+        if let Some(fee_policy) = &self.fee_policy {
+            let fee = fee_policy.estimate_fee(2);
+            if fee > 0 && fee_policy.fee_token == order.get_c_token() {
+                payments.push((fee_policy.recipient, fee));
+            } else if fee > 0 {
+                fee_payments.push((fee_policy.recipient, fee));
+            }
+        }
+        let mut entries = vec![];
+        let mut metadata = vec![];
+        let amounts: Vec<u64> = payments.iter().map(|(_, amount)| *amount).collect();
+        let selected = select_coins(
+            &owned(order.get_c_token())?,
+            amounts.iter().sum(),
+            CoinSelectionPolicy::FewestRanges,
+        )?;
+        let split = split_selected_ranges(&selected, &amounts)?;
+        // the taking order refers to one counter range
+        if split[0].len() != 1 {
+            return Err(Error::from(ErrorKind::InsufficientBalance));
+        }
+        let c_range = split[0][0].get_range();
+        let mut pieces: Vec<(Address, Address, Range)> = vec![];
+        for ((to_address, _), ranges) in payments.iter().zip(split.iter()) {
+            for s in ranges {
+                pieces.push((*to_address, order.get_c_token(), s.get_range()));
+            }
+        }
+        if let Some(fee_policy) = &self.fee_policy {
+            for (to_address, fee) in fee_payments {
+                let selected = select_coins(
+                    &owned(fee_policy.fee_token)?,
+                    fee,
+                    CoinSelectionPolicy::FewestRanges,
+                )?;
+                for s in selected {
+                    pieces.push((to_address, fee_policy.fee_token, s.get_range()));
+                }
+            }
+        }
+        for (to_address, token, range) in pieces {
+            entries.push(TransactionParams::new(
+                token,
+                range,
+                Bytes::from(PlasmaClientShell::create_ownership_state_object(to_address).to_abi()),
+            ));
+            metadata.push(Metadata::new(PAYMENT_TYPE, request.taker, to_address));
+        }
+        entries.push(TransactionParams::new(
+            order.get_token(),
+            order.get_fill_range(filled),
+            Bytes::from(
+                create_taking_order_state_object(
+                    request.taker,
+                    order.get_maker(),
+                    order.get_c_token(),
+                    c_range,
+                )
+                .to_abi(),
+            ),
+        ));
+        metadata.push(Metadata::new(
+            EXCHANGE_TYPE,
+            request.taker,
+            order.get_maker(),
+        ));
+        Ok(BatchTransaction::new(entries, metadata, Bytes::default()))
+    }
+
+    /// Ingests all entries of the batch or none of them.
+    /// All entries are validated before any of them changes the state.
+    /// If queueing fails, queued entries are evicted and the pending transactions they replaced are restored.
+    pub fn ingest_batch_transaction(
+        &mut self,
+        batch: BatchTransaction,
+    ) -> Result<Vec<NewTransactionEvent>, Error> {
+        if batch.get_entries().is_empty()
+            || batch.get_entries().len() != batch.get_metadata().len()
+            || batch.has_overlapping_entries()
+        {
+            return Err(Error::from(ErrorKind::InvalidTransaction));
+        }
+        // entries are ingested all together, so the fee is paid by the whole batch
+        if let Some(fee_policy) = &self.fee_policy {
+            fee_policy.check_fee(batch.get_entries())?;
+        }
+        let transactions = batch.to_transactions();
+        let mut undo = WitnessUndo::default();
+        let mut validated = vec![];
+        for transaction in transactions.iter() {
+            match self.validate_transaction(transaction, &mut undo) {
+                Ok(v) => validated.push(v),
+                Err(e) => {
+                    self.undo_witnesses(undo)?;
+                    return Err(e);
+                }
+            }
+        }
+        // a pending transaction can be replaced by several entries
+        let mut replaced: Vec<PendingTransaction> = vec![];
+        for pending in validated.iter().flat_map(|v| v.replaced.iter()) {
+            if !replaced
+                .iter()
+                .any(|r| r.get_transaction() == pending.get_transaction())
+            {
+                replaced.push(pending.clone());
+            }
+        }
+        let mut new_txs = vec![];
+        if let Err(e) = self.apply_batch(validated, &replaced, &mut new_txs) {
+            self.rollback_batch(&new_txs, &replaced, undo)?;
+            return Err(e);
+        }
+        Ok(new_txs)
+    }
+
+    fn apply_batch(
+        &mut self,
+        validated: Vec<ValidatedTransaction>,
+        replaced: &[PendingTransaction],
+        new_txs: &mut Vec<NewTransactionEvent>,
+    ) -> Result<(), Error> {
+        for pending in replaced {
+            self.evict_pending_transaction(pending)?;
+        }
+        let now = Instant::now();
+        for v in validated {
+            new_txs.push(self.queue_transaction(v, now)?);
+        }
+        Ok(())
+    }
+
+    /// Evicts queued entries of a batch, removes its witnesses and restores the pending transactions it replaced.
+    fn rollback_batch(
+        &mut self,
+        new_txs: &[NewTransactionEvent],
+        replaced: &[PendingTransaction],
+        undo: WitnessUndo,
+    ) -> Result<(), Error> {
+        for new_tx in new_txs {
+            if let Some(pending) = self.mempool.remove(&new_tx.transaction) {
+                self.evict_pending_transaction(&pending)?;
+            }
+        }
+        self.undo_witnesses(undo)?;
+        for pending in replaced {
+            if !self.mempool.contains(pending.get_transaction()) {
+                self.reinstate_pending_transaction(pending)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ingests the evicted pending transaction again keeping the time it was received.
+    fn reinstate_pending_transaction(&mut self, pending: &PendingTransaction) -> Result<(), Error> {
+        let mut undo = WitnessUndo::default();
+        let validated = self.validate_transaction(pending.get_transaction(), &mut undo)?;
+        self.queue_transaction(validated, pending.get_received_at())?;
+        Ok(())
+    }
+
+    /// Restores the state updates deprecated by pending transaction,
+    /// removes its queued state update from the next block and the transaction from witnesses.
+    fn evict_pending_transaction(&mut self, pending: &PendingTransaction) -> Result<(), Error> {
+        let transaction = pending.get_transaction();
+        let range = transaction.get_range();
+        self.mempool.remove(transaction);
+        self.update_order_book(
+            transaction.get_deposit_contract_address(),
+            range,
+            pending.get_prev_states(),
+        );
+        self.block_manager.dequeue_range(
+            transaction.get_deposit_contract_address(),
+            range.get_start(),
+            range.get_end(),
+        )?;
+        let mut state_db = StateDb::new(self.decider.get_range_db());
+        let transaction_db = TransactionDb::new(self.decider.get_range_db());
+        for prev_state in pending.get_prev_states() {
+            state_db.put_verified_state_update(prev_state)?;
+            transaction_db.remove_transaction(prev_state.get_block_number().0, transaction)?;
+        }
+        Ok(())
+    }
+
+    /// Evicts pending transactions which stayed in mempool longer than max_age at now.
+    pub fn expire_stale_transactions(&mut self, now: Instant) -> Result<Vec<Transaction>, Error> {
+        let expired = self.mempool.remove_expired(now);
+        for pending in expired.iter() {
+            self.evict_pending_transaction(pending)?;
+        }
+        Ok(expired
+            .into_iter()
+            .map(|p| p.get_transaction().clone())
+            .collect())
+    }
+
+    pub fn get_pending_transactions(&self) -> Vec<&PendingTransaction> {
+        self.mempool.get_pending_transactions()
+    }
+
+    pub fn get_oldest_pending_transaction_time(&self) -> Option<Instant> {
+        self.mempool
+            .get_pending_transactions()
+            .iter()
+            .map(|p| p.get_received_at())
+            .min()
+    }
+
+    pub fn get_queued_state_update_count(&self) -> usize {
+        self.block_manager.get_queued_state_updates().len()
+    }
+
     pub fn submit_next_block(&mut self) -> Result<(), Error> {
         // dequeue all state_update stored in range db
         // generate block using that data.
-        let block_manager = &mut self.block_manager;
-        block_manager.submit_next_block()
+        if let Some(fee_policy) = &self.fee_policy {
+            let fees: Vec<StateUpdate> = self
+                .block_manager
+                .get_queued_state_updates()
+                .into_iter()
+                .filter(|s| fee_policy.is_fee_state_update(s))
+                .collect();
+            FeeDb::new(self.decider.get_db())
+                .put_collected_fees(self.block_manager.get_current_block_number(), &fees)?;
+        }
+        self.block_manager.build_next_block()?;
+        // transactions in the persisted block must leave the mempool even if submission fails,
+        // otherwise expiring them would revert the included state updates.
+        self.mempool.clear();
+        for order in self
+            .order_book
+            .remove_expired(self.block_manager.get_current_block_number())
+        {
+            self.order_book_updates
+                .push(OrderBookUpdate::removed(&order));
+        }
+        // roots which failed to be submitted, including this one, are retried at the next block.
+        self.block_manager.resubmit_unsubmitted_blocks().map(|_| ())
+    }
+
+    /// Resubmits blocks which were built before restart but not submitted to CommitmentContract.
+    pub fn resubmit_unsubmitted_blocks(&self) -> Result<Vec<u64>, Error> {
+        self.block_manager.resubmit_unsubmitted_blocks()
+    }
+
+    /// Returns fee ranges credited to the aggregator in the block.
+    pub fn get_collected_fees(&self, block_number: u64) -> Result<Vec<StateUpdate>, Error> {
+        FeeDb::new(self.decider.get_db()).get_collected_fees(block_number)
+    }
+
+    pub fn get_current_block_number(&self) -> u64 {
+        self.block_manager.get_current_block_number()
     }
 
     pub fn get_aggregator_addres(&self) -> Address {
@@ -150,8 +686,9 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
             return;
         }
         let mut state_db = StateDb::new(self.decider.get_range_db());
-        let eth_token_address = Address::zero();
-        let dai_token_address = string_to_address("0000000000000000000000000000000000000001");
+        let tokens = default_tokens();
+        let eth_token_address = tokens[0].get_address();
+        let dai_token_address = tokens[1].get_address();
         for i in 0..3 {
             let state_update = StateUpdate::new(
                 Integer::new(0),
@@ -178,7 +715,14 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
 
     pub fn get_all_state_updates(&self) -> Vec<StateUpdate> {
         let state_db = StateDb::new(self.decider.get_range_db());
-        state_db.get_all_state_updates().unwrap_or_else(|_| vec![])
+        let addresses: Vec<Address> = self
+            .get_all_tokens()
+            .iter()
+            .map(|token| token.get_address())
+            .collect();
+        state_db
+            .get_all_state_updates(&addresses)
+            .unwrap_or_else(|_| vec![])
     }
 
     pub fn get_state_updates_of_block(
@@ -194,20 +738,40 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaAggregator<KVS> {
         self.block_manager.get_block_range(block_number)
     }
 
-    pub fn register_token(_token: Token) {
-        // TODO: implement
-        unimplemented!("Register Token is not impemented yet");
+    /// Returns stored blocks from from_block to to_block inclusive, at most MAX_BLOCKS_PER_FETCH blocks.
+    /// Stops at the first block which isn't produced yet.
+    pub fn get_plasma_blocks(&self, from_block: u64, to_block: u64) -> Vec<PlasmaBlock> {
+        (from_block..=to_block)
+            .take(MAX_BLOCKS_PER_FETCH as usize)
+            .map(|n| self.get_plasma_block_of_block(Integer::new(n)))
+            .take_while(|result| result.is_ok())
+            .filter_map(|result| result.ok())
+            .collect()
+    }
+
+    /// Registers token or updates registered one with the same deposit contract address.
+    pub fn register_token(&self, token: Token) -> Result<(), Error> {
+        TokenDb::new(self.decider.get_db()).put_token(&token)
+    }
+
+    /// Registers tokens from config.
+    pub fn register_tokens(&self, tokens: Vec<Token>) -> Result<(), Error> {
+        for token in tokens {
+            self.register_token(token)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_token(&self, address: Address) -> Option<Token> {
+        TokenDb::new(self.decider.get_db())
+            .get_token(address)
+            .unwrap_or(None)
     }
 
-    // TODO: get dynamically using token map?
     pub fn get_all_tokens(&self) -> Vec<Token> {
-        vec![
-            Token::new("ETH", Address::zero()),
-            Token::new(
-                "DAI",
-                string_to_address("0000000000000000000000000000000000000001"),
-            ),
-        ]
+        TokenDb::new(self.decider.get_db())
+            .get_all_tokens()
+            .unwrap_or_else(|_| vec![])
     }
 }
 
@@ -220,29 +784,142 @@ mod tests {
     use ovm::deciders::SignVerifier;
     use plasma_core::data_structure::{Metadata, Range, Transaction, TransactionParams};
     use plasma_db::impls::kvs::CoreDbMemoryImpl;
+    use std::time::Duration;
 
-    #[test]
-    fn test_ingest() {
-        let mut aggregator: PlasmaAggregator<CoreDbMemoryImpl> = PlasmaAggregator::new(
+    const PRIVATE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+
+    fn create_aggregator() -> PlasmaAggregator<CoreDbMemoryImpl> {
+        let mut aggregator = PlasmaAggregator::new(
             "test",
             Address::zero(),
             Address::zero(),
             Address::zero(),
-            "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3",
-        );
-        let secret_key_raw =
-            hex::decode("c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3")
-                .unwrap();
-        let secret_key = SecretKey::from_raw(&secret_key_raw).unwrap();
-        let test_range = Range::new(5, 15);
-        let parameters = PlasmaClientShell::create_ownership_state_object(Address::zero()).to_abi();
+            PRIVATE_KEY,
+        )
+        .unwrap();
         aggregator.insert_test_ranges();
+        aggregator
+    }
+
+    fn create_transaction(range: Range) -> Transaction {
+        let secret_key = SecretKey::from_raw(&hex::decode(PRIVATE_KEY).unwrap()).unwrap();
+        let parameters = PlasmaClientShell::create_ownership_state_object(Address::zero()).to_abi();
         let transaction_params =
-            TransactionParams::new(Address::zero(), test_range, Bytes::from(parameters));
+            TransactionParams::new(Address::zero(), range, Bytes::from(parameters));
         let signature = SignVerifier::sign(&secret_key, &Bytes::from(transaction_params.to_abi()));
-        let transaction =
-            Transaction::from_params(transaction_params, signature, Metadata::default());
-        let result = aggregator.ingest_transaction(transaction);
+        Transaction::from_params(transaction_params, signature, Metadata::default())
+    }
+
+    fn create_batch(ranges: &[Range]) -> BatchTransaction {
+        let secret_key = SecretKey::from_raw(&hex::decode(PRIVATE_KEY).unwrap()).unwrap();
+        let parameters = PlasmaClientShell::create_ownership_state_object(Address::zero()).to_abi();
+        let entries: Vec<TransactionParams> = ranges
+            .iter()
+            .map(|range| {
+                TransactionParams::new(Address::zero(), *range, Bytes::from(parameters.clone()))
+            })
+            .collect();
+        let signature = SignVerifier::sign(
+            &secret_key,
+            &Bytes::from(BatchTransaction::create_body_abi(&entries)),
+        );
+        BatchTransaction::new(entries, vec![Metadata::default(); ranges.len()], signature)
+    }
+
+    #[test]
+    fn test_ingest() {
+        let mut aggregator = create_aggregator();
+        let result = aggregator.ingest_transaction(create_transaction(Range::new(5, 15)));
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_batch_is_ingested_as_a_whole() {
+        let mut aggregator = create_aggregator();
+        // the second entry spends a range which isn't covered by state updates
+        let invalid = create_batch(&[Range::new(5, 15), Range::new(50, 90)]);
+        assert!(aggregator.ingest_batch_transaction(invalid).is_err());
+        assert!(aggregator.get_pending_transactions().is_empty());
+        assert_eq!(aggregator.get_queued_state_update_count(), 0);
+        // entries of a batch aren't accepted one by one
+        let batch = create_batch(&[Range::new(5, 15), Range::new(20, 30)]);
+        assert!(aggregator
+            .ingest_transaction(batch.to_transactions()[0].clone())
+            .is_err());
+        assert_eq!(aggregator.ingest_batch_transaction(batch).unwrap().len(), 2);
+        assert_eq!(aggregator.get_pending_transactions().len(), 2);
+    }
+
+    #[test]
+    fn test_failed_submission_keeps_block() {
+        let mut aggregator = create_aggregator();
+        aggregator.set_commitment_chain_options(CommitmentChainOptions {
+            abi_path: "not_found.json".to_string(),
+            ..Default::default()
+        });
+        aggregator.set_mempool_options(MempoolOptions {
+            max_age: Duration::from_secs(0),
+            ..Default::default()
+        });
+        assert!(aggregator
+            .ingest_transaction(create_transaction(Range::new(5, 15)))
+            .is_ok());
+        assert!(aggregator.submit_next_block().is_err());
+        assert_eq!(aggregator.get_current_block_number(), 2);
+        assert!(aggregator
+            .get_plasma_block_of_block(Integer::new(1))
+            .is_ok());
+        assert!(aggregator.get_pending_transactions().is_empty());
+        // transactions in the block are never expired and reverted
+        assert!(aggregator
+            .expire_stale_transactions(Instant::now() + Duration::from_secs(1))
+            .unwrap()
+            .is_empty());
+        assert!(aggregator.resubmit_unsubmitted_blocks().is_err());
+    }
+
+    #[test]
+    fn test_promised_transaction_is_not_expired() {
+        let mut aggregator = create_aggregator();
+        aggregator.set_mempool_options(MempoolOptions {
+            max_age: Duration::from_secs(0),
+            ..Default::default()
+        });
+        let promised = create_transaction(Range::new(5, 15));
+        let unpromised = create_transaction(Range::new(20, 30));
+        assert!(aggregator.ingest_transaction(promised.clone()).is_ok());
+        assert!(aggregator.ingest_transaction(unpromised.clone()).is_ok());
+        assert!(aggregator.create_inclusion_promise(&promised).is_some());
+        let expired = aggregator
+            .expire_stale_transactions(Instant::now() + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].get_transaction(), &unpromised);
+        assert_eq!(aggregator.get_pending_transactions().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_replacement_keeps_pending_transaction() {
+        let mut aggregator = create_aggregator();
+        aggregator.set_mempool_options(MempoolOptions {
+            conflict_policy: ConflictPolicy::ReplaceBySameSender,
+            ..Default::default()
+        });
+        let transaction = create_transaction(Range::new(5, 15));
+        assert!(aggregator.ingest_transaction(transaction.clone()).is_ok());
+        // the replacement spends a range which isn't covered by state updates
+        assert!(aggregator
+            .ingest_transaction(create_transaction(Range::new(10, 90)))
+            .is_err());
+        let pending = aggregator.get_pending_transactions();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].get_transaction(), &transaction);
+        assert_eq!(aggregator.get_queued_state_update_count(), 1);
+        // a valid replacement evicts it
+        let replacement = create_transaction(Range::new(10, 20));
+        assert!(aggregator.ingest_transaction(replacement.clone()).is_ok());
+        let pending = aggregator.get_pending_transactions();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].get_transaction(), &replacement);
+    }
 }
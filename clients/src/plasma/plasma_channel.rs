@@ -0,0 +1,291 @@
+use super::error::{Error, ErrorKind};
+use crate::state_channel::PaymentState;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::Address;
+use ovm::db::Message;
+use ovm::statements::plasma::{create_channel_state_object, create_ownership_state_object};
+use ovm::statements::{create_state_channel_property, plasma_checkpoint_property};
+use ovm::types::Property;
+use ovm::DeciderManager;
+use plasma_core::data_structure::{Metadata, Range, TransactionParams, PAYMENT_TYPE};
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Channel over coin ranges which both participants lock at block_number.
+/// Off-chain updates are payments of a PaymentChannel with the same channel_id
+/// opened with the sizes of the locked ranges as balances.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct PlasmaChannel {
+    channel_id: Bytes,
+    deposit_contract_address: Address,
+    block_number: Integer,
+    participant1: Address,
+    range1: Range,
+    participant2: Address,
+    range2: Range,
+}
+
+impl PlasmaChannel {
+    pub fn new(
+        channel_id: Bytes,
+        deposit_contract_address: Address,
+        block_number: Integer,
+        participant1: Address,
+        range1: Range,
+        participant2: Address,
+        range2: Range,
+    ) -> Self {
+        Self {
+            channel_id,
+            deposit_contract_address,
+            block_number,
+            participant1,
+            range1,
+            participant2,
+            range2,
+        }
+    }
+
+    pub fn get_channel_id(&self) -> &Bytes {
+        &self.channel_id
+    }
+
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.deposit_contract_address
+    }
+
+    pub fn get_block_number(&self) -> Integer {
+        self.block_number
+    }
+
+    /// The range locked by participant.
+    pub fn get_range(&self, participant: Address) -> Option<Range> {
+        if participant == self.participant1 {
+            Some(self.range1)
+        } else if participant == self.participant2 {
+            Some(self.range2)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_counter_party(&self, participant: Address) -> Option<Address> {
+        if participant == self.participant1 {
+            Some(self.participant2)
+        } else if participant == self.participant2 {
+            Some(self.participant1)
+        } else {
+            None
+        }
+    }
+
+    /// Balance of participant before any off-chain update.
+    pub fn get_initial_balance(&self, participant: Address) -> Option<u64> {
+        self.get_range(participant)
+            .map(|range| range.get_end() - range.get_start())
+    }
+
+    /// State object which locks the range of participant.
+    /// It refers to the range of the counter party locked at the same block.
+    pub fn create_state_object(&self, participant: Address) -> Result<Property, Error> {
+        let counter_party = self
+            .get_counter_party(participant)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let counter_party_range = self
+            .get_range(counter_party)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        Ok(create_channel_state_object(
+            participant,
+            counter_party,
+            self.block_number,
+            self.deposit_contract_address,
+            counter_party_range,
+        ))
+    }
+
+    /// Entries which return the locked ranges to ownership state objects by the balances of state.
+    /// Each participant keeps the start of the own range up to the balance and the rest goes to the counter party.
+    /// The state must have no pending lock and spread exactly the locked amount.
+    pub fn create_settlement(
+        &self,
+        state: &PaymentState,
+    ) -> Result<(Vec<TransactionParams>, Vec<Metadata>), Error> {
+        let balance1 = state.get_balance(self.participant1);
+        let balance2 = state.get_balance(self.participant2);
+        let (balance1, balance2) = match (balance1, balance2) {
+            (Some(balance1), Some(balance2)) => (balance1, balance2),
+            _ => return Err(Error::from(ErrorKind::InvalidParameter)),
+        };
+        let total = self.get_initial_balance(self.participant1).unwrap()
+            + self.get_initial_balance(self.participant2).unwrap();
+        if !state.locks.is_empty() || balance1 + balance2 != total {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let mut entries = vec![];
+        let mut metadata = vec![];
+        for (owner, range, balance, counter_party) in &[
+            (self.participant1, self.range1, balance1, self.participant2),
+            (self.participant2, self.range2, balance2, self.participant1),
+        ] {
+            let kept = std::cmp::min(range.get_start() + balance, range.get_end());
+            let allocations = [
+                (*owner, Range::new(range.get_start(), kept)),
+                (*counter_party, Range::new(kept, range.get_end())),
+            ];
+            for (to_address, allocated) in allocations.iter() {
+                if allocated.get_start() < allocated.get_end() {
+                    entries.push(TransactionParams::new(
+                        self.deposit_contract_address,
+                        *allocated,
+                        Bytes::from(create_ownership_state_object(*to_address).to_abi()),
+                    ));
+                    metadata.push(Metadata::new(PAYMENT_TYPE, *owner, *to_address));
+                }
+            }
+        }
+        Ok((entries, metadata))
+    }
+
+    /// Exit claim for the case the counter party doesn't sign the settlement.
+    /// Both locked ranges must be checkpointed at the lock block and latest_message,
+    /// which has the balances to pay out, must be the latest message of the channel.
+    /// The counter party disputes it with a later message through StateChannel::handle_exit.
+    pub fn create_exit_claim(
+        &self,
+        my_address: Address,
+        latest_message: Message,
+    ) -> Result<Property, Error> {
+        let counter_party = self
+            .get_counter_party(my_address)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        if latest_message.channel_id != self.channel_id {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        Ok(DeciderManager::and_decider(
+            DeciderManager::and_decider(
+                plasma_checkpoint_property(
+                    self.block_number,
+                    self.deposit_contract_address,
+                    self.range1,
+                ),
+                plasma_checkpoint_property(
+                    self.block_number,
+                    self.deposit_contract_address,
+                    self.range2,
+                ),
+            ),
+            create_state_channel_property(my_address, counter_party, latest_message),
+        ))
+    }
+}
+
+/// Plasma channels which the client locked ranges into, keyed by channel id.
+pub struct PlasmaChannelDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> PlasmaChannelDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_channel(&self, channel: &PlasmaChannel) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("plasma_channels").into())
+            .put(&channel.get_channel_id().clone().into(), &channel.to_abi())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_channel(&self, channel_id: &Bytes) -> Result<Option<PlasmaChannel>, Error> {
+        match self
+            .db
+            .bucket(&Bytes::from("plasma_channels").into())
+            .get(&channel_id.clone().into())?
+        {
+            Some(value) => Ok(Some(PlasmaChannel::from_abi(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_channels(&self) -> Result<Vec<PlasmaChannel>, Error> {
+        self.db
+            .bucket(&Bytes::from("plasma_channels").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| PlasmaChannel::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+
+    pub fn remove_channel(&self, channel_id: &Bytes) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("plasma_channels").into())
+            .del(&channel_id.clone().into())
+            .map_err::<Error, _>(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_channel::HashLock;
+    use ethereum_types::H256;
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+    use plasma_db::traits::DatabaseTrait;
+
+    fn create_channel(alice: Address, bob: Address) -> PlasmaChannel {
+        PlasmaChannel::new(
+            Bytes::from("channel"),
+            Address::zero(),
+            Integer(10),
+            alice,
+            Range::new(0, 100),
+            bob,
+            Range::new(100, 200),
+        )
+    }
+
+    #[test]
+    fn test_create_settlement() {
+        let alice = Address::random();
+        let bob = Address::random();
+        let channel = create_channel(alice, bob);
+        let state = PaymentState::new(bob, 130, alice, 70);
+        let (entries, metadata) = channel.create_settlement(&state).unwrap();
+        let ranges: Vec<Range> = entries.iter().map(|e| e.get_range()).collect();
+        assert_eq!(
+            ranges,
+            vec![Range::new(0, 70), Range::new(70, 100), Range::new(100, 200)]
+        );
+        assert_eq!(
+            entries[1].get_parameters(),
+            &Bytes::from(create_ownership_state_object(bob).to_abi())
+        );
+        assert_eq!(metadata.len(), 3);
+        // the locked amount isn't spread exactly
+        assert!(channel
+            .create_settlement(&PaymentState::new(alice, 100, bob, 50))
+            .is_err());
+        // a pending lock must be unlocked or refunded first
+        let locked = PaymentState::new(alice, 100, bob, 100)
+            .add_lock(HashLock::new(H256::zero(), alice, 40, 0, bob))
+            .unwrap();
+        assert!(channel.create_settlement(&locked).is_err());
+    }
+
+    #[test]
+    fn test_plasma_channel_db() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let db = PlasmaChannelDb::new(&kvs);
+        let channel = create_channel(Address::random(), Address::random());
+        assert!(db.put_channel(&channel).is_ok());
+        assert_eq!(
+            db.get_channel(channel.get_channel_id()).unwrap(),
+            Some(channel.clone())
+        );
+        assert_eq!(db.get_channels().unwrap(), vec![channel.clone()]);
+        assert!(db.remove_channel(channel.get_channel_id()).is_ok());
+        assert_eq!(db.get_channel(channel.get_channel_id()).unwrap(), None);
+    }
+}
@@ -1,19 +1,47 @@
-use super::command::{Command, NewTransactionEvent};
+use super::block_sync::{
+    block_submitted_event, decode_block_submitted, filter_relevant_state_updates, SyncDb,
+};
+use super::block_validator::{store_transaction_witnesses, validate_block, FraudDb, FraudEvidence};
+use super::coin_selection::{
+    select_coins, split_selected_ranges, CoinSelectionPolicy, SelectedRange,
+};
+use super::command::{Command, CommandResponse, NewTransactionEvent, MAX_BLOCKS_PER_FETCH};
+use super::data_availability::{
+    plan_exits, AvailabilityDb, ExitRecord, MassExitPlan, MassExitPolicy, PlannedExit,
+    WithholdingPolicy,
+};
+use super::defragmentation::{plan_defragmentation, DefragmentationPolicy, FragmentationMetrics};
+use super::error::{Error, ErrorKind};
+use super::fee::FeePolicy;
+use super::inclusion_promise::InclusionPromise;
+use super::misbehaviour::{MisbehaviourEvidence, PromiseDb};
+use super::order_book::{
+    check_match, MatchOrderRequest, Order, OrderBook, OrderBookUpdate, TokenPair,
+};
 use super::plasma_block::PlasmaBlock;
+use super::plasma_channel::{PlasmaChannel, PlasmaChannelDb};
 use super::query;
 use super::state_db::StateDb;
-use super::token::Token;
-use super::utils::string_to_address;
-use super::wallet_manager::WalletManager;
+use super::token::{default_tokens, Token};
+use super::token_db::TokenDb;
+use super::tx_journal::{now, JournalEntry, JournalPolicy, TransactionStatus, TxJournal};
+use super::utils::{get_transaction_hash, string_to_address};
+use super::wallet_manager::{KeyRing, WalletManager};
+use super::watchtower::{
+    AuditAction, AuditEntry, Challenge, Delegation, Verdict, Watchtower, WatchtowerDb,
+    WatchtowerPolicy,
+};
+use crate::state_channel::{is_channel_topic, PaymentState};
 use abi_utils::{Decodable, Encodable};
 use bytes::Bytes;
 use contract_wrapper::plasma_contract_adaptor::PlasmaContractAdaptor;
+use contract_wrapper::universal_decision_contract_adaptor::UniversalDecisionContractAdaptor;
 use ethabi::Contract as ContractABI;
-use ethabi::{Event, EventParam, ParamType};
-use ethereum_types::Address;
-use ethsign::SecretKey;
+use ethabi::Event;
+use ethereum_types::{Address, H256};
 use event_watcher::event_db::EventDbImpl;
 use event_watcher::event_watcher::{EventHandler, EventWatcher, Log};
+use futures::{Async, Future, Poll, Stream};
 use ovm::{
     db::*,
     deciders::SignVerifier,
@@ -22,14 +50,23 @@ use ovm::{
     DeciderManager,
 };
 use plasma_core::data_structure::{
-    Metadata, Range, Transaction, TransactionParams, EXCHANGE_TYPE, PAYMENT_TYPE,
+    BatchTransaction, Metadata, Range, Transaction, TransactionParams, EXCHANGE_TYPE, PAYMENT_TYPE,
 };
 use plasma_db::prelude::*;
 use pubsub_messaging::{connect, Client as PubsubClient, ClientHandler, Message, Sender};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use tokio::timer::Interval;
+
+/// How long the client waits for the response of a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DATA_AVAILABILITY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Upper bound of rounds to estimate a fee paid in the same token as the payment.
+const MAX_FEE_ESTIMATION_ROUNDS: usize = 8;
 
 pub struct PlasmaClientShell {
     db_name: String,
@@ -114,22 +151,7 @@ impl PlasmaClientShell {
         let controller = PlasmaClientController::new(plasma_client);
         let pubsub_client = connect(self.aggregator_endpoint.clone(), controller.clone()).unwrap();
         self.controller = Some(controller.clone_by_pubsub_client(pubsub_client));
-        let abi: Vec<Event> = vec![Event {
-            name: "BlockSubmitted".to_owned(),
-            inputs: vec![
-                EventParam {
-                    name: "blockNumber".to_owned(),
-                    kind: ParamType::Uint(64),
-                    indexed: false,
-                },
-                EventParam {
-                    name: "root".to_owned(),
-                    kind: ParamType::FixedBytes(32),
-                    indexed: false,
-                },
-            ],
-            anonymous: false,
-        }];
+        let abi: Vec<Event> = vec![block_submitted_event()];
         let kvs = CoreDbLevelDbImpl::open("eventdb");
         let db = EventDbImpl::from(kvs);
         let watcher = EventWatcher::new(
@@ -140,6 +162,15 @@ impl PlasmaClientShell {
             self.controller.clone().unwrap(),
         );
         tokio::spawn(watcher);
+        // withheld blocks have to be detected even if no event arrives
+        let controller = self.controller.clone().unwrap();
+        let availability_checker = Interval::new_interval(DATA_AVAILABILITY_CHECK_INTERVAL)
+            .for_each(move |_| {
+                controller.check_data_availability(&WithholdingPolicy::default());
+                Ok(())
+            })
+            .map_err(|e| println!("data availability checker stopped: {:?}", e));
+        tokio::spawn(availability_checker);
     }
     pub fn search_range(
         &self,
@@ -152,18 +183,91 @@ impl PlasmaClientShell {
             .unwrap()
             .search_range(deposit_contract_address, amount, owner)
     }
-    /// Creates new account
-    pub fn create_account(&self) -> (Bytes, SecretKey) {
+    /// Creates new account whose key is encrypted with password. Returns the unlocked session and address.
+    pub fn create_account(&self, password: &str) -> Result<(Bytes, Address), Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.create_account(password)
+    }
+    /// Imports hex encoded secret key and encrypts it with password.
+    pub fn import_account(
+        &self,
+        private_key: &str,
+        password: &str,
+    ) -> Result<(Bytes, Address), Error> {
+        let raw_key =
+            hex::decode(private_key).map_err(|_| Error::from(ErrorKind::InvalidParameter))?;
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.import_key(&raw_key, password)
+    }
+    /// Imports V3 keystore JSON exported by another wallet.
+    pub fn import_keystore(
+        &self,
+        keystore: &str,
+        password: &str,
+    ) -> Result<(Bytes, Address), Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.import_keystore(keystore.as_bytes(), password)
+    }
+    /// Returns V3 keystore JSON of session.
+    pub fn export_keystore(&self, session: &Bytes) -> Result<String, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.export_keystore(session)
+    }
+    /// Whether keys stored raw by older versions are left.
+    pub fn has_raw_keys(&self) -> bool {
         let controller = self.controller.clone().unwrap();
         let plasma_client = controller.plasma_client.lock().unwrap();
-        plasma_client.create_account()
+        plasma_client.has_raw_keys()
     }
-    /// Imports secret key
-    pub fn import_account(&self, private_key: &str) -> (Bytes, SecretKey) {
-        let raw_key = hex::decode(private_key).unwrap();
+    /// Encrypts keys stored raw by older versions with password. Returns the migrated sessions.
+    pub fn encrypt_raw_keys(&self, password: &str) -> Result<Vec<Bytes>, Error> {
         let controller = self.controller.clone().unwrap();
         let plasma_client = controller.plasma_client.lock().unwrap();
-        plasma_client.import_key(&raw_key)
+        plasma_client.encrypt_raw_keys(password)
+    }
+    /// Unlocks session so that transactions can be signed with it.
+    pub fn unlock_account(&self, session: &Bytes, password: &str) -> Result<Address, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.unlock(session, password)
+    }
+    /// Locks session and zeroes its key in memory.
+    pub fn lock_account(&self, session: &Bytes) -> bool {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.lock(session)
+    }
+    /// Creates an HD wallet and returns its mnemonic which the user must back up.
+    pub fn generate_mnemonic(&self, password: &str) -> Result<String, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.generate_mnemonic(password)
+    }
+    /// Restores the HD wallet of phrase. Returns unlocked sessions of its used accounts.
+    pub fn import_mnemonic(
+        &self,
+        phrase: &str,
+        password: &str,
+    ) -> Result<Vec<(Bytes, Address)>, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.import_mnemonic(phrase, password)
+    }
+    /// Opens an unlocked session of the HD wallet account at index of m/44'/60'/0'/0/index.
+    pub fn derive_account(&self, password: &str, index: u32) -> Result<(Bytes, Address), Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.derive_account(password, index)
+    }
+    /// Opens unlocked sessions of the HD wallet accounts used in synced blocks.
+    pub fn discover_accounts(&self, password: &str) -> Result<Vec<(Bytes, Address)>, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.discover_accounts(password)
     }
     pub fn send_transaction(
         &self,
@@ -173,23 +277,255 @@ impl PlasmaClientShell {
         end: u64,
         state_object: Property,
         metadata: Metadata,
-    ) {
+    ) -> Result<Option<InclusionPromise>, Error> {
         let deposit_contract_address = deposit_contract_address.unwrap_or_else(Address::zero);
         let controller = self.controller.clone().unwrap();
-        let tx = controller.plasma_client.lock().unwrap().create_transaction(
-            session,
-            deposit_contract_address,
-            Range::new(start, end),
-            Bytes::from(state_object.to_abi()),
-            metadata,
-        );
-        let command = Command {
-            command_type: Integer(0),
-            body: Bytes::from(tx.to_abi()),
+        let tx = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .create_transaction(
+                session,
+                deposit_contract_address,
+                Range::new(start, end),
+                Bytes::from(state_object.to_abi()),
+                metadata,
+            )?;
+        let promises = controller.submit_transactions(&[tx])?;
+        Ok(promises[0].clone())
+    }
+    /// Returns ranges which a payment of amount would spend without sending anything.
+    pub fn preview_payment(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        amount: u64,
+        policy: CoinSelectionPolicy,
+    ) -> Result<Vec<SelectedRange>, Error> {
+        let deposit_contract_address = deposit_contract_address.unwrap_or_else(Address::zero);
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let owner = plasma_client
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        plasma_client.select_coins(deposit_contract_address, amount, owner, policy)
+    }
+    /// Sends amount to to_address.
+    /// Ranges are selected by policy and one transaction is sent for each of them.
+    /// The rest of a partially spent range stays with the sender as change.
+    /// Returns the error of the first transaction the aggregator rejected.
+    /// If the fee policy is set, the payment is sent as a batch with the fee attached.
+    pub fn send_payment(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        amount: u64,
+        to_address: Address,
+        policy: CoinSelectionPolicy,
+    ) -> Result<Vec<Transaction>, Error> {
+        if self.get_fee_policy().is_some() {
+            return self
+                .send_batch_payment(
+                    session,
+                    deposit_contract_address,
+                    &[(to_address, amount)],
+                    policy,
+                )
+                .map(|batch| batch.to_transactions());
+        }
+        let selected = self.preview_payment(session, deposit_contract_address, amount, policy)?;
+        let (state_object, metadata) = self.ownership_property(session, to_address);
+        let controller = self.controller.clone().unwrap();
+        let txs: Vec<Transaction> = {
+            let plasma_client = controller.plasma_client.lock().unwrap();
+            selected
+                .iter()
+                .map(|s| {
+                    plasma_client.create_transaction(
+                        session,
+                        s.get_state_update().get_deposit_contract_address(),
+                        s.get_range(),
+                        Bytes::from(state_object.to_abi()),
+                        metadata.clone(),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?
         };
-        let mut pubsub_client = controller.pubsub_client.clone().unwrap();
-        let msg = Message::new("Aggregator".to_string(), command.to_abi());
-        pubsub_client.send(msg);
+        controller.submit_transactions(&txs)?;
+        Ok(txs)
+    }
+    /// Creates entries which pay amounts to recipients from ranges selected by policy.
+    fn create_payment_entries(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        payments: &[(Address, u64)],
+        policy: CoinSelectionPolicy,
+    ) -> Result<(Vec<TransactionParams>, Vec<Metadata>), Error> {
+        if payments.is_empty() {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let total = payments.iter().map(|(_, amount)| *amount).sum();
+        let selected = self.preview_payment(session, deposit_contract_address, total, policy)?;
+        let amounts: Vec<u64> = payments.iter().map(|(_, amount)| *amount).collect();
+        let split = split_selected_ranges(&selected, &amounts)?;
+        let mut entries = vec![];
+        let mut metadata = vec![];
+        for ((to_address, _), ranges) in payments.iter().zip(split.iter()) {
+            let (state_object, payment_metadata) = self.ownership_property(session, *to_address);
+            for s in ranges {
+                entries.push(TransactionParams::new(
+                    s.get_state_update().get_deposit_contract_address(),
+                    s.get_range(),
+                    Bytes::from(state_object.to_abi()),
+                ));
+                metadata.push(payment_metadata.clone());
+            }
+        }
+        Ok((entries, metadata))
+    }
+    /// Creates entries of payments and attaches the fee required by the fee policy.
+    /// If the fee is paid in the same token, the fee may split ranges into more entries,
+    /// so the fee is estimated again until it covers all entries.
+    fn create_batch_payment_entries(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        payments: &[(Address, u64)],
+        policy: CoinSelectionPolicy,
+    ) -> Result<(Vec<TransactionParams>, Vec<Metadata>), Error> {
+        let fee_policy = match self.get_fee_policy() {
+            Some(fee_policy) => fee_policy,
+            None => {
+                return self.create_payment_entries(
+                    session,
+                    deposit_contract_address,
+                    payments,
+                    policy,
+                )
+            }
+        };
+        let deposit_contract_address = deposit_contract_address.unwrap_or_else(Address::zero);
+        if deposit_contract_address != fee_policy.fee_token {
+            let (mut entries, mut metadata) = self.create_payment_entries(
+                session,
+                Some(deposit_contract_address),
+                payments,
+                policy,
+            )?;
+            let fee = fee_policy.estimate_fee(entries.len());
+            if fee > 0 {
+                let (fee_entries, fee_metadata) = self.create_payment_entries(
+                    session,
+                    Some(fee_policy.fee_token),
+                    &[(fee_policy.recipient, fee)],
+                    policy,
+                )?;
+                entries.extend(fee_entries);
+                metadata.extend(fee_metadata);
+            }
+            return Ok((entries, metadata));
+        }
+        let mut fee = fee_policy.estimate_fee(payments.len());
+        for _ in 0..MAX_FEE_ESTIMATION_ROUNDS {
+            let mut payments_with_fee = payments.to_vec();
+            if fee > 0 {
+                payments_with_fee.push((fee_policy.recipient, fee));
+            }
+            let (entries, metadata) = self.create_payment_entries(
+                session,
+                Some(deposit_contract_address),
+                &payments_with_fee,
+                policy,
+            )?;
+            let required = fee_policy.get_required_fee(&entries);
+            if required <= fee {
+                return Ok((entries, metadata));
+            }
+            fee = required;
+        }
+        Err(Error::from(ErrorKind::InsufficientFee))
+    }
+    /// Returns the fee which a batch payment would attach. 0 if no fee policy is set.
+    pub fn estimate_fee(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        payments: &[(Address, u64)],
+        policy: CoinSelectionPolicy,
+    ) -> Result<u64, Error> {
+        let (entries, _) =
+            self.create_batch_payment_entries(session, deposit_contract_address, payments, policy)?;
+        Ok(self
+            .get_fee_policy()
+            .map_or(0, |fee_policy| fee_policy.get_paid_fee(&entries)))
+    }
+    /// Sends amounts to several recipients by one batch transaction.
+    /// Ranges for the total are selected by policy and divided among recipients in order.
+    /// The fee is attached if the fee policy is set.
+    /// The aggregator accepts or rejects all payments at once.
+    pub fn send_batch_payment(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Option<Address>,
+        payments: &[(Address, u64)],
+        policy: CoinSelectionPolicy,
+    ) -> Result<BatchTransaction, Error> {
+        let (entries, metadata) =
+            self.create_batch_payment_entries(session, deposit_contract_address, payments, policy)?;
+        let controller = self.controller.clone().unwrap();
+        let batch = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .create_batch_transaction(session, entries, metadata)?;
+        controller.submit_batch_transaction(&batch)?;
+        Ok(batch)
+    }
+    /// Returns fragmentation metrics of owned ranges for each registered token.
+    pub fn get_fragmentation_metrics(&self, session: &Bytes) -> Vec<FragmentationMetrics> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client.get_my_address(session).unwrap();
+        plasma_client
+            .get_all_tokens()
+            .iter()
+            .filter_map(|token| {
+                plasma_client.get_fragmentation_metrics(token.get_address(), my_address)
+            })
+            .collect()
+    }
+    /// Merges adjacent owned ranges by self-transfers until each token has no more ranges than the policy allows.
+    /// Ranges separated by others' ranges can't be merged and are left as they are.
+    pub fn defragment(
+        &self,
+        session: &Bytes,
+        policy: &DefragmentationPolicy,
+    ) -> Result<Vec<Transaction>, Error> {
+        let my_address = self
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let (state_object, metadata) = self.ownership_property(session, my_address);
+        let controller = self.controller.clone().unwrap();
+        let txs: Vec<Transaction> = {
+            let plasma_client = controller.plasma_client.lock().unwrap();
+            let mut txs = vec![];
+            for token in plasma_client.get_all_tokens() {
+                let owned = plasma_client.get_owned_state_updates(token.get_address(), my_address);
+                for candidate in plan_defragmentation(&owned, policy) {
+                    txs.push(plasma_client.create_transaction(
+                        session,
+                        candidate.get_deposit_contract_address(),
+                        candidate.get_range(),
+                        Bytes::from(state_object.to_abi()),
+                        metadata.clone(),
+                    )?);
+                }
+            }
+            txs
+        };
+        controller.submit_transactions(&txs)?;
+        Ok(txs)
     }
     pub fn ownership_property(&self, session: &Bytes, to_address: Address) -> (Property, Metadata) {
         (
@@ -210,28 +546,191 @@ impl PlasmaClientShell {
         range: Range,
     ) -> (Property, Metadata) {
         let my_address = self.get_my_address(session).unwrap();
-        let dammy_property = DeciderManager::preimage_exists_decider(vec![]);
-        let state_update = StateUpdate::new(
-            block_number,
-            deposit_contract_address,
-            range,
-            dammy_property,
-        );
         (
             ovm::statements::plasma::create_channel_state_object(
                 my_address,
                 counter_party_address,
-                state_update,
+                block_number,
+                deposit_contract_address,
+                range,
             ),
             Metadata::new(PAYMENT_TYPE, my_address, counter_party_address),
         )
     }
+    /// Creates a channel which locks my_range and counter_party_range at the next block.
+    /// The counter party must lock its range with the same channel.
+    pub fn create_plasma_channel(
+        &self,
+        session: &Bytes,
+        channel_id: Bytes,
+        deposit_contract_address: Option<Address>,
+        my_range: Range,
+        counter_party_address: Address,
+        counter_party_range: Range,
+    ) -> Result<PlasmaChannel, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        Ok(PlasmaChannel::new(
+            channel_id,
+            deposit_contract_address.unwrap_or_else(Address::zero),
+            Integer::new(plasma_client.get_next_block_number()),
+            my_address,
+            my_range,
+            counter_party_address,
+            counter_party_range,
+        ))
+    }
+    /// Locks my owned range into the channel state object of channel.
+    /// Off-chain updates of the channel are run by a PaymentChannel with the same channel id.
+    pub fn lock_plasma_channel(
+        &self,
+        session: &Bytes,
+        channel: &PlasmaChannel,
+    ) -> Result<Option<InclusionPromise>, Error> {
+        let controller = self.controller.clone().unwrap();
+        let tx = {
+            let plasma_client = controller.plasma_client.lock().unwrap();
+            let my_address = plasma_client
+                .get_my_address(session)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+            let range = channel
+                .get_range(my_address)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+            if channel.get_block_number().0 != plasma_client.get_next_block_number() {
+                return Err(Error::from(ErrorKind::InvalidParameter));
+            }
+            let owned: u64 = plasma_client
+                .get_owned_state_updates(channel.get_deposit_contract_address(), my_address)
+                .iter()
+                .map(|s| {
+                    let start = std::cmp::max(s.get_range().get_start(), range.get_start());
+                    let end = std::cmp::min(s.get_range().get_end(), range.get_end());
+                    end.saturating_sub(start)
+                })
+                .sum();
+            if owned != range.get_end() - range.get_start() {
+                return Err(Error::from(ErrorKind::InsufficientBalance));
+            }
+            let state_object = channel.create_state_object(my_address)?;
+            let tx = plasma_client.create_transaction(
+                session,
+                channel.get_deposit_contract_address(),
+                range,
+                Bytes::from(state_object.to_abi()),
+                Metadata::new(
+                    PAYMENT_TYPE,
+                    my_address,
+                    channel.get_counter_party(my_address).unwrap(),
+                ),
+            )?;
+            plasma_client.put_plasma_channel(channel)?;
+            tx
+        };
+        let promises = controller.submit_transactions(&[tx])?;
+        Ok(promises[0].clone())
+    }
+    pub fn get_plasma_channels(&self) -> Vec<PlasmaChannel> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_plasma_channels()
+    }
+    /// Signs the settlement of the channel by the balances of state, the latest state of the closed PaymentChannel.
+    /// The signature is given to the counter party to settle the channel.
+    pub fn sign_plasma_channel_settlement(
+        &self,
+        session: &Bytes,
+        channel_id: &Bytes,
+        state: &PaymentState,
+    ) -> Result<Bytes, Error> {
+        Ok(self
+            .create_plasma_channel_settlement(session, channel_id, state)?
+            .get_signature()
+            .clone())
+    }
+    /// Returns the locked ranges to ownership state objects by the balances of state.
+    /// The settlement is co-signed with the signature of the counter party
+    /// so that the channel state object accepts it whether the counter party's range was locked or not.
+    /// Only one of the participants needs to submit it.
+    pub fn settle_plasma_channel(
+        &self,
+        session: &Bytes,
+        channel_id: &Bytes,
+        state: &PaymentState,
+        counter_party_signature: &Bytes,
+    ) -> Result<BatchTransaction, Error> {
+        let controller = self.controller.clone().unwrap();
+        let batch = self.create_plasma_channel_settlement(session, channel_id, state)?;
+        let counter_party = {
+            let plasma_client = controller.plasma_client.lock().unwrap();
+            let channel = plasma_client
+                .get_plasma_channel(channel_id)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+            let my_address = plasma_client
+                .get_my_address(session)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+            channel
+                .get_counter_party(my_address)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?
+        };
+        if SignVerifier::try_recover(counter_party_signature, &Bytes::from(batch.to_body_abi()))
+            != Some(counter_party)
+        {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let batch = batch.co_sign(counter_party_signature);
+        controller.submit_batch_transaction(&batch)?;
+        controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .remove_plasma_channel(channel_id)?;
+        Ok(batch)
+    }
+    fn create_plasma_channel_settlement(
+        &self,
+        session: &Bytes,
+        channel_id: &Bytes,
+        state: &PaymentState,
+    ) -> Result<BatchTransaction, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let channel = plasma_client
+            .get_plasma_channel(channel_id)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let (entries, metadata) = channel.create_settlement(state)?;
+        plasma_client.create_batch_transaction(session, entries, metadata)
+    }
+    /// Exit claim of the channel by the latest message of the PaymentChannel.
+    /// It's used when the counter party doesn't sign the settlement and is disputed through the channel exit property.
+    pub fn get_plasma_channel_exit_claim(
+        &self,
+        session: &Bytes,
+        channel_id: &Bytes,
+        latest_message: ovm::db::Message,
+    ) -> Result<Property, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        plasma_client
+            .get_plasma_channel(channel_id)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?
+            .create_exit_claim(my_address, latest_message)
+    }
     // Creates order swap property with token address and amount.
+    // The order can't be taken after expiry_block if it's specified.
+    // If fill_size is specified, amount is the price of fill_size and the order can be partially filled.
     pub fn making_order_property(
         &self,
         session: &Bytes,
         deposit_contract_address: Address,
         amount: Integer,
+        expiry_block: Option<u64>,
+        fill_size: Option<u64>,
     ) -> (Property, Metadata) {
         let my_address = self.get_my_address(session).unwrap();
         (
@@ -239,6 +738,8 @@ impl PlasmaClientShell {
                 my_address,
                 deposit_contract_address,
                 amount,
+                expiry_block.map(Integer::new),
+                fill_size.map(Integer::new),
             ),
             Metadata::new(PAYMENT_TYPE, my_address, my_address),
         )
@@ -268,15 +769,236 @@ impl PlasmaClientShell {
         //        controller.fetch_block(Integer(0));
         controller.initialize()
     }
-    /// Gets balances as HashMap which key is token address and value is balance
+    /// Requests blocks submitted after the last synced block.
+    pub fn sync(&self) {
+        self.controller.clone().unwrap().sync()
+    }
+    /// Gets balances as HashMap which key is token address and value is balance.
+    /// All registered tokens are included even if the balance is 0.
     pub fn get_balance(&self, session: &Bytes) -> HashMap<Address, u64> {
         let controller = self.controller.clone().unwrap();
         let plasma_client = controller.plasma_client.lock().unwrap();
         let my_address = plasma_client.get_my_address(session).unwrap();
-        let balances: HashMap<Address, u64> =
+        let mut balances: HashMap<Address, u64> =
             query::query_balance(plasma_client.get_all_state_updates(), my_address);
+        for token in plasma_client.get_all_tokens() {
+            balances.entry(token.get_address()).or_insert(0);
+        }
         balances
     }
+    /// Returns open orders selling token for c_token, best priced first.
+    pub fn get_order_book(&self, token: Address, c_token: Address) -> Vec<Order> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client
+            .get_order_book()
+            .get_orders(&TokenPair::new(token, c_token))
+    }
+    pub fn get_best_order(&self, token: Address, c_token: Address) -> Option<Order> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client
+            .get_order_book()
+            .get_best_order(&TokenPair::new(token, c_token))
+            .cloned()
+    }
+    /// Takes the best order selling amount of token or less for c_token.
+    /// A partially fillable order is filled up to amount.
+    /// The aggregator builds the batch which pays the maker and takes the order, and it's signed here.
+    pub fn take_best_order(
+        &self,
+        session: &Bytes,
+        token: Address,
+        c_token: Address,
+        amount: u64,
+    ) -> Result<BatchTransaction, Error> {
+        let my_address = self
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let fee_recipient = self.get_fee_policy().map(|p| p.recipient);
+        let controller = self.controller.clone().unwrap();
+        let request = MatchOrderRequest::new(my_address, token, c_token, Integer::new(amount));
+        let response = controller.query(Command::create_match_order_request(&request))?;
+        if let Some(kind) = response.get_error_kind() {
+            return Err(Error::from(kind));
+        }
+        let unsigned = BatchTransaction::from_abi(&response.body)?;
+        check_match(&unsigned, my_address, fee_recipient)?;
+        let batch = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .create_batch_transaction(
+                session,
+                unsigned.get_entries().to_vec(),
+                unsigned.get_metadata().to_vec(),
+            )?;
+        controller.submit_batch_transaction(&batch)?;
+        Ok(batch)
+    }
+    /// Takes filled amount from the start of the order at start.
+    /// The price is paid to the maker from one owned range of c_token, and the fee is attached if the fee policy is set.
+    /// The rest of a partially fillable order stays open.
+    pub fn fill_order(
+        &self,
+        session: &Bytes,
+        token: Address,
+        c_token: Address,
+        start: u64,
+        filled: u64,
+    ) -> Result<BatchTransaction, Error> {
+        let order = self
+            .get_order_book(token, c_token)
+            .into_iter()
+            .find(|o| o.get_range().get_start() == start)
+            .ok_or_else(|| Error::from(ErrorKind::OrderNotFound))?;
+        let price = order
+            .get_fill_price(filled)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let fee_policy = self.get_fee_policy();
+        let fee = fee_policy.as_ref().map_or(0, |p| p.estimate_fee(2));
+        let mut payments = vec![(order.get_maker(), price)];
+        if let Some(fee_policy) = fee_policy
+            .as_ref()
+            .filter(|p| fee > 0 && p.fee_token == c_token)
+        {
+            payments.push((fee_policy.recipient, fee));
+        }
+        let (mut entries, mut metadata) = self.create_payment_entries(
+            session,
+            Some(c_token),
+            &payments,
+            CoinSelectionPolicy::FewestRanges,
+        )?;
+        // the taking order refers to one counter range
+        let maker_payment =
+            Bytes::from(Self::create_ownership_state_object(order.get_maker()).to_abi());
+        if entries
+            .iter()
+            .filter(|e| *e.get_parameters() == maker_payment)
+            .count()
+            != 1
+        {
+            return Err(Error::from(ErrorKind::InsufficientBalance));
+        }
+        let c_range = entries[0].get_range();
+        if let Some(fee_policy) = fee_policy
+            .as_ref()
+            .filter(|p| fee > 0 && p.fee_token != c_token)
+        {
+            let (fee_entries, fee_metadata) = self.create_payment_entries(
+                session,
+                Some(fee_policy.fee_token),
+                &[(fee_policy.recipient, fee)],
+                CoinSelectionPolicy::FewestRanges,
+            )?;
+            entries.extend(fee_entries);
+            metadata.extend(fee_metadata);
+        }
+        let (property, taking_metadata) =
+            self.taking_order_property(session, order.get_maker(), c_token, c_range);
+        entries.push(TransactionParams::new(
+            token,
+            order.get_fill_range(filled),
+            Bytes::from(property.to_abi()),
+        ));
+        metadata.push(taking_metadata);
+        let controller = self.controller.clone().unwrap();
+        let batch = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .create_batch_transaction(session, entries, metadata)?;
+        controller.submit_batch_transaction(&batch)?;
+        Ok(batch)
+    }
+    /// Cancels my order of the range by transferring the range to myself.
+    /// Expired orders can be cancelled too. The fee is attached if the fee policy is set.
+    pub fn cancel_order(
+        &self,
+        session: &Bytes,
+        deposit_contract_address: Address,
+        range: Range,
+    ) -> Result<Vec<Transaction>, Error> {
+        let my_address = self
+            .get_my_address(session)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        self.get_my_orders(session)
+            .iter()
+            .find(|o| o.get_token() == deposit_contract_address && o.get_range() == range)
+            .ok_or_else(|| Error::from(ErrorKind::OrderNotFound))?;
+        let (state_object, metadata) = self.ownership_property(session, my_address);
+        let mut entries = vec![TransactionParams::new(
+            deposit_contract_address,
+            range,
+            Bytes::from(state_object.to_abi()),
+        )];
+        let mut metadata = vec![metadata];
+        let controller = self.controller.clone().unwrap();
+        let fee_policy = match self.get_fee_policy() {
+            Some(fee_policy) => fee_policy,
+            None => {
+                let tx = controller
+                    .plasma_client
+                    .lock()
+                    .unwrap()
+                    .create_transaction(
+                        session,
+                        deposit_contract_address,
+                        range,
+                        Bytes::from(state_object.to_abi()),
+                        metadata[0].clone(),
+                    )?;
+                controller.submit_transactions(&[tx.clone()])?;
+                return Ok(vec![tx]);
+            }
+        };
+        let fee = fee_policy.estimate_fee(entries.len());
+        if fee > 0 {
+            let (fee_entries, fee_metadata) = self.create_payment_entries(
+                session,
+                Some(fee_policy.fee_token),
+                &[(fee_policy.recipient, fee)],
+                CoinSelectionPolicy::default(),
+            )?;
+            entries.extend(fee_entries);
+            metadata.extend(fee_metadata);
+        }
+        let batch = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .create_batch_transaction(session, entries, metadata)?;
+        controller.submit_batch_transaction(&batch)?;
+        Ok(batch.to_transactions())
+    }
+    /// Returns my making orders including expired ones.
+    pub fn get_my_orders(&self, session: &Bytes) -> Vec<Order> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client.get_my_address(session).unwrap();
+        plasma_client
+            .get_all_state_updates()
+            .iter()
+            .filter_map(Order::from_state_update)
+            .filter(|o| o.get_maker() == my_address)
+            .collect()
+    }
+    /// Gets balances locked in my open orders and in my expired orders.
+    /// Expired orders can't be taken but they are kept locked until they are cancelled.
+    pub fn get_order_balance(
+        &self,
+        session: &Bytes,
+    ) -> (HashMap<Address, u64>, HashMap<Address, u64>) {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client.get_my_address(session).unwrap();
+        query::query_order_balance(
+            plasma_client.get_all_state_updates(),
+            my_address,
+            plasma_client.get_next_block_number(),
+        )
+    }
     pub fn get_orders(&self) -> Vec<(StateUpdate, Address, Integer, Address)> {
         let controller = self.controller.clone().unwrap();
         let plasma_client = controller.plasma_client.lock().unwrap();
@@ -288,31 +1010,186 @@ impl PlasmaClientShell {
             .unwrap()
             .get_related_transactions(session)
     }
-    // TODO: get dynamically using token map?
-    pub fn get_all_tokens(&self) -> Vec<Token> {
-        vec![
-            Token::new("ETH", Address::zero()),
-            Token::new(
-                "DAI",
-                string_to_address("0000000000000000000000000000000000000001"),
-            ),
-        ]
-    }
-    pub fn get_token_name(&self, address: Address) -> String {
-        if address == Address::zero() {
-            "ETH".to_string()
-        } else if address == string_to_address("0000000000000000000000000000000000000001") {
-            "DAI".to_string()
-        } else {
-            panic!("No token found")
+    /// Registers tokens from config.
+    pub fn register_tokens(&self, tokens: Vec<Token>) -> Result<(), Error> {
+        let controller = self.get_controller()?;
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        for token in tokens {
+            plasma_client.register_token(token)?;
         }
+        Ok(())
+    }
+    pub fn get_all_tokens(&self) -> Result<Vec<Token>, Error> {
+        let controller = self.get_controller()?;
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        Ok(plasma_client.get_all_tokens())
+    }
+    /// Returns transactions sent by the account of session and their status.
+    pub fn get_transaction_journal(&self, session: &Bytes) -> Vec<JournalEntry> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        let my_address = plasma_client.get_my_address(session);
+        plasma_client
+            .get_journal_entries()
+            .into_iter()
+            .filter(|e| Some(e.get_transaction().get_metadata().get_from()) == my_address)
+            .collect()
+    }
+    pub fn get_transaction_status(&self, hash: H256) -> Option<TransactionStatus> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client
+            .get_journal_entry(hash)
+            .map(|e| e.get_status())
+    }
+    /// Resubmits a dropped or failed transaction.
+    pub fn resubmit_transaction(&self, hash: H256) -> Result<Option<InclusionPromise>, Error> {
+        let controller = self.controller.clone().unwrap();
+        let entry = controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .get_journal_entry(hash)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let promises = controller.submit_transactions(&[entry.get_transaction().clone()])?;
+        Ok(promises[0].clone())
+    }
+    pub fn check_pending_transactions(&self, policy: &JournalPolicy) {
+        self.controller
+            .clone()
+            .unwrap()
+            .check_pending_transactions(policy)
+    }
+    pub fn get_misbehaviour_evidences(&self) -> Vec<MisbehaviourEvidence> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_misbehaviour_evidences()
+    }
+    /// Returns evidences of violations found in the served block.
+    pub fn get_fraud_evidences(&self, block_number: u64) -> Vec<FraudEvidence> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_fraud_evidences(block_number)
+    }
+    /// Returns the evidence for the transaction as hex string of its ABI encoding.
+    pub fn export_misbehaviour_evidence(&self, transaction_hash: H256) -> Option<String> {
+        self.get_misbehaviour_evidences()
+            .iter()
+            .find(|e| e.get_promise().get_transaction_hash() == transaction_hash)
+            .map(|e| hex::encode(e.to_abi()))
+    }
+    /// Polls claims on the universal decision contract and challenges ones conflicting with delegated ranges.
+    pub fn enable_watchtower(
+        &self,
+        universal_decision_contract_address: Address,
+        policy: WatchtowerPolicy,
+    ) {
+        let watcher = ClaimWatcher {
+            controller: self.controller.clone().unwrap(),
+            universal_decision_contract_address,
+            policy,
+            interval: Interval::new_interval(Duration::from_secs(1)),
+        };
+        tokio::spawn(watcher);
+    }
+    pub fn delegate_monitoring(&self, delegation: &Delegation) -> Result<(), Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.put_delegation(delegation)
+    }
+    pub fn revoke_monitoring(&self, delegation: &Delegation) -> Result<(), Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.remove_delegation(delegation)
+    }
+    pub fn get_delegations(&self) -> Vec<Delegation> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_delegations()
+    }
+    pub fn get_watchtower_audit_log(&self) -> Vec<AuditEntry> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_watchtower_audit_log()
     }
+    pub fn set_fee_policy(&self, fee_policy: Option<FeePolicy>) {
+        let controller = self.controller.clone().unwrap();
+        let mut plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.set_fee_policy(fee_policy)
+    }
+    pub fn get_fee_policy(&self) -> Option<FeePolicy> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_fee_policy()
+    }
+    /// Sets the contract which exits are claimed on.
+    pub fn set_universal_decision_contract_address(&self, address: Address) {
+        let controller = self.controller.clone().unwrap();
+        let mut plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.set_universal_decision_contract_address(address)
+    }
+    /// Returns the block detected as withheld by the aggregator, if any.
+    pub fn get_withheld_block(&self) -> Option<u64> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_withheld_block()
+    }
+    /// Starts exits of all owned ranges which haven't been started yet.
+    pub fn start_exits(&self) -> Result<Vec<ExitRecord>, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.start_exits()
+    }
+    pub fn get_exits(&self) -> Vec<ExitRecord> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_exits()
+    }
+    pub fn get_mass_exit_plan(
+        &self,
+        withheld_block_number: Option<u64>,
+        policy: &MassExitPolicy,
+    ) -> Result<MassExitPlan, Error> {
+        let controller = self.controller.clone().unwrap();
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        plasma_client.get_mass_exit_plan(withheld_block_number, policy)
+    }
+    /// Returns None if the token isn't registered.
+    pub fn get_token_name(&self, address: Address) -> Result<Option<String>, Error> {
+        let controller = self.get_controller()?;
+        let plasma_client = controller.plasma_client.lock().unwrap();
+        Ok(plasma_client
+            .get_token(address)
+            .map(|token| token.get_name()))
+    }
+    /// Returns NotConnected error if connect() hasn't been called.
+    fn get_controller(&self) -> Result<PlasmaClientController, Error> {
+        self.controller
+            .clone()
+            .ok_or_else(|| Error::from(ErrorKind::NotConnected))
+    }
+}
+
+/// What a request waiting for CommandResponse sent.
+/// A batch request has hashes of all transactions split from the batch.
+enum RequestKind {
+    Transaction(H256),
+    Batch(Vec<H256>),
+    Query,
+}
+
+/// Request waiting for CommandResponse.
+struct PendingRequest {
+    kind: RequestKind,
+    sender: mpsc::Sender<CommandResponse>,
 }
 
 #[derive(Clone)]
 pub struct PlasmaClientController {
     pub plasma_client: Arc<Mutex<PlasmaClient<CoreDbLevelDbImpl>>>,
     pub pubsub_client: Option<PubsubClient>,
+    pending_requests: Arc<Mutex<HashMap<u64, PendingRequest>>>,
+    next_request_id: Arc<AtomicU64>,
 }
 
 impl PlasmaClientController {
@@ -320,19 +1197,239 @@ impl PlasmaClientController {
         Self {
             plasma_client: Arc::new(Mutex::new(plasma_client)),
             pubsub_client: None,
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            next_request_id: Arc::new(AtomicU64::new(1)),
         }
     }
     fn clone_by_pubsub_client(&self, pubsub_client: PubsubClient) -> Self {
         PlasmaClientController {
             plasma_client: self.plasma_client.clone(),
             pubsub_client: Some(pubsub_client),
+            pending_requests: self.pending_requests.clone(),
+            next_request_id: self.next_request_id.clone(),
+        }
+    }
+    /// Records the transaction to the journal and sends it to the aggregator.
+    /// The response of the aggregator is delivered to returned receiver.
+    fn send_transaction(&self, tx: &Transaction) -> (u64, mpsc::Receiver<CommandResponse>) {
+        let transaction_hash = match self
+            .plasma_client
+            .lock()
+            .unwrap()
+            .record_submitted_transaction(tx)
+        {
+            Ok(hash) => hash,
+            Err(e) => {
+                println!("failed to record transaction: {:?}", e);
+                get_transaction_hash(tx)
+            }
+        };
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        self.pending_requests.lock().unwrap().insert(
+            request_id,
+            PendingRequest {
+                kind: RequestKind::Transaction(transaction_hash),
+                sender,
+            },
+        );
+        let command = Command::create_transaction(tx).with_request_id(request_id);
+        let msg = Message::new("Aggregator".to_string(), command.to_abi());
+        let mut pubsub_client = self.pubsub_client.clone().unwrap();
+        pubsub_client.send(msg);
+        (request_id, receiver)
+    }
+    /// Records all transactions of the batch to the journal and sends the batch to the aggregator.
+    fn send_batch_transaction(
+        &self,
+        batch: &BatchTransaction,
+    ) -> (u64, mpsc::Receiver<CommandResponse>) {
+        let transaction_hashes = {
+            let plasma_client = self.plasma_client.lock().unwrap();
+            batch
+                .to_transactions()
+                .iter()
+                .map(|tx| match plasma_client.record_submitted_transaction(tx) {
+                    Ok(hash) => hash,
+                    Err(e) => {
+                        println!("failed to record transaction: {:?}", e);
+                        get_transaction_hash(tx)
+                    }
+                })
+                .collect()
+        };
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        self.pending_requests.lock().unwrap().insert(
+            request_id,
+            PendingRequest {
+                kind: RequestKind::Batch(transaction_hashes),
+                sender,
+            },
+        );
+        let command = Command::create_batch_transaction(batch).with_request_id(request_id);
+        let msg = Message::new("Aggregator".to_string(), command.to_abi());
+        let mut pubsub_client = self.pubsub_client.clone().unwrap();
+        pubsub_client.send(msg);
+        (request_id, receiver)
+    }
+    /// Sends a request which doesn't change state and waits for its response.
+    fn query(&self, command: Command) -> Result<CommandResponse, Error> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        self.pending_requests.lock().unwrap().insert(
+            request_id,
+            PendingRequest {
+                kind: RequestKind::Query,
+                sender,
+            },
+        );
+        let msg = Message::new(
+            "Aggregator".to_string(),
+            command.with_request_id(request_id).to_abi(),
+        );
+        let mut pubsub_client = self.pubsub_client.clone().unwrap();
+        pubsub_client.send(msg);
+        self.receive_response(request_id, &receiver)
+    }
+    /// Sends the batch and waits for its response.
+    /// Returns inclusion promises of all entries or the error of the whole batch.
+    fn submit_batch_transaction(
+        &self,
+        batch: &BatchTransaction,
+    ) -> Result<Vec<InclusionPromise>, Error> {
+        let (request_id, receiver) = self.send_batch_transaction(batch);
+        self.receive_response(request_id, &receiver)?
+            .into_inclusion_promises()
+    }
+    /// Sends all transactions and waits for their responses.
+    /// Returns inclusion promises in the same order, None for accepted ones without promise, or the first error.
+    fn submit_transactions(
+        &self,
+        txs: &[Transaction],
+    ) -> Result<Vec<Option<InclusionPromise>>, Error> {
+        let requests: Vec<(u64, mpsc::Receiver<CommandResponse>)> =
+            txs.iter().map(|tx| self.send_transaction(tx)).collect();
+        let mut promises = vec![];
+        let mut first_error = None;
+        for (request_id, receiver) in requests {
+            match self.wait_response(request_id, &receiver) {
+                Ok(promise) => promises.push(promise),
+                Err(e) => {
+                    if first_error.is_none() {
+                        first_error = Some(e);
+                    }
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(promises),
+        }
+    }
+    fn wait_response(
+        &self,
+        request_id: u64,
+        receiver: &mpsc::Receiver<CommandResponse>,
+    ) -> Result<Option<InclusionPromise>, Error> {
+        self.receive_response(request_id, receiver)?
+            .into_inclusion_promise()
+    }
+    fn receive_response(
+        &self,
+        request_id: u64,
+        receiver: &mpsc::Receiver<CommandResponse>,
+    ) -> Result<CommandResponse, Error> {
+        match receiver.recv_timeout(REQUEST_TIMEOUT) {
+            Ok(response) => {
+                if !response.is_ok() {
+                    println!("request {} failed: {}", request_id, response.get_message());
+                }
+                Ok(response)
+            }
+            Err(_) => {
+                self.pending_requests.lock().unwrap().remove(&request_id);
+                Err(Error::from(ErrorKind::RequestTimeout))
+            }
+        }
+    }
+    fn handle_response(
+        &self,
+        plasma_client: &PlasmaClient<CoreDbLevelDbImpl>,
+        response: CommandResponse,
+    ) {
+        let pending = self
+            .pending_requests
+            .lock()
+            .unwrap()
+            .remove(&response.request_id.0);
+        if let Some(pending) = pending {
+            match &pending.kind {
+                RequestKind::Transaction(transaction_hash) => {
+                    plasma_client.handle_transaction_response(*transaction_hash, &response)
+                }
+                RequestKind::Batch(transaction_hashes) => {
+                    plasma_client.handle_batch_response(transaction_hashes, &response)
+                }
+                RequestKind::Query => {}
+            }
+            // the caller may not wait for the response
+            let _ = pending.sender.send(response);
+        }
+    }
+    fn fetch_blocks(&self, from_block: u64, to_block: u64) {
+        let command =
+            Command::create_fetch_block_range_request(Integer(from_block), Integer(to_block));
+        let msg = Message::new("Aggregator".to_string(), command.to_abi());
+        let mut pubsub_client = self.pubsub_client.clone().unwrap();
+        pubsub_client.send(msg);
+    }
+    /// Resumes from the last synced block and fetches blocks up to the latest submitted one.
+    /// The rest of the range is fetched by the next sync if it exceeds MAX_BLOCKS_PER_FETCH.
+    fn sync(&self) {
+        let missing_range = self.plasma_client.lock().unwrap().get_missing_block_range();
+        if let Some((from_block, to_block)) = missing_range {
+            let to_block = std::cmp::min(
+                to_block,
+                from_block.saturating_add(MAX_BLOCKS_PER_FETCH - 1),
+            );
+            if let Err(e) = self.plasma_client.lock().unwrap().record_block_requests(
+                from_block,
+                to_block,
+                now(),
+            ) {
+                println!("failed to record block requests: {:?}", e);
+            }
+            self.fetch_blocks(from_block, to_block);
+        }
+    }
+    /// Starts exits of all owned ranges if a requested block wasn't served in time.
+    fn check_data_availability(&self, policy: &WithholdingPolicy) {
+        let result = self
+            .plasma_client
+            .lock()
+            .unwrap()
+            .check_data_availability(now(), policy);
+        match result {
+            Ok(exits) => {
+                for exit in exits.iter() {
+                    println!("started exit of {:?}", exit.get_exit().get_range());
+                }
+            }
+            Err(e) => println!("failed to start exits: {:?}", e),
         }
     }
-    fn fetch_block(&self, block_number: Integer) {
-        let command = Command::create_fetch_block_request(block_number);
-        let msg = Message::new("Aggregator".to_string(), command.to_abi());
-        let mut pubsub_client = self.pubsub_client.clone().unwrap();
-        pubsub_client.send(msg);
+    /// Resubmits transactions which the aggregator didn't acknowledge in time.
+    /// Transactions which ran out of attempts are marked failed and accepted ones not confirmed in time are marked dropped.
+    fn check_pending_transactions(&self, policy: &JournalPolicy) {
+        let resubmitted = self
+            .plasma_client
+            .lock()
+            .unwrap()
+            .check_pending_transactions(now(), policy);
+        for tx in resubmitted.iter() {
+            self.send_transaction(tx);
+        }
     }
     fn initialize(&self) {
         let mut plasma_client = self.plasma_client.lock().unwrap();
@@ -359,13 +1456,28 @@ impl PlasmaClientController {
 
 impl ClientHandler for PlasmaClientController {
     fn handle_message(&self, msg: Message, _sender: Sender) {
-        let plasma_client = self.plasma_client.lock().unwrap();
+        if is_channel_topic(&msg.to) {
+            return;
+        }
+        let mut plasma_client = self.plasma_client.lock().unwrap();
         let command = Command::from_abi(&msg.message).unwrap();
         if command.command_type.0 == 3 {
-            plasma_client.handle_new_block(PlasmaBlock::from_abi(&command.body).unwrap());
+            let block = PlasmaBlock::from_abi(&command.body).unwrap();
+            let block_number = block.get_block_number();
+            if let Err(e) = plasma_client.handle_synced_block(block) {
+                println!("failed to sync block {:?}: {:?}", block_number, e);
+            }
         } else if command.command_type.0 == 4 {
             plasma_client
                 .handle_new_transaction(&NewTransactionEvent::from_abi(&command.body).unwrap());
+        } else if command.command_type.0 == 6 {
+            self.handle_response(
+                &plasma_client,
+                CommandResponse::from_abi(&command.body).unwrap(),
+            );
+        } else if command.command_type.0 == 8 {
+            plasma_client
+                .apply_order_book_update(&OrderBookUpdate::from_abi(&command.body).unwrap());
         } else {
             println!("undefined command type {:?}", command.command_type.0);
         }
@@ -374,32 +1486,106 @@ impl ClientHandler for PlasmaClientController {
 
 impl EventHandler for PlasmaClientController {
     fn on_event(&self, log: &Log) {
-        let decoded_param = log.params.first().unwrap();
-        println!(
-            "block number is {:?}",
-            decoded_param.token.clone().to_uint().unwrap()
-        );
-        let block_number = decoded_param.token.clone().to_uint().unwrap();
-        self.fetch_block(Integer(block_number.as_u64()));
+        let (block_number, root) = match decode_block_submitted(log) {
+            Some(event) => event,
+            None => {
+                println!("unknown event {:?}", log.event_signature);
+                return;
+            }
+        };
+        println!("block number is {:?}", block_number);
+        if let Err(e) = self
+            .plasma_client
+            .lock()
+            .unwrap()
+            .put_submitted_root(block_number, &root)
+        {
+            println!("failed to store root of block {:?}: {:?}", block_number, e);
+            return;
+        }
+        self.sync();
+        self.check_pending_transactions(&JournalPolicy::default());
+        self.check_data_availability(&WithholdingPolicy::default());
+    }
+}
+
+/// Polls claims on the universal decision contract for the watchtower.
+/// The contract emits no event for claims, so claimProperty transactions in new L1 blocks are inspected.
+pub struct ClaimWatcher {
+    controller: PlasmaClientController,
+    universal_decision_contract_address: Address,
+    policy: WatchtowerPolicy,
+    interval: Interval,
+}
+
+impl ClaimWatcher {
+    fn poll_claims(&self) -> Result<(), Error> {
+        let contract = load_universal_decision_contract(self.universal_decision_contract_address)?;
+        let head = contract.get_block_number()?;
+        self.controller
+            .plasma_client
+            .lock()
+            .unwrap()
+            .scan_claims(&contract, head, &self.policy)
+    }
+}
+
+impl Future for ClaimWatcher {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            match self.interval.poll().map_err(|_| ())? {
+                Async::Ready(Some(_)) => {
+                    if let Err(e) = self.poll_claims() {
+                        println!("failed to poll claims: {:?}", e);
+                    }
+                }
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
     }
 }
 
+fn load_universal_decision_contract(
+    universal_decision_contract_address: Address,
+) -> Result<UniversalDecisionContractAdaptor, Error> {
+    let f = File::open("../contract-wrapper/UniversalDecisionContract.json")?;
+    let reader = BufReader::new(f);
+    let contract_abi = ContractABI::load(reader)?;
+    Ok(UniversalDecisionContractAdaptor::new(
+        "http://127.0.0.1:8545",
+        &hex::encode(universal_decision_contract_address.as_bytes()),
+        contract_abi,
+    )?)
+}
+
 /// Plasma Client on OVM.
 pub struct PlasmaClient<KVS: KeyValueStore> {
     deposit_contract_address: Address,
+    universal_decision_contract_address: Option<Address>,
+    fee_policy: Option<FeePolicy>,
+    order_book: OrderBook,
     decider: PropertyExecutor<KVS>,
     wallet_db: KVS,
+    key_ring: KeyRing,
 }
 
 impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
     pub fn new(dbname: String, deposit_contract_address: Address) -> Self {
         PlasmaClient {
             deposit_contract_address,
+            universal_decision_contract_address: None,
+            fee_policy: None,
+            order_book: OrderBook::default(),
             decider: PropertyExecutor::new(PropertyExecuterOptions {
                 is_aggregator: false,
                 db_name: dbname,
             }),
             wallet_db: KVS::open("wallet"),
+            key_ring: KeyRing::default(),
         }
     }
 
@@ -423,25 +1609,151 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
             plasma_contract.deposit(self.get_my_address(session).unwrap(), amount, property);
     }
 
-    /// Creates new account
-    pub fn create_account(&self) -> (Bytes, SecretKey) {
+    /// Creates new account whose key is stored encrypted with password. The session is unlocked.
+    pub fn create_account(&self, password: &str) -> Result<(Bytes, Address), Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        let (session, key) = wallet.generate_key_session(password)?;
+        let address = key.get_address();
+        self.key_ring.insert(session.clone(), key);
+        Ok((session, address))
+    }
+
+    /// Imports a raw private key and stores it encrypted with password. The session is unlocked.
+    pub fn import_key(&self, secret_key: &[u8], password: &str) -> Result<(Bytes, Address), Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        let (session, key) = wallet.import_key(secret_key, password)?;
+        let address = key.get_address();
+        self.key_ring.insert(session.clone(), key);
+        Ok((session, address))
+    }
+
+    /// Imports V3 keystore JSON which password decrypts. The session is unlocked.
+    pub fn import_keystore(
+        &self,
+        keystore: &[u8],
+        password: &str,
+    ) -> Result<(Bytes, Address), Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        let (session, key) = wallet.import_keystore(keystore, password)?;
+        let address = key.get_address();
+        self.key_ring.insert(session.clone(), key);
+        Ok((session, address))
+    }
+
+    pub fn export_keystore(&self, session: &Bytes) -> Result<String, Error> {
+        let wallet = WalletManager::new(&self.wallet_db);
+        wallet.export_keystore(session)
+    }
+
+    /// Whether keys stored raw by older versions are left. They must be encrypted before use.
+    pub fn has_raw_keys(&self) -> bool {
+        WalletManager::new(&self.wallet_db).has_raw_keys()
+    }
+
+    /// Encrypts keys stored raw by older versions with password. Returns the migrated sessions.
+    pub fn encrypt_raw_keys(&self, password: &str) -> Result<Vec<Bytes>, Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        wallet.encrypt_raw_keys(password)
+    }
+
+    /// Decrypts the key of session with password and keeps it in memory until the session is locked.
+    pub fn unlock(&self, session: &Bytes, password: &str) -> Result<Address, Error> {
+        let wallet = WalletManager::new(&self.wallet_db);
+        let key = wallet.unlock_key(session, password)?;
+        let address = key.get_address();
+        self.key_ring.insert(session.clone(), key);
+        Ok(address)
+    }
+
+    /// Zeroes the key of session in memory. Returns false if it wasn't unlocked.
+    pub fn lock(&self, session: &Bytes) -> bool {
+        self.key_ring.lock(session)
+    }
+
+    pub fn lock_all(&self) {
+        self.key_ring.lock_all()
+    }
+
+    pub fn is_unlocked(&self, session: &Bytes) -> bool {
+        self.key_ring.is_unlocked(session)
+    }
+
+    /// Creates an HD wallet seed encrypted with password and returns its mnemonic.
+    pub fn generate_mnemonic(&self, password: &str) -> Result<String, Error> {
         let mut wallet = WalletManager::new(&self.wallet_db);
-        wallet.generate_key_session()
+        wallet.generate_mnemonic(password)
     }
 
-    pub fn import_key(&self, secret_key: &[u8]) -> (Bytes, SecretKey) {
+    /// Restores the HD wallet of phrase and opens sessions of its accounts already used in synced blocks.
+    /// The sessions are unlocked.
+    pub fn import_mnemonic(
+        &self,
+        phrase: &str,
+        password: &str,
+    ) -> Result<Vec<(Bytes, Address)>, Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        wallet.import_mnemonic(phrase, password)?;
+        let keys =
+            wallet.discover_key_sessions(password, |address| self.is_used_address(address))?;
+        Ok(keys
+            .into_iter()
+            .map(|(session, key)| {
+                let address = key.get_address();
+                self.key_ring.insert(session.clone(), key);
+                (session, address)
+            })
+            .collect())
+    }
+
+    /// Opens and unlocks a session of the HD wallet account at index.
+    pub fn derive_account(&self, password: &str, index: u32) -> Result<(Bytes, Address), Error> {
         let mut wallet = WalletManager::new(&self.wallet_db);
-        wallet.import_key(secret_key)
+        let (session, key) = wallet.derive_key_session(password, index)?;
+        let address = key.get_address();
+        self.key_ring.insert(session.clone(), key);
+        Ok((session, address))
+    }
+
+    /// Opens and unlocks sessions of the HD wallet accounts used in synced blocks.
+    pub fn discover_accounts(&self, password: &str) -> Result<Vec<(Bytes, Address)>, Error> {
+        let mut wallet = WalletManager::new(&self.wallet_db);
+        let keys =
+            wallet.discover_key_sessions(password, |address| self.is_used_address(address))?;
+        Ok(keys
+            .into_iter()
+            .map(|(session, key)| {
+                let address = key.get_address();
+                self.key_ring.insert(session.clone(), key);
+                (session, address)
+            })
+            .collect())
+    }
+
+    /// Whether address owns a state update or has signed a message.
+    fn is_used_address(&self, address: Address) -> bool {
+        !SignedByDb::new(self.decider.get_db())
+            .get_all_signed_by(address)
+            .is_empty()
+            || self.get_all_tokens().iter().any(|token| {
+                !self
+                    .get_owned_state_updates(token.get_address(), address)
+                    .is_empty()
+            })
+    }
+
+    /// Addresses of all accounts in the wallet including locked ones.
+    pub fn get_all_addresses(&self) -> Vec<Address> {
+        let wallet = WalletManager::new(&self.wallet_db);
+        wallet.get_all_addresses()
     }
 
     pub fn get_my_address(&self, session: &Bytes) -> Option<Address> {
         let wallet = WalletManager::new(&self.wallet_db);
-        wallet
-            .get_key(session)
-            .map(|secret_key| secret_key.public().address().into())
+        wallet.get_address(session)
     }
 
     /// Create transaction to update state for specific coin range.
+    /// The session must be unlocked.
     /// TODO: maybe need to specify Property for how state transition works.
     pub fn create_transaction(
         &self,
@@ -450,18 +1762,37 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
         range: Range,
         parameters: Bytes,
         metadata: Metadata,
-    ) -> Transaction {
+    ) -> Result<Transaction, Error> {
         let transaction_params =
             TransactionParams::new(deposit_contract_address, range, parameters);
+        let secret_key = self
+            .key_ring
+            .get_key(session)
+            .ok_or_else(|| Error::from(ErrorKind::KeyLocked))?;
+        let signature = SignVerifier::sign(&secret_key, &Bytes::from(transaction_params.to_abi()));
+        Ok(Transaction::from_params(
+            transaction_params,
+            signature,
+            metadata,
+        ))
+    }
 
-        let wallet = WalletManager::new(&self.wallet_db);
-        if let Some(secret_key) = wallet.get_key(session) {
-            let signature =
-                SignVerifier::sign(&secret_key, &Bytes::from(transaction_params.to_abi()));
-            Transaction::from_params(transaction_params, signature, metadata)
-        } else {
-            panic!("secret key not found");
-        }
+    /// Signs all entries at once. The session must be unlocked.
+    pub fn create_batch_transaction(
+        &self,
+        session: &Bytes,
+        entries: Vec<TransactionParams>,
+        metadata: Vec<Metadata>,
+    ) -> Result<BatchTransaction, Error> {
+        let secret_key = self
+            .key_ring
+            .get_key(session)
+            .ok_or_else(|| Error::from(ErrorKind::KeyLocked))?;
+        let signature = SignVerifier::sign(
+            &secret_key,
+            &Bytes::from(BatchTransaction::create_body_abi(&entries)),
+        );
+        Ok(BatchTransaction::new(entries, metadata, signature))
     }
 
     /// Start exit on plasma. return exit property
@@ -498,42 +1829,486 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
         let _result = plasma_contract.withdraw(self.get_my_address(session).unwrap(), checkpoint);
     }
 
-    /// Challenge to specific exit by claiming contradicting statement.
-    pub fn challenge(&self) {}
+    /// Challenges a false claim by claiming its negation and proving the two contradict.
+    /// Transactions are sent from the first account in the wallet.
+    /// Returns the hash of the transaction proving the contradiction.
+    pub fn challenge(
+        &self,
+        contract: &UniversalDecisionContractAdaptor,
+        challenge: &Challenge,
+    ) -> Result<H256, Error> {
+        let from = *self
+            .get_all_addresses()
+            .first()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        contract.claim_property(from, challenge.counter_claim.clone())?;
+        Ok(contract.prove_undecided_contradiction(
+            from,
+            (challenge.claim.clone(), challenge.counter_claim.clone()),
+            challenge.get_claim_implication_proof(),
+            challenge.counter_claim_implication_proof.clone(),
+            challenge.get_contradiction_witness(),
+        )?)
+    }
+
+    fn claim_property(
+        universal_decision_contract_address: Address,
+        from: Address,
+        property: Property,
+    ) -> Result<H256, Error> {
+        let contract = load_universal_decision_contract(universal_decision_contract_address)?;
+        Ok(contract.claim_property(from, property)?)
+    }
+
+    pub fn set_universal_decision_contract_address(&mut self, address: Address) {
+        self.universal_decision_contract_address = Some(address);
+    }
+
+    /// Fee policy of the aggregator. Payments attach fees only if it is set.
+    pub fn set_fee_policy(&mut self, fee_policy: Option<FeePolicy>) {
+        self.fee_policy = fee_policy;
+    }
+
+    pub fn get_fee_policy(&self) -> Option<FeePolicy> {
+        self.fee_policy.clone()
+    }
+
+    /// Applies an order book update broadcasted by the aggregator to the local replica.
+    pub fn apply_order_book_update(&mut self, update: &OrderBookUpdate) {
+        self.order_book.apply(update);
+    }
+
+    pub fn get_order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    pub fn record_block_requests(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        now: u64,
+    ) -> Result<(), Error> {
+        let db = AvailabilityDb::new(self.decider.get_db());
+        for block_number in from_block..=to_block {
+            db.record_requested(block_number, now)?;
+        }
+        Ok(())
+    }
+
+    /// Detects a block whose root was submitted but whose data wasn't served within the timeout.
+    /// Exits of all owned ranges are started when a withheld or an invalid block is detected,
+    /// and the block is recorded as withheld only after every exit started.
+    pub fn check_data_availability(
+        &self,
+        now: u64,
+        policy: &WithholdingPolicy,
+    ) -> Result<Vec<ExitRecord>, Error> {
+        let db = AvailabilityDb::new(self.decider.get_db());
+        if db.get_withheld_block()?.is_some() {
+            return Ok(vec![]);
+        }
+        // states after an invalid block are as unknown as states after a withheld block
+        if let Some(block_number) = FraudDb::new(self.decider.get_db()).get_fraudulent_block()? {
+            return self.exit_from_block(block_number);
+        }
+        let (from_block, to_block) = match self.get_missing_block_range() {
+            Some(range) => range,
+            None => return Ok(vec![]),
+        };
+        if let Some(block_number) =
+            db.find_withheld_block(from_block, to_block, now, policy.timeout)?
+        {
+            println!("block {:?} is withheld by the aggregator", block_number);
+            self.exit_from_block(block_number)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Starts exits because the block can't be synced and records the block once every exit started.
+    /// Exits which failed to start are retried by the next check.
+    fn exit_from_block(&self, block_number: u64) -> Result<Vec<ExitRecord>, Error> {
+        let records = self.start_exits()?;
+        if self.get_unstarted_exits()?.is_empty() {
+            AvailabilityDb::new(self.decider.get_db()).set_withheld_block(block_number)?;
+        }
+        Ok(records)
+    }
+
+    /// Returns exits of ranges owned by accounts in the wallet which haven't been started, in priority order.
+    fn get_unstarted_exits(&self) -> Result<Vec<PlannedExit>, Error> {
+        let started: Vec<PlannedExit> = AvailabilityDb::new(self.decider.get_db())
+            .get_exits()?
+            .iter()
+            .map(|record| record.get_exit().clone())
+            .collect();
+        let mut owned = vec![];
+        for owner in self.get_all_addresses() {
+            for token in self.get_all_tokens() {
+                owned.extend(self.get_owned_state_updates(token.get_address(), owner));
+            }
+        }
+        Ok(plan_exits(&owned)
+            .into_iter()
+            .filter(|exit| !started.contains(exit))
+            .collect())
+    }
+
+    /// Starts exits of all ranges owned by accounts in the wallet in priority order.
+    /// Exits which failed to start are skipped and can be started again later.
+    pub fn start_exits(&self) -> Result<Vec<ExitRecord>, Error> {
+        let universal_decision_contract_address = self
+            .universal_decision_contract_address
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        let db = AvailabilityDb::new(self.decider.get_db());
+        let mut records = vec![];
+        for exit in self.get_unstarted_exits()? {
+            let claim = self.get_exit_claim(
+                exit.get_block_number(),
+                exit.get_deposit_contract_address(),
+                exit.get_range(),
+            );
+            match Self::claim_property(universal_decision_contract_address, exit.get_owner(), claim)
+            {
+                Ok(hash) => {
+                    let record = ExitRecord::new(exit, hash);
+                    db.put_exit(&record)?;
+                    records.push(record);
+                }
+                Err(e) => println!("failed to start exit of {:?}: {:?}", exit.get_range(), e),
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn get_exits(&self) -> Vec<ExitRecord> {
+        AvailabilityDb::new(self.decider.get_db())
+            .get_exits()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_withheld_block(&self) -> Option<u64> {
+        AvailabilityDb::new(self.decider.get_db())
+            .get_withheld_block()
+            .unwrap_or(None)
+    }
+
+    /// Plans exits of all known ownership states for users of this client.
+    /// The withheld block defaults to the detected one, or the block next to the last synced one.
+    pub fn get_mass_exit_plan(
+        &self,
+        withheld_block_number: Option<u64>,
+        policy: &MassExitPolicy,
+    ) -> Result<MassExitPlan, Error> {
+        let withheld_block_number = match withheld_block_number.or(self.get_withheld_block()) {
+            Some(block_number) => block_number,
+            None => SyncDb::new(self.decider.get_db()).get_last_synced_block_number()? + 1,
+        };
+        Ok(MassExitPlan::create(
+            withheld_block_number,
+            &self.get_all_state_updates(),
+            policy,
+        ))
+    }
+
+    /// Inspects claims made in L1 blocks after the last scanned one up to head.
+    /// head is the current L1 block number which dispute windows are measured against.
+    pub fn scan_claims(
+        &self,
+        contract: &UniversalDecisionContractAdaptor,
+        head: u64,
+        policy: &WatchtowerPolicy,
+    ) -> Result<(), Error> {
+        let watchtower_db = WatchtowerDb::new(self.decider.get_db());
+        watchtower_db.update_last_seen_block(head)?;
+        if let Some((from_block, to_block)) = watchtower_db.get_blocks_to_scan(head, policy)? {
+            for block_number in from_block..=to_block {
+                for claim in contract.get_claims(block_number)? {
+                    self.handle_property_claim(&claim, block_number, contract, policy)?;
+                }
+                watchtower_db.set_last_scanned_block(block_number)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the claim against the synced history and challenges it if it conflicts with delegated ranges.
+    /// Every claim affecting delegated ranges is recorded in the audit log.
+    pub fn handle_property_claim(
+        &self,
+        claim: &Property,
+        claimed_at: u64,
+        contract: &UniversalDecisionContractAdaptor,
+        policy: &WatchtowerPolicy,
+    ) -> Result<(), Error> {
+        let watchtower_db = WatchtowerDb::new(self.decider.get_db());
+        let verdict =
+            Watchtower::new(&self.decider, policy.clone()).inspect_claim(claim, claimed_at)?;
+        let entry = match verdict {
+            Verdict::Unrelated => return Ok(()),
+            Verdict::Valid => {
+                AuditEntry::create(claim, claimed_at, AuditAction::Accepted, H256::zero(), "")
+            }
+            Verdict::Expired => AuditEntry::create(
+                claim,
+                claimed_at,
+                AuditAction::Expired,
+                H256::zero(),
+                "dispute period passed",
+            ),
+            Verdict::Undecidable => AuditEntry::create(
+                claim,
+                claimed_at,
+                AuditAction::Undecidable,
+                H256::zero(),
+                "not enough history",
+            ),
+            Verdict::Conflicting(challenge) => match self.challenge(contract, &challenge) {
+                Ok(hash) => {
+                    AuditEntry::create(claim, claimed_at, AuditAction::Challenged, hash, "")
+                }
+                Err(e) => AuditEntry::create(
+                    claim,
+                    claimed_at,
+                    AuditAction::ChallengeFailed,
+                    H256::zero(),
+                    &format!("{}", e),
+                ),
+            },
+        };
+        watchtower_db.append_audit_entry(&entry)
+    }
+
+    pub fn put_delegation(&self, delegation: &Delegation) -> Result<(), Error> {
+        WatchtowerDb::new(self.decider.get_db()).put_delegation(delegation)
+    }
+
+    pub fn remove_delegation(&self, delegation: &Delegation) -> Result<(), Error> {
+        WatchtowerDb::new(self.decider.get_db()).remove_delegation(delegation)
+    }
+
+    pub fn get_delegations(&self) -> Vec<Delegation> {
+        WatchtowerDb::new(self.decider.get_db())
+            .get_delegations()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_watchtower_audit_log(&self) -> Vec<AuditEntry> {
+        WatchtowerDb::new(self.decider.get_db())
+            .get_audit_log()
+            .unwrap_or_else(|_| vec![])
+    }
 
     /// Handle BlockSubmitted Event from aggregator
     /// check new state update and verify, store them.
-    pub fn handle_new_block(&self, mut block: PlasmaBlock) {
+    pub fn handle_new_block(&self, mut block: PlasmaBlock) -> Result<(), Error> {
         println!("handle_new_block {:?}", block.get_block_number());
         // println!("handle_new_block {:?} {:?}", block.get_block_number(), block.get_state_updates());
+        let root = block.merkelize()?;
+        let state_updates = block.get_state_updates().to_vec();
+        self.apply_block(&block, root, state_updates)
+    }
+
+    /// Handle block fetched by block sync.
+    /// Blocks are applied in order and only if they are valid against the root submitted to commitment contract.
+    /// Evidences of violations are stored for invalid blocks.
+    /// Only state updates relevant to addresses in the wallet are stored.
+    pub fn handle_synced_block(&self, mut block: PlasmaBlock) -> Result<(), Error> {
+        let sync_db = SyncDb::new(self.decider.get_db());
+        let block_number = block.get_block_number();
+        if block_number != sync_db.get_last_synced_block_number()? + 1 {
+            // already synced or previous block is missing. missing blocks are requested by next sync.
+            return Ok(());
+        }
+        let expected_root = match sync_db.get_submitted_root(block_number)? {
+            Some(root) => root,
+            None => {
+                // new block broadcast can arrive before BlockSubmitted event.
+                // it's applied when the root arrives.
+                return sync_db.put_pending_block(&block);
+            }
+        };
+        let evidences = validate_block(&mut block, &expected_root, &self.decider);
+        if !evidences.is_empty() {
+            println!(
+                "block {:?} is invalid: {:?}",
+                block_number,
+                evidences
+                    .iter()
+                    .map(|e| e.get_violation())
+                    .collect::<Vec<_>>()
+            );
+            let fraud_db = FraudDb::new(self.decider.get_db());
+            fraud_db.put_evidences(block_number, &evidences)?;
+            // the block is never applied. sync stops here and owned ranges are exited instead.
+            fraud_db.set_fraudulent_block(block_number)?;
+            self.exit_from_block(block_number)?;
+            return Err(Error::from(ErrorKind::InvalidBlock));
+        }
+        let root = expected_root;
+        let state_updates = filter_relevant_state_updates(
+            block.get_state_updates(),
+            &self.get_all_addresses(),
+            &self.get_all_state_updates(),
+        );
+        self.apply_block(&block, root.clone(), state_updates)?;
+        let journal = TxJournal::new(self.decider.get_db());
+        for tx in block.get_transactions().iter() {
+            journal.mark_confirmed(get_transaction_hash(&tx.transaction), block_number)?;
+        }
+        AvailabilityDb::new(self.decider.get_db()).remove_requested(block_number)?;
+        for evidence in PromiseDb::new(self.decider.get_db()).check_block(&block, &root)? {
+            println!(
+                "aggregator broke inclusion promise of {:?} at block {:?}",
+                evidence.get_promise().get_transaction_hash(),
+                block_number
+            );
+        }
+        sync_db.set_last_synced_block_number(block_number)
+    }
+
+    pub fn record_submitted_transaction(&self, transaction: &Transaction) -> Result<H256, Error> {
+        TxJournal::new(self.decider.get_db()).record_submitted(transaction, now())
+    }
+
+    /// Updates the journal by the response to the transaction of transaction_hash.
+    pub fn handle_transaction_response(&self, transaction_hash: H256, response: &CommandResponse) {
+        let journal = TxJournal::new(self.decider.get_db());
+        let result = if response.is_ok() {
+            if let Ok(Some(promise)) = response.clone().into_inclusion_promise() {
+                if let Err(e) = PromiseDb::new(self.decider.get_db()).put_promise(&promise) {
+                    println!("failed to store inclusion promise: {:?}", e);
+                }
+            }
+            journal.mark_accepted(transaction_hash, now())
+        } else {
+            println!("transaction rejected: {}", response.get_message());
+            journal.mark_failed(transaction_hash, &response.get_message())
+        };
+        if let Err(e) = result {
+            println!("failed to update transaction journal: {:?}", e);
+        }
+    }
+
+    /// Updates the journal by the response to a batch. The aggregator accepts or rejects all transactions at once.
+    pub fn handle_batch_response(&self, transaction_hashes: &[H256], response: &CommandResponse) {
+        let journal = TxJournal::new(self.decider.get_db());
+        if let Ok(promises) = response.clone().into_inclusion_promises() {
+            for promise in promises.iter() {
+                if let Err(e) = PromiseDb::new(self.decider.get_db()).put_promise(promise) {
+                    println!("failed to store inclusion promise: {:?}", e);
+                }
+            }
+        } else if !response.is_ok() {
+            println!("batch transaction rejected: {}", response.get_message());
+        }
+        for transaction_hash in transaction_hashes {
+            let result = if response.is_ok() {
+                journal.mark_accepted(*transaction_hash, now())
+            } else {
+                journal.mark_failed(*transaction_hash, &response.get_message())
+            };
+            if let Err(e) = result {
+                println!("failed to update transaction journal: {:?}", e);
+            }
+        }
+    }
+
+    /// Applies timeout to in-flight transactions in the journal and returns transactions to resubmit.
+    pub fn check_pending_transactions(&self, now: u64, policy: &JournalPolicy) -> Vec<Transaction> {
+        let journal = TxJournal::new(self.decider.get_db());
+        let mut resubmitted = vec![];
+        for entry in journal
+            .get_timed_out_entries(now, policy.timeout)
+            .unwrap_or_else(|_| vec![])
+        {
+            let result = match entry.get_status() {
+                TransactionStatus::Accepted => journal.mark_dropped(entry.get_hash()),
+                _ if entry.get_attempts() >= policy.max_attempts => {
+                    journal.mark_failed(entry.get_hash(), "timeout")
+                }
+                _ => {
+                    resubmitted.push(entry.get_transaction().clone());
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                println!("failed to update transaction journal: {:?}", e);
+            }
+        }
+        resubmitted
+    }
+
+    /// Returns evidences of inclusion promises the aggregator broke.
+    pub fn get_misbehaviour_evidences(&self) -> Vec<MisbehaviourEvidence> {
+        PromiseDb::new(self.decider.get_db())
+            .get_evidences()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_fraud_evidences(&self, block_number: u64) -> Vec<FraudEvidence> {
+        FraudDb::new(self.decider.get_db())
+            .get_evidences(block_number)
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_journal_entries(&self) -> Vec<JournalEntry> {
+        TxJournal::new(self.decider.get_db())
+            .get_all_entries()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_journal_entry(&self, hash: H256) -> Option<JournalEntry> {
+        TxJournal::new(self.decider.get_db())
+            .get_entry(hash)
+            .unwrap_or(None)
+    }
+
+    /// Stores the root and applies the block which was waiting for it.
+    pub fn put_submitted_root(&self, block_number: u64, root: &Bytes) -> Result<(), Error> {
+        let sync_db = SyncDb::new(self.decider.get_db());
+        sync_db.put_submitted_root(block_number, root)?;
+        let next_block_number = sync_db.get_last_synced_block_number()? + 1;
+        if let Some(block) = sync_db.take_pending_block(next_block_number)? {
+            self.handle_synced_block(block)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the range of blocks to fetch. Nothing is fetched after an invalid block.
+    pub fn get_missing_block_range(&self) -> Option<(u64, u64)> {
+        if FraudDb::new(self.decider.get_db())
+            .get_fraudulent_block()
+            .unwrap_or(None)
+            .is_some()
+        {
+            return None;
+        }
+        SyncDb::new(self.decider.get_db())
+            .get_missing_block_range()
+            .unwrap_or(None)
+    }
+
+    /// Stores witnesses of the block and updates state with given state updates.
+    fn apply_block(
+        &self,
+        block: &PlasmaBlock,
+        root: Bytes,
+        state_updates: Vec<StateUpdate>,
+    ) -> Result<(), Error> {
         let range_db = self.decider.get_range_db();
         let range_at_block_db = RangeAtBlockDb::new(range_db);
-        let transaction_db = TransactionDb::new(self.decider.get_range_db());
-        let signed_by_db = SignedByDb::new(self.decider.get_db());
-        let root = block.merkelize().unwrap();
 
         for s in block.get_state_updates().iter() {
-            assert!(range_at_block_db
-                .store_witness(
-                    root.clone(),
-                    true,
-                    block.get_inclusion_proof(s.clone()).unwrap(),
-                    s.clone(),
-                )
-                .is_ok());
+            let inclusion_proof = block
+                .get_inclusion_proof(s.clone())
+                .ok_or_else(|| Error::from(ErrorKind::InvalidBlock))?;
+            range_at_block_db
+                .store_witness(root.clone(), true, inclusion_proof, s.clone())
+                .map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
         }
         for tx in block.get_transactions().iter() {
-            for previous_block_number in tx.clone().prev_state_block_numbers {
-                transaction_db.put_transaction(previous_block_number.0, tx.transaction.clone());
-            }
-            let message = Bytes::from(tx.transaction.to_body_abi());
-            assert!(signed_by_db
-                .store_witness(
-                    SignVerifier::recover(tx.transaction.get_signature(), &message),
-                    message,
-                    tx.transaction.get_signature().clone(),
-                )
-                .is_ok());
+            store_transaction_witnesses(&self.decider, tx)?;
         }
         for su in self.get_all_state_updates() {
             let property = PlasmaClientShell::create_checkpoint_property(
@@ -548,11 +2323,12 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
                 decision.is_ok()
             );
         }
-        self.update_state_updates(block.get_state_updates().to_vec());
-        let _ = self.decider.get_db().put(
+        self.update_state_updates(state_updates);
+        self.decider.get_db().put(
             &Bytes::from(&b"latest_block_number"[..]).into(),
             &Bytes::from(Integer::new(block.get_block_number())),
-        );
+        )?;
+        Ok(())
     }
 
     fn get_latest_block_number(&self) -> u64 {
@@ -572,6 +2348,8 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
         for previous_block_number in event.clone().prev_state_block_numbers {
             transaction_db.put_transaction(previous_block_number.0, event.transaction.clone());
         }
+        let _ = TxJournal::new(self.decider.get_db())
+            .mark_accepted(get_transaction_hash(&event.transaction), now());
     }
 
     pub fn insert_test_ranges(&mut self) {
@@ -579,8 +2357,9 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
             return;
         }
         let mut state_updates = vec![];
-        let eth_token_address = Address::zero();
-        let dai_token_address = string_to_address("0000000000000000000000000000000000000001");
+        let tokens = default_tokens();
+        let eth_token_address = tokens[0].get_address();
+        let dai_token_address = tokens[1].get_address();
         for i in 0..3 {
             state_updates.push(StateUpdate::new(
                 Integer::new(0),
@@ -603,13 +2382,66 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
         }
 
         let plasma_block = PlasmaBlock::new(0, state_updates, vec![]);
-        self.handle_new_block(plasma_block);
+        if let Err(e) = self.handle_new_block(plasma_block) {
+            println!("failed to insert test ranges: {:?}", e);
+        }
+    }
+
+    /// Returns the number of the block which transactions sent now are expected to be included in.
+    pub fn get_next_block_number(&self) -> u64 {
+        SyncDb::new(self.decider.get_db())
+            .get_last_synced_block_number()
+            .unwrap_or(0)
+            + 1
     }
 
     pub fn get_all_state_updates(&self) -> Vec<StateUpdate> {
         let range_db = self.decider.get_range_db();
         let state_db = StateDb::new(range_db);
-        state_db.get_all_state_updates().unwrap_or_else(|_| vec![])
+        let addresses: Vec<Address> = self
+            .get_all_tokens()
+            .iter()
+            .map(|token| token.get_address())
+            .collect();
+        state_db
+            .get_all_state_updates(&addresses)
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn register_token(&self, token: Token) -> Result<(), Error> {
+        TokenDb::new(self.decider.get_db()).put_token(&token)
+    }
+
+    pub fn get_token(&self, address: Address) -> Option<Token> {
+        TokenDb::new(self.decider.get_db())
+            .get_token(address)
+            .unwrap_or(None)
+    }
+
+    pub fn get_all_tokens(&self) -> Vec<Token> {
+        TokenDb::new(self.decider.get_db())
+            .get_all_tokens()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn put_plasma_channel(&self, channel: &PlasmaChannel) -> Result<(), Error> {
+        PlasmaChannelDb::new(self.decider.get_db()).put_channel(channel)
+    }
+
+    pub fn get_plasma_channel(&self, channel_id: &Bytes) -> Option<PlasmaChannel> {
+        PlasmaChannelDb::new(self.decider.get_db())
+            .get_channel(channel_id)
+            .unwrap_or(None)
+    }
+
+    pub fn get_plasma_channels(&self) -> Vec<PlasmaChannel> {
+        PlasmaChannelDb::new(self.decider.get_db())
+            .get_channels()
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn remove_plasma_channel(&self, channel_id: &Bytes) -> Result<(), Error> {
+        PlasmaChannelDb::new(self.decider.get_db()).remove_channel(channel_id)
     }
 
     pub fn get_state_updates(&self, deposit_contract_address: Address) -> Vec<StateUpdate> {
@@ -637,14 +2469,47 @@ impl<KVS: KeyValueStore + DatabaseTrait> PlasmaClient<KVS> {
         owner: Address,
     ) -> Option<Range> {
         // TODO: decide if this property is owner's property.
-        self.get_state_updates(deposit_contract_address)
+        self.get_owned_state_updates(deposit_contract_address, owner)
             .iter()
-            .filter(|su| su.is_ownership_state() || su.is_exchanged_state())
-            .filter(|su| su.get_owner() == owner)
             .map(|su| su.get_range())
             .find(|range| amount <= range.get_end() - range.get_start())
     }
 
+    pub fn get_owned_state_updates(
+        &self,
+        deposit_contract_address: Address,
+        owner: Address,
+    ) -> Vec<StateUpdate> {
+        StateDb::new(self.decider.get_range_db())
+            .get_owned_state_updates(deposit_contract_address, owner)
+            .unwrap_or_else(|_| vec![])
+    }
+
+    pub fn get_fragmentation_metrics(
+        &self,
+        deposit_contract_address: Address,
+        owner: Address,
+    ) -> Option<FragmentationMetrics> {
+        StateDb::new(self.decider.get_range_db())
+            .get_fragmentation_metrics(deposit_contract_address, owner)
+            .ok()
+    }
+
+    /// Selects owned ranges to spend amount by policy.
+    pub fn select_coins(
+        &self,
+        deposit_contract_address: Address,
+        amount: u64,
+        owner: Address,
+        policy: CoinSelectionPolicy,
+    ) -> Result<Vec<SelectedRange>, Error> {
+        select_coins(
+            &self.get_owned_state_updates(deposit_contract_address, owner),
+            amount,
+            policy,
+        )
+    }
+
     fn get_related_transactions(&self, session: &Bytes) -> Vec<Transaction> {
         let address = self.get_my_address(session).unwrap();
         println!("{:?}", address);
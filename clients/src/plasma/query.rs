@@ -1,54 +1,54 @@
 // plasma_clients::plasma::query is Examples of query to StateUpdate list.
+use super::order_book::Order;
 use abi_utils::Integer;
 use ethereum_types::Address;
-use ovm::types::{Property, PropertyInput, StateUpdate};
+use ovm::types::{
+    match_state_object_kind,
+    state_object_kind::{EXCHANGED_KIND, ORDER_KIND},
+    Property, StateUpdate,
+};
 use plasma_core::data_structure::Range;
 use std::collections::HashMap;
 
-/// Filters all ownership properties and compute balance
+/// Computes balance of state objects whose kind captures my_address as the owner
 pub fn query_balance(
     state_updates: Vec<StateUpdate>,
     my_address: Address,
 ) -> HashMap<Address, u64> {
-    let balances: HashMap<Address, u64> = state_updates
+    state_updates
         .iter()
-        .filter(|s| {
-            let property = s.get_property();
-            if s.is_ownership_state() {
-                let p = &property.inputs[2];
-                if let PropertyInput::ConstantProperty(signed_by) = p {
-                    if let PropertyInput::ConstantAddress(address) = signed_by.inputs[0] {
-                        return address == my_address;
-                    }
-                }
-            } else if s.is_exchanged_state() {
-                let p = &property.inputs[2];
-                if let PropertyInput::ConstantProperty(q_property) = p {
-                    if let PropertyInput::ConstantProperty(q_su) = &q_property.inputs[2] {
-                        if let PropertyInput::ConstantProperty(or) = &q_su.inputs[2] {
-                            if let PropertyInput::ConstantProperty(and) = &or.inputs[0] {
-                                if let PropertyInput::ConstantProperty(signed_by) = &and.inputs[1] {
-                                    if let PropertyInput::ConstantAddress(address) =
-                                        signed_by.inputs[0]
-                                    {
-                                        return address == my_address;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            false
-        })
+        .filter(|s| s.try_get_owner() == Some(my_address))
         .fold(HashMap::new(), |mut acc, s| {
             let deposit_contract = s.get_deposit_contract_address();
             let b = acc.get(&deposit_contract).unwrap_or(&0);
             let new_balance = b + s.get_range().get_end() - s.get_range().get_start();
             acc.insert(deposit_contract, new_balance);
             acc
-        });
-    balances
+        })
+}
+
+/// Computes balances of making orders by the maker, which aren't counted by query_balance.
+/// Returns open orders and expired ones separately. Both can be reclaimed by cancelling them.
+pub fn query_order_balance(
+    state_updates: Vec<StateUpdate>,
+    maker: Address,
+    next_block_number: u64,
+) -> (HashMap<Address, u64>, HashMap<Address, u64>) {
+    let mut open = HashMap::new();
+    let mut expired = HashMap::new();
+    for order in state_updates
+        .iter()
+        .filter_map(Order::from_state_update)
+        .filter(|o| o.get_maker() == maker)
+    {
+        let balances = if order.is_expired(next_block_number) {
+            &mut expired
+        } else {
+            &mut open
+        };
+        *balances.entry(order.get_token()).or_insert(0) += order.get_amount();
+    }
+    (open, expired)
 }
 
 /// Filters all making order properties
@@ -58,54 +58,24 @@ pub fn query_orders(
     state_updates
         .iter()
         .filter_map(|s| {
-            if s.is_order_state() {
-                let property = s.get_property();
-                if property.inputs.len() >= 3 {
-                    let or = &property.inputs[2];
-                    if let PropertyInput::ConstantProperty(or) = or {
-                        if let PropertyInput::ConstantProperty(verify_tx) = &or.inputs[0] {
-                            if let PropertyInput::ConstantAddress(token_address) =
-                                verify_tx.inputs[1]
-                            {
-                                if let PropertyInput::ConstantInteger(amount) = verify_tx.inputs[2]
-                                {
-                                    if let PropertyInput::ConstantAddress(maker_address) =
-                                        verify_tx.inputs[3]
-                                    {
-                                        return Some((
-                                            s.clone(),
-                                            token_address,
-                                            amount,
-                                            maker_address,
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            None
+            let captures = match_state_object_kind(ORDER_KIND, s.get_property())?;
+            Some((
+                s.clone(),
+                captures.get_address("token")?,
+                captures.get_integer("amount")?,
+                captures.get_address("maker")?,
+            ))
         })
         .collect()
 }
 
+/// Returns token address and range which the exchanged state object requires.
 pub fn query_exchanged(state_object: Property) -> Option<(Address, Range)> {
-    if state_object.inputs.len() >= 3 {
-        let p = &state_object.inputs[2];
-        if let PropertyInput::ConstantProperty(q_property) = p {
-            if let PropertyInput::ConstantProperty(there) = &q_property.inputs[2] {
-                if let PropertyInput::ConstantProperty(q_su) = &there.inputs[0] {
-                    if let PropertyInput::ConstantAddress(token_address) = &q_su.inputs[1] {
-                        if let PropertyInput::ConstantRange(range) = &q_su.inputs[2] {
-                            return Some((*token_address, *range));
-                        }
-                    }
-                }
-            }
-        }
-    }
-    None
+    let captures = match_state_object_kind(EXCHANGED_KIND, &state_object)?;
+    Some((
+        captures.get_address("c_token")?,
+        captures.get_range("c_range")?,
+    ))
 }
 
 #[cfg(test)]
@@ -119,6 +89,8 @@ mod tests {
             Address::zero(),
             Address::zero(),
             Integer(100),
+            None,
+            None,
         );
         let state_update_list = vec![StateUpdate::new(
             Integer::new(7),
@@ -129,4 +101,62 @@ mod tests {
         let result = query_orders(state_update_list);
         assert_eq!(result.len(), 1);
     }
+
+    #[test]
+    fn test_query_balance_and_exchanged() {
+        let my_address = Address::random();
+        let c_token = Address::random();
+        let c_range = Range::new(100, 200);
+        let exchanged = ovm::statements::plasma::create_taking_order_state_object(
+            my_address,
+            Address::random(),
+            c_token,
+            c_range,
+        );
+        let state_update_list = vec![
+            StateUpdate::new(
+                Integer::new(1),
+                Address::zero(),
+                Range::new(0, 10),
+                ovm::statements::plasma::create_ownership_state_object(my_address),
+            ),
+            StateUpdate::new(
+                Integer::new(1),
+                Address::zero(),
+                Range::new(10, 30),
+                exchanged.clone(),
+            ),
+        ];
+        let balances = query_balance(state_update_list, my_address);
+        assert_eq!(balances.get(&Address::zero()), Some(&30));
+        assert_eq!(query_exchanged(exchanged), Some((c_token, c_range)));
+    }
+
+    #[test]
+    fn test_query_order_balance() {
+        let maker = Address::random();
+        let create_order = |start: u64, end: u64, expiry_block: Option<u64>| {
+            StateUpdate::new(
+                Integer::new(1),
+                Address::zero(),
+                Range::new(start, end),
+                ovm::statements::plasma::create_making_order_state_object(
+                    maker,
+                    Address::random(),
+                    Integer(100),
+                    expiry_block.map(Integer::new),
+                    None,
+                ),
+            )
+        };
+        let state_update_list = vec![
+            create_order(0, 10, None),
+            create_order(10, 30, Some(2)),
+            create_order(30, 60, Some(5)),
+        ];
+        let (open, expired) = query_order_balance(state_update_list.clone(), maker, 3);
+        assert_eq!(open.get(&Address::zero()), Some(&40));
+        assert_eq!(expired.get(&Address::zero()), Some(&20));
+        assert!(query_balance(state_update_list, maker).is_empty());
+    }
 }
@@ -1,4 +1,4 @@
-use super::utils::string_to_address;
+use super::defragmentation::FragmentationMetrics;
 use abi_derive::{AbiDecodable, AbiEncodable};
 use abi_utils::{Decodable, Encodable, Integer};
 use bytes::Bytes;
@@ -59,21 +59,46 @@ impl<'a, KVS: KeyValueStore> StateDb<'a, KVS> {
         StateDb { db: range_db }
     }
 
-    pub fn get_all_state_updates(&self) -> Result<Vec<StateUpdate>, PlasmaDbError> {
+    /// Returns all state updates of given deposit contracts.
+    pub fn get_all_state_updates(
+        &self,
+        deposit_contract_addresses: &[Address],
+    ) -> Result<Vec<StateUpdate>, PlasmaDbError> {
         let mut result = vec![];
-        // TODO: get dynamically
-        let mut state_updates =
-            self.get_verified_state_updates(Address::zero(), MIN_RANGE, MAX_RANGE)?;
-        result.append(&mut state_updates);
-        let mut state_updates = self.get_verified_state_updates(
-            string_to_address("0000000000000000000000000000000000000001"),
-            MIN_RANGE,
-            MAX_RANGE,
-        )?;
-        result.append(&mut state_updates);
+        for deposit_contract_address in deposit_contract_addresses {
+            let mut state_updates =
+                self.get_verified_state_updates(*deposit_contract_address, MIN_RANGE, MAX_RANGE)?;
+            result.append(&mut state_updates);
+        }
         Ok(result)
     }
 
+    /// Returns state updates owned by owner in order of range.
+    pub fn get_owned_state_updates(
+        &self,
+        deposit_contract_address: Address,
+        owner: Address,
+    ) -> Result<Vec<StateUpdate>, PlasmaDbError> {
+        let mut state_updates: Vec<StateUpdate> = self
+            .get_verified_state_updates(deposit_contract_address, MIN_RANGE, MAX_RANGE)?
+            .into_iter()
+            .filter(|s| s.try_get_owner() == Some(owner))
+            .collect();
+        state_updates.sort_by_key(|s| s.get_range().get_start());
+        Ok(state_updates)
+    }
+
+    pub fn get_fragmentation_metrics(
+        &self,
+        deposit_contract_address: Address,
+        owner: Address,
+    ) -> Result<FragmentationMetrics, PlasmaDbError> {
+        Ok(FragmentationMetrics::from_state_updates(
+            deposit_contract_address,
+            &self.get_owned_state_updates(deposit_contract_address, owner)?,
+        ))
+    }
+
     pub fn get_verified_state_updates(
         &self,
         deposit_contract_address: Address,
@@ -115,6 +140,7 @@ mod tests {
     use super::*;
     use abi_utils::Integer;
     use ovm::property_executor::DeciderManager;
+    use ovm::statements::plasma::create_ownership_state_object;
     use ovm::types::StateUpdate;
     use plasma_core::data_structure::Range;
 
@@ -149,4 +175,38 @@ mod tests {
         assert_eq!(state_updates[1].get_range().get_start(), 50);
         assert_eq!(state_updates[1].get_range().get_end(), 100);
     }
+
+    #[test]
+    fn test_fragmentation_metrics() {
+        let db = CoreDbMemoryImpl::open("test");
+        let range_db = RangeDbImpl::from(db);
+        let mut state_db = StateDb::new(&range_db);
+        let owner = Address::random();
+        for (start, end) in [(0, 10), (10, 20), (20, 40)].iter() {
+            let _ = state_db.put_verified_state_update(&StateUpdate::new(
+                Integer::new(1),
+                Address::zero(),
+                Range::new(*start, *end),
+                create_ownership_state_object(owner),
+            ));
+        }
+        let metrics = state_db
+            .get_fragmentation_metrics(Address::zero(), owner)
+            .unwrap();
+        assert_eq!(metrics.range_count, 3);
+        assert_eq!(metrics.merged_range_count, 1);
+
+        // a self-transfer over merged range replaces the adjacent ranges
+        let _ = state_db.put_verified_state_update(&StateUpdate::new(
+            Integer::new(2),
+            Address::zero(),
+            Range::new(0, 40),
+            create_ownership_state_object(owner),
+        ));
+        let metrics = state_db
+            .get_fragmentation_metrics(Address::zero(), owner)
+            .unwrap();
+        assert_eq!(metrics.range_count, 1);
+        assert_eq!(metrics.total_amount, 40);
+    }
 }
@@ -1,9 +1,13 @@
+use super::utils::string_to_address;
 use ethereum_types::Address;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Token deposited to plasma. address is the address of its deposit contract.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Token {
     name: String,
+    symbol: String,
+    decimals: u8,
     address: Address,
 }
 
@@ -11,15 +15,19 @@ impl Default for Token {
     fn default() -> Self {
         Self {
             name: "token".to_string(),
+            symbol: "TOKEN".to_string(),
+            decimals: 18,
             address: Address::zero(),
         }
     }
 }
 
 impl Token {
-    pub fn new(name: &str, address: Address) -> Self {
+    pub fn new(address: Address, name: &str, symbol: &str, decimals: u8) -> Self {
         Self {
             name: name.to_string(),
+            symbol: symbol.to_string(),
+            decimals,
             address,
         }
     }
@@ -28,7 +36,28 @@ impl Token {
         self.name.clone()
     }
 
+    pub fn get_symbol(&self) -> String {
+        self.symbol.clone()
+    }
+
+    pub fn get_decimals(&self) -> u8 {
+        self.decimals
+    }
+
     pub fn get_address(&self) -> Address {
         self.address
     }
 }
+
+/// Tokens which the example aggregator and clients register from config at startup.
+pub fn default_tokens() -> Vec<Token> {
+    vec![
+        Token::new(Address::zero(), "ETH", "ETH", 18),
+        Token::new(
+            string_to_address("0000000000000000000000000000000000000001"),
+            "DAI",
+            "DAI",
+            18,
+        ),
+    ]
+}
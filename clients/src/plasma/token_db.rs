@@ -0,0 +1,117 @@
+use super::error::Error;
+use super::token;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::Address;
+use plasma_db::traits::kvs::KeyValueStore;
+
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+struct TokenRecord {
+    address: Address,
+    name: Bytes,
+    symbol: Bytes,
+    decimals: Integer,
+}
+
+impl TokenRecord {
+    pub fn new(address: Address, name: Bytes, symbol: Bytes, decimals: Integer) -> Self {
+        Self {
+            address,
+            name,
+            symbol,
+            decimals,
+        }
+    }
+}
+
+impl From<&token::Token> for TokenRecord {
+    fn from(token: &token::Token) -> Self {
+        TokenRecord::new(
+            token.get_address(),
+            Bytes::from(token.get_name()),
+            Bytes::from(token.get_symbol()),
+            Integer::new(u64::from(token.get_decimals())),
+        )
+    }
+}
+
+impl From<TokenRecord> for token::Token {
+    fn from(record: TokenRecord) -> Self {
+        token::Token::new(
+            record.address,
+            &String::from_utf8_lossy(&record.name),
+            &String::from_utf8_lossy(&record.symbol),
+            record.decimals.0 as u8,
+        )
+    }
+}
+
+/// Registry of tokens keyed by deposit contract address.
+pub struct TokenDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> TokenDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_token(&self, token: &token::Token) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("tokens").into())
+            .put(
+                &Bytes::from(token.get_address().as_bytes()).into(),
+                &TokenRecord::from(token).to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_token(&self, address: Address) -> Result<Option<token::Token>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("tokens").into())
+            .get(&Bytes::from(address.as_bytes()).into())?;
+        if let Some(record) = result {
+            Ok(Some(token::Token::from(TokenRecord::from_abi(&record)?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns registered tokens ordered by address.
+    pub fn get_all_tokens(&self) -> Result<Vec<token::Token>, Error> {
+        self.db
+            .bucket(&Bytes::from("tokens").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| {
+                TokenRecord::from_abi(kv.get_value())
+                    .map(token::Token::from)
+                    .map_err(Into::into)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::token::Token as PlasmaToken;
+    use super::*;
+    use plasma_db::{impls::kvs::CoreDbMemoryImpl, traits::DatabaseTrait};
+
+    #[test]
+    fn test_register_tokens() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let token_db = TokenDb::new(&kvs);
+        let dai = PlasmaToken::new(Address::random(), "Dai Stablecoin", "DAI", 18);
+        assert!(token_db.put_token(&dai).is_ok());
+        assert_eq!(
+            token_db.get_token(dai.get_address()).unwrap(),
+            Some(dai.clone())
+        );
+        assert_eq!(token_db.get_token(Address::random()).unwrap(), None);
+        assert_eq!(token_db.get_all_tokens().unwrap(), vec![dai]);
+    }
+}
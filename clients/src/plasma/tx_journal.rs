@@ -0,0 +1,347 @@
+use super::error::Error;
+use super::utils::get_transaction_hash;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::H256;
+use plasma_core::data_structure::Transaction;
+use plasma_db::traits::kvs::KeyValueStore;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TransactionStatus {
+    /// Sent to the aggregator but not acknowledged yet.
+    Pending,
+    /// The aggregator broadcasted the transaction as ingested.
+    Accepted,
+    /// Included in a block whose root was submitted to the commitment contract.
+    Confirmed,
+    /// Rejected by the aggregator or gave up resubmitting.
+    Failed,
+    /// Accepted but not confirmed in time. The aggregator may have dropped it from its mempool.
+    Dropped,
+}
+
+impl TransactionStatus {
+    fn to_integer(self) -> Integer {
+        Integer::new(match self {
+            TransactionStatus::Pending => 0,
+            TransactionStatus::Accepted => 1,
+            TransactionStatus::Confirmed => 2,
+            TransactionStatus::Failed => 3,
+            TransactionStatus::Dropped => 4,
+        })
+    }
+
+    fn from_integer(status: Integer) -> Self {
+        match status.0 {
+            1 => TransactionStatus::Accepted,
+            2 => TransactionStatus::Confirmed,
+            3 => TransactionStatus::Failed,
+            4 => TransactionStatus::Dropped,
+            _ => TransactionStatus::Pending,
+        }
+    }
+
+    /// Whether the transaction may still be confirmed without user action.
+    pub fn is_in_flight(self) -> bool {
+        self == TransactionStatus::Pending || self == TransactionStatus::Accepted
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JournalPolicy {
+    /// Seconds to wait for acknowledgement or confirmation of a transaction.
+    pub timeout: u64,
+    /// Unacknowledged transactions are resubmitted until they were sent this many times.
+    pub max_attempts: u64,
+}
+
+impl Default for JournalPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: 60,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// A transaction submitted by this client and its status.
+/// submitted_at is unix time of the last submission and accepted_at is unix time of its acceptance.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct JournalEntry {
+    transaction: Transaction,
+    status: Integer,
+    submitted_at: Integer,
+    attempts: Integer,
+    block_number: Integer,
+    reason: Bytes,
+    accepted_at: Integer,
+}
+
+impl JournalEntry {
+    pub fn new(
+        transaction: Transaction,
+        status: Integer,
+        submitted_at: Integer,
+        attempts: Integer,
+        block_number: Integer,
+        reason: Bytes,
+        accepted_at: Integer,
+    ) -> Self {
+        Self {
+            transaction,
+            status,
+            submitted_at,
+            attempts,
+            block_number,
+            reason,
+            accepted_at,
+        }
+    }
+
+    pub fn get_hash(&self) -> H256 {
+        get_transaction_hash(&self.transaction)
+    }
+
+    pub fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn get_status(&self) -> TransactionStatus {
+        TransactionStatus::from_integer(self.status)
+    }
+
+    pub fn get_submitted_at(&self) -> u64 {
+        self.submitted_at.0
+    }
+
+    /// Returns None if the transaction hasn't been accepted.
+    pub fn get_accepted_at(&self) -> Option<u64> {
+        if self.accepted_at.0 == 0 {
+            None
+        } else {
+            Some(self.accepted_at.0)
+        }
+    }
+
+    /// Returns unix time which the timeout of the current status is measured from.
+    fn get_status_changed_at(&self) -> u64 {
+        if self.get_status() == TransactionStatus::Accepted {
+            self.get_accepted_at().unwrap_or(self.submitted_at.0)
+        } else {
+            self.submitted_at.0
+        }
+    }
+
+    pub fn get_attempts(&self) -> u64 {
+        self.attempts.0
+    }
+
+    /// Returns the block number which the transaction was confirmed at.
+    pub fn get_block_number(&self) -> Option<u64> {
+        if self.get_status() == TransactionStatus::Confirmed {
+            Some(self.block_number.0)
+        } else {
+            None
+        }
+    }
+
+    /// Returns why the transaction failed.
+    pub fn get_reason(&self) -> Option<String> {
+        if self.reason.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.reason).to_string())
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Journal of transactions submitted by this client keyed by transaction hash.
+/// Transactions not in the journal are ignored by status updates.
+pub struct TxJournal<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> TxJournal<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    /// Records submission of the transaction. Submitting the same transaction again counts up attempts.
+    pub fn record_submitted(&self, transaction: &Transaction, now: u64) -> Result<H256, Error> {
+        let hash = get_transaction_hash(transaction);
+        let attempts = self.get_entry(hash)?.map_or(0, |e| e.get_attempts());
+        self.put_entry(&JournalEntry::new(
+            transaction.clone(),
+            TransactionStatus::Pending.to_integer(),
+            Integer::new(now),
+            Integer::new(attempts + 1),
+            Integer::new(0),
+            Bytes::default(),
+            Integer::new(0),
+        ))?;
+        Ok(hash)
+    }
+
+    pub fn mark_accepted(&self, hash: H256, now: u64) -> Result<(), Error> {
+        self.update_entry(hash, |entry| {
+            if entry.get_status() == TransactionStatus::Pending {
+                entry.status = TransactionStatus::Accepted.to_integer();
+                entry.accepted_at = Integer::new(now);
+            }
+        })
+    }
+
+    /// Confirmation overrides any other status because the transaction is included in a committed block.
+    pub fn mark_confirmed(&self, hash: H256, block_number: u64) -> Result<(), Error> {
+        self.update_entry(hash, |entry| {
+            entry.status = TransactionStatus::Confirmed.to_integer();
+            entry.block_number = Integer::new(block_number);
+            entry.reason = Bytes::default();
+        })
+    }
+
+    pub fn mark_failed(&self, hash: H256, reason: &str) -> Result<(), Error> {
+        self.update_entry(hash, |entry| {
+            if entry.get_status().is_in_flight() {
+                entry.status = TransactionStatus::Failed.to_integer();
+                entry.reason = Bytes::from(reason);
+            }
+        })
+    }
+
+    pub fn mark_dropped(&self, hash: H256) -> Result<(), Error> {
+        self.update_entry(hash, |entry| {
+            if entry.get_status() == TransactionStatus::Accepted {
+                entry.status = TransactionStatus::Dropped.to_integer();
+            }
+        })
+    }
+
+    pub fn get_entry(&self, hash: H256) -> Result<Option<JournalEntry>, Error> {
+        let result = self
+            .db
+            .bucket(&Bytes::from("tx_journal").into())
+            .get(&Bytes::from(hash.as_bytes()).into())?;
+        if let Some(entry) = result {
+            Ok(Some(JournalEntry::from_abi(&entry)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_all_entries(&self) -> Result<Vec<JournalEntry>, Error> {
+        self.db
+            .bucket(&Bytes::from("tx_journal").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| JournalEntry::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+
+    /// Returns pending entries submitted and accepted entries accepted timeout seconds or more before now.
+    pub fn get_timed_out_entries(
+        &self,
+        now: u64,
+        timeout: u64,
+    ) -> Result<Vec<JournalEntry>, Error> {
+        Ok(self
+            .get_all_entries()?
+            .into_iter()
+            .filter(|e| e.get_status().is_in_flight() && e.get_status_changed_at() + timeout <= now)
+            .collect())
+    }
+
+    fn put_entry(&self, entry: &JournalEntry) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("tx_journal").into())
+            .put(
+                &Bytes::from(entry.get_hash().as_bytes()).into(),
+                &entry.to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    fn update_entry<F>(&self, hash: H256, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut JournalEntry),
+    {
+        if let Some(mut entry) = self.get_entry(hash)? {
+            f(&mut entry);
+            self.put_entry(&entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::Address;
+    use plasma_core::data_structure::{Metadata, Range, TransactionParams, PAYMENT_TYPE};
+    use plasma_db::{impls::kvs::CoreDbMemoryImpl, traits::DatabaseTrait};
+
+    fn create_transaction(start: u64, end: u64) -> Transaction {
+        Transaction::from_params(
+            TransactionParams::new(Address::zero(), Range::new(start, end), Bytes::default()),
+            Bytes::default(),
+            Metadata::new(PAYMENT_TYPE, Address::zero(), Address::zero()),
+        )
+    }
+
+    #[test]
+    fn test_journal_status() {
+        let db = CoreDbMemoryImpl::open("test");
+        let journal = TxJournal::new(&db);
+        let hash1 = journal
+            .record_submitted(&create_transaction(0, 10), 100)
+            .unwrap();
+        let hash2 = journal
+            .record_submitted(&create_transaction(10, 20), 100)
+            .unwrap();
+        journal.mark_accepted(hash1, 110).unwrap();
+        journal.mark_confirmed(hash1, 3).unwrap();
+        journal.mark_failed(hash2, "conflict").unwrap();
+        // rejection of a confirmed transaction doesn't change its status
+        journal.mark_failed(hash1, "conflict").unwrap();
+        let entry1 = journal.get_entry(hash1).unwrap().unwrap();
+        assert_eq!(entry1.get_status(), TransactionStatus::Confirmed);
+        assert_eq!(entry1.get_block_number(), Some(3));
+        let entry2 = journal.get_entry(hash2).unwrap().unwrap();
+        assert_eq!(entry2.get_status(), TransactionStatus::Failed);
+        assert_eq!(entry2.get_reason(), Some("conflict".to_string()));
+        assert_eq!(journal.get_all_entries().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_timed_out_entries() {
+        let db = CoreDbMemoryImpl::open("test");
+        let journal = TxJournal::new(&db);
+        let tx = create_transaction(0, 10);
+        let hash = journal.record_submitted(&tx, 100).unwrap();
+        assert!(journal.get_timed_out_entries(159, 60).unwrap().is_empty());
+        assert_eq!(journal.get_timed_out_entries(160, 60).unwrap().len(), 1);
+        journal.record_submitted(&tx, 160).unwrap();
+        let entry = journal.get_entry(hash).unwrap().unwrap();
+        assert_eq!(entry.get_attempts(), 2);
+        assert!(journal.get_timed_out_entries(160, 60).unwrap().is_empty());
+        // accepted transaction times out timeout seconds after the acceptance
+        journal.mark_accepted(hash, 200).unwrap();
+        assert_eq!(
+            journal.get_entry(hash).unwrap().unwrap().get_accepted_at(),
+            Some(200)
+        );
+        assert!(journal.get_timed_out_entries(259, 60).unwrap().is_empty());
+        assert_eq!(journal.get_timed_out_entries(260, 60).unwrap().len(), 1);
+    }
+}
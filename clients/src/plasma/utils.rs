@@ -1,5 +1,9 @@
+use abi_utils::Encodable;
 use bytes::Bytes;
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
+use ovm::types::StateUpdate;
+use ovm::utils::static_hash;
+use plasma_core::data_structure::{Range, Transaction};
 
 pub fn string_to_address(s: &str) -> Address {
     Address::from_slice(&hex::decode(s).unwrap())
@@ -24,3 +28,19 @@ pub fn decode_session(session: String) -> Result<Bytes, ()> {
 pub fn encode_session(raw: Bytes) -> String {
     hex::encode(raw.to_vec())
 }
+
+pub fn get_transaction_hash(transaction: &Transaction) -> H256 {
+    static_hash(&Bytes::from(transaction.to_abi()))
+}
+
+/// Whether state_updates sorted by start cover whole range.
+pub fn is_covered(range: Range, state_updates: &[StateUpdate]) -> bool {
+    let mut covered = range.get_start();
+    for state_update in state_updates {
+        if state_update.get_range().get_start() > covered {
+            return false;
+        }
+        covered = std::cmp::max(covered, state_update.get_range().get_end());
+    }
+    !state_updates.is_empty() && covered >= range.get_end()
+}
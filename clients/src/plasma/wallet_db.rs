@@ -1,5 +1,6 @@
 use super::error::Error;
 use bytes::Bytes;
+use ethereum_types::Address;
 use plasma_db::traits::kvs::KeyValueStore;
 
 pub struct WalletDb<'a, KVS> {
@@ -11,18 +12,121 @@ impl<'a, KVS: KeyValueStore> WalletDb<'a, KVS> {
         Self { db }
     }
 
-    pub fn put_private_key(&mut self, key: &Bytes, raw_key: &[u8]) -> Result<(), Error> {
+    /// Stores the V3 keystore JSON of the key of session.
+    pub fn put_keystore(&mut self, session: &Bytes, keystore: &[u8]) -> Result<(), Error> {
         self.db
-            .bucket(&Bytes::from("wallets").into())
-            .put(&key.into(), raw_key)
+            .bucket(&Bytes::from("keystores").into())
+            .put(&session.into(), keystore)
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_keystore(&self, session: &Bytes) -> Result<Option<Vec<u8>>, Error> {
+        self.db
+            .bucket(&Bytes::from("keystores").into())
+            .get(&session.into())
             .map_err::<Error, _>(Into::into)
     }
 
-    pub fn get_private_key(&self, key: &Bytes) -> Result<Vec<u8>, Error> {
+    /// Returns pairs of session and keystore.
+    pub fn get_all_keystores(&self) -> Vec<(Bytes, Vec<u8>)> {
+        self.db
+            .bucket(&Bytes::from("keystores").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| {
+                (
+                    Bytes::from(kv.get_key().as_bytes().to_vec()),
+                    kv.get_value().to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns pairs of session and raw private key stored without encryption by older versions.
+    pub fn get_all_raw_keys(&self) -> Vec<(Bytes, Vec<u8>)> {
         self.db
             .bucket(&Bytes::from("wallets").into())
-            .get(&key.into())
-            .map(|op| op.unwrap_or_else(|| vec![]))
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| {
+                (
+                    Bytes::from(kv.get_key().as_bytes().to_vec()),
+                    kv.get_value().to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn remove_raw_key(&mut self, session: &Bytes) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("wallets").into())
+            .del(&session.into())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    /// Stores the index and the address of an account derived from the HD wallet seed.
+    /// Its key isn't stored and is derived from the seed on unlock.
+    pub fn put_derived_account(
+        &mut self,
+        session: &Bytes,
+        index: u32,
+        address: Address,
+    ) -> Result<(), Error> {
+        let mut record = index.to_be_bytes().to_vec();
+        record.extend_from_slice(address.as_bytes());
+        self.db
+            .bucket(&Bytes::from("derived_accounts").into())
+            .put(&session.into(), &record)
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_derived_account(&self, session: &Bytes) -> Result<Option<(u32, Address)>, Error> {
+        self.db
+            .bucket(&Bytes::from("derived_accounts").into())
+            .get(&session.into())
+            .map(|record| record.and_then(|record| decode_derived_account(&record)))
             .map_err::<Error, _>(Into::into)
     }
+
+    /// Returns tuples of session, index and address of derived accounts.
+    pub fn get_all_derived_accounts(&self) -> Vec<(Bytes, u32, Address)> {
+        self.db
+            .bucket(&Bytes::from("derived_accounts").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .filter_map(|kv| {
+                decode_derived_account(kv.get_value()).map(|(index, address)| {
+                    (
+                        Bytes::from(kv.get_key().as_bytes().to_vec()),
+                        index,
+                        address,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Stores the HD wallet seed encrypted. There is at most one seed per wallet.
+    pub fn put_encrypted_seed(&mut self, encrypted_seed: &[u8]) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("hd_wallet").into())
+            .put(&Bytes::from("seed").into(), encrypted_seed)
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_encrypted_seed(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.db
+            .bucket(&Bytes::from("hd_wallet").into())
+            .get(&Bytes::from("seed").into())
+            .map_err::<Error, _>(Into::into)
+    }
+}
+
+fn decode_derived_account(record: &[u8]) -> Option<(u32, Address)> {
+    if record.len() != 24 {
+        return None;
+    }
+    let mut index = [0u8; 4];
+    index.copy_from_slice(&record[..4]);
+    Some((u32::from_be_bytes(index), Address::from_slice(&record[4..])))
 }
@@ -1,9 +1,152 @@
+use super::error::{Error, ErrorKind};
+use super::hd_wallet::{
+    derive_ethereum_key, discover_accounts, generate_mnemonic, mnemonic_to_seed,
+};
 use super::wallet_db::WalletDb;
 use bytes::Bytes;
-use ethsign::SecretKey as EthSecretKey;
+use ethereum_types::Address;
+use ethsign::keyfile::{Bytes as KeyFileBytes, Crypto, KeyFile};
+use ethsign::{Protected, SecretKey as EthSecretKey};
 use plasma_db::traits::kvs::KeyValueStore;
 use rand::*;
 use secp256k1::SecretKey;
+use std::collections::HashMap;
+use std::sync::atomic::{compiler_fence, Ordering};
+use std::sync::RwLock;
+
+/// Iterations of PBKDF2 deriving the key which encrypts keystores and the HD wallet seed.
+const KDF_ITERATIONS: u32 = 262_144;
+
+/// Overwrites bytes with zeros so that secrets don't stay in memory.
+fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Random version 4 UUID used as the id of keystores.
+fn random_uuid() -> String {
+    let mut bytes = rand::thread_rng().gen::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn new_session() -> Bytes {
+    Bytes::from(rand::thread_rng().gen::<[u8; 32]>().to_vec())
+}
+
+/// Private key decrypted from a keystore. The memory is zeroed when it's dropped.
+pub struct UnlockedKey {
+    raw: [u8; 32],
+}
+
+impl UnlockedKey {
+    fn new(secret_key_raw: &[u8]) -> Result<Self, Error> {
+        if secret_key_raw.len() != 32 || EthSecretKey::from_raw(secret_key_raw).is_err() {
+            return Err(Error::from(ErrorKind::InvalidParameter));
+        }
+        let mut raw = [0; 32];
+        raw.copy_from_slice(secret_key_raw);
+        Ok(Self { raw })
+    }
+
+    /// Secret key to sign with. Callers should drop it right after signing.
+    pub fn to_secret_key(&self) -> EthSecretKey {
+        EthSecretKey::from_raw(&self.raw).unwrap()
+    }
+
+    pub fn get_address(&self) -> Address {
+        self.to_secret_key().public().address().into()
+    }
+}
+
+impl Drop for UnlockedKey {
+    fn drop(&mut self) {
+        zeroize(&mut self.raw);
+    }
+}
+
+/// Keys of unlocked sessions held in memory. Locking drops and zeroes the key.
+#[derive(Default)]
+pub struct KeyRing {
+    keys: RwLock<HashMap<Bytes, UnlockedKey>>,
+}
+
+impl KeyRing {
+    pub fn insert(&self, session: Bytes, key: UnlockedKey) {
+        self.keys.write().unwrap().insert(session, key);
+    }
+
+    pub fn get_key(&self, session: &Bytes) -> Option<EthSecretKey> {
+        self.keys
+            .read()
+            .unwrap()
+            .get(session)
+            .map(UnlockedKey::to_secret_key)
+    }
+
+    pub fn is_unlocked(&self, session: &Bytes) -> bool {
+        self.keys.read().unwrap().contains_key(session)
+    }
+
+    /// Returns false if session wasn't unlocked.
+    pub fn lock(&self, session: &Bytes) -> bool {
+        self.keys.write().unwrap().remove(session).is_some()
+    }
+
+    pub fn lock_all(&self) {
+        self.keys.write().unwrap().clear();
+    }
+}
+
+/// Encrypts secret_key_raw with password into V3 keystore JSON.
+fn encrypt_keystore(secret_key_raw: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+    let address = UnlockedKey::new(secret_key_raw)?.get_address();
+    let keyfile = KeyFile {
+        id: random_uuid(),
+        version: 3,
+        crypto: Crypto::encrypt(secret_key_raw, &Protected::from(password), KDF_ITERATIONS)
+            .map_err(|_| Error::from(ErrorKind::InvalidPassword))?,
+        address: Some(KeyFileBytes(address.as_bytes().to_vec())),
+    };
+    serde_json::to_vec(&keyfile).map_err(|_| Error::from(ErrorKind::InvalidKeystore))
+}
+
+fn parse_keystore(keystore: &[u8]) -> Result<KeyFile, Error> {
+    let keyfile: KeyFile =
+        serde_json::from_slice(keystore).map_err(|_| Error::from(ErrorKind::InvalidKeystore))?;
+    if keyfile.version != 3 {
+        return Err(Error::from(ErrorKind::InvalidKeystore));
+    }
+    Ok(keyfile)
+}
+
+/// Decrypts keyfile with password. The address of keyfile, if any, must be the one of the key.
+fn decrypt_keystore(keyfile: &KeyFile, password: &str) -> Result<UnlockedKey, Error> {
+    let mut secret_key_raw = keyfile
+        .crypto
+        .decrypt(&Protected::from(password))
+        .map_err(|_| Error::from(ErrorKind::InvalidPassword))?;
+    let key = UnlockedKey::new(&secret_key_raw);
+    zeroize(&mut secret_key_raw);
+    let key = key.map_err(|_| Error::from(ErrorKind::InvalidKeystore))?;
+    match &keyfile.address {
+        Some(address) if address.0 != key.get_address().as_bytes() => {
+            Err(Error::from(ErrorKind::InvalidKeystore))
+        }
+        _ => Ok(key),
+    }
+}
 
 pub struct WalletManager<'a, KVS> {
     db: WalletDb<'a, KVS>,
@@ -16,35 +159,227 @@ impl<'a, KVS: KeyValueStore> WalletManager<'a, KVS> {
         }
     }
 
-    pub fn generate_key_session(&mut self) -> (Bytes, EthSecretKey) {
+    /// Generates a key and stores it as a keystore encrypted with password.
+    pub fn generate_key_session(&mut self, password: &str) -> Result<(Bytes, UnlockedKey), Error> {
         let mut rnd = rand::thread_rng();
-        let secret_key_raw = SecretKey::random(&mut rnd).serialize();
-        let session_raw = rand::thread_rng().gen::<[u8; 32]>().to_vec();
-        let session = Bytes::from(session_raw);
-        let _ = self.db.put_private_key(&session, &secret_key_raw); // TODO: error handling
-        let secret_key = EthSecretKey::from_raw(&secret_key_raw).unwrap();
+        let mut secret_key_raw = SecretKey::random(&mut rnd).serialize();
+        let result = self.import_key(&secret_key_raw, password);
+        zeroize(&mut secret_key_raw);
+        result
+    }
 
-        (session, secret_key)
+    /// Stores secret_key_raw as a keystore encrypted with password.
+    pub fn import_key(
+        &mut self,
+        secret_key_raw: &[u8],
+        password: &str,
+    ) -> Result<(Bytes, UnlockedKey), Error> {
+        let key = UnlockedKey::new(secret_key_raw)?;
+        let session = new_session();
+        self.db
+            .put_keystore(&session, &encrypt_keystore(secret_key_raw, password)?)?;
+        Ok((session, key))
     }
 
-    pub fn import_key(&mut self, secret_key_raw: &[u8]) -> (Bytes, EthSecretKey) {
-        let session_raw = rand::thread_rng().gen::<[u8; 32]>().to_vec();
-        let session = Bytes::from(session_raw);
-        let _ = self.db.put_private_key(&session, secret_key_raw); // TODO: error handling
+    /// Stores V3 keystore JSON exported by another wallet after checking that password decrypts it.
+    pub fn import_keystore(
+        &mut self,
+        keystore: &[u8],
+        password: &str,
+    ) -> Result<(Bytes, UnlockedKey), Error> {
+        let mut keyfile = parse_keystore(keystore)?;
+        let key = decrypt_keystore(&keyfile, password)?;
+        keyfile.address = Some(KeyFileBytes(key.get_address().as_bytes().to_vec()));
+        let keystore =
+            serde_json::to_vec(&keyfile).map_err(|_| Error::from(ErrorKind::InvalidKeystore))?;
+        let session = new_session();
+        self.db.put_keystore(&session, &keystore)?;
+        Ok((session, key))
+    }
 
-        (session, EthSecretKey::from_raw(&secret_key_raw).unwrap())
+    /// Returns the V3 keystore JSON of session to import into another wallet.
+    pub fn export_keystore(&self, session: &Bytes) -> Result<String, Error> {
+        let keystore = self
+            .db
+            .get_keystore(session)?
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        String::from_utf8(keystore).map_err(|_| Error::from(ErrorKind::InvalidKeystore))
     }
 
-    pub fn get_key(&self, session: &Bytes) -> Option<EthSecretKey> {
-        match self
+    /// Decrypts the keystore of session with password.
+    /// The key of a derived account is derived from the seed decrypted with password.
+    pub fn unlock_key(&self, session: &Bytes, password: &str) -> Result<UnlockedKey, Error> {
+        if let Some((index, _)) = self.db.get_derived_account(session)? {
+            let mut seed = self.get_seed(password)?;
+            let result = derive_unlocked_key(&seed, index);
+            zeroize(&mut seed);
+            return result;
+        }
+        let keystore = self
             .db
-            .get_private_key(session)
-            .map(|k| EthSecretKey::from_raw(&k).unwrap())
-        {
-            Ok(k) => Some(k),
-            Err(_) => None,
+            .get_keystore(session)?
+            .ok_or_else(|| Error::from(ErrorKind::InvalidParameter))?;
+        decrypt_keystore(&parse_keystore(&keystore)?, password)
+    }
+
+    /// Whether private keys stored raw by older versions are left.
+    pub fn has_raw_keys(&self) -> bool {
+        !self.db.get_all_raw_keys().is_empty()
+    }
+
+    /// Encrypts private keys stored raw by older versions with password and removes the raw keys.
+    /// Their sessions are kept. Returns the migrated sessions.
+    pub fn encrypt_raw_keys(&mut self, password: &str) -> Result<Vec<Bytes>, Error> {
+        let mut sessions = vec![];
+        for (session, mut secret_key_raw) in self.db.get_all_raw_keys() {
+            let keystore = encrypt_keystore(&secret_key_raw, password);
+            zeroize(&mut secret_key_raw);
+            self.db.put_keystore(&session, &keystore?)?;
+            self.db.remove_raw_key(&session)?;
+            sessions.push(session);
         }
+        Ok(sessions)
+    }
+
+    /// Creates a new BIP-39 mnemonic and stores its seed encrypted with password.
+    /// The mnemonic itself isn't stored, so the user must back it up.
+    pub fn generate_mnemonic(&mut self, password: &str) -> Result<String, Error> {
+        let phrase = generate_mnemonic();
+        self.import_mnemonic(&phrase, password)?;
+        Ok(phrase)
+    }
+
+    /// Stores the seed of phrase encrypted with password. The previous seed is replaced.
+    pub fn import_mnemonic(&mut self, phrase: &str, password: &str) -> Result<(), Error> {
+        let mut seed = mnemonic_to_seed(phrase, "")?;
+        let crypto = Crypto::encrypt(&seed, &Protected::from(password), KDF_ITERATIONS);
+        zeroize(&mut seed);
+        let crypto = crypto.map_err(|_| Error::from(ErrorKind::InvalidPassword))?;
+        let encrypted_seed =
+            serde_json::to_vec(&crypto).map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
+        self.db.put_encrypted_seed(&encrypted_seed)
+    }
+
+    fn get_seed(&self, password: &str) -> Result<Vec<u8>, Error> {
+        let encrypted_seed = self
+            .db
+            .get_encrypted_seed()?
+            .ok_or_else(|| Error::from(ErrorKind::SeedNotFound))?;
+        let crypto: Crypto = serde_json::from_slice(&encrypted_seed)
+            .map_err(|_| Error::from(ErrorKind::PlasmaDbError))?;
+        crypto
+            .decrypt(&Protected::from(password))
+            .map_err(|_| Error::from(ErrorKind::InvalidPassword))
     }
+
+    /// Opens a session of the account at index of seed. The session opened before is reused.
+    /// A new session stores only the index and the address, not the key.
+    fn open_derived_key_session(
+        &mut self,
+        seed: &[u8],
+        index: u32,
+    ) -> Result<(Bytes, UnlockedKey), Error> {
+        let key = derive_unlocked_key(seed, index)?;
+        let session = match self.find_session(key.get_address()) {
+            Some(session) => session,
+            None => {
+                let session = new_session();
+                self.db
+                    .put_derived_account(&session, index, key.get_address())?;
+                session
+            }
+        };
+        Ok((session, key))
+    }
+
+    /// Opens a session of the account at index of the BIP-44 Ethereum path m/44'/60'/0'/0/index.
+    pub fn derive_key_session(
+        &mut self,
+        password: &str,
+        index: u32,
+    ) -> Result<(Bytes, UnlockedKey), Error> {
+        let mut seed = self.get_seed(password)?;
+        let result = self.open_derived_key_session(&seed, index);
+        zeroize(&mut seed);
+        result
+    }
+
+    /// Opens sessions of the accounts of the seed for which is_used returns true.
+    pub fn discover_key_sessions<F>(
+        &mut self,
+        password: &str,
+        is_used: F,
+    ) -> Result<Vec<(Bytes, UnlockedKey)>, Error>
+    where
+        F: Fn(Address) -> bool,
+    {
+        let mut seed = self.get_seed(password)?;
+        let result = discover_accounts(&seed, is_used).and_then(|indices| {
+            indices
+                .into_iter()
+                .map(|index| self.open_derived_key_session(&seed, index))
+                .collect()
+        });
+        zeroize(&mut seed);
+        result
+    }
+
+    /// Address of the key of session. The keystore isn't decrypted.
+    pub fn get_address(&self, session: &Bytes) -> Option<Address> {
+        if let Ok(Some((_, address))) = self.db.get_derived_account(session) {
+            return Some(address);
+        }
+        match self.db.get_keystore(session) {
+            Ok(Some(keystore)) => keystore_address(&keystore),
+            _ => None,
+        }
+    }
+
+    pub fn get_all_addresses(&self) -> Vec<Address> {
+        self.get_all_sessions()
+            .into_iter()
+            .map(|(_, address)| address)
+            .collect()
+    }
+
+    /// Session whose keystore or derived account has the key of address.
+    pub fn find_session(&self, address: Address) -> Option<Bytes> {
+        self.get_all_sessions()
+            .into_iter()
+            .find(|(_, a)| *a == address)
+            .map(|(session, _)| session)
+    }
+
+    fn get_all_sessions(&self) -> Vec<(Bytes, Address)> {
+        let keystores =
+            self.db
+                .get_all_keystores()
+                .into_iter()
+                .filter_map(|(session, keystore)| {
+                    keystore_address(&keystore).map(|address| (session, address))
+                });
+        let derived_accounts = self
+            .db
+            .get_all_derived_accounts()
+            .into_iter()
+            .map(|(session, _, address)| (session, address));
+        keystores.chain(derived_accounts).collect()
+    }
+}
+
+fn derive_unlocked_key(seed: &[u8], index: u32) -> Result<UnlockedKey, Error> {
+    let mut secret_key_raw = derive_ethereum_key(seed, index)?;
+    let key = UnlockedKey::new(&secret_key_raw);
+    zeroize(&mut secret_key_raw);
+    key
+}
+
+fn keystore_address(keystore: &[u8]) -> Option<Address> {
+    parse_keystore(keystore)
+        .ok()
+        .and_then(|keyfile| keyfile.address)
+        .filter(|address| address.0.len() == 20)
+        .map(|address| Address::from_slice(&address.0))
 }
 
 #[cfg(test)]
@@ -56,15 +391,124 @@ mod tests {
     fn test_generate_key_session() {
         let kvs = CoreDbMemoryImpl::open("test");
         let mut wallet_manager = WalletManager::new(&kvs);
-        let (session, private_key) = wallet_manager.generate_key_session();
+        let (session, key) = wallet_manager.generate_key_session("password").unwrap();
+        assert_eq!(
+            wallet_manager.get_address(&session),
+            Some(key.get_address())
+        );
+        assert_eq!(
+            wallet_manager
+                .unlock_key(&session, "password")
+                .unwrap()
+                .get_address(),
+            key.get_address()
+        );
+        assert_eq!(
+            wallet_manager
+                .unlock_key(&session, "wrong")
+                .err()
+                .unwrap()
+                .kind()
+                .code(),
+            ErrorKind::InvalidPassword.code()
+        );
+        // the exported keystore is imported into another wallet
+        let keystore = wallet_manager.export_keystore(&session).unwrap();
+        let another_kvs = CoreDbMemoryImpl::open("another");
+        let mut another_wallet_manager = WalletManager::new(&another_kvs);
+        assert!(another_wallet_manager
+            .import_keystore(keystore.as_bytes(), "wrong")
+            .is_err());
+        let (_, imported) = another_wallet_manager
+            .import_keystore(keystore.as_bytes(), "password")
+            .unwrap();
+        assert_eq!(imported.get_address(), key.get_address());
+        assert_eq!(
+            another_wallet_manager.get_all_addresses(),
+            vec![key.get_address()]
+        );
+    }
+
+    #[test]
+    fn test_key_ring() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let mut wallet_manager = WalletManager::new(&kvs);
+        let (session, key) = wallet_manager.generate_key_session("password").unwrap();
+        let address = key.get_address();
+        let key_ring = KeyRing::default();
+        key_ring.insert(session.clone(), key);
+        let secret_key = key_ring.get_key(&session).unwrap();
+        assert_eq!(Address::from(secret_key.public().address()), address);
+        assert!(key_ring.lock(&session));
+        assert!(!key_ring.is_unlocked(&session));
+        assert!(key_ring.get_key(&session).is_none());
+        key_ring.insert(
+            session.clone(),
+            wallet_manager.unlock_key(&session, "password").unwrap(),
+        );
+        key_ring.lock_all();
+        assert!(!key_ring.lock(&session));
+    }
+
+    #[test]
+    fn test_encrypt_raw_keys() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let secret_key_raw = SecretKey::random(&mut rand::thread_rng()).serialize();
+        let session = Bytes::from("session");
+        assert!(kvs
+            .bucket(&Bytes::from("wallets").into())
+            .put(&session.clone().into(), &secret_key_raw)
+            .is_ok());
+        let mut wallet_manager = WalletManager::new(&kvs);
+        assert!(wallet_manager.has_raw_keys());
+        assert_eq!(
+            wallet_manager.encrypt_raw_keys("password").unwrap(),
+            vec![session.clone()]
+        );
+        assert!(!wallet_manager.has_raw_keys());
+        assert_eq!(
+            wallet_manager
+                .unlock_key(&session, "password")
+                .unwrap()
+                .get_address(),
+            UnlockedKey::new(&secret_key_raw).unwrap().get_address()
+        );
+    }
+
+    #[test]
+    fn test_derive_key_session() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let mut wallet_manager = WalletManager::new(&kvs);
+        let phrase = wallet_manager.generate_mnemonic("password").unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        let (session, key) = wallet_manager.derive_key_session("password", 1).unwrap();
+        // the same account is derived from the same mnemonic
+        let (same_session, _) = wallet_manager.derive_key_session("password", 1).unwrap();
+        assert_eq!(session, same_session);
+        // the derived key isn't stored
+        assert!(wallet_manager.export_keystore(&session).is_err());
+        assert_eq!(
+            wallet_manager
+                .unlock_key(&session, "password")
+                .unwrap()
+                .get_address(),
+            key.get_address()
+        );
+        assert!(wallet_manager.import_mnemonic(&phrase, "another").is_ok());
+        let address = key.get_address();
+        let sessions = wallet_manager
+            .discover_key_sessions("another", |a| a == address)
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0, session);
         assert_eq!(
             wallet_manager
-                .get_key(&session)
+                .derive_key_session("wrong", 0)
+                .err()
                 .unwrap()
-                .public()
-                .bytes()
-                .to_vec(),
-            private_key.public().bytes().to_vec()
+                .kind()
+                .code(),
+            ErrorKind::InvalidPassword.code()
         );
     }
 }
@@ -0,0 +1,490 @@
+use super::error::Error;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ovm::property_executor::PropertyExecutor;
+use ovm::types::{ImplicationProofElement, Property, PropertyPattern};
+use ovm::DeciderManager;
+use plasma_core::data_structure::Range;
+use plasma_db::traits::kvs::KeyValueStore;
+use serde::Serialize;
+
+/// Checkpoint claimed by an exit or a checkpoint.
+/// It says all state updates of the range before block_number are deprecated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckpointClaim {
+    pub block_number: u64,
+    pub deposit_contract_address: Address,
+    pub range: Range,
+}
+
+impl CheckpointClaim {
+    /// Parses the property created by plasma_checkpoint_property.
+    pub fn from_property(property: &Property) -> Option<Self> {
+        let pattern = PropertyPattern::parse(
+            "for_all_such_that(q_less_than(?block_number:integer), bytes, for_all_such_that(q_block(placeholder, ?token:address, ?range:range), bytes, is_deprecated(placeholder)))",
+        )
+        .ok()?;
+        let captures = pattern.match_property(property)?;
+        Some(Self {
+            block_number: captures.get_integer("block_number")?.0,
+            deposit_contract_address: captures.get_address("token")?,
+            range: captures.get_range("range")?,
+        })
+    }
+}
+
+/// Range whose monitoring the owner delegated to the watchtower.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct Delegation {
+    owner: Address,
+    deposit_contract_address: Address,
+    range: Range,
+}
+
+impl Delegation {
+    pub fn new(owner: Address, deposit_contract_address: Address, range: Range) -> Self {
+        Self {
+            owner,
+            deposit_contract_address,
+            range,
+        }
+    }
+
+    pub fn get_owner(&self) -> Address {
+        self.owner
+    }
+
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.deposit_contract_address
+    }
+
+    pub fn get_range(&self) -> Range {
+        self.range
+    }
+
+    pub fn is_affected_by(&self, claim: &CheckpointClaim) -> bool {
+        self.deposit_contract_address == claim.deposit_contract_address
+            && self.range.get_start() < claim.range.get_end()
+            && claim.range.get_start() < self.range.get_end()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum AuditAction {
+    /// The claim doesn't conflict with the known history.
+    Accepted,
+    /// The counter-claim and its contradiction with the claim were sent to the universal decision contract.
+    Challenged,
+    /// The claim conflicts but sending the challenge failed.
+    ChallengeFailed,
+    /// The claim conflicts but the dispute window had passed when it was found.
+    Expired,
+    /// The claim couldn't be decided with the known history.
+    Undecidable,
+}
+
+impl AuditAction {
+    fn to_integer(self) -> Integer {
+        Integer::new(match self {
+            AuditAction::Accepted => 0,
+            AuditAction::Challenged => 1,
+            AuditAction::ChallengeFailed => 2,
+            AuditAction::Expired => 3,
+            AuditAction::Undecidable => 4,
+        })
+    }
+
+    fn from_integer(action: Integer) -> Self {
+        match action.0 {
+            1 => AuditAction::Challenged,
+            2 => AuditAction::ChallengeFailed,
+            3 => AuditAction::Expired,
+            4 => AuditAction::Undecidable,
+            _ => AuditAction::Accepted,
+        }
+    }
+}
+
+/// What the watchtower did for a claim affecting delegated ranges.
+/// transaction_hash is the hash of the challenge transaction or zero.
+#[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
+pub struct AuditEntry {
+    claim: Property,
+    claimed_at: Integer,
+    action: Integer,
+    transaction_hash: H256,
+    reason: Bytes,
+}
+
+impl AuditEntry {
+    pub fn new(
+        claim: Property,
+        claimed_at: Integer,
+        action: Integer,
+        transaction_hash: H256,
+        reason: Bytes,
+    ) -> Self {
+        Self {
+            claim,
+            claimed_at,
+            action,
+            transaction_hash,
+            reason,
+        }
+    }
+
+    pub fn create(
+        claim: &Property,
+        claimed_at: u64,
+        action: AuditAction,
+        transaction_hash: H256,
+        reason: &str,
+    ) -> Self {
+        Self::new(
+            claim.clone(),
+            Integer::new(claimed_at),
+            action.to_integer(),
+            transaction_hash,
+            Bytes::from(reason),
+        )
+    }
+
+    pub fn get_claim(&self) -> &Property {
+        &self.claim
+    }
+
+    /// L1 block number at which the claim was made.
+    pub fn get_claimed_at(&self) -> u64 {
+        self.claimed_at.0
+    }
+
+    pub fn get_action(&self) -> AuditAction {
+        AuditAction::from_integer(self.action)
+    }
+
+    pub fn get_transaction_hash(&self) -> H256 {
+        self.transaction_hash
+    }
+
+    pub fn get_reason(&self) -> String {
+        String::from_utf8_lossy(&self.reason).to_string()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WatchtowerPolicy {
+    /// The number of L1 blocks after a claim in which it can be challenged.
+    pub dispute_period: u64,
+    /// The maximum number of L1 blocks scanned for claims per poll.
+    pub max_blocks_per_poll: u64,
+}
+
+impl Default for WatchtowerPolicy {
+    fn default() -> Self {
+        Self {
+            dispute_period: 20,
+            max_blocks_per_poll: 100,
+        }
+    }
+}
+
+/// Delegations and the audit log.
+/// Audit entries are keyed by big endian sequence number so that iteration follows the order of actions.
+pub struct WatchtowerDb<'a, KVS: KeyValueStore> {
+    db: &'a KVS,
+}
+
+impl<'a, KVS: KeyValueStore> WatchtowerDb<'a, KVS> {
+    pub fn new(db: &'a KVS) -> Self {
+        Self { db }
+    }
+
+    pub fn put_delegation(&self, delegation: &Delegation) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("watchtower_delegations").into())
+            .put(
+                &Bytes::from(delegation.to_abi()).into(),
+                &delegation.to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn remove_delegation(&self, delegation: &Delegation) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("watchtower_delegations").into())
+            .del(&Bytes::from(delegation.to_abi()).into())
+            .map_err::<Error, _>(Into::into)
+    }
+
+    pub fn get_delegations(&self) -> Result<Vec<Delegation>, Error> {
+        self.db
+            .bucket(&Bytes::from("watchtower_delegations").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| Delegation::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+
+    pub fn get_affected_delegations(
+        &self,
+        claim: &CheckpointClaim,
+    ) -> Result<Vec<Delegation>, Error> {
+        Ok(self
+            .get_delegations()?
+            .into_iter()
+            .filter(|d| d.is_affected_by(claim))
+            .collect())
+    }
+
+    pub fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), Error> {
+        let bucket = self.db.bucket(&Bytes::from("watchtower").into());
+        let sequence = bucket
+            .get(&Bytes::from("sequence").into())?
+            .map_or(0, |s| Integer::from(s).0);
+        bucket.put(
+            &Bytes::from("sequence").into(),
+            &Bytes::from(Integer::new(sequence + 1)),
+        )?;
+        self.db
+            .bucket(&Bytes::from("watchtower_audit_entries").into())
+            .put(
+                &Bytes::from(&sequence.to_be_bytes()[..]).into(),
+                &entry.to_abi(),
+            )
+            .map_err::<Error, _>(Into::into)
+    }
+
+    /// Returns the audit log in order of the actions.
+    pub fn get_audit_log(&self) -> Result<Vec<AuditEntry>, Error> {
+        self.db
+            .bucket(&Bytes::from("watchtower_audit_entries").into())
+            .iter_all(&Bytes::default().into(), Box::new(move |_k, _v| true))
+            .iter()
+            .map(|kv| AuditEntry::from_abi(kv.get_value()).map_err::<Error, _>(Into::into))
+            .collect()
+    }
+
+    /// The last L1 block whose claims were inspected. 0 if no block was scanned.
+    pub fn get_last_scanned_block(&self) -> Result<u64, Error> {
+        Ok(self
+            .db
+            .bucket(&Bytes::from("watchtower").into())
+            .get(&Bytes::from("last_scanned_block").into())?
+            .map_or(0, |n| Integer::from(n).0))
+    }
+
+    pub fn set_last_scanned_block(&self, block_number: u64) -> Result<(), Error> {
+        self.db.bucket(&Bytes::from("watchtower").into()).put(
+            &Bytes::from("last_scanned_block").into(),
+            &Bytes::from(Integer::new(block_number)),
+        )?;
+        Ok(())
+    }
+
+    /// Returns L1 blocks to scan for claims given the current L1 head.
+    /// Claims older than the dispute period are skipped because they can't be challenged anymore.
+    pub fn get_blocks_to_scan(
+        &self,
+        head: u64,
+        policy: &WatchtowerPolicy,
+    ) -> Result<Option<(u64, u64)>, Error> {
+        let from = std::cmp::max(
+            self.get_last_scanned_block()? + 1,
+            head.saturating_sub(policy.dispute_period),
+        );
+        if from > head {
+            return Ok(None);
+        }
+        let to = std::cmp::min(
+            head,
+            from.saturating_add(policy.max_blocks_per_poll.saturating_sub(1)),
+        );
+        Ok(Some((from, to)))
+    }
+
+    /// The latest L1 block number seen by the watchtower.
+    pub fn get_last_seen_block(&self) -> Result<u64, Error> {
+        Ok(self
+            .db
+            .bucket(&Bytes::from("watchtower").into())
+            .get(&Bytes::from("last_seen_block").into())?
+            .map_or(0, |n| Integer::from(n).0))
+    }
+
+    pub fn update_last_seen_block(&self, block_number: u64) -> Result<u64, Error> {
+        let last_seen_block = self.get_last_seen_block()?.max(block_number);
+        self.db.bucket(&Bytes::from("watchtower").into()).put(
+            &Bytes::from("last_seen_block").into(),
+            &Bytes::from(Integer::new(last_seen_block)),
+        )?;
+        Ok(last_seen_block)
+    }
+}
+
+/// Contradiction between a false claim and its negation decided true with the synced history.
+/// The watchtower claims counter_claim and proves the contradiction to the universal decision contract.
+#[derive(Clone, Debug)]
+pub struct Challenge {
+    pub claim: Property,
+    pub counter_claim: Property,
+    pub counter_claim_implication_proof: Vec<ImplicationProofElement>,
+}
+
+impl Challenge {
+    /// The implication proof of the claim itself.
+    pub fn get_claim_implication_proof(&self) -> Vec<ImplicationProofElement> {
+        vec![ImplicationProofElement::new(self.claim.clone(), None)]
+    }
+
+    /// The claim and its negation contradict without witness.
+    pub fn get_contradiction_witness(&self) -> Bytes {
+        Bytes::default()
+    }
+}
+
+/// Result of checking a claim against the known history.
+#[derive(Clone, Debug)]
+pub enum Verdict {
+    /// The claim isn't a checkpoint claim or no delegated range is affected.
+    Unrelated,
+    Valid,
+    Conflicting(Challenge),
+    Expired,
+    Undecidable,
+}
+
+/// Decides claims affecting delegated ranges with the history synced by PropertyExecutor.
+pub struct Watchtower<'a, KVS: KeyValueStore> {
+    decider: &'a PropertyExecutor<KVS>,
+    policy: WatchtowerPolicy,
+}
+
+impl<'a, KVS: KeyValueStore> Watchtower<'a, KVS> {
+    pub fn new(decider: &'a PropertyExecutor<KVS>, policy: WatchtowerPolicy) -> Self {
+        Self { decider, policy }
+    }
+
+    /// The claim conflicts if the decider decides it false.
+    /// The counter-claim is the negation of the claim. Its implication proof is taken from its decision.
+    /// The dispute window is measured against the latest L1 block seen.
+    pub fn inspect_claim(&self, claim: &Property, claimed_at: u64) -> Result<Verdict, Error> {
+        let checkpoint = match CheckpointClaim::from_property(claim) {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(Verdict::Unrelated),
+        };
+        let db = WatchtowerDb::new(self.decider.get_db());
+        if db.get_affected_delegations(&checkpoint)?.is_empty() {
+            return Ok(Verdict::Unrelated);
+        }
+        let decision = match self.decider.decide(claim) {
+            Ok(decision) => decision,
+            Err(_) => return Ok(Verdict::Undecidable),
+        };
+        if decision.get_outcome() {
+            Ok(Verdict::Valid)
+        } else if db.get_last_seen_block()? > claimed_at + self.policy.dispute_period {
+            Ok(Verdict::Expired)
+        } else {
+            let counter_claim = DeciderManager::not_decider(claim.clone());
+            match self.decider.decide(&counter_claim) {
+                Ok(decision) if decision.get_outcome() => Ok(Verdict::Conflicting(Challenge {
+                    claim: claim.clone(),
+                    counter_claim,
+                    counter_claim_implication_proof: decision.get_implication_proof().clone(),
+                })),
+                _ => Ok(Verdict::Undecidable),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::property_executor::PropertyExecuterOptions;
+    use ovm::statements::plasma::plasma_checkpoint_property;
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+    use plasma_db::traits::DatabaseTrait;
+
+    #[test]
+    fn test_checkpoint_claim() {
+        let property =
+            plasma_checkpoint_property(Integer::new(5), Address::zero(), Range::new(0, 100));
+        let checkpoint = CheckpointClaim::from_property(&property).unwrap();
+        assert_eq!(checkpoint.block_number, 5);
+        assert_eq!(checkpoint.range, Range::new(0, 100));
+        assert!(
+            Delegation::new(Address::zero(), Address::zero(), Range::new(90, 110))
+                .is_affected_by(&checkpoint)
+        );
+        assert!(
+            !Delegation::new(Address::zero(), Address::zero(), Range::new(100, 110))
+                .is_affected_by(&checkpoint)
+        );
+        assert!(CheckpointClaim::from_property(&DeciderManager::not_decider(property)).is_none());
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let decider: PropertyExecutor<CoreDbMemoryImpl> =
+            PropertyExecutor::new(PropertyExecuterOptions {
+                is_aggregator: false,
+                db_name: "test".to_string(),
+            });
+        let db = WatchtowerDb::new(decider.get_db());
+        let delegation = Delegation::new(Address::zero(), Address::zero(), Range::new(0, 10));
+        db.put_delegation(&delegation).unwrap();
+        assert_eq!(db.get_delegations().unwrap(), vec![delegation]);
+
+        let watchtower = Watchtower::new(&decider, WatchtowerPolicy::default());
+        let unrelated =
+            plasma_checkpoint_property(Integer::new(5), Address::zero(), Range::new(20, 30));
+        match watchtower.inspect_claim(&unrelated, 1).unwrap() {
+            Verdict::Unrelated => (),
+            verdict => panic!("unexpected verdict {:?}", verdict),
+        }
+
+        db.append_audit_entry(&AuditEntry::create(
+            &unrelated,
+            1,
+            AuditAction::Challenged,
+            H256::from([1; 32]),
+            "",
+        ))
+        .unwrap();
+        db.append_audit_entry(&AuditEntry::create(
+            &unrelated,
+            2,
+            AuditAction::Expired,
+            H256::zero(),
+            "dispute period passed",
+        ))
+        .unwrap();
+        let log = db.get_audit_log().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].get_action(), AuditAction::Challenged);
+        assert_eq!(log[1].get_claimed_at(), 2);
+        assert_eq!(log[1].get_reason(), "dispute period passed");
+        assert_eq!(db.update_last_seen_block(10).unwrap(), 10);
+        assert_eq!(db.update_last_seen_block(3).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_blocks_to_scan() {
+        let kvs = CoreDbMemoryImpl::open("test");
+        let db = WatchtowerDb::new(&kvs);
+        let policy = WatchtowerPolicy {
+            dispute_period: 20,
+            max_blocks_per_poll: 5,
+        };
+        // claims older than the dispute period are skipped
+        assert_eq!(db.get_blocks_to_scan(100, &policy).unwrap(), Some((80, 84)));
+        db.set_last_scanned_block(84).unwrap();
+        assert_eq!(db.get_blocks_to_scan(86, &policy).unwrap(), Some((85, 86)));
+        db.set_last_scanned_block(86).unwrap();
+        assert_eq!(db.get_blocks_to_scan(86, &policy).unwrap(), None);
+    }
+}
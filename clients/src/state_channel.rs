@@ -1,15 +1,82 @@
-use abi_utils::Encodable;
+pub mod command;
+pub mod error;
+pub mod hub;
+pub mod payment;
+
+pub use self::command::ChannelCommand;
+pub use self::error::{Error, ErrorKind};
+pub use self::hub::{Hub, Route, RouteRequest};
+pub use self::payment::{HashLock, PaymentChannel, PaymentMessage, PaymentState};
+
+use self::command::{CLOSE_ACCEPT, CLOSE_REQUEST, OPEN_ACCEPT, OPEN_REQUEST, UPDATE, UPDATE_ACK};
+use abi_utils::{Decodable, Encodable, Integer};
+use bincode::serialize;
 use bytes::Bytes;
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
 use ethsign::SecretKey;
-use ovm::db::{ChannelDb, Message, MessageDb, SignedByDb};
+use ovm::db::{Channel, ChannelDb, ChannelStatus, HashPreimageDb, Message, MessageDb, SignedByDb};
+use ovm::deciders::preimage_exists_decider::Verifier as PreimageVerifier;
+use ovm::deciders::signed_by_decider::{bytes_to_signature, hash};
 use ovm::deciders::SignVerifier;
-use ovm::property_executor::PropertyExecutor;
+use ovm::property_executor::{PropertyExecuterOptions, PropertyExecutor};
 use ovm::statements::create_state_channel_property;
-use ovm::types::{Decision, ImplicationProofElement, Property};
+use ovm::types::{Decision, Property, QuantifierResultItem};
+use plasma_db::prelude::*;
 use plasma_db::traits::db::DatabaseTrait;
-use plasma_db::traits::kvs::KeyValueStore;
+use plasma_db::traits::kvs::{Bucket, KeyValueStore};
+use pubsub_messaging::{
+    connect, Client as PubsubClient, ClientHandler, Message as PubsubMessage, Sender, WsMessage,
+};
+use std::sync::{Arc, Mutex};
+
+/// Prefix of pubsub topics used to deliver channel commands.
+pub const CHANNEL_TOPIC_PREFIX: &str = "channel:";
+
+/// Returns the pubsub topic where the commands to `address` are delivered.
+pub fn channel_topic(address: Address) -> String {
+    format!(
+        "{}{}",
+        CHANNEL_TOPIC_PREFIX,
+        hex::encode(address.as_bytes())
+    )
+}
+
+pub fn is_channel_topic(topic: &str) -> bool {
+    topic.starts_with(CHANNEL_TOPIC_PREFIX)
+}
+
+fn recover(signature: &Bytes, message: &Message) -> Option<Address> {
+    if signature.len() != 65 {
+        return None;
+    }
+    bytes_to_signature(signature)
+        .recover(hash(&Bytes::from(message.to_abi())).as_bytes())
+        .ok()
+        .map(|public| public.address().into())
+}
+
+/// Checks that the command is signed by its sender.
+pub fn verify_command(command: &ChannelCommand) -> Result<(), Error> {
+    if recover(&command.signature, &command.message) != Some(command.from) {
+        return Err(Error::from(ErrorKind::InvalidSignature));
+    }
+    Ok(())
+}
+
+/// Maximum length of channel ids. Longer ids are rejected.
+pub const MAX_CHANNEL_ID_LENGTH: usize = 32;
+
+fn check_channel_id(channel_id: &Bytes) -> Result<(), Error> {
+    if channel_id.is_empty() || channel_id.len() > MAX_CHANNEL_ID_LENGTH {
+        return Err(Error::from(ErrorKind::InvalidChannelId));
+    }
+    Ok(())
+}
 
+/// Participant of two party state channels.
+/// Every message is signed by both participants before the nonce of the channel advances.
+/// Messages and signatures of each channel are stored in the channel's own bucket of the database
+/// so that exit claims of a channel aren't affected by the other channels.
 pub struct StateChannel<KVS: KeyValueStore + DatabaseTrait> {
     db: KVS,
     secret_key: SecretKey,
@@ -17,92 +84,618 @@ pub struct StateChannel<KVS: KeyValueStore + DatabaseTrait> {
 }
 
 impl<KVS: KeyValueStore + DatabaseTrait> StateChannel<KVS> {
-    pub fn new(private_key: &str) -> Self {
-        let raw_key = hex::decode(private_key).unwrap();
-        let secret_key = SecretKey::from_raw(&raw_key).unwrap();
+    /// Opens the database under `db_name` namespaced by my address.
+    pub fn new(db_name: &str, private_key: &str) -> Result<Self, Error> {
+        let raw_key = hex::decode(private_key).map_err(|_| Error::from(ErrorKind::InvalidKey))?;
+        let secret_key =
+            SecretKey::from_raw(&raw_key).map_err(|_| Error::from(ErrorKind::InvalidKey))?;
         let my_address: Address = secret_key.public().address().into();
-        Self {
-            db: KVS::open("test"),
+        Ok(Self {
+            db: KVS::open(&format!(
+                "{}-{}-channels",
+                db_name,
+                hex::encode(my_address.as_bytes())
+            )),
             secret_key,
             my_address,
+        })
+    }
+
+    pub fn get_my_address(&self) -> Address {
+        self.my_address
+    }
+
+    /// Returns the decider holding messages and signatures of the channel.
+    /// Its stores are buckets keyed by the hash of channel id, so ids of any content can't collide.
+    fn get_decider(&self, channel_id: &Bytes) -> PropertyExecutor<Bucket> {
+        let db = &self.db;
+        let bucket = move |name: &[u8]| {
+            let mut prefix = b"channel_witnesses".to_vec();
+            prefix.extend_from_slice(hash(channel_id).as_bytes());
+            prefix.extend_from_slice(name);
+            db.bucket(&Bytes::from(prefix).into())
+        };
+        PropertyExecutor::from_stores(
+            bucket(b"kvs"),
+            bucket(b"range"),
+            PropertyExecuterOptions::default(),
+        )
+    }
+
+    /// Lists all channels including closed and exited ones.
+    pub fn get_channels(&self) -> Vec<Channel> {
+        let channel_db: ChannelDb<KVS> = (&self.db).into();
+        channel_db.get_all_channels()
+    }
+
+    pub fn get_channel(&self, channel_id: &Bytes) -> Result<Channel, Error> {
+        let channel_db: ChannelDb<KVS> = (&self.db).into();
+        channel_db
+            .get_channel(channel_id)?
+            .ok_or_else(|| Error::from(ErrorKind::ChannelNotFound))
+    }
+
+    /// Returns the latest message signed by both participants.
+    pub fn get_latest_message(&self, channel_id: &Bytes) -> Result<Message, Error> {
+        let channel = self.get_channel(channel_id)?;
+        self.get_message(channel_id, channel.nonce)
+            .ok_or_else(|| Error::from(ErrorKind::ChannelNotFound))
+    }
+
+    fn get_message(&self, channel_id: &Bytes, nonce: Integer) -> Option<Message> {
+        let decider = self.get_decider(channel_id);
+        let message_db: MessageDb<Bucket> = decider.get_db().into();
+        message_db.get_message_by_channel_id_and_nonce(channel_id.clone(), nonce)
+    }
+
+    fn put_channel(&self, channel: &Channel) -> Result<(), Error> {
+        let channel_db: ChannelDb<KVS> = (&self.db).into();
+        channel_db.put_channel(channel).map_err(Into::into)
+    }
+
+    /// Signs the message without storing it.
+    pub(crate) fn sign(&self, message: &Message) -> Bytes {
+        SignVerifier::sign(&self.secret_key, &Bytes::from(message.to_abi()))
+    }
+
+    /// Stores the preimage as the witness of hash locks in the channel.
+    pub fn store_preimage(&self, channel_id: &Bytes, preimage: &Bytes) -> Result<(), Error> {
+        let decider = self.get_decider(channel_id);
+        let db = HashPreimageDb::new(decider.get_db());
+        db.store_witness(PreimageVerifier::static_hash(preimage), preimage)
+            .map_err(Into::into)
+    }
+
+    pub fn get_preimage(&self, channel_id: &Bytes, hash: H256) -> Result<Option<Bytes>, Error> {
+        let decider = self.get_decider(channel_id);
+        let db = HashPreimageDb::new(decider.get_db());
+        if !db.has_witness(hash)? {
+            return Ok(None);
         }
+        Ok(Some(db.get_witness(hash)?.preimage))
     }
-    /// Called handling new message through pubsub network
-    pub fn handle_message(&self, channel_message: &Message, signature: Bytes) -> Bytes {
-        let message = Bytes::from(channel_message.to_abi());
-        let counter_party = SignVerifier::recover(&signature, &message);
-        let db = SignedByDb::new(&self.db);
-        assert!(db.store_witness(counter_party, message, signature).is_ok());
-        self.sign_and_store_message(channel_message)
+
+    /// Decides the property with the witnesses stored in the channel.
+    pub fn decide(&self, channel_id: &Bytes, property: &Property) -> Result<Decision, Error> {
+        self.get_decider(channel_id)
+            .decide(property)
+            .map_err(Into::into)
     }
 
-    fn sign_and_store_message(&self, channel_message: &Message) -> Bytes {
-        let message_db: MessageDb<KVS> = (&self.db).into();
-        assert!(message_db.store_message(channel_message).is_ok());
+    /// Decides the property at unix time `now`, which timeouts in it are compared with.
+    pub fn decide_at(
+        &self,
+        channel_id: &Bytes,
+        property: &Property,
+        now: u64,
+    ) -> Result<Decision, Error> {
+        let decider = self.get_decider(channel_id);
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(now)),
+        );
+        decider.decide(property).map_err(Into::into)
+    }
+
+    fn sign_and_store_message(&self, channel_message: &Message) -> Result<Bytes, Error> {
+        let decider = self.get_decider(&channel_message.channel_id);
+        let message_db: MessageDb<Bucket> = decider.get_db().into();
+        message_db.store_message(channel_message)?;
         let message = Bytes::from(channel_message.to_abi());
         let signature = SignVerifier::sign(&self.secret_key, &message);
-        let db = SignedByDb::new(&self.db);
-        assert!(db
-            .store_witness(self.my_address, message, signature.clone())
-            .is_ok());
-        signature.clone()
+        let db = SignedByDb::new(decider.get_db());
+        db.store_witness(self.my_address, message, signature.clone())?;
+        Ok(signature)
     }
 
-    /// Gets exit claim
-    pub fn exit_claim(
+    fn store_counter_party_signature(&self, command: &ChannelCommand) -> Result<(), Error> {
+        let decider = self.get_decider(&command.message.channel_id);
+        let db = SignedByDb::new(decider.get_db());
+        db.store_witness(
+            command.from,
+            Bytes::from(command.message.to_abi()),
+            command.signature.clone(),
+        )
+        .map_err(Into::into)
+    }
+
+    pub(crate) fn create_command(
+        &self,
+        command_type: u64,
+        message: Message,
+        signature: Bytes,
+    ) -> ChannelCommand {
+        ChannelCommand::new(command_type, self.my_address, message, signature)
+    }
+
+    /// Fails with PendingUpdate if my proposal for the next nonce isn't signed by the counter party yet.
+    fn check_no_pending(&self, channel: &Channel) -> Result<(), Error> {
+        if self
+            .get_message(&channel.channel_id, Integer(channel.nonce.0 + 1))
+            .is_some()
+        {
+            return Err(Error::from(ErrorKind::PendingUpdate));
+        }
+        Ok(())
+    }
+
+    /// Opens a channel with initial state. Returned command should be sent to the counter party.
+    pub fn open(
         &self,
         channel_id: &Bytes,
-        my_address: Address,
         counter_party: Address,
-    ) -> Vec<u8> {
-        self.get_exit_claim(channel_id, my_address, counter_party)
-            .to_abi()
+        initial_state: Bytes,
+    ) -> Result<ChannelCommand, Error> {
+        check_channel_id(channel_id)?;
+        let channel_db: ChannelDb<KVS> = (&self.db).into();
+        if channel_db.get_channel(channel_id)?.is_some() {
+            return Err(Error::from(ErrorKind::ChannelAlreadyExists));
+        }
+        let message = Message::new(channel_id.clone(), Integer(0), initial_state);
+        let signature = self.sign_and_store_message(&message)?;
+        self.put_channel(&Channel::new(
+            channel_id.clone(),
+            self.my_address,
+            counter_party,
+        ))?;
+        Ok(self.create_command(OPEN_REQUEST, message, signature))
     }
 
-    /// Called when channel is opened
-    pub fn handle_opening_channel(&self, channel_message: &Message) {
-        let message_db: MessageDb<KVS> = (&self.db).into();
-        assert!(message_db.store_message(channel_message).is_ok());
+    /// Proposes next state of the channel.
+    pub fn update(&self, channel_id: &Bytes, state: Bytes) -> Result<ChannelCommand, Error> {
+        let channel = self.get_channel(channel_id)?;
+        if channel.get_status() != ChannelStatus::Open {
+            return Err(Error::from(ErrorKind::InvalidStatus));
+        }
+        self.check_no_pending(&channel)?;
+        let message = Message::new(channel_id.clone(), Integer(channel.nonce.0 + 1), state);
+        let signature = self.sign_and_store_message(&message)?;
+        Ok(self.create_command(UPDATE, message, signature))
     }
 
-    /// Called when someone exit state
-    pub fn handle_exit(
+    /// Proposes to close the channel cooperatively with the latest state.
+    pub fn close(&self, channel_id: &Bytes) -> Result<ChannelCommand, Error> {
+        let mut channel = self.get_channel(channel_id)?;
+        if channel.get_status() != ChannelStatus::Open {
+            return Err(Error::from(ErrorKind::InvalidStatus));
+        }
+        self.check_no_pending(&channel)?;
+        let latest_message = self.get_latest_message(channel_id)?;
+        let message = Message::new(
+            channel_id.clone(),
+            Integer(channel.nonce.0 + 1),
+            latest_message.get_message().clone(),
+        );
+        let signature = self.sign_and_store_message(&message)?;
+        channel.set_status(ChannelStatus::Closing);
+        self.put_channel(&channel)?;
+        Ok(self.create_command(CLOSE_REQUEST, message, signature))
+    }
+
+    /// Handles a command from the counter party and returns the reply to send back if any.
+    pub fn handle_command(
         &self,
-        channel_id: &Bytes,
-        claim: &Property,
-    ) -> Vec<ImplicationProofElement> {
-        let decider: PropertyExecutor<KVS> = Default::default();
-        let decision: Decision = decider.decide(&claim).unwrap();
+        command: &ChannelCommand,
+    ) -> Result<Option<ChannelCommand>, Error> {
+        verify_command(command)?;
+        let message = &command.message;
+        let channel_id = &message.channel_id;
+        check_channel_id(channel_id)?;
+        match command.command_type.0 {
+            OPEN_REQUEST => {
+                let channel_db: ChannelDb<KVS> = (&self.db).into();
+                if channel_db.get_channel(channel_id)?.is_some() {
+                    return Err(Error::from(ErrorKind::ChannelAlreadyExists));
+                }
+                if message.nonce != Integer(0) {
+                    return Err(Error::from(ErrorKind::InvalidNonce));
+                }
+                self.store_counter_party_signature(command)?;
+                let signature = self.sign_and_store_message(message)?;
+                let mut channel = Channel::new(channel_id.clone(), self.my_address, command.from);
+                channel.set_status(ChannelStatus::Open);
+                self.put_channel(&channel)?;
+                Ok(Some(self.create_command(
+                    OPEN_ACCEPT,
+                    message.clone(),
+                    signature,
+                )))
+            }
+            OPEN_ACCEPT => {
+                let mut channel =
+                    self.get_counter_party_channel(command, ChannelStatus::Opening)?;
+                if message.nonce != channel.nonce {
+                    return Err(Error::from(ErrorKind::InvalidNonce));
+                }
+                if self.get_message(channel_id, message.nonce).as_ref() != Some(message) {
+                    return Err(Error::from(ErrorKind::UnexpectedMessage));
+                }
+                self.store_counter_party_signature(command)?;
+                channel.set_status(ChannelStatus::Open);
+                self.put_channel(&channel)?;
+                Ok(None)
+            }
+            UPDATE | CLOSE_REQUEST => {
+                let mut channel = self.get_counter_party_channel(command, ChannelStatus::Open)?;
+                if message.nonce != Integer(channel.nonce.0 + 1) {
+                    return Err(Error::from(ErrorKind::InvalidNonce));
+                }
+                self.check_no_pending(&channel)?;
+                if command.command_type.0 == CLOSE_REQUEST
+                    && self.get_latest_message(channel_id)?.get_message() != message.get_message()
+                {
+                    return Err(Error::from(ErrorKind::UnexpectedMessage));
+                }
+                self.store_counter_party_signature(command)?;
+                let signature = self.sign_and_store_message(message)?;
+                channel.nonce = message.nonce;
+                if command.command_type.0 == CLOSE_REQUEST {
+                    channel.set_status(ChannelStatus::Closed);
+                }
+                self.put_channel(&channel)?;
+                Ok(Some(self.create_command(
+                    command.get_reply_type().unwrap(),
+                    message.clone(),
+                    signature,
+                )))
+            }
+            UPDATE_ACK | CLOSE_ACCEPT => {
+                let status = if command.command_type.0 == UPDATE_ACK {
+                    ChannelStatus::Open
+                } else {
+                    ChannelStatus::Closing
+                };
+                let mut channel = self.get_counter_party_channel(command, status)?;
+                if message.nonce != Integer(channel.nonce.0 + 1) {
+                    return Err(Error::from(ErrorKind::InvalidNonce));
+                }
+                if self.get_message(channel_id, message.nonce).as_ref() != Some(message) {
+                    return Err(Error::from(ErrorKind::UnexpectedMessage));
+                }
+                self.store_counter_party_signature(command)?;
+                channel.nonce = message.nonce;
+                if command.command_type.0 == CLOSE_ACCEPT {
+                    channel.set_status(ChannelStatus::Closed);
+                }
+                self.put_channel(&channel)?;
+                Ok(None)
+            }
+            _ => Err(Error::from(ErrorKind::UnknownCommand)),
+        }
+    }
+
+    /// Gets the channel of the command checking that it comes from the counter party.
+    fn get_counter_party_channel(
+        &self,
+        command: &ChannelCommand,
+        status: ChannelStatus,
+    ) -> Result<Channel, Error> {
+        let channel = self.get_channel(&command.message.channel_id)?;
+        if channel.counter_party != command.from {
+            return Err(Error::from(ErrorKind::InvalidSignature));
+        }
+        if channel.get_status() != status {
+            return Err(Error::from(ErrorKind::InvalidStatus));
+        }
+        Ok(channel)
+    }
+
+    /// Gets exit claim
+    pub fn exit_claim(&self, channel_id: &Bytes) -> Result<Vec<u8>, Error> {
+        Ok(self.get_exit_claim(channel_id)?.to_abi())
+    }
+
+    /// Starts unilateral exit with the latest state. Returned claim should be submitted.
+    pub fn start_exit(&self, channel_id: &Bytes) -> Result<Property, Error> {
+        let mut channel = self.get_channel(channel_id)?;
+        match channel.get_status() {
+            ChannelStatus::Open | ChannelStatus::Closing | ChannelStatus::Closed => {}
+            _ => return Err(Error::from(ErrorKind::InvalidStatus)),
+        }
+        let claim = self.get_exit_claim(channel_id)?;
+        channel.set_status(ChannelStatus::Exiting);
+        self.put_channel(&channel)?;
+        Ok(claim)
+    }
+
+    /// Called when my exit is finalized.
+    pub fn finalize_exit(&self, channel_id: &Bytes) -> Result<(), Error> {
+        let mut channel = self.get_channel(channel_id)?;
+        if channel.get_status() != ChannelStatus::Exiting {
+            return Err(Error::from(ErrorKind::InvalidStatus));
+        }
+        channel.set_status(ChannelStatus::Exited);
+        self.put_channel(&channel)
+    }
+
+    /// Called when the counter party exits.
+    /// A false outcome means the claim is stale and should be challenged.
+    pub fn handle_exit(&self, channel_id: &Bytes, claim: &Property) -> Result<Decision, Error> {
+        let decision: Decision = self.get_decider(channel_id).decide(claim)?;
         if decision.get_outcome() {
             let channel_db: ChannelDb<KVS> = (&self.db).into();
-            assert!(channel_db.mark_exited(channel_id).is_ok());
+            if !channel_db.mark_exited(channel_id)? {
+                return Err(Error::from(ErrorKind::ChannelNotFound));
+            }
         }
-        decision.get_implication_proof().clone()
+        Ok(decision)
     }
 
-    pub fn check_claim(
-        &self,
-        channel_id: &Bytes,
-        my_address: Address,
-        counter_party: Address,
-    ) -> Option<Decision> {
-        let property = self.get_exit_claim(channel_id, my_address, counter_party);
-        let decider: PropertyExecutor<KVS> = Default::default();
-        decider.decide(&property).ok()
+    /// Decides my exit claim with the signatures I have.
+    pub fn check_claim(&self, channel_id: &Bytes) -> Result<Decision, Error> {
+        let property = self.get_exit_claim(channel_id)?;
+        self.get_decider(channel_id)
+            .decide(&property)
+            .map_err(Into::into)
+    }
+
+    fn get_exit_claim(&self, channel_id: &Bytes) -> Result<Property, Error> {
+        let channel = self.get_channel(channel_id)?;
+        let latest_message = self.get_latest_message(channel_id)?;
+        Ok(create_state_channel_property(
+            self.my_address,
+            channel.counter_party,
+            latest_message,
+        ))
     }
+}
 
-    fn get_exit_claim(
+/// Sends channel commands through pubsub and replies to the commands addressed to me.
+#[derive(Clone)]
+pub struct StateChannelController {
+    pub state_channel: Arc<Mutex<StateChannel<CoreDbLevelDbImpl>>>,
+    pub pubsub_client: Option<PubsubClient>,
+}
+
+impl StateChannelController {
+    pub fn new(state_channel: StateChannel<CoreDbLevelDbImpl>) -> Self {
+        Self {
+            state_channel: Arc::new(Mutex::new(state_channel)),
+            pubsub_client: None,
+        }
+    }
+
+    /// Connects to the pubsub server and returns the controller which can send commands.
+    pub fn connect(&self, host: String) -> Result<Self, Error> {
+        let pubsub_client =
+            connect(host, self.clone()).map_err(|_| Error::from(ErrorKind::NotConnected))?;
+        Ok(Self {
+            state_channel: self.state_channel.clone(),
+            pubsub_client: Some(pubsub_client),
+        })
+    }
+
+    fn send(&self, to: Address, command: &ChannelCommand) -> Result<(), Error> {
+        let mut pubsub_client = self
+            .pubsub_client
+            .clone()
+            .ok_or_else(|| Error::from(ErrorKind::NotConnected))?;
+        pubsub_client.send(PubsubMessage::new(channel_topic(to), command.to_abi()));
+        Ok(())
+    }
+
+    pub fn open(
         &self,
         channel_id: &Bytes,
-        my_address: Address,
         counter_party: Address,
-    ) -> Property {
-        let message_db: MessageDb<KVS> = (&self.db).into();
-        let most_recent_message = message_db.get_most_recent_message(channel_id);
-        if let Some(most_recent_message) = most_recent_message {
-            create_state_channel_property(my_address, counter_party, most_recent_message)
-        } else {
-            panic!("There are no messages!!")
+        initial_state: Bytes,
+    ) -> Result<(), Error> {
+        let command =
+            self.state_channel
+                .lock()
+                .unwrap()
+                .open(channel_id, counter_party, initial_state)?;
+        self.send(counter_party, &command)
+    }
+
+    pub fn update(&self, channel_id: &Bytes, state: Bytes) -> Result<(), Error> {
+        let state_channel = self.state_channel.lock().unwrap();
+        let command = state_channel.update(channel_id, state)?;
+        self.send(
+            state_channel.get_channel(channel_id)?.counter_party,
+            &command,
+        )
+    }
+
+    pub fn close(&self, channel_id: &Bytes) -> Result<(), Error> {
+        let state_channel = self.state_channel.lock().unwrap();
+        let command = state_channel.close(channel_id)?;
+        self.send(
+            state_channel.get_channel(channel_id)?.counter_party,
+            &command,
+        )
+    }
+}
+
+impl ClientHandler for StateChannelController {
+    fn handle_message(&self, msg: PubsubMessage, sender: Sender) {
+        let state_channel = self.state_channel.lock().unwrap();
+        if msg.to != channel_topic(state_channel.get_my_address()) {
+            return;
+        }
+        let command = match ChannelCommand::from_abi(&msg.message) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("invalid channel command: {:?}", e);
+                return;
+            }
+        };
+        match state_channel.handle_command(&command) {
+            Ok(Some(reply)) => {
+                let message = PubsubMessage::new(channel_topic(command.from), reply.to_abi());
+                let _ = sender.send(WsMessage::Binary(serialize(&message).unwrap()));
+            }
+            Ok(None) => {}
+            Err(e) => println!("failed to handle channel command: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+    const BOB_KEY: &str = "ae6ae8e5ccbfb04590405997ee2d52d2b330726137b875053c36d94e974d162f";
+
+    fn deliver(
+        to: &StateChannel<CoreDbMemoryImpl>,
+        from: &StateChannel<CoreDbMemoryImpl>,
+        command: ChannelCommand,
+    ) {
+        let reply = to.handle_command(&command).unwrap().unwrap();
+        assert_eq!(from.handle_command(&reply).unwrap(), None);
+    }
+
+    #[test]
+    fn test_channel_lifecycle() {
+        let alice: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", ALICE_KEY).unwrap();
+        let bob: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", BOB_KEY).unwrap();
+        let channel_id = Bytes::from("channel");
+        assert_eq!(
+            alice
+                .open(
+                    &Bytes::from(&[0; MAX_CHANNEL_ID_LENGTH + 1][..]),
+                    bob.get_my_address(),
+                    Bytes::from("0")
+                )
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::InvalidChannelId
+        );
+        let open = alice
+            .open(&channel_id, bob.get_my_address(), Bytes::from("0"))
+            .unwrap();
+        assert_eq!(
+            alice.get_channel(&channel_id).unwrap().get_status(),
+            ChannelStatus::Opening
+        );
+        deliver(&bob, &alice, open);
+        assert_eq!(
+            alice.get_channel(&channel_id).unwrap().get_status(),
+            ChannelStatus::Open
+        );
+
+        let update = alice.update(&channel_id, Bytes::from("1")).unwrap();
+        assert_eq!(
+            alice
+                .update(&channel_id, Bytes::from("2"))
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::PendingUpdate
+        );
+        // a command signed by someone else is rejected
+        let mut forged = update.clone();
+        forged.from = Address::random();
+        assert_eq!(
+            bob.handle_command(&forged).unwrap_err().kind(),
+            &ErrorKind::InvalidSignature
+        );
+        deliver(&bob, &alice, update.clone());
+        // replayed update has stale nonce
+        assert_eq!(
+            bob.handle_command(&update).unwrap_err().kind(),
+            &ErrorKind::InvalidNonce
+        );
+
+        let close = bob.close(&channel_id).unwrap();
+        deliver(&alice, &bob, close);
+        for state_channel in [&alice, &bob].iter() {
+            let channel = state_channel.get_channel(&channel_id).unwrap();
+            assert_eq!(channel.get_status(), ChannelStatus::Closed);
+            assert_eq!(channel.nonce, Integer(2));
+            assert_eq!(
+                state_channel
+                    .get_latest_message(&channel_id)
+                    .unwrap()
+                    .get_message(),
+                &Bytes::from("1")
+            );
+        }
+    }
+
+    #[test]
+    fn test_exit_dispute() {
+        let alice: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", ALICE_KEY).unwrap();
+        let bob: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", BOB_KEY).unwrap();
+        let channel_id = Bytes::from("channel");
+        let open = alice
+            .open(&channel_id, bob.get_my_address(), Bytes::from("0"))
+            .unwrap();
+        deliver(&bob, &alice, open);
+        let stale_message = alice.get_latest_message(&channel_id).unwrap();
+        let update = alice.update(&channel_id, Bytes::from("1")).unwrap();
+        deliver(&bob, &alice, update);
+        assert!(alice.check_claim(&channel_id).unwrap().get_outcome());
+
+        // bob has alice's signature on a newer message
+        let stale_claim = create_state_channel_property(
+            alice.get_my_address(),
+            bob.get_my_address(),
+            stale_message,
+        );
+        assert!(!bob
+            .handle_exit(&channel_id, &stale_claim)
+            .unwrap()
+            .get_outcome());
+        assert!(!bob.get_channel(&channel_id).unwrap().is_exited());
+
+        let claim = alice.start_exit(&channel_id).unwrap();
+        assert_eq!(
+            alice
+                .update(&channel_id, Bytes::from("2"))
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::InvalidStatus
+        );
+        assert!(bob.handle_exit(&channel_id, &claim).unwrap().get_outcome());
+        assert!(bob.get_channel(&channel_id).unwrap().is_exited());
+        assert!(alice.finalize_exit(&channel_id).is_ok());
+        assert!(alice.get_channel(&channel_id).unwrap().is_exited());
+    }
+
+    #[test]
+    fn test_channels_are_isolated() {
+        let alice: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", ALICE_KEY).unwrap();
+        let bob: StateChannel<CoreDbMemoryImpl> = StateChannel::new("test", BOB_KEY).unwrap();
+        let channel_a = Bytes::from("channel_a");
+        let channel_b = Bytes::from("channel_b");
+        for channel_id in [&channel_a, &channel_b].iter() {
+            let open = alice
+                .open(channel_id, bob.get_my_address(), Bytes::from("0"))
+                .unwrap();
+            deliver(&bob, &alice, open);
+        }
+        for state in ["1", "2"].iter() {
+            let update = alice.update(&channel_b, Bytes::from(*state)).unwrap();
+            deliver(&bob, &alice, update);
         }
+        assert_eq!(alice.get_channels().len(), 2);
+        assert_eq!(bob.get_channels().len(), 2);
+        // messages with higher nonce in channel b don't invalidate the claim of channel a
+        assert!(alice.check_claim(&channel_a).unwrap().get_outcome());
+        let claim = alice.start_exit(&channel_a).unwrap();
+        assert!(bob.handle_exit(&channel_a, &claim).unwrap().get_outcome());
+        assert!(!bob.get_channel(&channel_b).unwrap().is_exited());
     }
 }
@@ -0,0 +1,46 @@
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::Address;
+use ovm::db::Message;
+
+pub const OPEN_REQUEST: u64 = 0;
+pub const OPEN_ACCEPT: u64 = 1;
+pub const UPDATE: u64 = 2;
+pub const UPDATE_ACK: u64 = 3;
+pub const CLOSE_REQUEST: u64 = 4;
+pub const CLOSE_ACCEPT: u64 = 5;
+pub const ROUTE_REQUEST: u64 = 6;
+pub const ROUTE_RESPONSE: u64 = 7;
+
+/// Channel message signed by `from` and sent to the counter party.
+/// Requests are answered by the counter party signing the same message.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct ChannelCommand {
+    pub command_type: Integer,
+    pub from: Address,
+    pub message: Message,
+    pub signature: Bytes,
+}
+
+impl ChannelCommand {
+    pub fn new(command_type: u64, from: Address, message: Message, signature: Bytes) -> Self {
+        Self {
+            command_type: Integer(command_type),
+            from,
+            message,
+            signature,
+        }
+    }
+
+    /// Returns the type of the command answering this one. None if it isn't a request.
+    pub fn get_reply_type(&self) -> Option<u64> {
+        match self.command_type.0 {
+            OPEN_REQUEST => Some(OPEN_ACCEPT),
+            UPDATE => Some(UPDATE_ACK),
+            CLOSE_REQUEST => Some(CLOSE_ACCEPT),
+            _ => None,
+        }
+    }
+}
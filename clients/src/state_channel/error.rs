@@ -0,0 +1,126 @@
+use abi_utils::Error as AbiError;
+use ethabi::Error as AbiDecodeError;
+use failure::{Backtrace, Context, Fail};
+use ovm::error::Error as OvmError;
+use plasma_db::error::Error as PlasmaDbError;
+use std::fmt;
+use std::fmt::Display;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum ErrorKind {
+    #[fail(display = "ABI Decode error")]
+    AbiDecode,
+    #[fail(display = "ABI error")]
+    AbiError,
+    #[fail(display = "OVM Error")]
+    OvmError,
+    #[fail(display = "Db Error")]
+    PlasmaDbError,
+    #[fail(display = "Invalid Key")]
+    InvalidKey,
+    #[fail(display = "Invalid Signature")]
+    InvalidSignature,
+    #[fail(display = "Invalid Nonce")]
+    InvalidNonce,
+    #[fail(display = "Invalid Channel Status")]
+    InvalidStatus,
+    #[fail(display = "Channel Not Found")]
+    ChannelNotFound,
+    #[fail(display = "Channel Already Exists")]
+    ChannelAlreadyExists,
+    #[fail(display = "Unexpected Message")]
+    UnexpectedMessage,
+    #[fail(display = "Pending Update")]
+    PendingUpdate,
+    #[fail(display = "Invalid Transition")]
+    InvalidTransition,
+    #[fail(display = "Insufficient Balance")]
+    InsufficientBalance,
+    #[fail(display = "Lock Not Found")]
+    LockNotFound,
+    #[fail(display = "No Route")]
+    NoRoute,
+    #[fail(display = "Unknown Command")]
+    UnknownCommand,
+    #[fail(display = "Not Connected")]
+    NotConnected,
+    #[fail(display = "Invalid Channel Id")]
+    InvalidChannelId,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error {
+    pub fn new(inner: Context<ErrorKind>) -> Error {
+        Error { inner }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<ErrorKind>> for Error {
+    fn from(inner: Context<ErrorKind>) -> Error {
+        Error { inner }
+    }
+}
+
+impl From<AbiDecodeError> for Error {
+    fn from(_error: AbiDecodeError) -> Error {
+        Error {
+            inner: Context::from(ErrorKind::AbiDecode),
+        }
+    }
+}
+
+impl From<AbiError> for Error {
+    fn from(_error: AbiError) -> Error {
+        Error {
+            inner: Context::from(ErrorKind::AbiError),
+        }
+    }
+}
+
+impl From<OvmError> for Error {
+    fn from(error: OvmError) -> Error {
+        Error {
+            inner: error.context(ErrorKind::OvmError),
+        }
+    }
+}
+
+impl From<PlasmaDbError> for Error {
+    fn from(error: PlasmaDbError) -> Error {
+        Error {
+            inner: error.context(ErrorKind::PlasmaDbError),
+        }
+    }
+}
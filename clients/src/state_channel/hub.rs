@@ -0,0 +1,462 @@
+use super::command::{ChannelCommand, ROUTE_REQUEST, ROUTE_RESPONSE, UPDATE};
+use super::error::{Error, ErrorKind};
+use super::payment::{HashLock, PaymentChannel, PaymentState};
+use super::{verify_command, StateChannel};
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ovm::db::{Channel, ChannelStatus, Message};
+use plasma_db::traits::db::DatabaseTrait;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Seconds the outgoing lock of the hub expires before the incoming lock,
+/// so that the hub can unlock the incoming lock with the preimage revealed to it.
+pub const TIMEOUT_DELTA: u64 = 600;
+
+/// Route of a payment from the sender to the destination through the hub.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct Route {
+    pub hub: Address,
+    /// channel between the sender and the hub
+    pub incoming_channel_id: Bytes,
+    /// channel between the hub and the destination
+    pub outgoing_channel_id: Bytes,
+    /// amount the hub can forward to the destination
+    pub capacity: Integer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct RouteRequest {
+    pub destination: Address,
+    pub amount: Integer,
+}
+
+/// Creates the command asking the hub for a route to the destination.
+pub fn create_route_request<KVS: KeyValueStore + DatabaseTrait>(
+    state_channel: &StateChannel<KVS>,
+    destination: Address,
+    amount: u64,
+) -> ChannelCommand {
+    let request = RouteRequest {
+        destination,
+        amount: Integer(amount),
+    };
+    let message = Message::new(Bytes::new(), Integer(0), Bytes::from(request.to_abi()));
+    let signature = state_channel.sign(&message);
+    state_channel.create_command(ROUTE_REQUEST, message, signature)
+}
+
+/// Gets the route from the response of the hub.
+pub fn get_route(command: &ChannelCommand) -> Result<Route, Error> {
+    if command.command_type.0 != ROUTE_RESPONSE {
+        return Err(Error::from(ErrorKind::UnknownCommand));
+    }
+    verify_command(command)?;
+    let route = Route::from_abi(command.message.get_message())?;
+    if route.hub != command.from {
+        return Err(Error::from(ErrorKind::InvalidSignature));
+    }
+    Ok(route)
+}
+
+/// Intermediary of payments between participants having channels with it.
+/// A payment from A to C is locked by the same hash in the channels A-hub and hub-C,
+/// so the hub gets paid by A only after C reveals the preimage to receive the payment.
+pub struct Hub<KVS: KeyValueStore + DatabaseTrait> {
+    payment_channel: PaymentChannel<KVS>,
+}
+
+impl<KVS: KeyValueStore + DatabaseTrait> Hub<KVS> {
+    pub fn new(payment_channel: PaymentChannel<KVS>) -> Self {
+        Self { payment_channel }
+    }
+
+    pub fn get_payment_channel(&self) -> &PaymentChannel<KVS> {
+        &self.payment_channel
+    }
+
+    pub fn get_my_address(&self) -> Address {
+        self.payment_channel.get_my_address()
+    }
+
+    fn get_open_channels(&self) -> Vec<(Channel, PaymentState)> {
+        self.payment_channel
+            .get_state_channel()
+            .get_channels()
+            .into_iter()
+            .filter(|c| c.get_status() == ChannelStatus::Open)
+            .filter_map(|c| {
+                let state = self.payment_channel.get_state(&c.channel_id).ok()?;
+                Some((c, state))
+            })
+            .collect()
+    }
+
+    fn get_open_channel_with(&self, counter_party: Address) -> Option<(Channel, PaymentState)> {
+        self.get_open_channels()
+            .into_iter()
+            .find(|(c, _)| c.counter_party == counter_party)
+    }
+
+    /// Finds the channels to route the payment from sender to destination.
+    pub fn find_route(
+        &self,
+        sender: Address,
+        destination: Address,
+        amount: u64,
+    ) -> Result<Route, Error> {
+        let (incoming, incoming_state) = self
+            .get_open_channel_with(sender)
+            .ok_or_else(|| Error::from(ErrorKind::NoRoute))?;
+        let (outgoing, outgoing_state) = self
+            .get_open_channel_with(destination)
+            .ok_or_else(|| Error::from(ErrorKind::NoRoute))?;
+        let capacity = outgoing_state
+            .get_balance(self.get_my_address())
+            .unwrap_or(0);
+        if capacity < amount || incoming_state.get_balance(sender).unwrap_or(0) < amount {
+            return Err(Error::from(ErrorKind::NoRoute));
+        }
+        Ok(Route {
+            hub: self.get_my_address(),
+            incoming_channel_id: incoming.channel_id,
+            outgoing_channel_id: outgoing.channel_id,
+            capacity: Integer(capacity),
+        })
+    }
+
+    /// Handles a command sent to the hub.
+    /// Returns the commands to send with their receivers.
+    pub fn handle_command(
+        &self,
+        command: &ChannelCommand,
+        now: u64,
+    ) -> Result<Vec<(Address, ChannelCommand)>, Error> {
+        if command.command_type.0 == ROUTE_REQUEST {
+            verify_command(command)?;
+            let request = RouteRequest::from_abi(command.message.get_message())?;
+            let route = self.find_route(command.from, request.destination, request.amount.0)?;
+            let message = Message::new(
+                route.incoming_channel_id.clone(),
+                Integer(0),
+                Bytes::from(route.to_abi()),
+            );
+            let state_channel = self.payment_channel.get_state_channel();
+            let signature = state_channel.sign(&message);
+            return Ok(vec![(
+                command.from,
+                state_channel.create_command(ROUTE_RESPONSE, message, signature),
+            )]);
+        }
+        let channel_id = &command.message.channel_id;
+        let prev_state = if command.command_type.0 == UPDATE {
+            Some(self.payment_channel.get_state(channel_id)?)
+        } else {
+            None
+        };
+        let mut commands: Vec<(Address, ChannelCommand)> = self
+            .payment_channel
+            .handle_command(command, now)?
+            .into_iter()
+            .map(|reply| (command.from, reply))
+            .collect();
+        if let Some(prev_state) = prev_state {
+            let state = self.payment_channel.get_state(channel_id)?;
+            commands.extend(self.handle_payment_update(channel_id, &prev_state, &state, now));
+        }
+        Ok(commands)
+    }
+
+    /// Forwards new incoming locks and settles incoming locks with the outgoing ones.
+    fn handle_payment_update(
+        &self,
+        channel_id: &Bytes,
+        prev_state: &PaymentState,
+        state: &PaymentState,
+        now: u64,
+    ) -> Vec<(Address, ChannelCommand)> {
+        let my_address = self.get_my_address();
+        let mut commands = vec![];
+        for lock in state.locks.iter() {
+            if prev_state.get_lock(lock.hash).is_some()
+                || lock.sender == my_address
+                || lock.destination == my_address
+            {
+                continue;
+            }
+            match self.forward(lock, now) {
+                Ok(forwarded) => commands.push(forwarded),
+                Err(e) => {
+                    println!("failed to forward lock {:?}: {:?}", lock.hash, e);
+                    match self.payment_channel.refund(channel_id, lock.hash, now) {
+                        Ok(refund) => commands.push((lock.sender, refund)),
+                        Err(e) => println!("failed to refund lock {:?}: {:?}", lock.hash, e),
+                    }
+                }
+            }
+        }
+        for lock in prev_state.locks.iter() {
+            if state.get_lock(lock.hash).is_some() || lock.sender != my_address {
+                continue;
+            }
+            // the outgoing lock is unlocked or refunded by the receiver
+            if let Some(settled) = self.settle_incoming(lock.hash, now) {
+                commands.push(settled);
+            }
+        }
+        commands
+    }
+
+    /// Locks the same amount to the destination with the shorter timeout.
+    fn forward(&self, lock: &HashLock, now: u64) -> Result<(Address, ChannelCommand), Error> {
+        if lock.timeout.0 < now + TIMEOUT_DELTA {
+            return Err(Error::from(ErrorKind::InvalidTransition));
+        }
+        let (outgoing, _) = self
+            .get_open_channel_with(lock.destination)
+            .ok_or_else(|| Error::from(ErrorKind::NoRoute))?;
+        let command = self.payment_channel.lock(
+            &outgoing.channel_id,
+            lock.hash,
+            lock.amount.0,
+            lock.timeout.0 - TIMEOUT_DELTA,
+            lock.destination,
+        )?;
+        Ok((lock.destination, command))
+    }
+
+    fn find_preimage(&self, hash: H256) -> Option<Bytes> {
+        let state_channel = self.payment_channel.get_state_channel();
+        state_channel
+            .get_channels()
+            .iter()
+            .filter_map(|c| state_channel.get_preimage(&c.channel_id, hash).ok())
+            .find_map(|preimage| preimage)
+    }
+
+    /// Unlocks the incoming lock of hash if the preimage is known, otherwise refunds it.
+    fn settle_incoming(&self, hash: H256, now: u64) -> Option<(Address, ChannelCommand)> {
+        let my_address = self.get_my_address();
+        let (channel, lock) =
+            self.get_open_channels()
+                .into_iter()
+                .find_map(|(channel, state)| {
+                    state
+                        .get_lock(hash)
+                        .filter(|l| l.sender != my_address)
+                        .cloned()
+                        .map(|l| (channel, l))
+                })?;
+        let result = match self.find_preimage(hash) {
+            Some(preimage) => self
+                .payment_channel
+                .unlock(&channel.channel_id, preimage, now),
+            None => self.payment_channel.refund(&channel.channel_id, hash, now),
+        };
+        match result {
+            Ok(command) => Some((lock.sender, command)),
+            Err(e) => {
+                println!("failed to settle lock {:?}: {:?}", hash, e);
+                None
+            }
+        }
+    }
+
+    /// Refunds expired outgoing locks and settles incoming locks whose outgoing lock is resolved.
+    /// Should be called periodically. At most one update per channel is proposed.
+    pub fn expire_locks(&self, now: u64) -> Vec<(Address, ChannelCommand)> {
+        let my_address = self.get_my_address();
+        let channels = self.get_open_channels();
+        let is_forwarded = |hash: H256| {
+            channels.iter().any(|(_, state)| {
+                state
+                    .get_lock(hash)
+                    .map(|l| l.sender == my_address)
+                    .unwrap_or(false)
+            })
+        };
+        let mut commands = vec![];
+        for (channel, state) in channels.iter() {
+            let result = state.locks.iter().find_map(|lock| {
+                if lock.sender == my_address {
+                    if !lock.is_expired(now) {
+                        return None;
+                    }
+                    Some(
+                        self.payment_channel
+                            .refund(&channel.channel_id, lock.hash, now),
+                    )
+                } else if let Some(preimage) = self.find_preimage(lock.hash) {
+                    Some(
+                        self.payment_channel
+                            .unlock(&channel.channel_id, preimage, now),
+                    )
+                } else if lock.destination != my_address
+                    && !is_forwarded(lock.hash)
+                    && lock.timeout.0 <= now + TIMEOUT_DELTA
+                {
+                    Some(
+                        self.payment_channel
+                            .refund(&channel.channel_id, lock.hash, now),
+                    )
+                } else {
+                    None
+                }
+            });
+            match result {
+                Some(Ok(command)) => commands.push((channel.counter_party, command)),
+                Some(Err(e)) => println!(
+                    "failed to resolve lock in channel {:?}: {:?}",
+                    channel.channel_id, e
+                ),
+                None => {}
+            }
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ovm::deciders::preimage_exists_decider::Verifier as PreimageVerifier;
+    use plasma_db::prelude::*;
+
+    const ALICE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+    const HUB_KEY: &str = "ae6ae8e5ccbfb04590405997ee2d52d2b330726137b875053c36d94e974d162f";
+    const CAROL_KEY: &str = "c2baf6c66618acd49fb133cebc22f55bd907fe9f0d69a726d45b7539ba6bbe08";
+
+    fn create_channel(key: &str) -> PaymentChannel<CoreDbMemoryImpl> {
+        PaymentChannel::new(StateChannel::new("test", key).unwrap())
+    }
+
+    /// Delivers commands between the hub and the participants until no command is left.
+    fn run(
+        hub: &Hub<CoreDbMemoryImpl>,
+        participants: &[&PaymentChannel<CoreDbMemoryImpl>],
+        mut commands: Vec<(Address, ChannelCommand)>,
+        now: u64,
+    ) {
+        while let Some((to, command)) = commands.pop() {
+            if to == hub.get_my_address() {
+                commands.extend(hub.handle_command(&command, now).unwrap());
+            } else {
+                let participant = participants
+                    .iter()
+                    .find(|p| p.get_my_address() == to)
+                    .unwrap();
+                if let Some(reply) = participant.handle_command(&command, now).unwrap() {
+                    commands.push((hub.get_my_address(), reply));
+                }
+            }
+        }
+    }
+
+    fn setup() -> (
+        Hub<CoreDbMemoryImpl>,
+        PaymentChannel<CoreDbMemoryImpl>,
+        PaymentChannel<CoreDbMemoryImpl>,
+    ) {
+        let hub = Hub::new(create_channel(HUB_KEY));
+        let alice = create_channel(ALICE_KEY);
+        let carol = create_channel(CAROL_KEY);
+        let hub_address = hub.get_my_address();
+        let open_alice = alice
+            .open(&Bytes::from("alice-hub"), hub_address, 100, 0)
+            .unwrap();
+        let open_carol = hub
+            .get_payment_channel()
+            .open(&Bytes::from("hub-carol"), carol.get_my_address(), 100, 0)
+            .unwrap();
+        run(
+            &hub,
+            &[&alice, &carol],
+            vec![
+                (hub_address, open_alice),
+                (carol.get_my_address(), open_carol),
+            ],
+            0,
+        );
+        (hub, alice, carol)
+    }
+
+    #[test]
+    fn test_route_payment() {
+        let (hub, alice, carol) = setup();
+        let hub_address = hub.get_my_address();
+        let request = create_route_request(alice.get_state_channel(), carol.get_my_address(), 30);
+        let response = hub.handle_command(&request, 0).unwrap();
+        let route = get_route(&response[0].1).unwrap();
+        assert_eq!(route.outgoing_channel_id, Bytes::from("hub-carol"));
+        assert!(hub
+            .find_route(alice.get_my_address(), carol.get_my_address(), 101)
+            .is_err());
+
+        let preimage = Bytes::from("secret");
+        let hash = PreimageVerifier::static_hash(&preimage);
+        let lock = alice
+            .lock(
+                &route.incoming_channel_id,
+                hash,
+                30,
+                2 * TIMEOUT_DELTA,
+                carol.get_my_address(),
+            )
+            .unwrap();
+        run(&hub, &[&alice, &carol], vec![(hub_address, lock)], 10);
+        let forwarded = carol.get_state(&route.outgoing_channel_id).unwrap();
+        assert_eq!(
+            forwarded.get_lock(hash).unwrap().timeout,
+            Integer(TIMEOUT_DELTA)
+        );
+
+        let unlock = carol
+            .unlock(&route.outgoing_channel_id, preimage, 20)
+            .unwrap();
+        run(&hub, &[&alice, &carol], vec![(hub_address, unlock)], 20);
+        let incoming = alice.get_state(&route.incoming_channel_id).unwrap();
+        assert!(incoming.locks.is_empty());
+        assert_eq!(incoming.get_balance(hub_address), Some(30));
+        let outgoing = carol.get_state(&route.outgoing_channel_id).unwrap();
+        assert_eq!(outgoing.get_balance(carol.get_my_address()), Some(30));
+        assert!(hub
+            .get_payment_channel()
+            .prove_unlock(&route.incoming_channel_id, hash)
+            .unwrap()
+            .get_outcome());
+    }
+
+    #[test]
+    fn test_route_timeout() {
+        let (hub, alice, carol) = setup();
+        let hub_address = hub.get_my_address();
+        let hash = PreimageVerifier::static_hash(&Bytes::from("secret"));
+        let lock = alice
+            .lock(
+                &Bytes::from("alice-hub"),
+                hash,
+                30,
+                2 * TIMEOUT_DELTA,
+                carol.get_my_address(),
+            )
+            .unwrap();
+        run(&hub, &[&alice, &carol], vec![(hub_address, lock)], 10);
+        assert!(hub.expire_locks(10).is_empty());
+
+        // carol doesn't reveal the preimage until the outgoing lock expires
+        let refunds = hub.expire_locks(TIMEOUT_DELTA);
+        assert_eq!(refunds.len(), 1);
+        run(&hub, &[&alice, &carol], refunds, TIMEOUT_DELTA);
+        let refunds = hub.expire_locks(TIMEOUT_DELTA);
+        assert_eq!(refunds.len(), 1);
+        run(&hub, &[&alice, &carol], refunds, TIMEOUT_DELTA);
+        let incoming = alice.get_state(&Bytes::from("alice-hub")).unwrap();
+        assert!(incoming.locks.is_empty());
+        assert_eq!(incoming.get_balance(alice.get_my_address()), Some(100));
+        let outgoing = carol.get_state(&Bytes::from("hub-carol")).unwrap();
+        assert!(outgoing.locks.is_empty());
+        assert_eq!(outgoing.get_balance(hub_address), Some(100));
+    }
+}
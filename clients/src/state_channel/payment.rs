@@ -0,0 +1,599 @@
+use super::command::{ChannelCommand, CLOSE_REQUEST, OPEN_REQUEST, UPDATE};
+use super::error::{Error, ErrorKind};
+use super::StateChannel;
+use abi_derive::{AbiDecodable, AbiEncodable};
+use abi_utils::{Decodable, Encodable, Error as AbiError, ErrorKind as AbiErrorKind, Integer};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+use ethereum_types::{Address, H256};
+use ovm::deciders::preimage_exists_decider::Verifier as PreimageVerifier;
+use ovm::statements::{create_hash_lock_property, create_hash_lock_settlement_property};
+use ovm::types::{Decision, Property};
+use ovm::DeciderManager;
+use plasma_db::traits::db::DatabaseTrait;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Conditional payment from sender to the other participant.
+/// It's unlocked by the preimage of hash, and refunded to sender after timeout.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct HashLock {
+    pub hash: H256,
+    pub sender: Address,
+    pub amount: Integer,
+    /// unix time in seconds
+    pub timeout: Integer,
+    /// final receiver of the payment routed by hubs
+    pub destination: Address,
+}
+
+impl HashLock {
+    pub fn new(
+        hash: H256,
+        sender: Address,
+        amount: u64,
+        timeout: u64,
+        destination: Address,
+    ) -> Self {
+        Self {
+            hash,
+            sender,
+            amount: Integer(amount),
+            timeout: Integer(timeout),
+            destination,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.timeout.0 <= now
+    }
+}
+
+/// Balances of the participants of a payment channel.
+/// Locked amounts are already subtracted from the balance of their sender.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentState {
+    pub participant1: Address,
+    pub balance1: Integer,
+    pub participant2: Address,
+    pub balance2: Integer,
+    pub locks: Vec<HashLock>,
+}
+
+impl PaymentState {
+    pub fn new(participant1: Address, balance1: u64, participant2: Address, balance2: u64) -> Self {
+        Self {
+            participant1,
+            balance1: Integer(balance1),
+            participant2,
+            balance2: Integer(balance2),
+            locks: vec![],
+        }
+    }
+
+    pub fn is_participant(&self, address: Address) -> bool {
+        self.participant1 == address || self.participant2 == address
+    }
+
+    pub fn get_balance(&self, participant: Address) -> Option<u64> {
+        if participant == self.participant1 {
+            Some(self.balance1.0)
+        } else if participant == self.participant2 {
+            Some(self.balance2.0)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_counter_party(&self, participant: Address) -> Option<Address> {
+        if participant == self.participant1 {
+            Some(self.participant2)
+        } else if participant == self.participant2 {
+            Some(self.participant1)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_lock(&self, hash: H256) -> Option<&HashLock> {
+        self.locks.iter().find(|l| l.hash == hash)
+    }
+
+    fn get_balance_mut(&mut self, participant: Address) -> Result<&mut Integer, Error> {
+        if participant == self.participant1 {
+            Ok(&mut self.balance1)
+        } else if participant == self.participant2 {
+            Ok(&mut self.balance2)
+        } else {
+            Err(Error::from(ErrorKind::InvalidTransition))
+        }
+    }
+
+    fn add_balance(&mut self, participant: Address, amount: u64) -> Result<(), Error> {
+        let balance = self.get_balance_mut(participant)?;
+        balance.0 = balance
+            .0
+            .checked_add(amount)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidTransition))?;
+        Ok(())
+    }
+
+    fn sub_balance(&mut self, participant: Address, amount: u64) -> Result<(), Error> {
+        let balance = self.get_balance_mut(participant)?;
+        if balance.0 < amount {
+            return Err(Error::from(ErrorKind::InsufficientBalance));
+        }
+        balance.0 -= amount;
+        Ok(())
+    }
+
+    fn remove_lock(&mut self, hash: H256) -> Result<HashLock, Error> {
+        let index = self
+            .locks
+            .iter()
+            .position(|l| l.hash == hash)
+            .ok_or_else(|| Error::from(ErrorKind::LockNotFound))?;
+        Ok(self.locks.remove(index))
+    }
+
+    /// Pays amount from `from` to the other participant.
+    pub fn pay(&self, from: Address, amount: u64) -> Result<Self, Error> {
+        let mut next = self.clone();
+        let to = self
+            .get_counter_party(from)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidTransition))?;
+        next.sub_balance(from, amount)?;
+        next.add_balance(to, amount)?;
+        Ok(next)
+    }
+
+    pub fn add_lock(&self, lock: HashLock) -> Result<Self, Error> {
+        if self.get_lock(lock.hash).is_some() || lock.amount.0 == 0 {
+            return Err(Error::from(ErrorKind::InvalidTransition));
+        }
+        let mut next = self.clone();
+        next.sub_balance(lock.sender, lock.amount.0)?;
+        next.locks.push(lock);
+        Ok(next)
+    }
+
+    /// Removes the lock of the preimage paying its amount to the receiver.
+    pub fn unlock(&self, preimage: &Bytes) -> Result<Self, Error> {
+        let mut next = self.clone();
+        let lock = next.remove_lock(PreimageVerifier::static_hash(preimage))?;
+        let receiver = self
+            .get_counter_party(lock.sender)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidTransition))?;
+        next.add_balance(receiver, lock.amount.0)?;
+        Ok(next)
+    }
+
+    /// Removes the lock returning its amount to the sender.
+    pub fn refund(&self, hash: H256) -> Result<Self, Error> {
+        let mut next = self.clone();
+        let lock = next.remove_lock(hash)?;
+        next.add_balance(lock.sender, lock.amount.0)?;
+        Ok(next)
+    }
+
+    /// Verifies that `proposer` can update this state to the state of the message.
+    /// Locks can be unlocked only before timeout. The sender of a lock can refund it
+    /// only after timeout, while the receiver can refund it anytime.
+    pub fn verify_transition(
+        &self,
+        message: &PaymentMessage,
+        proposer: Address,
+        now: u64,
+    ) -> Result<(), Error> {
+        let next = &message.state;
+        let expected = if !message.preimage.is_empty() {
+            let hash = PreimageVerifier::static_hash(&message.preimage);
+            match self.get_lock(hash) {
+                Some(lock) if !lock.is_expired(now) => self.unlock(&message.preimage)?,
+                Some(_) => return Err(Error::from(ErrorKind::InvalidTransition)),
+                None => return Err(Error::from(ErrorKind::LockNotFound)),
+            }
+        } else if next.locks.len() == self.locks.len() + 1 {
+            let lock = next.locks.last().unwrap();
+            if lock.sender != proposer || lock.is_expired(now) {
+                return Err(Error::from(ErrorKind::InvalidTransition));
+            }
+            self.add_lock(lock.clone())?
+        } else if next.locks.len() + 1 == self.locks.len() {
+            let lock = self
+                .locks
+                .iter()
+                .find(|l| next.get_lock(l.hash).is_none())
+                .ok_or_else(|| Error::from(ErrorKind::LockNotFound))?;
+            if lock.sender == proposer && !lock.is_expired(now) {
+                return Err(Error::from(ErrorKind::InvalidTransition));
+            }
+            self.refund(lock.hash)?
+        } else {
+            let balance = self
+                .get_balance(proposer)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidTransition))?;
+            let next_balance = next
+                .get_balance(proposer)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidTransition))?;
+            if next_balance > balance {
+                return Err(Error::from(ErrorKind::InvalidTransition));
+            }
+            self.pay(proposer, balance - next_balance)?
+        };
+        if &expected != next {
+            return Err(Error::from(ErrorKind::InvalidTransition));
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for PaymentState {
+    fn to_tuple(&self) -> Vec<Token> {
+        vec![
+            Token::Address(self.participant1),
+            Token::Uint(self.balance1.0.into()),
+            Token::Address(self.participant2),
+            Token::Uint(self.balance2.0.into()),
+            Token::Array(
+                self.locks
+                    .iter()
+                    .map(|l| Token::Tuple(l.to_tuple()))
+                    .collect(),
+            ),
+        ]
+    }
+}
+
+impl Decodable for PaymentState {
+    type Ok = PaymentState;
+    fn from_tuple(tuple: &[Token]) -> Result<Self, AbiError> {
+        let participant1 = tuple[0].clone().to_address();
+        let balance1 = tuple[1].clone().to_uint();
+        let participant2 = tuple[2].clone().to_address();
+        let balance2 = tuple[3].clone().to_uint();
+        let locks = tuple[4].clone().to_array();
+        if let (
+            Some(participant1),
+            Some(balance1),
+            Some(participant2),
+            Some(balance2),
+            Some(locks),
+        ) = (participant1, balance1, participant2, balance2, locks)
+        {
+            let locks: Result<Vec<HashLock>, AbiError> = locks
+                .iter()
+                .map(|l| match l.clone().to_tuple() {
+                    Some(tuple) => HashLock::from_tuple(&tuple),
+                    None => Err(AbiError::from(AbiErrorKind::AbiDecode)),
+                })
+                .collect();
+            Ok(PaymentState {
+                participant1,
+                balance1: Integer(balance1.as_u64()),
+                participant2,
+                balance2: Integer(balance2.as_u64()),
+                locks: locks?,
+            })
+        } else {
+            Err(AbiError::from(AbiErrorKind::AbiDecode))
+        }
+    }
+    fn get_param_types() -> Vec<ParamType> {
+        vec![
+            ParamType::Address,
+            ParamType::Uint(64),
+            ParamType::Address,
+            ParamType::Uint(64),
+            ParamType::Array(Box::new(ParamType::Tuple(HashLock::get_param_types()))),
+        ]
+    }
+}
+
+/// Body of payment channel messages.
+/// preimage is revealed by the update unlocking a hash lock.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
+pub struct PaymentMessage {
+    pub state: PaymentState,
+    pub preimage: Bytes,
+}
+
+impl PaymentMessage {
+    pub fn new(state: PaymentState, preimage: Bytes) -> Self {
+        Self { state, preimage }
+    }
+}
+
+/// State channel whose messages are payments between the participants.
+/// Updates from the counter party are verified against the latest state before signing.
+pub struct PaymentChannel<KVS: KeyValueStore + DatabaseTrait> {
+    state_channel: StateChannel<KVS>,
+}
+
+impl<KVS: KeyValueStore + DatabaseTrait> PaymentChannel<KVS> {
+    pub fn new(state_channel: StateChannel<KVS>) -> Self {
+        Self { state_channel }
+    }
+
+    pub fn get_state_channel(&self) -> &StateChannel<KVS> {
+        &self.state_channel
+    }
+
+    pub fn get_my_address(&self) -> Address {
+        self.state_channel.get_my_address()
+    }
+
+    /// Returns the latest state signed by both participants.
+    pub fn get_state(&self, channel_id: &Bytes) -> Result<PaymentState, Error> {
+        let message = self.state_channel.get_latest_message(channel_id)?;
+        Ok(PaymentMessage::from_abi(message.get_message())?.state)
+    }
+
+    pub fn open(
+        &self,
+        channel_id: &Bytes,
+        counter_party: Address,
+        my_balance: u64,
+        counter_party_balance: u64,
+    ) -> Result<ChannelCommand, Error> {
+        let state = PaymentState::new(
+            self.get_my_address(),
+            my_balance,
+            counter_party,
+            counter_party_balance,
+        );
+        self.state_channel.open(
+            channel_id,
+            counter_party,
+            Bytes::from(PaymentMessage::new(state, Bytes::new()).to_abi()),
+        )
+    }
+
+    fn propose(
+        &self,
+        channel_id: &Bytes,
+        state: PaymentState,
+        preimage: Bytes,
+    ) -> Result<ChannelCommand, Error> {
+        self.state_channel.update(
+            channel_id,
+            Bytes::from(PaymentMessage::new(state, preimage).to_abi()),
+        )
+    }
+
+    pub fn pay(&self, channel_id: &Bytes, amount: u64) -> Result<ChannelCommand, Error> {
+        let state = self
+            .get_state(channel_id)?
+            .pay(self.get_my_address(), amount)?;
+        self.propose(channel_id, state, Bytes::new())
+    }
+
+    /// Locks amount until timeout. The counter party gets it by revealing the preimage of hash.
+    pub fn lock(
+        &self,
+        channel_id: &Bytes,
+        hash: H256,
+        amount: u64,
+        timeout: u64,
+        destination: Address,
+    ) -> Result<ChannelCommand, Error> {
+        let lock = HashLock::new(hash, self.get_my_address(), amount, timeout, destination);
+        let state = self.get_state(channel_id)?.add_lock(lock)?;
+        self.propose(channel_id, state, Bytes::new())
+    }
+
+    /// Reveals the preimage to receive the locked amount.
+    pub fn unlock(
+        &self,
+        channel_id: &Bytes,
+        preimage: Bytes,
+        now: u64,
+    ) -> Result<ChannelCommand, Error> {
+        let state = self.get_state(channel_id)?;
+        let lock = state
+            .get_lock(PreimageVerifier::static_hash(&preimage))
+            .ok_or_else(|| Error::from(ErrorKind::LockNotFound))?;
+        if lock.is_expired(now) {
+            return Err(Error::from(ErrorKind::InvalidTransition));
+        }
+        let state = state.unlock(&preimage)?;
+        let command = self.propose(channel_id, state, preimage.clone())?;
+        self.state_channel.store_preimage(channel_id, &preimage)?;
+        Ok(command)
+    }
+
+    /// Refunds the lock. Only the receiver can refund it before timeout.
+    pub fn refund(
+        &self,
+        channel_id: &Bytes,
+        hash: H256,
+        now: u64,
+    ) -> Result<ChannelCommand, Error> {
+        let state = self.get_state(channel_id)?;
+        let lock = state
+            .get_lock(hash)
+            .ok_or_else(|| Error::from(ErrorKind::LockNotFound))?;
+        if lock.sender == self.get_my_address() && !lock.is_expired(now) {
+            return Err(Error::from(ErrorKind::InvalidTransition));
+        }
+        self.propose(channel_id, state.refund(hash)?, Bytes::new())
+    }
+
+    /// Closes the channel. All locks must be resolved before.
+    pub fn close(&self, channel_id: &Bytes) -> Result<ChannelCommand, Error> {
+        if !self.get_state(channel_id)?.locks.is_empty() {
+            return Err(Error::from(ErrorKind::InvalidStatus));
+        }
+        self.state_channel.close(channel_id)
+    }
+
+    /// Verifies the payment in the command and handles it by the state channel.
+    pub fn handle_command(
+        &self,
+        command: &ChannelCommand,
+        now: u64,
+    ) -> Result<Option<ChannelCommand>, Error> {
+        let channel_id = &command.message.channel_id;
+        let mut preimage = None;
+        match command.command_type.0 {
+            OPEN_REQUEST => {
+                let message = PaymentMessage::from_abi(command.message.get_message())?;
+                let state = message.state;
+                if !state.is_participant(command.from)
+                    || state.get_counter_party(command.from) != Some(self.get_my_address())
+                    || !state.locks.is_empty()
+                {
+                    return Err(Error::from(ErrorKind::InvalidTransition));
+                }
+            }
+            UPDATE => {
+                let message = PaymentMessage::from_abi(command.message.get_message())?;
+                self.get_state(channel_id)?
+                    .verify_transition(&message, command.from, now)?;
+                if !message.preimage.is_empty() {
+                    preimage = Some(message.preimage);
+                }
+            }
+            CLOSE_REQUEST => {
+                if !self.get_state(channel_id)?.locks.is_empty() {
+                    return Err(Error::from(ErrorKind::InvalidStatus));
+                }
+            }
+            _ => {}
+        }
+        let reply = self.state_channel.handle_command(command)?;
+        if let Some(preimage) = preimage {
+            self.state_channel.store_preimage(channel_id, &preimage)?;
+        }
+        Ok(reply)
+    }
+
+    /// Starts exit with the latest state.
+    /// Each lock in the claim is settled by its preimage, or refunded to its sender after timeout.
+    pub fn start_exit(&self, channel_id: &Bytes) -> Result<Property, Error> {
+        let claim = self.state_channel.start_exit(channel_id)?;
+        self.add_lock_settlements(channel_id, claim)
+    }
+
+    /// Decides my exit claim at `now`.
+    /// It can't be decided while a lock is neither unlocked nor expired.
+    pub fn check_exit_claim(&self, channel_id: &Bytes, now: u64) -> Result<Decision, Error> {
+        let claim = self.state_channel.get_exit_claim(channel_id)?;
+        let claim = self.add_lock_settlements(channel_id, claim)?;
+        self.state_channel.decide_at(channel_id, &claim, now)
+    }
+
+    fn add_lock_settlements(&self, channel_id: &Bytes, claim: Property) -> Result<Property, Error> {
+        Ok(self
+            .get_state(channel_id)?
+            .locks
+            .iter()
+            .fold(claim, |claim, lock| {
+                DeciderManager::and_decider(
+                    claim,
+                    create_hash_lock_settlement_property(lock.hash, lock.timeout),
+                )
+            }))
+    }
+
+    /// Proves that the lock of hash is unlocked by the revealed preimage.
+    pub fn prove_unlock(&self, channel_id: &Bytes, hash: H256) -> Result<Decision, Error> {
+        if self.state_channel.get_preimage(channel_id, hash)?.is_none() {
+            return Err(Error::from(ErrorKind::LockNotFound));
+        }
+        self.state_channel
+            .decide(channel_id, &create_hash_lock_property(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plasma_db::prelude::*;
+
+    const ALICE_KEY: &str = "c87509a1c067bbde78beb793e6fa76530b6382a4c0241e5e4a9ec0a0f44dc0d3";
+    const BOB_KEY: &str = "ae6ae8e5ccbfb04590405997ee2d52d2b330726137b875053c36d94e974d162f";
+
+    fn create_channel(key: &str) -> PaymentChannel<CoreDbMemoryImpl> {
+        PaymentChannel::new(StateChannel::new("test", key).unwrap())
+    }
+
+    fn deliver(
+        to: &PaymentChannel<CoreDbMemoryImpl>,
+        from: &PaymentChannel<CoreDbMemoryImpl>,
+        command: ChannelCommand,
+        now: u64,
+    ) {
+        let reply = to.handle_command(&command, now).unwrap().unwrap();
+        assert_eq!(from.handle_command(&reply, now).unwrap(), None);
+    }
+
+    #[test]
+    fn test_hash_locked_payment() {
+        let alice = create_channel(ALICE_KEY);
+        let bob = create_channel(BOB_KEY);
+        let channel_id = Bytes::from("channel");
+        let open = alice
+            .open(&channel_id, bob.get_my_address(), 100, 0)
+            .unwrap();
+        deliver(&bob, &alice, open, 0);
+        let pay = alice.pay(&channel_id, 10).unwrap();
+        deliver(&bob, &alice, pay, 0);
+
+        let preimage = Bytes::from("secret");
+        let hash = PreimageVerifier::static_hash(&preimage);
+        let lock = alice
+            .lock(&channel_id, hash, 30, 100, bob.get_my_address())
+            .unwrap();
+        // the lock is already expired
+        assert_eq!(
+            bob.handle_command(&lock, 100).unwrap_err().kind(),
+            &ErrorKind::InvalidTransition
+        );
+        deliver(&bob, &alice, lock, 10);
+        assert_eq!(
+            alice.refund(&channel_id, hash, 10).unwrap_err().kind(),
+            &ErrorKind::InvalidTransition
+        );
+        assert!(bob.unlock(&channel_id, Bytes::from("wrong"), 20).is_err());
+        let unlock = bob.unlock(&channel_id, preimage.clone(), 20).unwrap();
+        deliver(&alice, &bob, unlock, 20);
+
+        let state = alice.get_state(&channel_id).unwrap();
+        assert_eq!(state, bob.get_state(&channel_id).unwrap());
+        assert_eq!(state.get_balance(alice.get_my_address()), Some(60));
+        assert_eq!(state.get_balance(bob.get_my_address()), Some(40));
+        assert!(state.locks.is_empty());
+        assert!(alice.prove_unlock(&channel_id, hash).unwrap().get_outcome());
+    }
+
+    #[test]
+    fn test_refund_after_timeout() {
+        let alice = create_channel(ALICE_KEY);
+        let bob = create_channel(BOB_KEY);
+        let channel_id = Bytes::from("channel");
+        let open = alice
+            .open(&channel_id, bob.get_my_address(), 100, 0)
+            .unwrap();
+        deliver(&bob, &alice, open, 0);
+        let hash = PreimageVerifier::static_hash(&Bytes::from("secret"));
+        let lock = alice
+            .lock(&channel_id, hash, 30, 100, bob.get_my_address())
+            .unwrap();
+        deliver(&bob, &alice, lock, 10);
+        assert_eq!(
+            alice.close(&channel_id).unwrap_err().kind(),
+            &ErrorKind::InvalidStatus
+        );
+        assert!(alice.check_exit_claim(&channel_id, 99).is_err());
+        assert!(alice
+            .check_exit_claim(&channel_id, 100)
+            .unwrap()
+            .get_outcome());
+        let refund = alice.refund(&channel_id, hash, 100).unwrap();
+        deliver(&bob, &alice, refund, 100);
+        let state = bob.get_state(&channel_id).unwrap();
+        assert_eq!(state.get_balance(alice.get_my_address()), Some(100));
+        assert!(state.locks.is_empty());
+        assert!(alice.close(&channel_id).is_ok());
+    }
+}
@@ -29,25 +29,31 @@ impl CommitmentContractAdaptor {
         })
     }
 
+    /// Submits root of the block and waits until the transaction is mined.
+    /// Returns the transaction hash, or an error if the transaction reverted.
     pub fn submit_block(
         &self,
         from: Address,
         block_number: u64,
         root: Bytes,
     ) -> Result<H256, Error> {
-        let result = self.inner.call(
+        let result = self.inner.call_with_confirmations(
             "submit_root",
             (block_number, H256::from_slice(root.to_vec().as_slice())),
             from,
             Options::default(),
+            0,
         );
 
         match result.wait() {
-            Ok(r) => Ok(r),
-            Err(e) => {
-                println!("{:?}", e);
-                Err(e.into())
+            Ok(receipt) => {
+                if receipt.status == Some(0.into()) {
+                    Err(Error::from(ErrorKind::TransactionReverted))
+                } else {
+                    Ok(receipt.transaction_hash)
+                }
             }
+            Err(_) => Err(Error::from(ErrorKind::Web3)),
         }
     }
 }
@@ -15,6 +15,8 @@ pub enum ErrorKind {
     Abi,
     #[fail(display = "Web3 error")]
     Web3,
+    #[fail(display = "Transaction reverted")]
+    TransactionReverted,
 }
 
 #[derive(Debug)]
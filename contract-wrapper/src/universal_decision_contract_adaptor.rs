@@ -1,18 +1,20 @@
 use crate::error::{Error, ErrorKind};
+use abi_utils::Decodable;
 use bytes::Bytes;
 use ethabi::Contract as ContractABI;
-use ethabi::Token;
+use ethabi::{Function, ParamType, Token};
 use ovm::types::core::{ImplicationProofElement, Property};
 use web3::contract::{Contract, Options};
 use web3::futures::Future;
 use web3::transports::{EventLoopHandle, Http};
-use web3::types::{Address, H256};
+use web3::types::{Address, BlockId, BlockNumber, H256};
 
 pub struct UniversalDecisionContractAdaptor {
     _eloop: EventLoopHandle,
     _web3: web3::Web3<web3::transports::Http>,
     _address: Address,
     inner: Contract<Http>,
+    claim_property_function: Function,
 }
 
 impl UniversalDecisionContractAdaptor {
@@ -24,6 +26,7 @@ impl UniversalDecisionContractAdaptor {
         let address: Address = address
             .parse()
             .map_err(|_| Error::from(ErrorKind::InvalidInputType))?;
+        let claim_property_function = abi.function("claimProperty")?.clone();
         let contract = Contract::new(web3.eth(), address, abi);
 
         Ok(Self {
@@ -31,9 +34,49 @@ impl UniversalDecisionContractAdaptor {
             _eloop,
             _address: address,
             inner: contract,
+            claim_property_function,
         })
     }
 
+    /// Returns the latest L1 block number.
+    pub fn get_block_number(&self) -> Result<u64, Error> {
+        self._web3
+            .eth()
+            .block_number()
+            .wait()
+            .map(|n| n.low_u64())
+            .map_err(|_| Error::from(ErrorKind::Web3))
+    }
+
+    /// Returns properties claimed by claimProperty transactions to the contract in the L1 block.
+    /// The contract emits no event for claims, so they are read from the calldata.
+    pub fn get_claims(&self, block_number: u64) -> Result<Vec<Property>, Error> {
+        let block = self
+            ._web3
+            .eth()
+            .block_with_txs(BlockId::Number(BlockNumber::Number(block_number)))
+            .wait()
+            .map_err(|_| Error::from(ErrorKind::Web3))?;
+        let selector = self.claim_property_function.short_signature();
+        let param_types: Vec<ParamType> = self
+            .claim_property_function
+            .inputs
+            .iter()
+            .map(|p| p.kind.clone())
+            .collect();
+        Ok(block
+            .map(|b| b.transactions)
+            .unwrap_or_else(|| vec![])
+            .iter()
+            .filter(|tx| tx.to == Some(self._address) && tx.input.0.starts_with(&selector))
+            .filter_map(|tx| ethabi::decode(&param_types, &tx.input.0[4..]).ok())
+            .filter_map(|tokens| match tokens.get(0) {
+                Some(Token::Tuple(tuple)) => Property::from_tuple(tuple).ok(),
+                _ => None,
+            })
+            .collect())
+    }
+
     pub fn claim_property(&self, from: Address, property: Property) -> Result<H256, Error> {
         let params: Token = property.into();
         let result = self
@@ -147,7 +190,7 @@ impl UniversalDecisionContractAdaptor {
         let contradiction_witness: Token = Token::Bytes(contradiction_witness.to_vec());
 
         let result = self.inner.call(
-            "verifyContradictingImplications",
+            "proveClaimContradictsDecision",
             (
                 decided_property,
                 decided_implication_proof,
@@ -1,8 +1,10 @@
+pub mod batch_transaction;
 pub mod error;
 pub mod metadata;
 pub mod range;
 pub mod transaction;
 
+pub use self::batch_transaction::BatchTransaction;
 pub use self::metadata::{Metadata, EXCHANGE_TYPE, PAYMENT_TYPE};
 pub use self::range::Range;
 pub use self::transaction::{Transaction, TransactionParams};
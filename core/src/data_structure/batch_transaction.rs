@@ -0,0 +1,244 @@
+use super::{Metadata, Transaction, TransactionParams};
+use abi_utils::abi::{Decodable, Encodable};
+use abi_utils::{Error as AbiError, ErrorKind as AbiErrorKind};
+use bytes::Bytes;
+use ethabi::{ParamType, Token};
+
+/// Transactions of several ranges signed at once.
+/// metadata[i] is the metadata of entries[i]. Metadata isn't signed like Transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchTransaction {
+    entries: Vec<TransactionParams>,
+    metadata: Vec<Metadata>,
+    signature: Bytes,
+}
+
+impl BatchTransaction {
+    pub fn new(entries: Vec<TransactionParams>, metadata: Vec<Metadata>, signature: Bytes) -> Self {
+        BatchTransaction {
+            entries,
+            metadata,
+            signature,
+        }
+    }
+
+    /// The message which the signature signs.
+    pub fn create_body_abi(entries: &[TransactionParams]) -> Vec<u8> {
+        ethabi::encode(&[Token::Array(
+            entries.iter().map(|e| Token::Tuple(e.to_tuple())).collect(),
+        )])
+    }
+
+    pub fn to_body_abi(&self) -> Vec<u8> {
+        Self::create_body_abi(&self.entries)
+    }
+
+    pub fn get_entries(&self) -> &[TransactionParams] {
+        &self.entries
+    }
+
+    pub fn get_metadata(&self) -> &[Metadata] {
+        &self.metadata
+    }
+
+    pub fn get_signature(&self) -> &Bytes {
+        &self.signature
+    }
+
+    /// Adds the signature of another signer of the same body.
+    /// Signatures are concatenated in the order of signing.
+    pub fn co_sign(&self, signature: &Bytes) -> Self {
+        let mut signatures = self.signature.to_vec();
+        signatures.extend_from_slice(signature);
+        BatchTransaction::new(
+            self.entries.clone(),
+            self.metadata.clone(),
+            Bytes::from(signatures),
+        )
+    }
+
+    /// Whether two entries of the same deposit contract overlap.
+    pub fn has_overlapping_entries(&self) -> bool {
+        self.entries.iter().enumerate().any(|(i, a)| {
+            self.entries.iter().skip(i + 1).any(|b| {
+                a.get_deposit_contract_address() == b.get_deposit_contract_address()
+                    && a.get_range().get_start() < b.get_range().get_end()
+                    && b.get_range().get_start() < a.get_range().get_end()
+            })
+        })
+    }
+
+    /// Splits the batch into transactions of each entry.
+    /// Their signatures carry the batch body so that they can be verified one by one.
+    pub fn to_transactions(&self) -> Vec<Transaction> {
+        let signature = Bytes::from(ethabi::encode(&[
+            Token::Bytes(self.signature.to_vec()),
+            Token::Bytes(self.to_body_abi()),
+        ]));
+        self.entries
+            .iter()
+            .zip(self.metadata.iter())
+            .map(|(entry, metadata)| {
+                Transaction::from_params(entry.clone(), signature.clone(), metadata.clone())
+            })
+            .collect()
+    }
+}
+
+/// Decodes the signature of a transaction split from a batch into the raw signature and the batch body.
+/// Returns None if the signature isn't the one of a batch.
+pub fn decode_batch_signature(signature: &Bytes) -> Option<(Bytes, Bytes)> {
+    let tokens = ethabi::decode(&[ParamType::Bytes, ParamType::Bytes], signature).ok()?;
+    let raw_signature = tokens[0].clone().to_bytes()?;
+    let body = tokens[1].clone().to_bytes()?;
+    Some((Bytes::from(raw_signature), Bytes::from(body)))
+}
+
+/// Decodes entries of the batch body.
+pub fn decode_batch_body(body: &Bytes) -> Option<Vec<TransactionParams>> {
+    let tokens = ethabi::decode(
+        &[ParamType::Array(Box::new(ParamType::Tuple(
+            TransactionParams::get_param_types(),
+        )))],
+        body,
+    )
+    .ok()?;
+    tokens[0]
+        .clone()
+        .to_array()?
+        .iter()
+        .map(|t| {
+            t.clone()
+                .to_tuple()
+                .and_then(|tuple| TransactionParams::from_tuple(&tuple).ok())
+        })
+        .collect()
+}
+
+impl Encodable for BatchTransaction {
+    fn to_tuple(&self) -> Vec<Token> {
+        vec![
+            Token::Array(
+                self.entries
+                    .iter()
+                    .map(|e| Token::Tuple(e.to_tuple()))
+                    .collect(),
+            ),
+            Token::Array(
+                self.metadata
+                    .iter()
+                    .map(|m| Token::Tuple(m.to_tuple()))
+                    .collect(),
+            ),
+            Token::Bytes(self.signature.to_vec()),
+        ]
+    }
+}
+
+impl Decodable for BatchTransaction {
+    type Ok = BatchTransaction;
+    fn from_tuple(tuple: &[Token]) -> Result<Self, AbiError> {
+        let entries = tuple[0].clone().to_array();
+        let metadata = tuple[1].clone().to_array();
+        let signature = tuple[2].clone().to_bytes();
+        if let (Some(entries), Some(metadata), Some(signature)) = (entries, metadata, signature) {
+            let entries: Result<Vec<_>, _> = entries
+                .iter()
+                .map(|e| match e.clone().to_tuple() {
+                    Some(t) => TransactionParams::from_tuple(&t),
+                    None => Err(AbiError::from(AbiErrorKind::AbiDecode)),
+                })
+                .collect();
+            let metadata: Result<Vec<_>, _> = metadata
+                .iter()
+                .map(|m| match m.clone().to_tuple() {
+                    Some(t) => Metadata::from_tuple(&t),
+                    None => Err(AbiError::from(AbiErrorKind::AbiDecode)),
+                })
+                .collect();
+            Ok(BatchTransaction::new(
+                entries?,
+                metadata?,
+                Bytes::from(signature),
+            ))
+        } else {
+            Err(AbiError::from(AbiErrorKind::AbiDecode))
+        }
+    }
+    fn get_param_types() -> Vec<ParamType> {
+        vec![
+            ParamType::Array(Box::new(ParamType::Tuple(
+                TransactionParams::get_param_types(),
+            ))),
+            ParamType::Array(Box::new(ParamType::Tuple(Metadata::get_param_types()))),
+            ParamType::Bytes,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structure::Range;
+    use ethereum_types::Address;
+
+    fn create_batch() -> BatchTransaction {
+        let entries = vec![
+            TransactionParams::new(Address::zero(), Range::new(0, 10), Bytes::from("to alice")),
+            TransactionParams::new(Address::zero(), Range::new(10, 20), Bytes::from("to bob")),
+        ];
+        BatchTransaction::new(
+            entries,
+            vec![Metadata::default(), Metadata::default()],
+            Bytes::from(&[1; 65][..]),
+        )
+    }
+
+    #[test]
+    fn test_abi_encode() {
+        let batch = create_batch();
+        let decoded = BatchTransaction::from_abi(&batch.to_abi()).unwrap();
+        assert_eq!(decoded, batch);
+        assert!(!batch.has_overlapping_entries());
+    }
+
+    #[test]
+    fn test_to_transactions() {
+        let batch = create_batch();
+        let txs = batch.to_transactions();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[1].get_range(), Range::new(10, 20));
+        assert_eq!(txs[1].get_raw_signature(), Bytes::from(&[1; 65][..]));
+        assert_eq!(
+            txs[1].get_signed_message(),
+            Bytes::from(batch.to_body_abi())
+        );
+        // a range which isn't in the batch doesn't get the batch body as its message
+        let forged = Transaction::new(
+            Address::zero(),
+            Range::new(20, 30),
+            Bytes::from("to bob"),
+            txs[1].get_signature().clone(),
+            Metadata::default(),
+        );
+        assert_eq!(
+            forged.get_signed_message(),
+            Bytes::from(forged.to_body_abi())
+        );
+    }
+
+    #[test]
+    fn test_co_sign() {
+        let batch = create_batch().co_sign(&Bytes::from(&[2; 65][..]));
+        let txs = batch.to_transactions();
+        assert_eq!(txs[0].get_raw_signature(), Bytes::from(&[1; 65][..]));
+        assert_eq!(
+            txs[0].get_signatures(),
+            vec![Bytes::from(&[1; 65][..]), Bytes::from(&[2; 65][..])]
+        );
+        assert_eq!(
+            txs[0].get_signed_message(),
+            Bytes::from(batch.to_body_abi())
+        );
+    }
+}
@@ -1,6 +1,7 @@
 extern crate ethereum_types;
 extern crate tiny_keccak;
 
+use super::batch_transaction::{decode_batch_body, decode_batch_signature};
 use super::{Metadata, Range};
 use abi_derive::{AbiDecodable, AbiEncodable};
 use abi_utils::abi::Encodable;
@@ -25,6 +26,15 @@ impl TransactionParams {
             parameters,
         }
     }
+    pub fn get_deposit_contract_address(&self) -> Address {
+        self.deposit_contract_address
+    }
+    pub fn get_range(&self) -> Range {
+        self.range
+    }
+    pub fn get_parameters(&self) -> &Bytes {
+        &self.parameters
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, AbiEncodable, AbiDecodable)]
@@ -127,6 +137,51 @@ impl Transaction {
     pub fn get_metadata(&self) -> &Metadata {
         &self.metadata
     }
+    pub fn to_params(&self) -> TransactionParams {
+        TransactionParams::new(
+            self.deposit_contract_address,
+            self.range,
+            self.parameters.clone(),
+        )
+    }
+    /// Whether the transaction was split from a BatchTransaction.
+    pub fn is_batched(&self) -> bool {
+        self.signature.len() != 65 && decode_batch_signature(&self.signature).is_some()
+    }
+    /// The 65 bytes signature. A batched transaction has the signature of the batch.
+    /// A co-signed transaction has the signature of the first signer.
+    pub fn get_raw_signature(&self) -> Bytes {
+        self.get_signatures()[0].clone()
+    }
+    /// Signatures of all signers of the signed message.
+    /// Co-signed transactions like channel settlements carry 65 bytes signatures concatenated.
+    pub fn get_signatures(&self) -> Vec<Bytes> {
+        if self.signature.len() == 65 {
+            return vec![self.signature.clone()];
+        }
+        let signature = match decode_batch_signature(&self.signature) {
+            Some((signature, _)) => signature,
+            None => self.signature.clone(),
+        };
+        if signature.len() > 65 && signature.len() % 65 == 0 {
+            signature.chunks(65).map(Bytes::from).collect()
+        } else {
+            vec![signature]
+        }
+    }
+    /// The message which the raw signature signs.
+    /// It is the batch body only if the batch contains this transaction.
+    pub fn get_signed_message(&self) -> Bytes {
+        if self.signature.len() != 65 {
+            if let Some((_, body)) = decode_batch_signature(&self.signature) {
+                let params = self.to_params();
+                if decode_batch_body(&body).map_or(false, |entries| entries.contains(&params)) {
+                    return body;
+                }
+            }
+        }
+        Bytes::from(self.to_body_abi())
+    }
 }
 
 #[cfg(test)]
@@ -6,7 +6,7 @@ pub mod signed_by_db;
 pub mod transaction_db;
 pub mod transaction_filter;
 
-pub use self::channel_db::{Channel, ChannelDb};
+pub use self::channel_db::{Channel, ChannelDb, ChannelStatus};
 pub use self::hash_preimage_db::HashPreimageDb;
 pub use self::message_db::{Message, MessageDb};
 pub use self::range_at_block_db::{RangeAtBlockDb, RangeAtBlockRecord};
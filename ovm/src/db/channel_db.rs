@@ -4,21 +4,81 @@ use abi_derive::{AbiDecodable, AbiEncodable};
 use abi_utils::{Decodable, Encodable};
 use bytes::Bytes;
 use ethabi::{ParamType, Token};
+use ethereum_types::Address;
 use plasma_db::traits::kvs::KeyValueStore;
 
-#[derive(Clone, Debug, AbiDecodable, AbiEncodable)]
+/// Lifecycle of a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelStatus {
+    /// Opening request is sent and the counter party hasn't signed it yet.
+    Opening,
+    Open,
+    /// Closing request is sent and the counter party hasn't signed it yet.
+    Closing,
+    /// Closed cooperatively by the final message signed by both.
+    Closed,
+    /// Exit is claimed by myself.
+    Exiting,
+    Exited,
+}
+
+impl ChannelStatus {
+    pub fn to_integer(self) -> Integer {
+        Integer(match self {
+            ChannelStatus::Opening => 0,
+            ChannelStatus::Open => 1,
+            ChannelStatus::Closing => 2,
+            ChannelStatus::Closed => 3,
+            ChannelStatus::Exiting => 4,
+            ChannelStatus::Exited => 5,
+        })
+    }
+
+    pub fn from_integer(status: Integer) -> Option<Self> {
+        match status.0 {
+            0 => Some(ChannelStatus::Opening),
+            1 => Some(ChannelStatus::Open),
+            2 => Some(ChannelStatus::Closing),
+            3 => Some(ChannelStatus::Closed),
+            4 => Some(ChannelStatus::Exiting),
+            5 => Some(ChannelStatus::Exited),
+            _ => None,
+        }
+    }
+}
+
+/// nonce is the nonce of the latest message signed by both participants.
+#[derive(Clone, Debug, PartialEq, Eq, AbiDecodable, AbiEncodable)]
 pub struct Channel {
     pub channel_id: Bytes,
-    pub exitted: Integer,
+    pub my_address: Address,
+    pub counter_party: Address,
+    pub status: Integer,
+    pub nonce: Integer,
 }
 
 impl Channel {
-    pub fn new(channel_id: Bytes, exitted: Integer) -> Self {
+    pub fn new(channel_id: Bytes, my_address: Address, counter_party: Address) -> Self {
         Self {
             channel_id,
-            exitted,
+            my_address,
+            counter_party,
+            status: ChannelStatus::Opening.to_integer(),
+            nonce: Integer(0),
         }
     }
+
+    pub fn get_status(&self) -> ChannelStatus {
+        ChannelStatus::from_integer(self.status).unwrap_or(ChannelStatus::Opening)
+    }
+
+    pub fn set_status(&mut self, status: ChannelStatus) {
+        self.status = status.to_integer();
+    }
+
+    pub fn is_exited(&self) -> bool {
+        self.get_status() == ChannelStatus::Exited
+    }
 }
 
 pub struct ChannelDb<'a, KVS> {
@@ -29,22 +89,40 @@ impl<'a, KVS> ChannelDb<'a, KVS>
 where
     KVS: KeyValueStore,
 {
-    pub fn mark_exited(&self, channel_id: &Bytes) -> Result<(), Error> {
+    pub fn put_channel(&self, channel: &Channel) -> Result<(), Error> {
         self.db
             .bucket(&Bytes::from("channels").into())
-            .put(
-                &channel_id.into(),
-                &Channel::new(channel_id.clone(), Integer(1)).to_abi(),
-            )
+            .put(&channel.channel_id.clone().into(), &channel.to_abi())
             .map_err(Into::into)
     }
-    pub fn get_exited(&self, channel_id: &Bytes) -> Result<Channel, Error> {
+    pub fn get_channel(&self, channel_id: &Bytes) -> Result<Option<Channel>, Error> {
         let result = self
             .db
             .bucket(&Bytes::from("channels").into())
             .get(&channel_id.into())
             .map_err::<Error, _>(Into::into)?;
-        Channel::from_abi(&result.unwrap()).map_err(Into::into)
+        match result {
+            Some(bytes) => Ok(Some(Channel::from_abi(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn get_all_channels(&self) -> Vec<Channel> {
+        self.db
+            .bucket(&Bytes::from("channels").into())
+            .iter_all(&Bytes::from("").into(), Box::new(move |_k, _v| true))
+            .iter()
+            .filter_map(|kv| Channel::from_abi(kv.get_value()).ok())
+            .collect()
+    }
+    /// Marks the channel exited. Returns false if the channel isn't known.
+    pub fn mark_exited(&self, channel_id: &Bytes) -> Result<bool, Error> {
+        if let Some(mut channel) = self.get_channel(channel_id)? {
+            channel.set_status(ChannelStatus::Exited);
+            self.put_channel(&channel)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
     }
 }
 
@@ -56,3 +134,33 @@ where
         Self { db: kvs }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plasma_db::prelude::*;
+
+    #[test]
+    fn test_channel_db() {
+        let db = CoreDbMemoryImpl::open("test");
+        let channel_db: ChannelDb<CoreDbMemoryImpl> = (&db).into();
+        let channel_id = Bytes::from("channel_id");
+        assert_eq!(channel_db.get_channel(&channel_id).unwrap(), None);
+        assert!(!channel_db.mark_exited(&channel_id).unwrap());
+        assert!(channel_db.get_all_channels().is_empty());
+        let mut channel = Channel::new(channel_id.clone(), Address::zero(), Address::random());
+        channel.set_status(ChannelStatus::Open);
+        channel_db.put_channel(&channel).unwrap();
+        assert_eq!(
+            channel_db.get_channel(&channel_id).unwrap(),
+            Some(channel.clone())
+        );
+        assert_eq!(channel_db.get_all_channels(), vec![channel]);
+        assert!(channel_db.mark_exited(&channel_id).unwrap());
+        assert!(channel_db
+            .get_channel(&channel_id)
+            .unwrap()
+            .unwrap()
+            .is_exited());
+    }
+}
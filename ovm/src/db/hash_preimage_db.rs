@@ -33,6 +33,14 @@ impl<'a, KVS: KeyValueStore> HashPreimageDb<'a, KVS> {
             .put(&BaseDbKey::from(hash.as_bytes()), &record.to_abi())
             .map_err::<Error, _>(Into::into)
     }
+    pub fn has_witness(&self, hash: H256) -> Result<bool, Error> {
+        let result = self
+            .db
+            .bucket(&BaseDbKey::from(&b"preimage_exists_decider"[..]))
+            .get(&BaseDbKey::from(hash.as_bytes()))
+            .map_err::<Error, _>(Into::into)?;
+        Ok(result.is_some())
+    }
     pub fn get_witness(&self, hash: H256) -> Result<PreimageRecord, Error> {
         let result = self
             .db
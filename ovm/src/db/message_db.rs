@@ -21,6 +21,10 @@ impl Message {
             message,
         }
     }
+
+    pub fn get_message(&self) -> &Bytes {
+        &self.message
+    }
 }
 
 pub struct MessageDb<'a, KVS> {
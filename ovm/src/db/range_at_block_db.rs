@@ -81,4 +81,28 @@ impl<'a, KVS: KeyValueStore> RangeAtBlockDb<'a, KVS> {
         }
         RangeAtBlockRecord::from_abi(&result[0].get_value()).map_err::<Error, _>(Into::into)
     }
+    /// Returns state updates of the block which intersect with coin_range, sorted by start.
+    pub fn get_state_updates(
+        &self,
+        block_number: Integer,
+        deposit_contract_address: Address,
+        coin_range: Range,
+    ) -> Result<Vec<StateUpdate>, Error> {
+        let mut state_updates = self
+            .db
+            .bucket(&Bytes::from(&b"range_at_block"[..]))
+            .bucket(&block_number.into())
+            .bucket(&Bytes::from(deposit_contract_address.as_bytes()))
+            .get(coin_range.get_start(), coin_range.get_end())
+            .map_err::<Error, _>(Into::into)?
+            .iter()
+            .map(|r| {
+                RangeAtBlockRecord::from_abi(&r.get_value())
+                    .map(|record| record.state_update)
+                    .map_err::<Error, _>(Into::into)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        state_updates.sort_by_key(|s| s.get_range().get_start());
+        Ok(state_updates)
+    }
 }
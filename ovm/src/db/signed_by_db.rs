@@ -64,6 +64,13 @@ impl<'a, KVS: KeyValueStore> SignedByDb<'a, KVS> {
         }
         SignedByRecord::from_abi(&result.unwrap()).map_err::<Error, _>(Into::into)
     }
+    pub fn remove_witness(&self, public_key: Address, message: &Bytes) -> Result<(), Error> {
+        self.db
+            .bucket(&Bytes::from("signed_by_decider").into())
+            .bucket(&BaseDbKey::from(public_key.as_bytes()))
+            .del(&BaseDbKey::from(static_hash(message).as_bytes()))
+            .map_err::<Error, _>(Into::into)
+    }
     pub fn get_all_signed_by(&self, signer: Address) -> Vec<SignedByRecord> {
         self.db
             .bucket(&Bytes::from("signed_by_decider").into())
@@ -3,6 +3,8 @@ use abi_utils::{Decodable, Encodable, Error as AbiError};
 use bytes::Bytes;
 use plasma_core::data_structure::{Range, Transaction};
 use plasma_db::{
+    error::Error as PlasmaDbError,
+    range::Range as RangeRecord,
     traits::{kvs::KeyValueStore, rangestore::RangeStore},
     RangeDbImpl,
 };
@@ -46,6 +48,65 @@ where
             .put(range.get_start(), range.get_end(), &transaction.to_abi());
     }
 
+    /// Returns raw records stored in range of the block.
+    /// put_transaction is undone by passing them to restore_records.
+    pub fn get_records(
+        &self,
+        block_number: u64,
+        range: Range,
+    ) -> Result<Vec<RangeRecord>, PlasmaDbError> {
+        Ok(self
+            .db
+            .bucket(&Bytes::from(&b"transaction_db"[..]))
+            .bucket(&Bytes::from(format!("block_{}", block_number).as_bytes()))
+            .get(range.get_start(), range.get_end())?
+            .to_vec())
+    }
+
+    /// Replaces records in range of the block with records returned by get_records.
+    pub fn restore_records(
+        &self,
+        block_number: u64,
+        range: Range,
+        records: &[RangeRecord],
+    ) -> Result<(), PlasmaDbError> {
+        let bucket = self
+            .db
+            .bucket(&Bytes::from(&b"transaction_db"[..]))
+            .bucket(&Bytes::from(format!("block_{}", block_number).as_bytes()));
+        // pieces of records split by put_transaction lie within the records
+        let start = records
+            .iter()
+            .map(|r| r.get_start())
+            .fold(range.get_start(), std::cmp::min);
+        let end = records
+            .iter()
+            .map(|r| r.get_end())
+            .fold(range.get_end(), std::cmp::max);
+        bucket.del_batch(start, end)?;
+        bucket.put_batch(records)
+    }
+
+    /// Removes transaction from the block. Other transactions in its range are kept.
+    pub fn remove_transaction(
+        &self,
+        block_number: u64,
+        transaction: &Transaction,
+    ) -> Result<(), PlasmaDbError> {
+        let range = transaction.get_range();
+        let bucket = self
+            .db
+            .bucket(&Bytes::from(&b"transaction_db"[..]))
+            .bucket(&Bytes::from(format!("block_{}", block_number).as_bytes()));
+        let kept: Vec<RangeRecord> = bucket
+            .del_batch(range.get_start(), range.get_end())?
+            .iter()
+            .filter(|r| Transaction::from_abi(r.get_value()).ok().as_ref() != Some(transaction))
+            .cloned()
+            .collect();
+        bucket.put_batch(&kept)
+    }
+
     pub fn query_transaction(
         &self,
         transaction_filter: TransactionFilter,
@@ -109,4 +170,43 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 2);
     }
+
+    #[test]
+    fn test_restore_and_remove_transaction() {
+        let db = CoreDbMemoryImpl::open("test");
+        let range_db = RangeDbImpl::from(db);
+        let tx_db = TransactionDb::new(&range_db);
+        let create_transaction = |start: u64, end: u64| {
+            Transaction::new(
+                Address::zero(),
+                Range::new(start, end),
+                Bytes::default(),
+                Bytes::default(),
+                Metadata::default(),
+            )
+        };
+        let tx1 = create_transaction(0, 20);
+        let tx2 = create_transaction(5, 15);
+        tx_db.put_transaction(1, tx1.clone());
+        let records = tx_db.get_records(1, Range::new(5, 15)).unwrap();
+        tx_db.put_transaction(1, tx2.clone());
+        assert_eq!(
+            tx_db.get_transactions(1, Range::new(5, 15)).unwrap(),
+            vec![tx2.clone()]
+        );
+        // undoes put of tx2
+        assert!(tx_db
+            .restore_records(1, Range::new(5, 15), &records)
+            .is_ok());
+        assert_eq!(
+            tx_db.get_transactions(1, Range::new(0, 20)).unwrap(),
+            vec![tx1.clone()]
+        );
+        tx_db.put_transaction(1, tx2.clone());
+        assert!(tx_db.remove_transaction(1, &tx1).is_ok());
+        assert_eq!(
+            tx_db.get_transactions(1, Range::new(0, 20)).unwrap(),
+            vec![tx2]
+        );
+    }
 }
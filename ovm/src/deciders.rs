@@ -9,6 +9,7 @@ pub mod ownership_decider;
 pub mod preimage_exists_decider;
 pub mod signed_by_decider;
 pub mod there_exists_such_that_decider;
+pub mod timeout_decider;
 pub mod verify_tx_decider;
 
 pub use self::and_decider::AndDecider;
@@ -22,4 +23,5 @@ pub use self::ownership_decider::OwnershipDecider;
 pub use self::preimage_exists_decider::PreimageExistsDecider;
 pub use self::signed_by_decider::{SignedByDecider, Verifier as SignVerifier};
 pub use self::there_exists_such_that_decider::ThereExistsSuchThatDecider;
+pub use self::timeout_decider::TimeoutDecider;
 pub use self::verify_tx_decider::VerifyTxDecider;
@@ -10,18 +10,18 @@ pub struct ForAllSuchThatDecider {}
 impl ForAllSuchThatDecider {
     fn get_decision(
         inputs: &[PropertyInput],
-        false_decision: Decision,
+        false_decision: Option<Decision>,
         true_decisions: Vec<Decision>,
         undecided: bool,
     ) -> Result<Decision, Error> {
-        if !false_decision.get_outcome() && undecided {
+        if false_decision.is_none() && undecided {
             return Err(Error::from(ErrorKind::Undecided));
         }
         let mut justification = vec![ImplicationProofElement::new(
             DeciderManager::for_all_such_that_decider_raw(inputs),
             None,
         )];
-        if false_decision.get_outcome() {
+        if let Some(false_decision) = &false_decision {
             justification.extend(false_decision.get_implication_proof().clone())
         } else {
             for decision in true_decisions {
@@ -29,7 +29,7 @@ impl ForAllSuchThatDecider {
             }
         }
 
-        Ok(Decision::new(!false_decision.get_outcome(), justification))
+        Ok(Decision::new(false_decision.is_none(), justification))
     }
 }
 
@@ -51,14 +51,14 @@ impl Decider for ForAllSuchThatDecider {
         let quantifier_result: QuantifierResult = decider.get_all_quantified(&quantifier);
 
         let mut any_undecided: bool = false;
-        let mut false_decision: Decision = Decision::new(false, vec![]);
+        let mut false_decision: Option<Decision> = None;
         let mut true_decisions: Vec<Decision> = vec![];
         for res in quantifier_result.get_results() {
             decider.set_variable(placeholder.clone(), res.clone());
             let decision_result = property.decide(decider);
             if let Ok(decision) = decision_result {
                 if !decision.get_outcome() {
-                    false_decision = decision;
+                    false_decision = Some(decision);
                     break;
                 }
                 true_decisions.push(decision)
@@ -82,34 +82,77 @@ mod tests {
     use crate::db::HashPreimageDb;
     use crate::deciders::preimage_exists_decider::Verifier;
     use crate::property_executor::PropertyExecutor;
-    use crate::types::{Decider, Decision, Integer, PropertyInput};
+    use crate::types::{Decider, Decision, Integer, Property, PropertyInput};
     use crate::DeciderManager;
     use bytes::Bytes;
     use plasma_core::data_structure::Range;
     use plasma_db::impls::kvs::CoreDbMemoryImpl;
 
-    #[test]
-    fn test_decide() {
-        let property = DeciderManager::for_all_such_that_decider(
+    fn create_property(inner: Property) -> Property {
+        DeciderManager::for_all_such_that_decider(
             DeciderManager::q_range(vec![PropertyInput::ConstantRange(Range::new(5, 20))]),
             Bytes::from("n"),
             DeciderManager::for_all_such_that_decider(
                 DeciderManager::q_hash(vec![PropertyInput::Placeholder(Bytes::from("n"))]),
                 Bytes::from("h"),
-                DeciderManager::preimage_exists_decider(vec![PropertyInput::Placeholder(
-                    Bytes::from("h"),
-                )]),
+                inner,
             ),
-        );
+        )
+    }
+
+    fn create_decider(preimages: std::ops::Range<u64>) -> PropertyExecutor<CoreDbMemoryImpl> {
         let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
         let db = HashPreimageDb::new(decider.get_db());
-        for i in 5..20 {
+        for i in preimages {
             let integer = Integer(i);
             assert!(db
                 .store_witness(Verifier::static_hash(&integer.into()), &integer.into())
                 .is_ok());
         }
+        decider
+    }
+
+    fn preimage_exists() -> Property {
+        DeciderManager::preimage_exists_decider(vec![PropertyInput::Placeholder(Bytes::from("h"))])
+    }
+
+    #[test]
+    fn test_decide() {
+        let property = create_property(preimage_exists());
+        let decider = create_decider(5..20);
         let decided: Decision = ForAllSuchThatDecider::decide(&decider, &property.inputs).unwrap();
         assert_eq!(decided.get_outcome(), true);
     }
+
+    #[test]
+    fn test_decide_false() {
+        // the inner property is false for every n, and one false decision decides the whole
+        let property = create_property(DeciderManager::not_decider(preimage_exists()));
+        let decider = create_decider(5..20);
+        let decided: Decision = ForAllSuchThatDecider::decide(&decider, &property.inputs).unwrap();
+        assert_eq!(decided.get_outcome(), false);
+        // it is false even if other results are undecided
+        let decider = create_decider(19..20);
+        let decided: Decision = ForAllSuchThatDecider::decide(&decider, &property.inputs).unwrap();
+        assert_eq!(decided.get_outcome(), false);
+    }
+
+    #[test]
+    fn test_decide_undecided() {
+        // preimages of 10..20 are unknown
+        let property = create_property(preimage_exists());
+        let decider = create_decider(5..10);
+        assert!(ForAllSuchThatDecider::decide(&decider, &property.inputs).is_err());
+    }
+
+    #[test]
+    fn test_false_decision_is_justified_by_false_result() {
+        // regression: a false result used to be ignored and the for-all was decided as true
+        let property = create_property(DeciderManager::not_decider(preimage_exists()));
+        let decider = create_decider(5..6);
+        let decided: Decision = ForAllSuchThatDecider::decide(&decider, &property.inputs).unwrap();
+        assert_eq!(decided.get_outcome(), false);
+        // the outer for-all, the inner for-all, the not and the preimage of the false result
+        assert_eq!(decided.get_implication_proof().len(), 4);
+    }
 }
@@ -3,7 +3,6 @@ use crate::deciders::signed_by_decider::Verifier;
 use crate::error::{Error, ErrorKind};
 use crate::property_executor::PropertyExecutor;
 use crate::types::{Decider, Decision, PropertyInput};
-use bytes::Bytes;
 use plasma_db::traits::kvs::KeyValueStore;
 
 /// OwnershipInput {
@@ -42,7 +41,7 @@ impl Decider for OwnershipDecider {
             return Err(Error::from(ErrorKind::CannotDecide));
         }
         for tx in txs.iter() {
-            if Verifier::recover(tx.get_signature(), &Bytes::from(tx.to_body_abi())) == owner {
+            if Verifier::recover(&tx.get_raw_signature(), &tx.get_signed_message()) == owner {
                 return Ok(Decision::new(true, vec![]));
             }
         }
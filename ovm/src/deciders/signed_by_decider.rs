@@ -46,6 +46,17 @@ impl Verifier {
             .address()
             .into()
     }
+    /// Recovers signer without panicking on malformed signatures.
+    pub fn try_recover(sig_bytes: &Bytes, message: &Bytes) -> Option<Address> {
+        if sig_bytes.len() != 65 {
+            return None;
+        }
+        let signature: Signature = bytes_to_signature(sig_bytes);
+        signature
+            .recover(hash(message).as_bytes())
+            .ok()
+            .map(|public| public.address().into())
+    }
     pub fn sign(key: &SecretKey, message: &Bytes) -> Bytes {
         signature_to_bytes(&key.sign(hash(message).as_bytes()).unwrap())
     }
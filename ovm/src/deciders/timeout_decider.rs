@@ -0,0 +1,66 @@
+use crate::error::Error;
+use crate::property_executor::PropertyExecutor;
+use crate::types::{Decider, Decision, ImplicationProofElement, PropertyInput};
+use crate::DeciderManager;
+use bytes::Bytes;
+use plasma_db::traits::kvs::KeyValueStore;
+
+/// Decides true once the "now" variable reaches the timeout.
+/// It's false while "now" isn't set.
+pub struct TimeoutDecider {}
+
+impl Default for TimeoutDecider {
+    fn default() -> Self {
+        TimeoutDecider {}
+    }
+}
+
+impl Decider for TimeoutDecider {
+    fn decide<T: KeyValueStore>(
+        decider: &PropertyExecutor<T>,
+        inputs: &[PropertyInput],
+    ) -> Result<Decision, Error> {
+        let timeout = decider.get_variable(&inputs[0]).to_integer();
+        let now = decider
+            .try_get_variable(&Bytes::from("now"))
+            .map(|now| now.to_integer());
+        if now.map_or(false, |now| now.0 >= timeout.0) {
+            Ok(Decision::new(
+                true,
+                vec![ImplicationProofElement::new(
+                    DeciderManager::timeout_decider(inputs.to_vec()),
+                    None,
+                )],
+            ))
+        } else {
+            Ok(Decision::new(false, vec![]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::property_executor::PropertyExecutor;
+    use crate::types::{Integer, PropertyInput, QuantifierResultItem};
+    use crate::DeciderManager;
+    use bytes::Bytes;
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+
+    #[test]
+    fn test_decide() {
+        let property =
+            DeciderManager::timeout_decider(vec![PropertyInput::ConstantInteger(Integer(100))]);
+        let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
+        assert!(!decider.decide(&property).unwrap().get_outcome());
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(99)),
+        );
+        assert!(!decider.decide(&property).unwrap().get_outcome());
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(100)),
+        );
+        assert!(decider.decide(&property).unwrap().get_outcome());
+    }
+}
@@ -1,6 +1,11 @@
 use crate::error::Error;
 use crate::property_executor::PropertyExecutor;
-use crate::types::{Decider, Decision, PropertyInput};
+use crate::statements::plasma::verify_fill;
+use crate::types::{Decider, Decision, Integer, PropertyInput};
+use abi_utils::Decodable;
+use bytes::Bytes;
+use plasma_core::data_structure::batch_transaction::decode_batch_body;
+use plasma_core::data_structure::TransactionParams;
 use plasma_db::traits::kvs::KeyValueStore;
 
 pub struct VerifyTxDecider {}
@@ -11,14 +16,46 @@ impl Default for VerifyTxDecider {
     }
 }
 
+impl VerifyTxDecider {
+    /// Returns entries signed by the message, which is the body of a transaction or a batch.
+    fn decode_entries(message: &Bytes) -> Vec<TransactionParams> {
+        decode_batch_body(message)
+            .or_else(|| TransactionParams::from_abi(message).ok().map(|p| vec![p]))
+            .unwrap_or_else(|| vec![])
+    }
+}
+
 impl Decider for VerifyTxDecider {
     fn decide<T: KeyValueStore>(
         decider: &PropertyExecutor<T>,
         inputs: &[PropertyInput],
     ) -> Result<Decision, Error> {
-        let _tx_bytes = decider.get_variable(&inputs[0]).to_bytes();
-        //let _tx = Transaction::from_abi(&tx_bytes).expect("inputs[0] should be Transaction.");
-        // TODO: check tx.params
-        Ok(Decision::new(true, vec![]))
+        let tx_bytes = decider.get_variable(&inputs[0]).to_bytes();
+        let token = decider.get_variable(&inputs[1]).to_address();
+        let amount = decider.get_variable(&inputs[2]).to_integer();
+        let maker = decider.get_variable(&inputs[3]).to_address();
+        // inputs[4] is the expiry block. The order can't be taken if the next block is unknown.
+        if let Some(expiry) = inputs.get(4) {
+            let expiry = decider.get_variable(expiry).to_integer();
+            if expiry.0 > 0 {
+                let next_block = decider
+                    .try_get_variable(&Bytes::from("next_block"))
+                    .map(|b| b.to_integer());
+                if next_block.map_or(true, |b| b.0 > expiry.0) {
+                    return Ok(Decision::new(false, vec![]));
+                }
+            }
+        }
+        let fill_size = inputs
+            .get(5)
+            .map_or(Integer(0), |i| decider.get_variable(i).to_integer());
+        let state_update = match decider.try_get_variable(&Bytes::from("state_update")) {
+            Some(state_update) => state_update.to_state_update(),
+            None => return Ok(Decision::new(false, vec![])),
+        };
+        let filled = Self::decode_entries(&tx_bytes)
+            .iter()
+            .any(|entry| verify_fill(entry, &state_update, token, amount, maker, fill_size));
+        Ok(Decision::new(filled, vec![]))
     }
 }
@@ -26,6 +26,8 @@ pub enum ErrorKind {
     Undecided,
     #[fail(display = "CannotDecide")]
     CannotDecide,
+    #[fail(display = "Invalid Pattern")]
+    InvalidPattern,
 }
 
 #[derive(Debug)]
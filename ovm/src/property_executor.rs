@@ -1,7 +1,7 @@
 use crate::deciders::{
     AndDecider, ForAllSuchThatDecider, HasLowerNonceDecider, IncludedAtBlockDecider,
     IsDeprecatedDecider, NotDecider, OrDecider, OwnershipDecider, PreimageExistsDecider,
-    SignedByDecider, ThereExistsSuchThatDecider, VerifyTxDecider,
+    SignedByDecider, ThereExistsSuchThatDecider, TimeoutDecider, VerifyTxDecider,
 };
 use crate::error::Error;
 use crate::quantifiers::{
@@ -16,7 +16,7 @@ use bytes::Bytes;
 use ethereum_types::Address;
 use plasma_db::prelude::*;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Mutex, MutexGuard, RwLock};
 
 fn get_address(address: &str) -> Address {
     Address::from_slice(&hex::decode(address).unwrap())
@@ -126,6 +126,9 @@ impl DeciderManager {
     pub fn verify_tx(inputs: Vec<PropertyInput>) -> Property {
         Property::new(Self::get_decider_address(11), inputs)
     }
+    pub fn timeout_decider(inputs: Vec<PropertyInput>) -> Property {
+        Property::new(Self::get_decider_address(12), inputs)
+    }
     pub fn q_range(inputs: Vec<PropertyInput>) -> Property {
         Property::new(Self::get_decider_address(20), inputs)
     }
@@ -185,6 +188,9 @@ pub struct PropertyExecutor<KVS: KeyValueStore> {
     db: KVS,
     range_db: RangeDbImpl<KVS>,
     variables: RwLock<HashMap<Bytes, QuantifierResultItem>>,
+    // Held while a state transition sets variables and decides, so that
+    // concurrent transitions don't overwrite each other's variables.
+    transition_lock: Mutex<()>,
     pub options: PropertyExecuterOptions,
 }
 
@@ -197,6 +203,7 @@ where
             db: KVS::open("kvs"),
             range_db: RangeDbImpl::from(KVS::open("range")),
             variables: RwLock::new(Default::default()),
+            transition_lock: Mutex::new(()),
             options: Default::default(),
         }
     }
@@ -212,6 +219,7 @@ where
             db: KVS::open(&format!("{}-{}", db_name, "kvs")),
             range_db: RangeDbImpl::from(KVS::open(&format!("{}-{}", db_name, "range"))),
             variables: RwLock::new(Default::default()),
+            transition_lock: Mutex::new(()),
             options,
         }
     }
@@ -221,6 +229,16 @@ impl<KVS> PropertyExecutor<KVS>
 where
     KVS: KeyValueStore,
 {
+    /// Creates an executor on stores which are already open, e.g. buckets of a shared database.
+    pub fn from_stores(db: KVS, range_db: KVS, options: PropertyExecuterOptions) -> Self {
+        PropertyExecutor {
+            db,
+            range_db: RangeDbImpl::from(range_db),
+            variables: RwLock::new(Default::default()),
+            transition_lock: Mutex::new(()),
+            options,
+        }
+    }
     pub fn get_db(&self) -> &KVS {
         &self.db
     }
@@ -230,6 +248,17 @@ where
     pub fn set_variable(&self, placeholder: Bytes, result: QuantifierResultItem) {
         self.variables.write().unwrap().insert(placeholder, result);
     }
+    pub fn remove_variable(&self, placeholder: &Bytes) {
+        self.variables.write().unwrap().remove(placeholder);
+    }
+    /// Returns None instead of panicking if the variable isn't set.
+    pub fn try_get_variable(&self, placeholder: &Bytes) -> Option<QuantifierResultItem> {
+        self.variables.read().unwrap().get(placeholder).cloned()
+    }
+    /// Locks variables for a state transition until the guard is dropped.
+    pub fn lock_transition(&self) -> MutexGuard<()> {
+        self.transition_lock.lock().unwrap()
+    }
     pub fn get_variable(&self, placeholder: &PropertyInput) -> QuantifierResultItem {
         match placeholder {
             PropertyInput::Placeholder(placeholder) => self
@@ -281,6 +310,8 @@ where
             ThereExistsSuchThatDecider::decide(self, &property.inputs)
         } else if decider_id == DECIDER_LIST[11] {
             VerifyTxDecider::decide(self, &property.inputs)
+        } else if decider_id == DECIDER_LIST[12] {
+            TimeoutDecider::decide(self, &property.inputs)
         } else {
             panic!("unknown decider")
         }
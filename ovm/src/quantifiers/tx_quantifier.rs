@@ -1,7 +1,6 @@
 use crate::db::TransactionDb;
 use crate::property_executor::PropertyExecutor;
 use crate::types::{PropertyInput, QuantifierResult, QuantifierResultItem};
-use bytes::Bytes;
 use plasma_db::traits::kvs::KeyValueStore;
 
 pub struct TxQuantifier {}
@@ -28,7 +27,7 @@ impl TxQuantifier {
             QuantifierResult::new(
                 txs.iter()
                     .filter(move |tx| state_update.get_range().is_subrange(&tx.get_range()))
-                    .map(|tx| QuantifierResultItem::Bytes(Bytes::from(tx.to_body_abi())))
+                    .map(|tx| QuantifierResultItem::Bytes(tx.get_signed_message()))
                     .collect(),
                 true,
             )
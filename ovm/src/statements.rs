@@ -1,5 +1,7 @@
+pub mod hash_lock;
 pub mod plasma;
 pub mod state_channel;
 
+pub use self::hash_lock::{create_hash_lock_property, create_hash_lock_settlement_property};
 pub use self::plasma::plasma_checkpoint_property;
 pub use self::state_channel::create_state_channel_property;
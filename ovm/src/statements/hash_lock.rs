@@ -0,0 +1,66 @@
+use crate::types::{Integer, Property, PropertyInput};
+use crate::DeciderManager;
+use ethereum_types::H256;
+
+/// Condition of a conditional payment.
+/// It's decided true once the preimage of hash is revealed.
+pub fn create_hash_lock_property(hash: H256) -> Property {
+    DeciderManager::preimage_exists_decider(vec![PropertyInput::ConstantH256(hash)])
+}
+
+/// Settlement of a locked amount on exit.
+/// It's decided true once the preimage is revealed or the timeout passes,
+/// and can't be decided while neither happened.
+pub fn create_hash_lock_settlement_property(hash: H256, timeout: Integer) -> Property {
+    DeciderManager::or_decider(
+        create_hash_lock_property(hash),
+        DeciderManager::timeout_decider(vec![PropertyInput::ConstantInteger(timeout)]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_hash_lock_property, create_hash_lock_settlement_property};
+    use crate::db::HashPreimageDb;
+    use crate::deciders::preimage_exists_decider::Verifier;
+    use crate::property_executor::PropertyExecutor;
+    use crate::types::{Integer, QuantifierResultItem};
+    use bytes::Bytes;
+    use plasma_db::impls::kvs::CoreDbMemoryImpl;
+
+    #[test]
+    fn test_hash_lock() {
+        let preimage = Bytes::from("secret");
+        let hash = Verifier::static_hash(&preimage);
+        let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
+        let db = HashPreimageDb::new(decider.get_db());
+        assert!(db.store_witness(hash, &preimage).is_ok());
+        let decision = decider.decide(&create_hash_lock_property(hash)).unwrap();
+        assert!(decision.get_outcome());
+    }
+
+    #[test]
+    fn test_hash_lock_settlement() {
+        let preimage = Bytes::from("secret");
+        let hash = Verifier::static_hash(&preimage);
+        let property = create_hash_lock_settlement_property(hash, Integer(100));
+        let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(99)),
+        );
+        assert!(decider.decide(&property).is_err());
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(100)),
+        );
+        assert!(decider.decide(&property).unwrap().get_outcome());
+        decider.set_variable(
+            Bytes::from("now"),
+            QuantifierResultItem::Integer(Integer(99)),
+        );
+        let db = HashPreimageDb::new(decider.get_db());
+        assert!(db.store_witness(hash, &preimage).is_ok());
+        assert!(decider.decide(&property).unwrap().get_outcome());
+    }
+}
@@ -1,9 +1,10 @@
 use super::atomic_state::create_atomic_state;
 use crate::property_executor::PropertyExecutor;
-use crate::types::{Integer, Property, PropertyInput, StateUpdate};
+use crate::types::{Integer, Property, PropertyInput};
 use crate::DeciderManager;
 use bytes::Bytes;
 use ethereum_types::Address;
+use plasma_core::data_structure::Range;
 use plasma_db::traits::kvs::KeyValueStore;
 
 pub fn create_channel_state_object_for_variables<KVS: KeyValueStore>(
@@ -16,15 +17,20 @@ pub fn create_channel_state_object_for_variables<KVS: KeyValueStore>(
     create_channel_state_object(
         my_address,
         counter_party_address,
-        corresponding_state_update,
+        corresponding_state_update.get_block_number(),
+        corresponding_state_update.get_deposit_contract_address(),
+        corresponding_state_update.get_range(),
     )
 }
 
-/// channel property for Plasma
+/// channel property for Plasma.
+/// The corresponding range is the range of the counter party locked at the block.
 pub fn create_channel_state_object(
     my_address: Address,
     counter_party_address: Address,
-    corresponding_state_update: StateUpdate,
+    corresponding_block_number: Integer,
+    corresponding_deposit_contract_address: Address,
+    corresponding_range: Range,
 ) -> Property {
     /*
      * There exists tx such that state_update.is_same_coin_range(tx):
@@ -52,9 +58,9 @@ pub fn create_channel_state_object(
         )])),
         PropertyInput::ConstantBytes(Bytes::from("tx")),
         PropertyInput::ConstantProperty(create_atomic_state(
-            corresponding_state_update.get_block_number(),
-            corresponding_state_update.get_deposit_contract_address(),
-            corresponding_state_update.get_range(),
+            corresponding_block_number,
+            corresponding_deposit_contract_address,
+            corresponding_range,
             vec![
                 // TODO: This should be PropertyFactory address
                 PropertyInput::ConstantInteger(Integer(0)),
@@ -88,7 +94,6 @@ mod tests {
     use crate::deciders::signed_by_decider::Verifier as SignatureVerifier;
     use crate::property_executor::PropertyExecutor;
     use crate::types::{Property, QuantifierResultItem, StateUpdate};
-    use crate::DeciderManager;
     use abi_utils::abi::Encodable;
     use abi_utils::Integer;
     use bytes::Bytes;
@@ -107,14 +112,13 @@ mod tests {
         alice: Address,
         bob: Address,
     ) -> (Property, StateUpdate) {
-        let dammy_property = DeciderManager::preimage_exists_decider(vec![]);
-        let corresponding_state_update = StateUpdate::new(
+        let property = create_channel_state_object(
+            alice,
+            bob,
             block_number,
             corresponding_deposit_contract_address,
             corresponding_range,
-            dammy_property,
         );
-        let property = create_channel_state_object(alice, bob, corresponding_state_update.clone());
         (
             property.clone(),
             StateUpdate::new(
@@ -1,20 +1,35 @@
 use crate::statements::plasma::create_plasma_checkpoint_property_for_variables;
-use crate::types::{Integer, Property, PropertyInput};
+use crate::types::{
+    match_state_object_kind, state_object_kind::EXCHANGED_KIND, Integer, Property, PropertyInput,
+    StateUpdate,
+};
 use crate::DeciderManager;
+use abi_utils::Decodable;
 use bytes::Bytes;
 use ethereum_types::Address;
-use plasma_core::data_structure::Range;
+use plasma_core::data_structure::{Range, TransactionParams};
+
+/// Expiry block of orders which never expire.
+pub const NO_EXPIRY: Integer = Integer(0);
+/// Fill size of orders which must be taken whole.
+pub const WHOLE_FILL_ONLY: Integer = Integer(0);
 
 /// Pre-swap property for Plasma
+/// The order can't be taken in blocks after expiry_block. None never expires.
+/// If fill_size is specified, amount is the price of fill_size of the range and
+/// any sub-range can be taken for the proportional amount. The rest stays the same order.
+/// The maker can cancel the order anytime by SignedBy branch.
 pub fn create_making_order_state_object(
     my_address: Address,
     token_type: Address,
     amount: Integer,
+    expiry_block: Option<Integer>,
+    fill_size: Option<Integer>,
 ) -> Property {
     /*
      * There exists tx such that state_update.is_same_coin_range(tx):
      *   Or(
-     *     verify_state_transition(OFFLINE_SWAP, tx, token_type, amount),
+     *     verify_state_transition(OFFLINE_SWAP, tx, token_type, amount, expiry_block, fill_size),
      *     SignedBy(tx, my_address)
      *   )
      *
@@ -30,6 +45,8 @@ pub fn create_making_order_state_object(
                 PropertyInput::ConstantAddress(token_type),
                 PropertyInput::ConstantInteger(amount),
                 PropertyInput::ConstantAddress(my_address),
+                PropertyInput::ConstantInteger(expiry_block.unwrap_or(NO_EXPIRY)),
+                PropertyInput::ConstantInteger(fill_size.unwrap_or(WHOLE_FILL_ONLY)),
             ]),
             DeciderManager::signed_by_decider(vec![
                 PropertyInput::ConstantAddress(my_address),
@@ -39,6 +56,47 @@ pub fn create_making_order_state_object(
     ])
 }
 
+/// Returns the amount of c_token paid for filled of the order priced amount for fill_size.
+/// It's rounded up so that the maker never receives less than the price.
+pub fn get_fill_price(amount: Integer, fill_size: Integer, filled: u64) -> u64 {
+    if fill_size.0 == 0 {
+        return amount.0;
+    }
+    let price = u128::from(amount.0) * u128::from(filled);
+    ((price + u128::from(fill_size.0) - 1) / u128::from(fill_size.0)) as u64
+}
+
+/// Whether the entry takes a part of the order in state_update and pays the proportional amount to maker.
+/// An order without fill_size must be taken whole.
+pub fn verify_fill(
+    entry: &TransactionParams,
+    state_update: &StateUpdate,
+    token: Address,
+    amount: Integer,
+    maker: Address,
+    fill_size: Integer,
+) -> bool {
+    let range = entry.get_range();
+    if entry.get_deposit_contract_address() != state_update.get_deposit_contract_address()
+        || !state_update.get_range().is_subrange(&range)
+        || (fill_size.0 == 0 && range != state_update.get_range())
+    {
+        return false;
+    }
+    let captures = match Property::from_abi(entry.get_parameters())
+        .ok()
+        .and_then(|p| match_state_object_kind(EXCHANGED_KIND, &p))
+    {
+        Some(captures) => captures,
+        None => return false,
+    };
+    captures.get_address("maker") == Some(maker)
+        && captures.get_address("c_token") == Some(token)
+        && captures.get_range("c_range").map_or(false, |c_range| {
+            c_range.get_amount() >= get_fill_price(amount, fill_size, range.get_amount())
+        })
+}
+
 pub fn create_offline_atomic_state(
     deposit_contract_address: Address,
     coin_range: Range,
@@ -300,4 +358,121 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.ok().unwrap().get_outcome());
     }
+
+    #[test]
+    fn test_making_order_expires() {
+        let maker = Address::from([1; 20]);
+        let c_token = Address::from([2; 20]);
+        let order = StateUpdate::new(
+            Integer(1),
+            Address::zero(),
+            Range::new(0, 10),
+            create_making_order_state_object(maker, c_token, Integer(10), Some(Integer(5)), None),
+        );
+        let expired = |next_block: Option<u64>| {
+            let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
+            let tx_params = TransactionParams::new(
+                Address::zero(),
+                Range::new(0, 10),
+                Bytes::from(
+                    create_taking_order_state_object(
+                        Address::random(),
+                        maker,
+                        c_token,
+                        Range::new(0, 10),
+                    )
+                    .to_abi(),
+                ),
+            );
+            decider.set_variable(
+                Bytes::from("tx"),
+                QuantifierResultItem::Bytes(Bytes::from(tx_params.to_abi())),
+            );
+            decider.set_variable(
+                Bytes::from("state_update"),
+                QuantifierResultItem::StateUpdate(order.clone()),
+            );
+            if let Some(next_block) = next_block {
+                decider.set_variable(
+                    Bytes::from("next_block"),
+                    QuantifierResultItem::Integer(Integer(next_block)),
+                );
+            }
+            let property = DeciderManager::verify_tx(vec![
+                PropertyInput::Placeholder(Bytes::from("tx")),
+                PropertyInput::ConstantAddress(c_token),
+                PropertyInput::ConstantInteger(Integer(10)),
+                PropertyInput::ConstantAddress(maker),
+                PropertyInput::ConstantInteger(Integer(5)),
+            ]);
+            !decider.decide(&property).unwrap().get_outcome()
+        };
+        assert!(!expired(Some(5)));
+        assert!(expired(Some(6)));
+        // the order can't be taken if the next block is unknown
+        assert!(expired(None));
+    }
+
+    #[test]
+    fn test_decide_making_order_fill() {
+        let maker = Address::from([1; 20]);
+        let c_token = Address::from([2; 20]);
+        let order = StateUpdate::new(
+            Integer(1),
+            Address::zero(),
+            Range::new(0, 100),
+            create_making_order_state_object(
+                maker,
+                c_token,
+                Integer(30),
+                Some(Integer(5)),
+                Some(Integer(10)),
+            ),
+        );
+        let verify_tx = |taken: Range, c_amount: u64, next_block: u64| {
+            let decider: PropertyExecutor<CoreDbMemoryImpl> = Default::default();
+            let tx_params = TransactionParams::new(
+                Address::zero(),
+                taken,
+                Bytes::from(
+                    create_taking_order_state_object(
+                        Address::random(),
+                        maker,
+                        c_token,
+                        Range::new(0, c_amount),
+                    )
+                    .to_abi(),
+                ),
+            );
+            decider.set_variable(
+                Bytes::from("tx"),
+                QuantifierResultItem::Bytes(Bytes::from(tx_params.to_abi())),
+            );
+            decider.set_variable(
+                Bytes::from("state_update"),
+                QuantifierResultItem::StateUpdate(order.clone()),
+            );
+            decider.set_variable(
+                Bytes::from("next_block"),
+                QuantifierResultItem::Integer(Integer(next_block)),
+            );
+            let property = DeciderManager::verify_tx(vec![
+                PropertyInput::Placeholder(Bytes::from("tx")),
+                PropertyInput::ConstantAddress(c_token),
+                PropertyInput::ConstantInteger(Integer(30)),
+                PropertyInput::ConstantAddress(maker),
+                PropertyInput::ConstantInteger(Integer(5)),
+                PropertyInput::ConstantInteger(Integer(10)),
+            ]);
+            decider.decide(&property).unwrap().get_outcome()
+        };
+        assert_eq!(get_fill_price(Integer(30), Integer(10), 25), 75);
+        assert_eq!(get_fill_price(Integer(30), Integer(10), 1), 3);
+        assert_eq!(get_fill_price(Integer(10), Integer(3), 1), 4);
+        assert!(verify_tx(Range::new(0, 100), 300, 5));
+        assert!(verify_tx(Range::new(20, 45), 75, 5));
+        assert!(!verify_tx(Range::new(20, 45), 74, 5));
+        assert!(!verify_tx(Range::new(90, 110), 300, 5));
+        assert!(!verify_tx(Range::new(20, 45), 75, 6));
+    }
 }
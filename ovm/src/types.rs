@@ -1,6 +1,8 @@
 pub mod core;
 pub mod decision_value;
 pub mod property_input;
+pub mod property_pattern;
+pub mod state_object_kind;
 pub mod state_update;
 pub mod state_update_list;
 
@@ -10,5 +12,10 @@ pub use self::core::{
 };
 pub use self::decision_value::DecisionValue;
 pub use self::property_input::PropertyInput;
+pub use self::property_pattern::{Captures, InputType, PropertyPattern};
+pub use self::state_object_kind::{
+    match_state_object, match_state_object_kind, register_state_object_kind, StateObjectKind,
+    StateObjectKindRegistry,
+};
 pub use self::state_update::StateUpdate;
 pub use self::state_update_list::StateUpdateList;
@@ -33,7 +33,7 @@ impl Property {
     }
 
     // TODO: use macro
-    fn get_decider_name(&self) -> String {
+    pub fn get_decider_name(&self) -> String {
         let decider = self.decider;
         if decider == DECIDER_LIST[0] {
             "and".to_string()
@@ -0,0 +1,312 @@
+use crate::error::{Error, ErrorKind};
+use crate::types::{Integer, Property, PropertyInput, StateUpdate};
+use bytes::Bytes;
+use ethereum_types::{Address, H256};
+use plasma_core::data_structure::Range;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Type of PropertyInput. Names are the same as PropertyInput::get_type_string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputType {
+    Placeholder,
+    Address,
+    Bytes,
+    H256,
+    Integer,
+    Range,
+    Property,
+    StateUpdate,
+    Message,
+}
+
+impl InputType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "placeholder" => Some(InputType::Placeholder),
+            "address" => Some(InputType::Address),
+            "bytes" => Some(InputType::Bytes),
+            "h256" => Some(InputType::H256),
+            "integer" => Some(InputType::Integer),
+            "range" => Some(InputType::Range),
+            "property" => Some(InputType::Property),
+            "state_update" => Some(InputType::StateUpdate),
+            "message" => Some(InputType::Message),
+            _ => None,
+        }
+    }
+
+    pub fn matches(self, input: &PropertyInput) -> bool {
+        match (self, input) {
+            (InputType::Placeholder, PropertyInput::Placeholder(_))
+            | (InputType::Address, PropertyInput::ConstantAddress(_))
+            | (InputType::Bytes, PropertyInput::ConstantBytes(_))
+            | (InputType::H256, PropertyInput::ConstantH256(_))
+            | (InputType::Integer, PropertyInput::ConstantInteger(_))
+            | (InputType::Range, PropertyInput::ConstantRange(_))
+            | (InputType::Property, PropertyInput::ConstantProperty(_))
+            | (InputType::StateUpdate, PropertyInput::ConstantStateUpdate(_))
+            | (InputType::Message, PropertyInput::ConstantMessage(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Inputs captured by PropertyPattern.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Captures {
+    values: HashMap<String, PropertyInput>,
+}
+
+impl Captures {
+    pub fn get(&self, name: &str) -> Option<&PropertyInput> {
+        self.values.get(name)
+    }
+
+    pub fn get_address(&self, name: &str) -> Option<Address> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantAddress(address)) => Some(*address),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, name: &str) -> Option<Bytes> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantBytes(bytes)) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_h256(&self, name: &str) -> Option<H256> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantH256(h256)) => Some(*h256),
+            _ => None,
+        }
+    }
+
+    pub fn get_integer(&self, name: &str) -> Option<Integer> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantInteger(integer)) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn get_range(&self, name: &str) -> Option<Range> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantRange(range)) => Some(*range),
+            _ => None,
+        }
+    }
+
+    pub fn get_property(&self, name: &str) -> Option<Property> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantProperty(property)) => Some(property.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_state_update(&self, name: &str) -> Option<StateUpdate> {
+        match self.get(name) {
+            Some(PropertyInput::ConstantStateUpdate(state_update)) => Some(state_update.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Declarative pattern of Property.
+/// The text form is
+/// - `_` matches any input.
+/// - `address`, `integer`, ... matches an input of the type.
+/// - `?name:type` matches an input of the type and captures it as name. `?name` captures any input.
+/// - `decider(p1, p2, ...)` matches a property of the decider whose inputs match p1, p2, ...
+///
+/// ```ignore
+/// let pattern = PropertyPattern::parse(
+///     "there_exists_such_that(q_tx(placeholder), bytes, signed_by(?owner:address, placeholder))",
+/// )?;
+/// let owner = pattern.match_property(&property).and_then(|c| c.get_address("owner"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyPattern {
+    Any,
+    Type(InputType),
+    Capture(String, Option<InputType>),
+    Property(String, Vec<PropertyPattern>),
+}
+
+impl PropertyPattern {
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        Parser::new(pattern).parse()
+    }
+
+    /// Returns captured inputs if the property matches.
+    pub fn match_property(&self, property: &Property) -> Option<Captures> {
+        let mut captures = Captures::default();
+        if self.match_input(
+            &PropertyInput::ConstantProperty(property.clone()),
+            &mut captures,
+        ) {
+            Some(captures)
+        } else {
+            None
+        }
+    }
+
+    fn match_input(&self, input: &PropertyInput, captures: &mut Captures) -> bool {
+        match self {
+            PropertyPattern::Any => true,
+            PropertyPattern::Type(input_type) => input_type.matches(input),
+            PropertyPattern::Capture(name, input_type) => {
+                if input_type.map_or(true, |t| t.matches(input)) {
+                    captures.values.insert(name.clone(), input.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+            PropertyPattern::Property(decider_name, patterns) => match input {
+                PropertyInput::ConstantProperty(property) => {
+                    &property.get_decider_name() == decider_name
+                        && patterns.len() == property.inputs.len()
+                        && patterns
+                            .iter()
+                            .zip(property.inputs.iter())
+                            .all(|(p, i)| p.match_input(i, captures))
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(pattern: &'a str) -> Self {
+        Self {
+            chars: pattern.chars().peekable(),
+        }
+    }
+
+    fn parse(mut self) -> Result<PropertyPattern, Error> {
+        let pattern = self.parse_pattern()?;
+        self.skip_whitespace();
+        if self.chars.peek().is_some() {
+            return Err(Error::from(ErrorKind::InvalidPattern));
+        }
+        Ok(pattern)
+    }
+
+    fn parse_pattern(&mut self) -> Result<PropertyPattern, Error> {
+        if self.eat('?') {
+            let name = self.parse_ident()?;
+            if self.eat(':') {
+                let input_type = self.parse_ident()?;
+                return InputType::from_name(&input_type)
+                    .map(|t| PropertyPattern::Capture(name, Some(t)))
+                    .ok_or_else(|| Error::from(ErrorKind::InvalidPattern));
+            }
+            return Ok(PropertyPattern::Capture(name, None));
+        }
+        let ident = self.parse_ident()?;
+        if ident == "_" {
+            return Ok(PropertyPattern::Any);
+        }
+        if self.eat('(') {
+            let mut patterns = vec![];
+            if !self.eat(')') {
+                loop {
+                    patterns.push(self.parse_pattern()?);
+                    if self.eat(')') {
+                        break;
+                    }
+                    if !self.eat(',') {
+                        return Err(Error::from(ErrorKind::InvalidPattern));
+                    }
+                }
+            }
+            return Ok(PropertyPattern::Property(ident, patterns));
+        }
+        InputType::from_name(&ident)
+            .map(PropertyPattern::Type)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidPattern))
+    }
+
+    fn parse_ident(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let mut ident = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                ident.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if ident.is_empty() {
+            Err(Error::from(ErrorKind::InvalidPattern))
+        } else {
+            Ok(ident)
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statements::plasma::create_ownership_state_object;
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern = PropertyPattern::parse("signed_by(?owner:address, _)").unwrap();
+        assert_eq!(
+            pattern,
+            PropertyPattern::Property(
+                "signed_by".to_string(),
+                vec![
+                    PropertyPattern::Capture("owner".to_string(), Some(InputType::Address)),
+                    PropertyPattern::Any,
+                ]
+            )
+        );
+        assert!(PropertyPattern::parse("signed_by(?owner:unknown)").is_err());
+        assert!(PropertyPattern::parse("signed_by(address").is_err());
+    }
+
+    #[test]
+    fn test_match_property() {
+        let owner = Address::random();
+        let property = create_ownership_state_object(owner);
+        let pattern = PropertyPattern::parse(
+            "there_exists_such_that(q_tx(placeholder), bytes, signed_by(?owner:address, placeholder))",
+        )
+        .unwrap();
+        let captures = pattern.match_property(&property).unwrap();
+        assert_eq!(captures.get_address("owner"), Some(owner));
+        let pattern = PropertyPattern::parse("there_exists_such_that(_, _, or(_, _))").unwrap();
+        assert_eq!(pattern.match_property(&property), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::Error;
+use crate::types::property_pattern::{Captures, PropertyPattern};
+use crate::types::Property;
+use std::sync::RwLock;
+
+pub const OWNERSHIP_KIND: &str = "ownership";
+pub const ORDER_KIND: &str = "order";
+pub const EXCHANGED_KIND: &str = "exchanged";
+
+const OWNERSHIP_PATTERN: &str =
+    "there_exists_such_that(q_tx(placeholder), bytes, signed_by(?owner:address, placeholder))";
+const ORDER_PATTERN: &str = "there_exists_such_that(q_tx(placeholder), bytes, or(verify_tx(placeholder, ?token:address, ?amount:integer, ?maker:address, ?expiry:integer, ?fill_size:integer), signed_by(address, placeholder)))";
+const EXCHANGED_PATTERN: &str = "there_exists_such_that(q_tx(placeholder), bytes, there_exists_such_that(q_property(integer, ?maker:address), bytes, there_exists_such_that(q_state_update(placeholder, ?c_token:address, ?c_range:range, placeholder), bytes, or(and(and(for_all_such_that(q_less_than(placeholder), bytes, for_all_such_that(q_block(placeholder, address, range), bytes, is_deprecated(placeholder))), included_at_block(placeholder, placeholder)), signed_by(?owner:address, placeholder)), and(not(and(for_all_such_that(q_less_than(placeholder), bytes, for_all_such_that(q_block(placeholder, address, range), bytes, is_deprecated(placeholder))), included_at_block(placeholder, placeholder))), signed_by(address, placeholder))))))";
+
+/// Named pattern of state object.
+/// Kinds capturing `?owner:address` are counted as balance of the owner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateObjectKind {
+    name: String,
+    pattern: PropertyPattern,
+}
+
+impl StateObjectKind {
+    pub fn new(name: &str, pattern: PropertyPattern) -> Self {
+        Self {
+            name: name.to_string(),
+            pattern,
+        }
+    }
+
+    pub fn parse(name: &str, pattern: &str) -> Result<Self, Error> {
+        Ok(Self::new(name, PropertyPattern::parse(pattern)?))
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_pattern(&self) -> &PropertyPattern {
+        &self.pattern
+    }
+}
+
+/// Named kinds of state object. Kinds are matched in the order of registration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateObjectKindRegistry {
+    kinds: Vec<StateObjectKind>,
+}
+
+impl StateObjectKindRegistry {
+    pub fn new(kinds: Vec<StateObjectKind>) -> Self {
+        Self { kinds }
+    }
+
+    /// Registers new kind of state object. A kind with the same name is replaced.
+    pub fn register(&mut self, kind: StateObjectKind) {
+        if let Some(registered) = self.kinds.iter_mut().find(|k| k.name == kind.name) {
+            *registered = kind;
+        } else {
+            self.kinds.push(kind);
+        }
+    }
+
+    pub fn get_kinds(&self) -> &[StateObjectKind] {
+        &self.kinds
+    }
+
+    /// Returns captures if the property matches the kind of given name.
+    pub fn match_kind(&self, name: &str, property: &Property) -> Option<Captures> {
+        self.kinds
+            .iter()
+            .find(|k| k.name == name)
+            .and_then(|k| k.pattern.match_property(property))
+    }
+
+    /// Returns the name of the first registered kind which the property matches and its captures.
+    pub fn match_property(&self, property: &Property) -> Option<(String, Captures)> {
+        self.kinds.iter().find_map(|k| {
+            k.pattern
+                .match_property(property)
+                .map(|captures| (k.name.clone(), captures))
+        })
+    }
+}
+
+lazy_static! {
+    static ref STATE_OBJECT_KINDS: RwLock<StateObjectKindRegistry> =
+        RwLock::new(StateObjectKindRegistry::new(vec![
+            StateObjectKind::parse(OWNERSHIP_KIND, OWNERSHIP_PATTERN).unwrap(),
+            StateObjectKind::parse(ORDER_KIND, ORDER_PATTERN).unwrap(),
+            StateObjectKind::parse(EXCHANGED_KIND, EXCHANGED_PATTERN).unwrap(),
+        ]));
+}
+
+/// Registers new kind of state object to the global registry. A kind with the same name is replaced.
+pub fn register_state_object_kind(kind: StateObjectKind) {
+    STATE_OBJECT_KINDS.write().unwrap().register(kind)
+}
+
+pub fn get_state_object_kinds() -> Vec<StateObjectKind> {
+    STATE_OBJECT_KINDS.read().unwrap().get_kinds().to_vec()
+}
+
+/// Returns captures if the property matches the kind of given name.
+pub fn match_state_object_kind(name: &str, property: &Property) -> Option<Captures> {
+    STATE_OBJECT_KINDS
+        .read()
+        .unwrap()
+        .match_kind(name, property)
+}
+
+/// Returns the name of the first registered kind which the property matches and its captures.
+pub fn match_state_object(property: &Property) -> Option<(String, Captures)> {
+    STATE_OBJECT_KINDS.read().unwrap().match_property(property)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PropertyInput;
+    use crate::DeciderManager;
+    use ethereum_types::Address;
+
+    #[test]
+    fn test_register_state_object_kind() {
+        let owner = Address::random();
+        let property = DeciderManager::signed_by_decider(vec![
+            PropertyInput::ConstantAddress(owner),
+            PropertyInput::placeholder("tx"),
+        ]);
+        let mut registry = StateObjectKindRegistry::new(get_state_object_kinds());
+        assert_eq!(registry.match_property(&property), None);
+        registry.register(
+            StateObjectKind::parse("test_signed_by", "signed_by(?owner:address, placeholder)")
+                .unwrap(),
+        );
+        let (name, captures) = registry.match_property(&property).unwrap();
+        assert_eq!(name, "test_signed_by");
+        assert_eq!(captures.get_address("owner"), Some(owner));
+    }
+}
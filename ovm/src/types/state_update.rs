@@ -1,7 +1,10 @@
 use crate::property_executor::PropertyExecutor;
 use crate::types::{
     core::{Property, QuantifierResultItem},
-    PropertyInput,
+    property_pattern::Captures,
+    state_object_kind::{
+        match_state_object, match_state_object_kind, EXCHANGED_KIND, ORDER_KIND, OWNERSHIP_KIND,
+    },
 };
 use crate::DecideMixin;
 use abi_derive::{AbiDecodable, AbiEncodable};
@@ -80,61 +83,65 @@ impl StateUpdate {
         self.range = Range::new(self.get_range().get_start(), end);
     }
 
+    /// Returns the name of the kind of state object and its captures.
+    pub fn get_kind(&self) -> Option<(String, Captures)> {
+        match_state_object(&self.property)
+    }
+
+    pub fn is_kind(&self, name: &str) -> bool {
+        match_state_object_kind(name, &self.property).is_some()
+    }
+
     pub fn is_ownership_state(&self) -> bool {
-        self.property.get_type_string()
-            == "there_exists_such_that(q_tx(placeholder),bytes,signed_by(address,placeholder))"
+        self.is_kind(OWNERSHIP_KIND)
     }
 
     pub fn is_order_state(&self) -> bool {
-        self.property.get_type_string() == "there_exists_such_that(q_tx(placeholder),bytes,or(verify_tx(placeholder,address,integer,address),signed_by(address,placeholder)))"
+        self.is_kind(ORDER_KIND)
     }
 
     pub fn is_exchanged_state(&self) -> bool {
-        self.property.get_type_string()
-            == "there_exists_such_that(q_tx(placeholder),bytes,there_exists_such_that(q_property(integer,address),bytes,there_exists_such_that(q_state_update(placeholder,address,range,placeholder),bytes,or(and(and(for_all_such_that(q_less_than(placeholder),bytes,for_all_such_that(q_block(placeholder,address,range),bytes,is_deprecated(placeholder))),included_at_block(placeholder,placeholder)),signed_by(address,placeholder)),and(not(and(for_all_such_that(q_less_than(placeholder),bytes,for_all_such_that(q_block(placeholder,address,range),bytes,is_deprecated(placeholder))),included_at_block(placeholder,placeholder))),signed_by(address,placeholder))))))"
+        self.is_kind(EXCHANGED_KIND)
     }
+
+    /// Returns the owner if the kind of state object captures `owner`.
+    pub fn try_get_owner(&self) -> Option<Address> {
+        self.get_kind()
+            .and_then(|(_, captures)| captures.get_address("owner"))
+    }
+
     pub fn get_owner(&self) -> Address {
-        // if property is ownership
-        if self.is_ownership_state() {
-            return match &self.property.inputs[2] {
-                PropertyInput::ConstantProperty(signed_by) => match signed_by.inputs[0] {
-                    PropertyInput::ConstantAddress(addr) => addr,
-                    _ => panic!("not ownership property."),
-                },
-                _ => panic!("this is not ownership property."),
-            };
-        } else if self.is_exchanged_state() {
-            let p = &self.get_property().inputs[2];
-            if let PropertyInput::ConstantProperty(q_property) = p {
-                if let PropertyInput::ConstantProperty(q_su) = &q_property.inputs[2] {
-                    if let PropertyInput::ConstantProperty(or) = &q_su.inputs[2] {
-                        if let PropertyInput::ConstantProperty(and) = &or.inputs[0] {
-                            if let PropertyInput::ConstantProperty(signed_by) = &and.inputs[1] {
-                                if let PropertyInput::ConstantAddress(address) = signed_by.inputs[0]
-                                {
-                                    return address;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            panic!("Not exchanged property");
-        }
-        panic!("Not ownership property.");
-        // TODO: handle other property.
+        self.try_get_owner().expect("Not ownership property.")
     }
 
     pub fn verify_state_transition<T: KeyValueStore>(
         &self,
         decider: &PropertyExecutor<T>,
         transaction: &Transaction,
+    ) -> bool {
+        self.decide_state_transition(decider, transaction, None)
+    }
+
+    /// Deciders can read next_block_number from "next_block" variable if it's known.
+    fn decide_state_transition<T: KeyValueStore>(
+        &self,
+        decider: &PropertyExecutor<T>,
+        transaction: &Transaction,
+        next_block_number: Option<Integer>,
     ) -> bool {
         let property = self.get_property();
+        let _guard = decider.lock_transition();
         decider.set_variable(
             Bytes::from("state_update"),
             QuantifierResultItem::StateUpdate(self.clone()),
         );
+        match next_block_number {
+            Some(next_block_number) => decider.set_variable(
+                Bytes::from("next_block"),
+                QuantifierResultItem::Integer(next_block_number),
+            ),
+            None => decider.remove_variable(&Bytes::from("next_block")),
+        }
         let decided = property.decide(decider);
         println!(
             "decide local deprecation claim {:?}. decision = {:?}",
@@ -156,9 +163,10 @@ impl StateUpdate {
             next_block_number,
             transaction.get_deposit_contract_address(),
             transaction.get_range(),
-            Property::from_abi(transaction.get_parameters()).unwrap(),
+            Property::from_abi(transaction.get_parameters())
+                .map_err(|_| PlasmaCoreError::from(PlasmaCoreErrorKind::InvalidTransaction))?,
         );
-        if !self.verify_state_transition(decider, transaction) {
+        if !self.decide_state_transition(decider, transaction, Some(next_block_number)) {
             return Err(PlasmaCoreError::from(
                 PlasmaCoreErrorKind::InvalidTransaction,
             ));